@@ -0,0 +1,129 @@
+//! Queries against the ALeRCE broker for recent ZTF transients in a field.
+
+use astro_rs::coordinates::Icrs;
+
+use chrono::Utc;
+
+use serde::{Deserialize, Serialize};
+
+use tracing::instrument;
+
+use uom::si::angle::arcsecond;
+use uom::si::f64::Angle;
+
+use std::error::Error;
+
+use crate::coords::icrs_to_degrees;
+use crate::mjd::datetime_to_mjd;
+use crate::{EarendelServer, Upstream};
+
+const ALERCE_API_URL: &str = "https://api.alerce.online/ztf/v1/objects";
+const ALERCE_OBJECT_URL: &str = "https://alerce.online/object";
+/// The maximum number of transients listed in a field.
+const MAX_TRANSIENTS: usize = 100;
+
+/// A ZTF transient classified by the ALeRCE broker.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Transient {
+    /// The ZTF object identifier.
+    pub oid: String,
+    /// The mean right ascension of the detections, in degrees.
+    pub ra: f64,
+    /// The mean declination of the detections, in degrees.
+    pub dec: f64,
+    /// The modified Julian date of the first detection.
+    pub first_mjd: f64,
+    /// The modified Julian date of the latest detection.
+    pub last_mjd: f64,
+    /// The number of detections.
+    pub detections: u32,
+    /// The most probable class, such as SNIa or AGN.
+    pub class: Option<String>,
+    /// The probability of the class.
+    pub probability: Option<f64>,
+    /// The URL of the ALeRCE page showing the live light curve.
+    pub light_curve_url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct AlerceResponse {
+    items: Vec<AlerceObject>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AlerceObject {
+    oid: String,
+    meanra: f64,
+    meandec: f64,
+    firstmjd: f64,
+    lastmjd: f64,
+    ndet: u32,
+    class: Option<String>,
+    probability: Option<f64>,
+}
+
+impl From<AlerceObject> for Transient {
+    fn from(object: AlerceObject) -> Self {
+        Transient {
+            light_curve_url: format!("{}/{}", ALERCE_OBJECT_URL, object.oid),
+            oid: object.oid,
+            ra: object.meanra,
+            dec: object.meandec,
+            first_mjd: object.firstmjd,
+            last_mjd: object.lastmjd,
+            detections: object.ndet,
+            class: object.class,
+            probability: object.probability,
+        }
+    }
+}
+
+impl EarendelServer {
+    /// Gets the transients within the given radius of the given coordinates that were detected within the given number
+    /// of days, most recent first. Returns an error if the web request fails.
+    #[instrument(skip(self, coords, radius))]
+    pub async fn get_recent_transients(
+        &self,
+        coords: &Icrs,
+        radius: Angle,
+        days: u32,
+    ) -> Result<Vec<Transient>, Box<dyn Error + Send + Sync>> {
+        let (ra, dec) = icrs_to_degrees(coords);
+        let now_mjd = datetime_to_mjd(Utc::now());
+        let since_mjd = now_mjd - f64::from(days);
+
+        let request = self.client.get(ALERCE_API_URL).query(&[
+            ("ra", ra.to_string()),
+            ("dec", dec.to_string()),
+            ("radius", radius.get::<arcsecond>().to_string()),
+            ("order_by", String::from("lastmjd")),
+            ("order_mode", String::from("DESC")),
+            ("page_size", MAX_TRANSIENTS.to_string()),
+        ]);
+        let resp = self
+            .send(Upstream::Alerce, request)
+            .await?
+            .error_for_status()?;
+        let body = resp.text().await?;
+        let alerce = self.parse::<AlerceResponse>(Upstream::Alerce, &body)?;
+
+        Ok(alerce
+            .items
+            .into_iter()
+            .filter(|object| object.lastmjd >= since_mjd)
+            .map(Transient::from)
+            .collect())
+    }
+
+    /// Gets the transients near the target of the current APOD that were detected within the given number of days.
+    /// Returns an error if the target cannot be resolved or if the web request fails.
+    pub async fn get_recent_transients_for_apod(
+        &self,
+        radius: Angle,
+        days: u32,
+    ) -> Result<Vec<Transient>, Box<dyn Error + Send + Sync>> {
+        let coords = self.resolve_apod_target().await?;
+
+        self.get_recent_transients(&coords, radius, days).await
+    }
+}
@@ -0,0 +1,112 @@
+//! Rotation between several keys for the NASA APIs, so that busy deployments stay within the hourly limit of each key.
+
+use reqwest::header::HeaderValue;
+use reqwest::StatusCode;
+
+use serde::{Deserialize, Serialize};
+
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::RateLimitStatus;
+
+/// The header carrying the key for the NASA APIs, which keeps the key out of request URLs, and so out of errors and
+/// traces.
+pub(crate) const API_KEY_HEADER: &str = "X-Api-Key";
+/// The time after which a rate-limited key is tried again, as the NASA APIs limit requests per rolling hour.
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// How requests to the NASA APIs choose between the configured keys.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum KeyRotation {
+    /// Each request uses the next key in turn, spreading requests evenly over the keys.
+    #[default]
+    RoundRobin,
+    /// Requests use the same key until it is rate limited, then move on to the next.
+    OnRateLimit,
+}
+
+/// The keys for the NASA APIs and what is known of their remaining quota.
+pub(crate) struct ApiKeys {
+    keys: Vec<String>,
+    rotation: KeyRotation,
+    state: Mutex<KeyState>,
+}
+
+struct KeyState {
+    /// The index of the key used by the next request.
+    next: usize,
+    /// The rate-limit status last reported for each key.
+    quotas: Vec<Option<RateLimitStatus>>,
+    /// The time until which each key is known to be rate limited.
+    limited_until: Vec<Option<Instant>>,
+}
+
+impl ApiKeys {
+    pub(crate) fn new(keys: Vec<String>, rotation: KeyRotation) -> Self {
+        let count = keys.len();
+        ApiKeys {
+            keys,
+            rotation,
+            state: Mutex::new(KeyState {
+                next: 0,
+                quotas: vec![None; count],
+                limited_until: vec![None; count],
+            }),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Gets the rate-limit status last reported for each key, in the order the keys were configured.
+    pub(crate) fn quotas(&self) -> Vec<Option<RateLimitStatus>> {
+        self.lock().quotas.to_owned()
+    }
+
+    /// Sets the key of the given request, if it is a NASA API request, to the key chosen by the rotation, skipping
+    /// keys that are rate limited unless every key is. Returns the index of the chosen key.
+    pub(crate) fn assign(&self, request: &mut reqwest::Request) -> Option<usize> {
+        if self.keys.is_empty() || !request.headers().contains_key(API_KEY_HEADER) {
+            return None;
+        }
+
+        let mut state = self.lock();
+        let now = Instant::now();
+        let count = self.keys.len();
+        let start = state.next;
+        let index = (0..count)
+            .map(|offset| (start + offset) % count)
+            .find(|&index| state.limited_until[index].is_none_or(|until| until <= now))
+            .unwrap_or(start);
+        state.next = match self.rotation {
+            KeyRotation::RoundRobin => (index + 1) % count,
+            KeyRotation::OnRateLimit => index,
+        };
+        drop(state);
+
+        let value = HeaderValue::from_str(&self.keys[index]).ok()?;
+        request.headers_mut().insert(API_KEY_HEADER, value);
+
+        Some(index)
+    }
+
+    /// Records the rate-limit status reported by the given response to a request sent with the key of the given index.
+    pub(crate) fn record(&self, index: usize, resp: &reqwest::Response) {
+        let status = RateLimitStatus::from_headers(resp.headers());
+        let mut state = self.lock();
+        if let Some(status) = status {
+            state.quotas[index] = Some(status);
+        }
+        if resp.status() == StatusCode::TOO_MANY_REQUESTS
+            || status.is_some_and(|status| status.remaining == 0)
+        {
+            state.limited_until[index] = Some(Instant::now() + RATE_LIMIT_WINDOW);
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, KeyState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
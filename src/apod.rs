@@ -0,0 +1,1604 @@
+//! Retrieval and caching of the Astronomy Picture of the Day.
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, Utc, Weekday};
+
+use reqwest::header::{HeaderMap, HeaderName};
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::{IntoUrl, RequestBuilder, StatusCode};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use tokio::sync::{broadcast, watch, OnceCell};
+
+use tracing::{debug, instrument, warn};
+
+use std::env;
+use std::error::Error;
+use std::sync::{Arc, MutexGuard, PoisonError};
+
+use crate::api_keys::API_KEY_HEADER;
+use crate::metadata::{content_type, image_dimensions, read_metadata};
+use crate::potw::text;
+#[cfg(feature = "stream")]
+use crate::ByteStream;
+use crate::{CacheEntry, CacheKind, EarendelError, EarendelServer, ImageMetadata, Upstream};
+
+/// The URL of the APOD website, which serves the APOD pages and images.
+const APOD_SITE_URL: &str = "https://apod.nasa.gov/apod/";
+/// The environment variable holding the key for the NASA APIs, or several keys separated by commas.
+const API_KEY_VAR: &str = "EARENDEL_APOD_API_KEY";
+/// The date of the first APOD.
+pub(crate) const FIRST_APOD_DATE: NaiveDate = match NaiveDate::from_ymd_opt(1995, 6, 16) {
+    Some(date) => date,
+    None => panic!("invalid date of the first APOD"),
+};
+/// The time between checks for today's APOD while it has not been published yet.
+pub(crate) const PENDING_RECHECK: std::time::Duration = std::time::Duration::from_secs(10 * 60);
+/// The time for which an APOD is kept in the configured cache backend, which covers its whole publication day in
+/// every time zone.
+const SHARED_APOD_TTL: std::time::Duration = std::time::Duration::from_secs(2 * 24 * 60 * 60);
+/// The largest number of days of APODs requested from the NASA API at once.
+const RANGE_CHUNK_DAYS: i64 = 100;
+/// The number of published APODs buffered for each subscriber that has not yet received them.
+pub(crate) const PUBLISH_CAPACITY: usize = 4;
+/// The digits of the standard base 64 encoding, used for data URIs.
+const BASE64_DIGITS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Gets the URL of the APOD web page of the given date.
+pub(crate) fn apod_page_url(date: NaiveDate) -> String {
+    format!("{}ap{}.html", APOD_SITE_URL, date.format("%y%m%d"))
+}
+
+/// Finds the bounds of the text between the given markers after the given offset of the given lowercase page.
+fn between(lowercase: &str, start: usize, open: &str, close: &str) -> Option<(usize, usize)> {
+    let open = start + lowercase[start..].find(open)? + open.len();
+    let close = open + lowercase[open..].find(close)?;
+
+    Some((open, close))
+}
+
+/// Resolves the given URL linked from an APOD page.
+fn absolute_url(url: &str) -> String {
+    if url.starts_with("http") {
+        url.to_owned()
+    } else {
+        [APOD_SITE_URL, url].concat()
+    }
+}
+
+/// Finds the bounds of the credit line of the given lowercase APOD page, as the start of its label, the end of its
+/// label, and its end.
+fn credit_bounds(lowercase: &str) -> Option<(usize, usize, usize)> {
+    let header_end = lowercase.find("</h1>").unwrap_or_default();
+    let start = header_end + lowercase[header_end..].find("credit")?;
+    let (label_end, end) = between(lowercase, start, "</b>", "</center>")?;
+
+    Some((start, label_end, end))
+}
+
+/// Finds the bounds of the explanation of the given lowercase APOD page, which follows the given lowercase label.
+fn explanation_bounds(lowercase: &str, label: &str) -> Option<(usize, usize)> {
+    let start = lowercase.find(label)?;
+
+    between(lowercase, start, "</b>", "<center>")
+}
+
+/// Parses the given APOD web page, published on or near the given date, for the fields reported by the API. Returns
+/// None if the page has no title.
+fn parse_apod_page(page: &str, date: NaiveDate) -> Option<Apod> {
+    // HTML tags are matched case-insensitively, and lowercasing ASCII keeps the byte offsets of the page
+    let lowercase = page.to_ascii_lowercase();
+
+    let (open, close) = between(&lowercase, 0, "<title>", "</title>")?;
+    // the title is preceded by the date, such as `APOD: 2024 January 1 - NGC 1232`
+    let (published, title) = page[open..close].split_once(" - ")?;
+    let published = NaiveDate::parse_from_str(
+        published.trim().trim_start_matches("APOD:").trim(),
+        "%Y %B %d",
+    )
+    .unwrap_or(date);
+    let title = text(title);
+    let header_end = lowercase.find("</h1>").unwrap_or_default();
+    let (media_type, url, hdurl) =
+        if let Some((open, close)) = between(&lowercase, header_end, "<img src=\"", "\"") {
+            // the high-resolution image is linked from the standard-resolution image
+            let hdurl = lowercase[header_end..open]
+                .rfind("<a href=\"")
+                .and_then(|href| between(&lowercase, header_end + href, "\"", "\""))
+                .map(|(open, close)| absolute_url(&page[open..close]));
+            ("image", Some(absolute_url(&page[open..close])), hdurl)
+        } else if let Some((open, close)) = between(&lowercase, header_end, "<iframe", ">") {
+            let src = page[open..close]
+                .split_once("src=\"")
+                .and_then(|(_, src)| src.split_once('"'))
+                .map(|(src, _)| absolute_url(src));
+            ("video", src, None)
+        } else {
+            ("other", None, None)
+        };
+    // the API only reports the credit of copyrighted images
+    let copyright = credit_bounds(&lowercase)
+        .filter(|(start, label_end, _)| lowercase[*start..*label_end].contains("copyright"))
+        .map(|(_, label_end, end)| text(&page[label_end..end]));
+    let explanation = explanation_bounds(&lowercase, "explanation:")
+        .map(|(open, close)| text(&page[open..close]));
+
+    Some(Apod {
+        id: None,
+        copyright,
+        date: published.format("%Y-%m-%d").to_string(),
+        explanation,
+        hdurl,
+        media_type: String::from(media_type),
+        service_version: None,
+        title,
+        url,
+        concepts: None,
+    })
+}
+
+/// Parses the title and explanation of the given page of the given translation mirror. Returns None if the page has no
+/// translated title.
+fn parse_mirror_page(page: &str, mirror: &ApodMirror) -> Option<(String, Option<String>)> {
+    let lowercase = page.to_ascii_lowercase();
+    let (open, close) = between(&lowercase, 0, "<title>", "</title>")?;
+    let title = page[open..close]
+        .split_once(" - ")
+        .map(|(_, title)| text(title))
+        .filter(|title| !title.is_empty())?;
+    let label = mirror.explanation_label.to_lowercase();
+    let explanation =
+        explanation_bounds(&lowercase, &label).map(|(open, close)| text(&page[open..close]));
+
+    Some((title, explanation))
+}
+
+/// Parses the credit line and the links of the given APOD web page.
+fn parse_page_details(page: &str) -> ApodPageDetails {
+    let lowercase = page.to_ascii_lowercase();
+    let credit = credit_bounds(&lowercase);
+    let explanation = explanation_bounds(&lowercase, "explanation:");
+
+    ApodPageDetails {
+        credit: credit.map(|(_, label_end, end)| text(&page[label_end..end])),
+        credit_html: credit.map(|(_, label_end, end)| page[label_end..end].trim().to_owned()),
+        credit_links: credit
+            .map(|(_, label_end, end)| links(page, &lowercase, label_end, end))
+            .unwrap_or_default(),
+        explanation_links: explanation
+            .map(|(open, close)| links(page, &lowercase, open, close))
+            .unwrap_or_default(),
+    }
+}
+
+/// Gets the links between the given offsets of the given APOD page.
+fn links(page: &str, lowercase: &str, start: usize, end: usize) -> Vec<ApodLink> {
+    let mut links = Vec::new();
+    let mut position = start;
+    while let Some((open, close)) = between(lowercase, position, "<a href=\"", "\"") {
+        let Some((text_open, text_close)) = between(lowercase, close, ">", "</a>") else {
+            break;
+        };
+        if text_close > end {
+            break;
+        }
+        links.push(ApodLink {
+            text: text(&page[text_open..text_close]),
+            url: absolute_url(&page[open..close]),
+        });
+        position = text_close;
+    }
+
+    links
+}
+
+/// Gets the offset of US Eastern time from UTC at the given instant, in hours. Daylight saving time starts at 2:00 on
+/// the second Sunday of March and ends at 2:00 on the first Sunday of November.
+fn eastern_offset(instant: DateTime<Utc>) -> i64 {
+    let transition = |month, week, hour| {
+        NaiveDate::from_weekday_of_month_opt(instant.year(), month, Weekday::Sun, week)
+            .and_then(|date| date.and_hms_opt(hour, 0, 0))
+            .map(|time| time.and_utc())
+    };
+    match (transition(3, 2, 7), transition(11, 1, 6)) {
+        (Some(start), Some(end)) if start <= instant && instant < end => -4,
+        _ => -5,
+    }
+}
+
+/// Gets the date in US Eastern time at the given instant, on which APODs are published.
+fn eastern_date(instant: DateTime<Utc>) -> NaiveDate {
+    (instant + Duration::hours(eastern_offset(instant))).date_naive()
+}
+
+/// Gets the date of the current APOD, which rolls over at midnight US Eastern time.
+pub(crate) fn apod_today() -> NaiveDate {
+    eastern_date(Utc::now())
+}
+
+/// Gets the instant of the next APOD rollover, at midnight US Eastern time.
+pub(crate) fn next_rollover() -> DateTime<Utc> {
+    let today = apod_today();
+    let midnight = today
+        .succ_opt()
+        .unwrap_or(today)
+        .and_time(NaiveTime::MIN)
+        .and_utc();
+    // midnight is never skipped or repeated, as the clocks change at 2:00
+    let daylight = midnight + Duration::hours(4);
+    if eastern_offset(daylight) == -4 {
+        daylight
+    } else {
+        midnight + Duration::hours(5)
+    }
+}
+
+/// Checks that an APOD was published on the given date, so that a request for it is not sent in vain.
+fn validate_date(date: NaiveDate) -> Result<(), EarendelError> {
+    let today = apod_today();
+    if date < FIRST_APOD_DATE || date > today {
+        return Err(EarendelError::InvalidDate {
+            date,
+            earliest: FIRST_APOD_DATE,
+            latest: today,
+        });
+    }
+
+    Ok(())
+}
+
+/// Gets the keys for the NASA APIs from the environment, if any.
+pub(crate) fn nasa_api_keys() -> Vec<String> {
+    env::var(API_KEY_VAR)
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Information used to display the APOD.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(remote = "Self")]
+pub struct EarendelApod {
+    /// The title of the APOD.
+    pub title: String,
+    /// The date the APOD was published.
+    pub date: NaiveDate,
+    /// The URL the image was downloaded from.
+    pub image_url: String,
+    /// The explanation of the APOD, if any.
+    pub explanation: Option<String>,
+    /// The image, which is only downloaded when first fetched if the APOD was returned by `EarendelServer::get_apod`.
+    #[serde(rename = "img")]
+    pub image: ApodImage,
+    /// The width of the image, in pixels, if it could be read from the image header.
+    pub width: Option<u32>,
+    /// The height of the image, in pixels, if it could be read from the image header.
+    pub height: Option<u32>,
+    /// The copyright string.
+    pub copyright: Option<String>,
+    /// The EXIF and XMP metadata embedded in the image, if any.
+    pub metadata: Option<ImageMetadata>,
+    /// The language of the title and explanation, if they were translated by the mirror configured with
+    /// `EarendelServerBuilder::locale`. Untranslated APODs are in English.
+    #[serde(default)]
+    pub language: Option<String>,
+    /// The credits and links of the APOD web page, if enabled with `EarendelServerBuilder::page_details`.
+    #[serde(default)]
+    pub page_details: Option<ApodPageDetails>,
+    /// The concepts the APOD is tagged with, such as `Milky Way`, if enabled with
+    /// `EarendelServerBuilder::concept_tags`. Empty if the NASA API reports no concepts.
+    #[serde(default)]
+    pub concepts: Vec<String>,
+}
+
+impl Serialize for EarendelApod {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        EarendelApod::serialize(self, serializer)
+    }
+}
+
+// only the bytes of the image are serialized, so its handle is given back the URL it is downloaded from, so that an
+// image that had not been downloaded can still be fetched
+impl<'de> Deserialize<'de> for EarendelApod {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut apod = EarendelApod::deserialize(deserializer)?;
+        apod.image.url = apod.image_url.to_owned();
+
+        Ok(apod)
+    }
+}
+
+impl EarendelApod {
+    /// Gets the binary representation of the image, or an empty slice if it has not been downloaded.
+    pub fn img(&self) -> &[u8] {
+        self.image.bytes().unwrap_or_default()
+    }
+
+    /// Encodes the image as a data URI, such as `data:image/jpeg;base64,...`, with the MIME type detected from the
+    /// image, so that HTML can embed the image inline without it being hosted. Returns None if the image has not been
+    /// downloaded.
+    pub fn to_data_uri(&self) -> Option<String> {
+        let img = self.image.bytes()?;
+
+        Some(format!(
+            "data:{};base64,{}",
+            content_type(img),
+            base64_encode(img)
+        ))
+    }
+}
+
+/// Encodes the given bytes with the standard, padded base 64 encoding.
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (index, byte)| {
+            group | (u32::from(*byte) << (16 - 8 * index))
+        });
+        for digit in 0..4 {
+            if digit <= chunk.len() {
+                let index = (group >> (18 - 6 * digit)) & 0x3f;
+                encoded.push(char::from(BASE64_DIGITS[index as usize]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+/// The image of an APOD, downloaded on demand. Clones share the downloaded image, so it is downloaded at most once.
+#[derive(Clone, Debug, Default)]
+pub struct ApodImage {
+    url: String,
+    bytes: Arc<OnceCell<Vec<u8>>>,
+}
+
+impl ApodImage {
+    /// Creates a handle to the image at the given URL, which has not been downloaded yet.
+    fn new(url: String) -> Self {
+        ApodImage {
+            url,
+            bytes: Arc::new(OnceCell::new()),
+        }
+    }
+
+    /// Creates a handle to the given image, downloaded from the given URL.
+    fn loaded(url: String, bytes: Vec<u8>) -> Self {
+        ApodImage {
+            url,
+            bytes: Arc::new(OnceCell::new_with(Some(bytes))),
+        }
+    }
+
+    /// Gets the URL of the image.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Gets the binary representation of the image, if it has been downloaded.
+    pub fn bytes(&self) -> Option<&[u8]> {
+        self.bytes.get().map(Vec::as_slice)
+    }
+
+    /// Gets the binary representation of the image, downloading it with the given server if it has not been
+    /// downloaded yet. The image is taken from the cache of the server if it is the cached APOD's image. Returns an
+    /// error if the web request fails.
+    pub async fn fetch(
+        &self,
+        server: &EarendelServer,
+    ) -> Result<&[u8], Box<dyn Error + Send + Sync>> {
+        self.bytes
+            .get_or_try_init(|| server.load_apod_image(&self.url))
+            .await
+            .map(Vec::as_slice)
+    }
+
+    /// Takes the binary representation of the image, if it has been downloaded. The image is copied if it is shared
+    /// with clones of this handle.
+    pub fn into_bytes(self) -> Option<Vec<u8>> {
+        match Arc::try_unwrap(self.bytes) {
+            Ok(bytes) => bytes.into_inner(),
+            Err(bytes) => bytes.get().cloned(),
+        }
+    }
+}
+
+// the image is serialized as its binary representation, which is empty if it has not been downloaded
+impl Serialize for ApodImage {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serde_bytes::serialize(self.bytes().unwrap_or_default(), serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ApodImage {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bytes = serde_bytes::deserialize::<Vec<u8>, D>(deserializer)?;
+
+        if bytes.is_empty() {
+            Ok(ApodImage::default())
+        } else {
+            Ok(ApodImage::loaded(String::new(), bytes))
+        }
+    }
+}
+
+/// The information of an APOD without its image, for consumers that only link to it.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ApodMetadata {
+    /// The title of the APOD.
+    pub title: String,
+    /// The date the APOD was published.
+    pub date: NaiveDate,
+    /// The explanation of the APOD, if any.
+    pub explanation: Option<String>,
+    /// The copyright string.
+    pub copyright: Option<String>,
+    /// The type of the APOD, such as `image` or `video`.
+    pub media_type: String,
+    /// The URL of the image that `EarendelServer::get_apod_image` would download, or of the video, if any.
+    pub image_url: Option<String>,
+    /// The URL of the high-resolution image, if any.
+    pub hd_image_url: Option<String>,
+    /// The URL of the APOD web page.
+    pub page_url: String,
+    /// The language of the title and explanation, if they were translated by the mirror configured with
+    /// `EarendelServerBuilder::locale`. Untranslated APODs are in English.
+    pub language: Option<String>,
+    /// The concepts the APOD is tagged with, if enabled with `EarendelServerBuilder::concept_tags`.
+    #[serde(default)]
+    pub concepts: Vec<String>,
+}
+
+/// An official translation mirror of the APOD website, whose pages are named like those of the original, such as
+/// `ap240101.html`. The mirrors are listed at <https://apod.nasa.gov/apod/lib/about_apod.html>.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ApodMirror {
+    /// The language of the mirror, as a BCP 47 tag such as `es`.
+    pub language: String,
+    /// The URL of the directory containing the pages of the mirror, ending with `/`.
+    pub base_url: String,
+    /// The label preceding the explanation on the pages of the mirror, such as `Explicación:`.
+    pub explanation_label: String,
+}
+
+impl ApodMirror {
+    /// Gets the URL of the page of the mirror for the given date.
+    pub fn page_url(&self, date: NaiveDate) -> String {
+        format!("{}ap{}.html", self.base_url, date.format("%y%m%d"))
+    }
+}
+
+/// The details of the APOD web page that are omitted by the NASA API.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ApodPageDetails {
+    /// The full credit line as plain text, including uncopyrighted credits omitted by the API.
+    pub credit: Option<String>,
+    /// The HTML markup of the credit line.
+    pub credit_html: Option<String>,
+    /// The links in the credit line, typically to the authors.
+    pub credit_links: Vec<ApodLink>,
+    /// The links in the explanation.
+    pub explanation_links: Vec<ApodLink>,
+}
+
+/// A link on the APOD web page.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ApodLink {
+    /// The text of the link.
+    pub text: String,
+    /// The absolute URL of the link.
+    pub url: String,
+}
+
+/// The NASA API rate-limit status reported by the most recent NASA API response.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct RateLimitStatus {
+    /// The number of requests allowed per hour.
+    pub limit: u32,
+    /// The number of requests remaining in the current hour.
+    pub remaining: u32,
+}
+
+impl RateLimitStatus {
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let get = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<u32>().ok())
+        };
+
+        Some(RateLimitStatus {
+            limit: get("X-RateLimit-Limit")?,
+            remaining: get("X-RateLimit-Remaining")?,
+        })
+    }
+}
+
+#[cfg(feature = "imaging")]
+impl EarendelApod {
+    /// Resizes the image to fit within the given dimensions, keeping its aspect ratio. Returns an error if the image
+    /// cannot be decoded or encoded.
+    pub fn thumbnail(
+        &self,
+        max_width: u32,
+        max_height: u32,
+    ) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        crate::imaging::thumbnail(self.img(), max_width, max_height)
+    }
+
+    /// Scales the image to exactly the given resolution, cropped or letterboxed as described by the given fit, for
+    /// setting as a wallpaper. See `imaging::wallpaper`. Returns an error if either dimension is zero or if the image
+    /// cannot be decoded or encoded.
+    pub fn wallpaper(
+        &self,
+        width: u32,
+        height: u32,
+        fit: crate::imaging::WallpaperFit,
+    ) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        crate::imaging::wallpaper(self.img(), width, height, fit)
+    }
+
+    /// Computes the average color and up to the given number of dominant colors of the image, for theming
+    /// backgrounds and placeholders. Returns an error if the image cannot be decoded.
+    pub fn palette(
+        &self,
+        count: usize,
+    ) -> Result<crate::imaging::Palette, Box<dyn Error + Send + Sync>> {
+        crate::imaging::palette(self.img(), count)
+    }
+
+    /// Computes the BlurHash of the image with the given number of horizontal and vertical components, so that web
+    /// clients can render a blurred placeholder while the image loads. Returns an error if the image cannot be decoded
+    /// or if a component count is not between 1 and 9.
+    pub fn blurhash(
+        &self,
+        components_x: u32,
+        components_y: u32,
+    ) -> Result<String, Box<dyn Error + Send + Sync>> {
+        crate::imaging::blurhash(self.img(), components_x, components_y)
+    }
+
+    /// Computes the perceptual hash of the image, which survives re-encoding and resizing. See
+    /// `imaging::perceptual_hash`. Returns an error if the image cannot be decoded.
+    pub fn perceptual_hash(&self) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        crate::imaging::perceptual_hash(self.img())
+    }
+
+    /// Computes the SHA-256 digest of the image bytes, as lowercase hexadecimal.
+    pub fn sha256(&self) -> String {
+        crate::imaging::sha256(self.img())
+    }
+
+    /// Determines whether the image is the same picture as that of the given APOD, either byte for byte or by their
+    /// perceptual hashes, so that a silently replaced image can be told from a re-encoded one. Returns an error if
+    /// either image cannot be decoded.
+    pub fn is_same_image(
+        &self,
+        other: &EarendelApod,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        if self.img() == other.img() {
+            return Ok(true);
+        }
+        let distance =
+            crate::imaging::hash_distance(self.perceptual_hash()?, other.perceptual_hash()?);
+
+        Ok(distance <= crate::imaging::SAME_IMAGE_DISTANCE)
+    }
+}
+
+#[cfg(feature = "overlay")]
+impl EarendelApod {
+    /// Draws the title and copyright of the APOD onto the image, as described by the given options, for displays that
+    /// must credit the image, such as digital signage and wallpapers. Returns an error if the font is invalid or if
+    /// the image cannot be decoded or encoded.
+    pub fn with_attribution(
+        &self,
+        options: &crate::overlay::OverlayOptions,
+    ) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        crate::overlay::overlay_attribution(
+            self.img(),
+            &self.title,
+            self.copyright.as_deref(),
+            options,
+        )
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Apod {
+    id: Option<u32>,
+    copyright: Option<String>,
+    date: String,
+    explanation: Option<String>,
+    hdurl: Option<String>,
+    media_type: String,
+    service_version: Option<String>,
+    title: String,
+    url: Option<String>,
+    /// The concept tags, requested with `concept_tags=true`. The NASA API lists them as an object keyed by index, or
+    /// reports a message instead when concept tagging is unavailable.
+    #[serde(default)]
+    concepts: Option<serde_json::Value>,
+}
+
+impl Apod {
+    /// Gets the concept tags of the APOD in the order reported, or an empty list if none were reported.
+    fn concepts(&self) -> Vec<String> {
+        let values = match self.concepts.as_ref() {
+            Some(serde_json::Value::Object(concepts)) => {
+                // the keys are indices, which sort as strings with "10" before "2"
+                let mut indexed = concepts
+                    .iter()
+                    .filter_map(|(index, value)| Some((index.parse::<usize>().ok()?, value)))
+                    .collect::<Vec<(usize, &serde_json::Value)>>();
+                indexed.sort_unstable_by_key(|(index, _)| *index);
+                indexed.into_iter().map(|(_, value)| value).collect()
+            }
+            Some(serde_json::Value::Array(concepts)) => concepts.iter().collect(),
+            _ => Vec::new(),
+        };
+
+        values
+            .into_iter()
+            .filter_map(serde_json::Value::as_str)
+            .map(|concept| concept.trim().to_owned())
+            .filter(|concept| !concept.is_empty())
+            .collect()
+    }
+
+    /// Converts the APOD, published on or near the given date, to its information. The image URL is that of the
+    /// high-resolution image if it is preferred.
+    fn into_metadata(self, date: NaiveDate, prefer_hd: bool) -> ApodMetadata {
+        let published = NaiveDate::parse_from_str(&self.date, "%Y-%m-%d").unwrap_or(date);
+        let image_url = match self.hdurl.as_ref() {
+            Some(hdurl) if prefer_hd && self.media_type == "image" => Some(hdurl.to_owned()),
+            Some(_) | None => self.url,
+        };
+
+        let concepts = self.concepts();
+
+        ApodMetadata {
+            title: self.title,
+            date: published,
+            explanation: self.explanation,
+            copyright: self.copyright,
+            media_type: self.media_type,
+            image_url,
+            hd_image_url: self.hdurl,
+            page_url: apod_page_url(published),
+            language: None,
+            concepts,
+        }
+    }
+}
+
+/// Validators returned with the APOD image, used to issue conditional requests on refresh.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+struct ImageValidators {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+impl ImageValidators {
+    fn from_headers(headers: &HeaderMap) -> Self {
+        let get = |name: HeaderName| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(String::from)
+        };
+
+        ImageValidators {
+            etag: get(ETAG),
+            last_modified: get(LAST_MODIFIED),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct CachedApod {
+    /// The date the APOD was published, in US Eastern time.
+    date: NaiveDate,
+    apod: EarendelApod,
+    validators: ImageValidators,
+    /// When the NASA API was last asked for a newer APOD.
+    #[serde(default)]
+    checked: DateTime<Utc>,
+    /// The URL of the high-resolution image, if any, which may not be the downloaded image.
+    #[serde(default)]
+    hdurl: Option<String>,
+}
+
+impl CachedApod {
+    /// Determines whether the cached APOD is still current on the given date, either because it was published on it
+    /// or because the next APOD was recently found not to be out yet.
+    fn is_current(&self, today: NaiveDate) -> bool {
+        let recently_checked = (Utc::now() - self.checked)
+            .to_std()
+            .is_ok_and(|elapsed| elapsed < PENDING_RECHECK);
+
+        self.date == today || (self.date < today && recently_checked)
+    }
+
+    /// Gets the date the APOD was published.
+    pub(crate) fn date(&self) -> NaiveDate {
+        self.date
+    }
+
+    pub(crate) fn entry(&self) -> CacheEntry {
+        CacheEntry {
+            kind: CacheKind::Apod,
+            key: self.date.to_string(),
+            age: (Utc::now() - self.checked).to_std().ok(),
+            bytes: self.apod.img().len() as u64,
+        }
+    }
+
+    fn metadata(&self) -> ApodMetadata {
+        ApodMetadata {
+            title: self.apod.title.to_owned(),
+            date: self.apod.date,
+            explanation: self.apod.explanation.to_owned(),
+            copyright: self.apod.copyright.to_owned(),
+            media_type: String::from("image"),
+            image_url: Some(self.apod.image_url.to_owned()),
+            hd_image_url: self.hdurl.to_owned(),
+            page_url: apod_page_url(self.apod.date),
+            language: self.apod.language.to_owned(),
+            concepts: self.apod.concepts.to_owned(),
+        }
+    }
+}
+
+impl EarendelServer {
+    /// Gets the current APOD image data. APODs roll over at midnight US Eastern time; until the new APOD is published,
+    /// the previous one is returned. Returns an Error if the web request fails or if deserialization fails.
+    #[instrument(skip(self))]
+    pub async fn get_apod_image(&self) -> Result<EarendelApod, Box<dyn Error + Send + Sync>> {
+        let today = apod_today();
+        let cached = self
+            .cached_apod()
+            .as_ref()
+            .filter(|cached| cached.is_current(today))
+            .map(|cached| cached.apod.to_owned());
+        if let Some(apod) = cached {
+            self.metrics.record_cache(true);
+            return Ok(apod);
+        }
+        if let Some(shared) = self.load_shared_apod(today).await {
+            self.metrics.record_cache(true);
+            return Ok(self.replace_apod(shared));
+        }
+        self.metrics.record_cache(false);
+        let apod = match self.fetch_apod(None).await? {
+            Some(apod) => apod,
+            // today's APOD is not out yet, so the previous one is still current
+            None => match self.recheck_cached_apod(|date| Some(date) == today.pred_opt()) {
+                Some(apod) => return Ok(apod),
+                None => self
+                    .fetch_apod(today.pred_opt())
+                    .await?
+                    .ok_or("no APOD has been published")?,
+            },
+        };
+        let published = NaiveDate::parse_from_str(&apod.date, "%Y-%m-%d").unwrap_or(today);
+        if let Some(apod) = self.recheck_cached_apod(|date| date == published) {
+            return Ok(apod);
+        }
+        let new_state = self.fetch_apod_image(apod, published).await?;
+        self.store_shared_apod(&new_state).await;
+
+        Ok(self.replace_apod(new_state))
+    }
+
+    /// Locks the cached APOD. The guard must not be held across an await, so that other calls are not blocked on
+    /// requests in flight.
+    pub(crate) fn cached_apod(&self) -> MutexGuard<'_, Option<CachedApod>> {
+        self.cached_state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Gets the cached APOD if it was published on a date matching the given predicate, recording that the NASA API was
+    /// just checked for a newer APOD.
+    fn recheck_cached_apod<F: FnOnce(NaiveDate) -> bool>(
+        &self,
+        predicate: F,
+    ) -> Option<EarendelApod> {
+        let mut cached = self.cached_apod();
+        let cached = cached.as_mut().filter(|cached| predicate(cached.date))?;
+        cached.checked = Utc::now();
+
+        Some(cached.apod.to_owned())
+    }
+
+    /// Gets the information of the cached APOD if it was published on a date matching the given predicate.
+    fn cached_metadata<F: FnOnce(NaiveDate) -> bool>(&self, predicate: F) -> Option<ApodMetadata> {
+        self.cached_apod()
+            .as_ref()
+            .filter(|cached| predicate(cached.date))
+            .map(CachedApod::metadata)
+    }
+
+    /// Replaces the cached APOD with the given newly fetched APOD, publishing it to the subscribers.
+    fn replace_apod(&self, state: CachedApod) -> EarendelApod {
+        let apod = state.apod.to_owned();
+        self.cache_apod(Some(state));
+        // an error only means that there are no subscribers
+        let _ = self.apod_published.send(Arc::new(apod.to_owned()));
+
+        apod
+    }
+
+    /// Loads the APOD of the given date from the configured cache backend, if any. Failures are logged rather than
+    /// returned, as the APOD can still be fetched.
+    async fn load_shared_apod(&self, date: NaiveDate) -> Option<CachedApod> {
+        let backend = self.cache_backend.as_ref()?;
+        let key = format!("apod:{}", date);
+        let loaded = async {
+            let Some(state) = backend.get(&key).await? else {
+                return Ok(None);
+            };
+            let Some(img) = backend.get(&[&key, ":image"].concat()).await? else {
+                return Ok(None);
+            };
+            let mut state = serde_json::from_slice::<CachedApod>(&state)?;
+            state.apod.image = ApodImage::loaded(state.apod.image_url.to_owned(), img);
+            Ok::<Option<CachedApod>, Box<dyn Error + Send + Sync>>(Some(state))
+        };
+
+        match loaded.await {
+            Ok(state) => state,
+            Err(e) => {
+                warn!("failed to load APOD from the cache backend: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Stores the given APOD in the configured cache backend, if any, for other servers to load. The image is stored
+    /// separately, so that it is not encoded as JSON.
+    async fn store_shared_apod(&self, state: &CachedApod) {
+        let Some(backend) = self.cache_backend.as_ref() else {
+            return;
+        };
+        let key = format!("apod:{}", state.date);
+        let mut metadata = state.to_owned();
+        let img = std::mem::take(&mut metadata.apod.image)
+            .into_bytes()
+            .unwrap_or_default();
+        let stored = async {
+            // the image is stored first, so that the APOD is never found without it
+            backend
+                .set(&[&key, ":image"].concat(), img, SHARED_APOD_TTL)
+                .await?;
+            backend
+                .set(&key, serde_json::to_vec(&metadata)?, SHARED_APOD_TTL)
+                .await
+        };
+
+        if let Err(e) = stored.await {
+            warn!("failed to store APOD in the cache backend: {}", e);
+        }
+    }
+
+    /// Gets the APOD image data for the given date. Only the current APOD is cached. Returns an Error if no APOD was
+    /// published on the date, if the web request fails, or if deserialization fails.
+    #[instrument(skip(self))]
+    pub async fn get_apod_image_for_date(
+        &self,
+        date: NaiveDate,
+    ) -> Result<EarendelApod, Box<dyn Error + Send + Sync>> {
+        if date == apod_today() {
+            return self.get_apod_image().await;
+        }
+        let apod = self
+            .fetch_apod(Some(date))
+            .await?
+            .ok_or_else(|| format!("no APOD was published on {}", date))?;
+
+        Ok(self.fetch_apod_image(apod, date).await?.apod)
+    }
+
+    /// Gets the current APOD image as a stream, so that it can be passed on without being held in memory. The image is
+    /// streamed from the cache if it is current, and otherwise from the APOD website without being cached or
+    /// transcoded. Returns an Error if the web request fails or if the APOD is not an image.
+    #[cfg(feature = "stream")]
+    #[instrument(skip(self))]
+    pub async fn stream_apod_image(&self) -> Result<ByteStream, Box<dyn Error + Send + Sync>> {
+        let today = apod_today();
+        let cached = self
+            .cached_apod()
+            .as_ref()
+            .filter(|cached| cached.is_current(today))
+            .map(|cached| cached.apod.img().to_vec());
+        if let Some(img) = cached {
+            self.metrics.record_cache(true);
+            return Ok(ByteStream::from_bytes(img));
+        }
+        self.metrics.record_cache(false);
+        let apod = match self.fetch_apod(None).await? {
+            Some(apod) => apod,
+            // today's APOD is not out yet, so the previous one is still current
+            None => self
+                .fetch_apod(today.pred_opt())
+                .await?
+                .ok_or("no APOD has been published")?,
+        };
+        let published = NaiveDate::parse_from_str(&apod.date, "%Y-%m-%d").unwrap_or(today);
+        let (image_url, _) = self.image_urls(&apod, published)?;
+        let resp = self
+            .send(Upstream::ApodWebsite, self.client.get(&image_url))
+            .await?
+            .error_for_status()?;
+
+        ByteStream::from_response(resp, self.max_download_size)
+    }
+
+    /// Gets the current APOD without downloading its image, which is downloaded when first fetched with
+    /// `ApodImage::fetch`. The cached APOD is returned with its image if it is current. Returns an Error if the web
+    /// request fails, if deserialization fails, or if the APOD is not an image.
+    #[instrument(skip(self))]
+    pub async fn get_apod(&self) -> Result<EarendelApod, Box<dyn Error + Send + Sync>> {
+        let cached = self
+            .cached_apod()
+            .as_ref()
+            .filter(|cached| cached.is_current(apod_today()))
+            .map(|cached| cached.apod.to_owned());
+        if let Some(apod) = cached {
+            self.metrics.record_cache(true);
+            return Ok(apod);
+        }
+        let metadata = self.get_apod_metadata().await?;
+        let image_url = match metadata.image_url {
+            Some(image_url) if metadata.media_type == "image" => image_url,
+            Some(_) => {
+                return Err(EarendelError::ApodNotImage {
+                    media_type: metadata.media_type,
+                    page_url: metadata.page_url,
+                }
+                .into())
+            }
+            None => {
+                return Err(EarendelError::ApodImageUrlMissing {
+                    date: metadata.date,
+                    media_type: metadata.media_type,
+                    page_url: metadata.page_url,
+                }
+                .into())
+            }
+        };
+
+        Ok(EarendelApod {
+            title: metadata.title,
+            date: metadata.date,
+            image: ApodImage::new(image_url.to_owned()),
+            image_url,
+            explanation: metadata.explanation,
+            width: None,
+            height: None,
+            copyright: metadata.copyright,
+            metadata: None,
+            language: metadata.language,
+            page_details: None,
+            concepts: metadata.concepts,
+        })
+    }
+
+    /// Gets the information of the current APOD without downloading its image. Unlike `EarendelServer::get_apod_image`,
+    /// APODs that are not images, such as videos, are returned. Returns an Error if the web request fails or if
+    /// deserialization fails.
+    #[instrument(skip(self))]
+    pub async fn get_apod_metadata(&self) -> Result<ApodMetadata, Box<dyn Error + Send + Sync>> {
+        let today = apod_today();
+        let cached = self
+            .cached_apod()
+            .as_ref()
+            .filter(|cached| cached.is_current(today))
+            .map(CachedApod::metadata);
+        if let Some(metadata) = cached {
+            self.metrics.record_cache(true);
+            return Ok(metadata);
+        }
+        self.metrics.record_cache(false);
+        let apod = match self.fetch_apod(None).await? {
+            Some(apod) => apod,
+            // today's APOD is not out yet, so the previous one is still current
+            None => match self.cached_metadata(|date| Some(date) == today.pred_opt()) {
+                Some(metadata) => return Ok(metadata),
+                None => self
+                    .fetch_apod(today.pred_opt())
+                    .await?
+                    .ok_or("no APOD has been published")?,
+            },
+        };
+
+        Ok(self.apod_metadata(apod, today).await)
+    }
+
+    /// Gets the information of the APOD for the given date without downloading its image. Returns an Error if no APOD
+    /// was published on the date, if the web request fails, or if deserialization fails.
+    #[instrument(skip(self))]
+    pub async fn get_apod_metadata_for_date(
+        &self,
+        date: NaiveDate,
+    ) -> Result<ApodMetadata, Box<dyn Error + Send + Sync>> {
+        if date == apod_today() {
+            return self.get_apod_metadata().await;
+        }
+        let apod = self
+            .fetch_apod(Some(date))
+            .await?
+            .ok_or_else(|| format!("no APOD was published on {}", date))?;
+
+        Ok(self.apod_metadata(apod, date).await)
+    }
+
+    /// Subscribes to the APODs fetched and cached by this server, starting with the next one. A subscriber that falls
+    /// behind skips to the most recent APODs.
+    pub fn subscribe_apod(&self) -> broadcast::Receiver<Arc<EarendelApod>> {
+        self.apod_published.subscribe()
+    }
+
+    /// Determines whether today's APOD has not been published yet, so that the cached APOD is the previous one. This is
+    /// expected for a few hours after midnight US Eastern time, and is not an error.
+    pub fn apod_pending(&self) -> bool {
+        self.cached_apod()
+            .as_ref()
+            .is_some_and(|cached| cached.date < apod_today())
+    }
+
+    /// Subscribes to changes of the cached APOD, such as when a new APOD is fetched or a cache snapshot is imported.
+    /// Unlike `subscribe_apod`, the receiver always holds the latest cached APOD, if any, so a subscriber that falls
+    /// behind only sees the most recent change.
+    pub fn subscribe(&self) -> watch::Receiver<Option<Arc<EarendelApod>>> {
+        self.apod_current.subscribe()
+    }
+
+    /// Replaces the cached APOD, notifying the subscribers of the change.
+    pub(crate) fn cache_apod(&self, state: Option<CachedApod>) {
+        let apod = state.as_ref().map(|state| Arc::new(state.apod.to_owned()));
+        *self.cached_apod() = state;
+        self.apod_current.send_replace(apod);
+    }
+
+    /// Gets the history in which fetched APODs are recorded, if one was configured.
+    #[cfg(feature = "history")]
+    pub fn history(&self) -> Option<&crate::ApodHistory> {
+        self.history.as_ref()
+    }
+
+    /// Gets the NASA API rate-limit status reported by the most recent NASA API response, if any.
+    pub fn rate_limit_status(&self) -> Option<RateLimitStatus> {
+        *self
+            .rate_limit
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Gets the NASA API rate-limit status last reported for each configured key, in the order the keys were
+    /// configured.
+    pub fn api_key_quotas(&self) -> Vec<Option<RateLimitStatus>> {
+        self.api_keys.quotas()
+    }
+
+    /// Records the rate-limit status reported by a NASA API response.
+    pub(crate) fn record_rate_limit(&self, headers: &HeaderMap) {
+        if let Some(rate_limit) = RateLimitStatus::from_headers(headers) {
+            *self
+                .rate_limit
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) = Some(rate_limit);
+        }
+    }
+
+    /// Creates a GET request to the given NASA API URL, authenticated with one of the configured keys. Returns an
+    /// error if no key is configured.
+    pub(crate) fn nasa_api_get<U: IntoUrl>(
+        &self,
+        url: U,
+    ) -> Result<RequestBuilder, Box<dyn Error + Send + Sync>> {
+        if self.api_keys.is_empty() {
+            return Err(format!("no NASA API key is configured; set {}", API_KEY_VAR).into());
+        }
+
+        // the key is chosen as each attempt is sent, so that a rate-limited key can be replaced on retry
+        Ok(self.client.get(url).header(API_KEY_HEADER, ""))
+    }
+
+    pub(crate) async fn get_apod_title(&self) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let today = apod_today();
+        let cached = self
+            .cached_apod()
+            .as_ref()
+            .map(|cached| (cached.is_current(today), cached.apod.title.to_owned()));
+        match cached {
+            Some((true, title)) => {
+                self.metrics.record_cache(true);
+                Ok(title)
+            }
+            Some((false, title)) => {
+                self.metrics.record_cache(false);
+                Ok(self
+                    .fetch_apod(None)
+                    .await?
+                    .map_or(title, |apod| apod.title))
+            }
+            None => {
+                self.metrics.record_cache(false);
+                self.fetch_apod(None)
+                    .await?
+                    .map(|apod| apod.title)
+                    .ok_or_else(|| "today's APOD has not been published yet".into())
+            }
+        }
+    }
+
+    /// Gets the date the current APOD was published, fetching it if it is not cached.
+    pub(crate) async fn get_apod_date(&self) -> Result<NaiveDate, Box<dyn Error + Send + Sync>> {
+        let today = apod_today();
+        let cached = self
+            .cached_apod()
+            .as_ref()
+            .map(|cached| (cached.is_current(today), cached.date));
+        if let Some((true, date)) = cached {
+            self.metrics.record_cache(true);
+            return Ok(date);
+        }
+        self.metrics.record_cache(false);
+        match self.fetch_apod(None).await? {
+            Some(apod) => Ok(NaiveDate::parse_from_str(&apod.date, "%Y-%m-%d").unwrap_or(today)),
+            None => cached
+                .map(|(_, date)| date)
+                .ok_or_else(|| "today's APOD has not been published yet".into()),
+        }
+    }
+
+    /// Fetches the APOD for the given date, or the current APOD if no date is given. If the NASA API is unavailable,
+    /// the APOD web page is scraped instead. Returns None if there is no APOD for the date, such as before today's APOD
+    /// is published.
+    async fn fetch_apod(
+        &self,
+        date: Option<NaiveDate>,
+    ) -> Result<Option<Apod>, Box<dyn Error + Send + Sync>> {
+        if let Some(date) = date {
+            validate_date(date)?;
+        }
+        match self.fetch_apod_from_api(date).await {
+            Ok(apod) => Ok(apod),
+            Err(e) => {
+                warn!(
+                    "APOD API is unavailable, scraping the APOD page instead: {}",
+                    e
+                );
+                self.scrape_apod(date).await.map_err(|scrape_error| {
+                    warn!("failed to scrape the APOD page: {}", scrape_error);
+                    e
+                })
+            }
+        }
+    }
+
+    async fn fetch_apod_from_api(
+        &self,
+        date: Option<NaiveDate>,
+    ) -> Result<Option<Apod>, Box<dyn Error + Send + Sync>> {
+        let api_url = "https://api.nasa.gov/planetary/apod";
+
+        let mut request = self.nasa_api_get(api_url)?;
+        if let Some(date) = date {
+            request = request.query(&[("date", date.format("%Y-%m-%d").to_string())]);
+        }
+        if self.concept_tags {
+            request = request.query(&[("concept_tags", "true")]);
+        }
+        let resp = self.send(Upstream::Apod, request).await?;
+        self.record_rate_limit(resp.headers());
+        if resp.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let body = resp.error_for_status()?.text().await?;
+
+        self.parse::<Apod>(Upstream::Apod, &body).map(Some)
+    }
+
+    /// Scrapes the APOD web page of the given date, or the current APOD page if no date is given.
+    async fn scrape_apod(
+        &self,
+        date: Option<NaiveDate>,
+    ) -> Result<Option<Apod>, Box<dyn Error + Send + Sync>> {
+        let page_url = match date {
+            Some(date) => apod_page_url(date),
+            None => [APOD_SITE_URL, "astropix.html"].concat(),
+        };
+        let resp = self
+            .send(Upstream::ApodWebsite, self.client.get(page_url))
+            .await?;
+        if resp.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let page = resp.error_for_status()?.text().await?;
+
+        parse_apod_page(&page, date.unwrap_or_else(apod_today))
+            .map(Some)
+            .ok_or_else(|| "unrecognized APOD page".into())
+    }
+
+    async fn fetch_apod_image(
+        &self,
+        apod: Apod,
+        date: NaiveDate,
+    ) -> Result<CachedApod, Box<dyn Error + Send + Sync>> {
+        let published = NaiveDate::parse_from_str(&apod.date, "%Y-%m-%d").unwrap_or(date);
+        let (image_url, standard_url) = self.image_urls(&apod, published)?;
+
+        let (image_url, (img, metadata, validators)) =
+            match self.download_apod_image(&image_url).await {
+                Err(e)
+                    if image_url != standard_url
+                        && matches!(
+                            e.downcast_ref::<EarendelError>(),
+                            Some(EarendelError::DownloadTooLarge { .. })
+                        ) =>
+                {
+                    warn!("{}, falling back to the standard-resolution image", e);
+                    let downloaded = self.download_apod_image(&standard_url).await?;
+                    (standard_url, downloaded)
+                }
+                result => (image_url, result?),
+            };
+
+        let dimensions = image_dimensions(&img);
+        let page_details = if self.page_details {
+            self.fetch_page_details(published).await
+        } else {
+            None
+        };
+        let concepts = apod.concepts();
+        let (title, explanation, language) = match self.fetch_translation(published).await {
+            Some((title, explanation, language)) => {
+                (title, explanation.or(apod.explanation), Some(language))
+            }
+            None => (apod.title, apod.explanation, None),
+        };
+
+        let cached = CachedApod {
+            date,
+            apod: EarendelApod {
+                title,
+                date: NaiveDate::parse_from_str(&apod.date, "%Y-%m-%d").unwrap_or(date),
+                image: ApodImage::loaded(image_url.to_owned(), img),
+                image_url,
+                explanation,
+                language,
+                width: dimensions.map(|(width, _)| width),
+                height: dimensions.map(|(_, height)| height),
+                copyright: apod.copyright,
+                metadata,
+                page_details,
+                concepts,
+            },
+            validators,
+            checked: Utc::now(),
+            hdurl: apod.hdurl,
+        };
+        #[cfg(feature = "history")]
+        if let Some(history) = self.history.as_ref() {
+            if let Err(e) = history.record(&cached.apod) {
+                warn!("failed to record APOD in history: {}", e);
+            }
+        }
+
+        Ok(cached)
+    }
+
+    /// Converts the given APOD, published on or near the given date, to its information, translating it if a mirror is
+    /// configured.
+    async fn apod_metadata(&self, apod: Apod, date: NaiveDate) -> ApodMetadata {
+        let mut metadata = apod.into_metadata(date, self.prefer_hd);
+        if let Some((title, explanation, language)) = self.fetch_translation(metadata.date).await {
+            metadata.title = title;
+            metadata.explanation = explanation.or(metadata.explanation);
+            metadata.language = Some(language);
+        }
+
+        metadata
+    }
+
+    /// Fetches the information of the APODs published between the given dates, inclusive, from the NASA API, without
+    /// their images or translations. Returns an Error if either date is outside the archive, if a web request fails,
+    /// or if deserialization fails.
+    pub(crate) async fn fetch_apod_range(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<Vec<ApodMetadata>, Box<dyn Error + Send + Sync>> {
+        validate_date(start)?;
+        validate_date(end)?;
+        let api_url = "https://api.nasa.gov/planetary/apod";
+
+        let mut apods = Vec::new();
+        let mut chunk_start = start;
+        while chunk_start <= end {
+            let chunk_end = end.min(chunk_start + Duration::days(RANGE_CHUNK_DAYS - 1));
+            let mut request = self.nasa_api_get(api_url)?.query(&[
+                ("start_date", chunk_start.format("%Y-%m-%d").to_string()),
+                ("end_date", chunk_end.format("%Y-%m-%d").to_string()),
+            ]);
+            if self.concept_tags {
+                request = request.query(&[("concept_tags", "true")]);
+            }
+            let resp = self.send(Upstream::Apod, request).await?;
+            self.record_rate_limit(resp.headers());
+            let body = resp.error_for_status()?.text().await?;
+            let prefer_hd = self.prefer_hd;
+            apods.extend(
+                self.parse::<Vec<Apod>>(Upstream::Apod, &body)?
+                    .into_iter()
+                    .map(|apod| apod.into_metadata(chunk_start, prefer_hd)),
+            );
+            chunk_start = chunk_end + Duration::days(1);
+        }
+
+        Ok(apods)
+    }
+
+    /// Gets the URL of the preferred image of the given APOD, published on the given date, along with the URL of its
+    /// standard-resolution image. Returns an error if the APOD is not an image.
+    fn image_urls(
+        &self,
+        apod: &Apod,
+        published: NaiveDate,
+    ) -> Result<(String, String), Box<dyn Error + Send + Sync>> {
+        if apod.media_type != "image" {
+            return Err(EarendelError::ApodNotImage {
+                media_type: apod.media_type.to_owned(),
+                page_url: apod_page_url(published),
+            }
+            .into());
+        }
+        let Some(standard_url) = apod.url.to_owned() else {
+            return Err(EarendelError::ApodImageUrlMissing {
+                date: published,
+                media_type: apod.media_type.to_owned(),
+                page_url: apod_page_url(published),
+            }
+            .into());
+        };
+        let image_url = match apod.hdurl.as_ref() {
+            Some(hdurl) if self.prefer_hd => hdurl.to_owned(),
+            Some(_) | None => standard_url.to_owned(),
+        };
+
+        Ok((image_url, standard_url))
+    }
+
+    /// Fetches the translated title and explanation of the APOD of the given date, along with their language, from the
+    /// configured mirror. Returns None if no mirror is configured or if the mirror has not translated the APOD, in
+    /// which case the English text is used.
+    async fn fetch_translation(&self, date: NaiveDate) -> Option<(String, Option<String>, String)> {
+        let mirror = self.locale.as_ref()?;
+        let page = async {
+            let resp = self
+                .send(Upstream::ApodMirror, self.client.get(mirror.page_url(date)))
+                .await?
+                .error_for_status()?;
+            Ok::<String, Box<dyn Error + Send + Sync>>(resp.text().await?)
+        };
+
+        match page.await {
+            Ok(page) => parse_mirror_page(&page, mirror)
+                .map(|(title, explanation)| (title, explanation, mirror.language.to_owned())),
+            Err(e) => {
+                debug!("no {} translation of the APOD: {}", mirror.language, e);
+                None
+            }
+        }
+    }
+
+    /// Fetches the credits and links of the APOD web page of the given date. Failures are logged rather than returned,
+    /// as the details are optional.
+    async fn fetch_page_details(&self, date: NaiveDate) -> Option<ApodPageDetails> {
+        let page = async {
+            let resp = self
+                .send(Upstream::ApodWebsite, self.client.get(apod_page_url(date)))
+                .await?
+                .error_for_status()?;
+            Ok::<String, Box<dyn Error + Send + Sync>>(resp.text().await?)
+        };
+
+        match page.await {
+            Ok(page) => Some(parse_page_details(&page)),
+            Err(e) => {
+                warn!("failed to fetch the APOD page: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Gets the APOD image at the given URL, from the cache if it is the image of the cached APOD.
+    async fn load_apod_image(
+        &self,
+        image_url: &str,
+    ) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        let cached = self
+            .cached_apod()
+            .as_ref()
+            .filter(|cached| cached.apod.image_url == image_url)
+            .and_then(|cached| cached.apod.image.bytes())
+            .map(<[u8]>::to_vec);
+        if let Some(img) = cached {
+            return Ok(img);
+        }
+
+        Ok(self.download_apod_image(image_url).await?.0)
+    }
+
+    async fn download_apod_image(
+        &self,
+        image_url: &str,
+    ) -> Result<(Vec<u8>, Option<ImageMetadata>, ImageValidators), Box<dyn Error + Send + Sync>>
+    {
+        // only the same image URL can be validated against what was previously downloaded
+        let previous = self
+            .cached_apod()
+            .as_ref()
+            .filter(|cached| cached.apod.image_url == image_url)
+            .cloned();
+
+        let mut request = self.client.get(image_url);
+        if let Some(previous) = previous {
+            if let Some(etag) = previous.validators.etag.as_ref() {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = previous.validators.last_modified.as_ref() {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        let resp = self.send(Upstream::ApodWebsite, request).await?;
+
+        match previous {
+            Some(previous) if resp.status() == StatusCode::NOT_MODIFIED => Ok((
+                previous.apod.img().to_vec(),
+                previous.apod.metadata.to_owned(),
+                previous.validators.to_owned(),
+            )),
+            Some(_) | None => {
+                let resp = resp.error_for_status()?;
+                let validators = ImageValidators::from_headers(resp.headers());
+                let img = self.read_limited(resp).await?;
+                // transcoding drops the embedded metadata, so it is read from the original
+                let metadata = read_metadata(&img);
+                #[cfg(any(feature = "webp", feature = "avif"))]
+                let img = match self.transcode.as_ref() {
+                    Some(options) => crate::imaging::transcode(&img, options)?,
+                    None => img,
+                };
+                Ok((img, metadata, validators))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apod_with_image(image: ApodImage) -> EarendelApod {
+        EarendelApod {
+            title: String::from("NGC 4632"),
+            date: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            image_url: String::new(),
+            explanation: None,
+            image,
+            width: None,
+            height: None,
+            copyright: None,
+            metadata: None,
+            language: None,
+            page_details: None,
+            concepts: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn base64_encodes_rfc_4648_vectors() {
+        for (input, expected) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(
+                base64_encode(input.as_bytes()),
+                expected,
+                "input {:?}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn base64_encodes_every_digit() {
+        let bytes = (0..=255).collect::<Vec<u8>>();
+        let encoded = base64_encode(&bytes);
+        assert_eq!(encoded.len(), 344);
+        assert!(encoded.starts_with("AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8g"));
+        assert!(encoded.ends_with("+/w=="));
+    }
+
+    #[test]
+    fn data_uri_uses_detected_type() {
+        let png = b"\x89PNG\r\n\x1a\n".to_vec();
+        let apod = apod_with_image(ApodImage::loaded(String::new(), png));
+        assert_eq!(
+            apod.to_data_uri().as_deref(),
+            Some("data:image/png;base64,iVBORw0KGgo=")
+        );
+    }
+
+    #[test]
+    fn data_uri_requires_downloaded_image() {
+        assert_eq!(apod_with_image(ApodImage::default()).to_data_uri(), None);
+    }
+
+    #[test]
+    fn deserialized_image_keeps_url() {
+        let mut apod = apod_with_image(ApodImage::new(String::from(
+            "https://apod.nasa.gov/apod/image/2401/NGC4632.jpg",
+        )));
+        apod.image_url = apod.image.url().to_owned();
+        let json = serde_json::to_string(&apod).unwrap();
+        let apod = serde_json::from_str::<EarendelApod>(&json).unwrap();
+        assert_eq!(apod.image.url(), apod.image_url);
+        assert_eq!(apod.image.bytes(), None);
+
+        let mut apod = apod_with_image(ApodImage::loaded(String::new(), vec![1, 2, 3]));
+        apod.image_url = String::from("https://apod.nasa.gov/apod/image/2401/NGC4632.jpg");
+        let json = serde_json::to_string(&apod).unwrap();
+        let apod = serde_json::from_str::<EarendelApod>(&json).unwrap();
+        assert_eq!(apod.image.url(), apod.image_url);
+        assert_eq!(apod.image.bytes(), Some(&[1, 2, 3][..]));
+    }
+
+    #[test]
+    fn concepts_keep_index_order() {
+        let concepts = (0..12)
+            .map(|index| (index.to_string(), format!("concept {}", index).into()))
+            .collect::<serde_json::Map<String, serde_json::Value>>();
+        let apod = serde_json::from_value::<Apod>(serde_json::json!({
+            "date": "2024-01-01",
+            "media_type": "image",
+            "title": "NGC 4632",
+            "concepts": concepts,
+        }))
+        .unwrap();
+
+        let expected = (0..12)
+            .map(|index| format!("concept {}", index))
+            .collect::<Vec<String>>();
+        assert_eq!(apod.concepts(), expected);
+    }
+
+    #[test]
+    fn concepts_ignore_disabled_message() {
+        let apod = serde_json::from_value::<Apod>(serde_json::json!({
+            "date": "2024-01-01",
+            "media_type": "image",
+            "title": "NGC 4632",
+            "concepts": "concept_tags functionality turned off in current service",
+        }))
+        .unwrap();
+
+        assert!(apod.concepts().is_empty());
+    }
+}
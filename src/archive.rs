@@ -0,0 +1,389 @@
+//! The common interface of the observation archives searched for the APOD target.
+
+use astro_rs::coordinates::Icrs;
+
+use async_trait::async_trait;
+
+use chrono::{DateTime, Utc};
+
+use futures::future;
+use futures::stream::{self, StreamExt};
+
+use reqwest::header::CONTENT_LENGTH;
+
+use serde::{Deserialize, Serialize};
+
+use tracing::{instrument, warn};
+
+use uom::si::f64::Length;
+use uom::si::length::{micrometer, nanometer};
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+
+use crate::{EarendelServer, SkyCoords, TargetInfo, Upstream};
+
+/// The number of observations listed per page.
+pub(crate) const PAGE_SIZE: usize = 25;
+/// The radius of the cone searched around the target, in degrees.
+pub(crate) const SEARCH_RADIUS_DEG: f64 = 0.2;
+/// The largest number of pages fetched by `EarendelServer::get_all_archive_fits_for_apod`, which bounds the requests
+/// made for targets with very many observations.
+const MAX_PAGES: usize = 200;
+
+/// A single observation listed by an archive.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct Observation {
+    /// The name of the archive that listed the observation.
+    pub archive: String,
+    /// The archive identifier of the observation.
+    pub obs_id: String,
+    /// The mission or collection of the observation, such as HST or JWST.
+    pub collection: Option<String>,
+    /// The instrument that acquired the observation.
+    pub instrument: Option<String>,
+    /// The filters used for the observation.
+    pub filters: Option<String>,
+    /// The name of the observed target.
+    pub target_name: Option<String>,
+    /// The classification of the observed target.
+    pub target_classification: Option<String>,
+    /// The type of the data product, such as image or spectrum.
+    pub dataproduct_type: Option<String>,
+    /// The right ascension of the observation, in degrees.
+    pub ra: Option<f64>,
+    /// The declination of the observation, in degrees.
+    pub dec: Option<f64>,
+    /// The exposure time of the observation, in seconds.
+    pub exposure_time: Option<f64>,
+    /// When the observation started.
+    pub start_time: Option<DateTime<Utc>>,
+    /// When the observation ended.
+    pub end_time: Option<DateTime<Utc>>,
+    /// When the data of the observation becomes public, at the end of its proprietary period.
+    pub release_date: Option<DateTime<Utc>>,
+    /// The URL of a preview image of the observation.
+    pub preview_url: Option<String>,
+    /// The URL of the observation data.
+    pub data_url: Option<String>,
+    /// The archive identifier used to list the data products of the observation, such as the MAST `obsid`, if it
+    /// differs from `obs_id`.
+    pub product_group: Option<String>,
+    /// The footprint of the observation on the sky, as an STC-S region. See `Observation::footprint`.
+    pub s_region: Option<String>,
+    /// The angular distance of the observation from the searched position, in arcminutes, if reported by the archive.
+    #[serde(default)]
+    pub distance: Option<f64>,
+    /// The shortest wavelength of the bandpass of the observation, if reported by the archive. Serialized in meters.
+    #[serde(default)]
+    #[cfg_attr(feature = "graphql", graphql(skip))]
+    pub em_min: Option<Length>,
+    /// The longest wavelength of the bandpass of the observation, if reported by the archive. Serialized in meters.
+    #[serde(default)]
+    #[cfg_attr(feature = "graphql", graphql(skip))]
+    pub em_max: Option<Length>,
+}
+
+impl Observation {
+    /// Determines whether the data of the observation is public, so that its data URL can be downloaded.
+    /// Observations without a known release date are assumed to be public.
+    pub fn is_public_now(&self) -> bool {
+        self.release_date
+            .is_none_or(|release_date| release_date <= Utc::now())
+    }
+
+    /// Gets the shortest wavelength of the bandpass of the observation in nanometers, as used for optical
+    /// observations.
+    pub fn em_min_nm(&self) -> Option<f64> {
+        self.em_min.map(|em_min| em_min.get::<nanometer>())
+    }
+
+    /// Gets the longest wavelength of the bandpass of the observation in nanometers, as used for optical
+    /// observations.
+    pub fn em_max_nm(&self) -> Option<f64> {
+        self.em_max.map(|em_max| em_max.get::<nanometer>())
+    }
+
+    /// Gets the shortest wavelength of the bandpass of the observation in micrometers, as used for infrared
+    /// observations.
+    pub fn em_min_um(&self) -> Option<f64> {
+        self.em_min.map(|em_min| em_min.get::<micrometer>())
+    }
+
+    /// Gets the longest wavelength of the bandpass of the observation in micrometers, as used for infrared
+    /// observations.
+    pub fn em_max_um(&self) -> Option<f64> {
+        self.em_max.map(|em_max| em_max.get::<micrometer>())
+    }
+}
+
+/// Information used to display FITS files available for the APOD.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct EarendelFits {
+    /// The names of the FITS files for the current page.
+    pub files: Vec<String>,
+    /// The size of each FITS file, in bytes, by name. Sizes are only known once requested with
+    /// `EarendelServer::get_file_sizes`, and are omitted for files whose size the archive does not report.
+    #[serde(default)]
+    pub file_sizes: BTreeMap<String, u64>,
+    /// The observations for the current page.
+    pub observations: Vec<Observation>,
+    /// The current page number.
+    pub page: usize,
+    /// The total number of available FITS files.
+    pub total_hits: usize,
+    /// The largest number of observations listed per page.
+    #[serde(default)]
+    pub page_size: usize,
+    /// The number of pages needed to list every available observation.
+    #[serde(default)]
+    pub total_pages: usize,
+    /// Whether a page follows the current page.
+    #[serde(default)]
+    pub has_next: bool,
+    /// Whether a page precedes the current page.
+    #[serde(default)]
+    pub has_prev: bool,
+    /// The SIMBAD details of the searched target, if known.
+    pub target: Option<TargetInfo>,
+}
+
+impl EarendelFits {
+    /// Creates a page of results listing the given files and observations, deriving the paging metadata from the
+    /// page number, page size, and total. Pages 0 and 1 both list the first page.
+    pub(crate) fn new(
+        files: Vec<String>,
+        observations: Vec<Observation>,
+        page: usize,
+        page_size: usize,
+        total_hits: usize,
+    ) -> Self {
+        let total_pages = total_hits.div_ceil(page_size.max(1));
+        EarendelFits {
+            files,
+            file_sizes: BTreeMap::new(),
+            observations,
+            page,
+            total_hits,
+            page_size,
+            total_pages,
+            has_next: page.max(1) < total_pages,
+            has_prev: page > 1,
+            target: None,
+        }
+    }
+
+    /// Sorts the observations by their distance from the searched position, closest first. Observations of unknown
+    /// distance are listed last, in their original order. Returns the results for chaining with other refinements.
+    pub fn sort_by_distance(&mut self) -> &mut Self {
+        self.observations
+            .sort_by(|a, b| match (a.distance, b.distance) {
+                (Some(a), Some(b)) => a.total_cmp(&b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            });
+        self
+    }
+
+    /// Removes the observations, along with their files, whose mission or collection is not one of the given ones,
+    /// such as `HST` or `JWST`, compared without regard to ASCII case. Returns the results for chaining with other
+    /// refinements.
+    pub fn only_missions<S: AsRef<str>>(&mut self, missions: &[S]) -> &mut Self {
+        self.retain_observations(|observation| {
+            observation.collection.as_deref().is_some_and(|collection| {
+                missions
+                    .iter()
+                    .any(|mission| mission.as_ref().eq_ignore_ascii_case(collection))
+            })
+        });
+        self
+    }
+
+    /// Removes the observations, along with their files, exposed for less than the given time, in seconds.
+    /// Observations of unknown exposure time are removed too. Returns the results for chaining with other
+    /// refinements.
+    pub fn min_exposure(&mut self, seconds: f64) -> &mut Self {
+        self.retain_observations(|observation| {
+            observation
+                .exposure_time
+                .is_some_and(|exposure_time| exposure_time >= seconds)
+        });
+        self
+    }
+
+    /// Keeps only the observations matching the given predicate, removing the files of the others.
+    pub(crate) fn retain_observations<F: FnMut(&Observation) -> bool>(&mut self, predicate: F) {
+        let (kept, removed): (Vec<Observation>, Vec<Observation>) =
+            self.observations.drain(..).partition(predicate);
+        self.observations = kept;
+        self.files.retain(|file| {
+            !removed
+                .iter()
+                .any(|observation| observation.data_url.as_deref() == Some(file.as_str()))
+        });
+    }
+}
+
+/// An archive that can list observations around a position.
+#[async_trait]
+pub trait ObservationArchive: Send + Sync {
+    /// Gets the name of the archive.
+    fn name(&self) -> &str;
+
+    /// Lists a page of observations near the given coordinates, using the given server for web requests.
+    async fn search(
+        &self,
+        server: &EarendelServer,
+        coords: &Icrs,
+        page: usize,
+    ) -> Result<EarendelFits, Box<dyn Error + Send + Sync>>;
+}
+
+impl EarendelServer {
+    /// Gets a page of observations near the given coordinates from the given archive. Returns an error if the web
+    /// request fails.
+    #[instrument(skip(self, archive, coords), fields(archive = archive.name()))]
+    pub async fn search_archive(
+        &self,
+        archive: &dyn ObservationArchive,
+        coords: &Icrs,
+        page: usize,
+    ) -> Result<EarendelFits, Box<dyn Error + Send + Sync>> {
+        archive.search(self, coords, page).await
+    }
+
+    /// Gets a page of observations near the given coordinates, in any supported frame, from the given archive.
+    /// Returns an error if the web request fails.
+    pub async fn search_archive_at(
+        &self,
+        archive: &dyn ObservationArchive,
+        coords: SkyCoords,
+        page: usize,
+    ) -> Result<EarendelFits, Box<dyn Error + Send + Sync>> {
+        self.search_archive(archive, &coords.to_icrs(), page).await
+    }
+
+    /// Gets a page of observations of the current APOD's target from the given archive, along with the SIMBAD
+    /// details of the target. Returns an error if the target cannot be resolved or if the web request fails.
+    pub async fn get_archive_fits_for_apod(
+        &self,
+        archive: &dyn ObservationArchive,
+        page: usize,
+    ) -> Result<EarendelFits, Box<dyn Error + Send + Sync>> {
+        let coords = self.resolve_apod_target().await?;
+        let mut fits = self.search_archive(archive, &coords, page).await?;
+        fits.target = self.apod_target_info().await;
+
+        Ok(fits)
+    }
+
+    /// Gets every page of observations of the current APOD's target from the given archive, combined into one page
+    /// numbered 0, with duplicate files and observations removed. At most 200 pages are fetched, subject to the
+    /// configured rate limits. Returns an error if the target cannot be resolved or if a web request fails.
+    #[instrument(skip(self, archive), fields(archive = archive.name()))]
+    pub async fn get_all_archive_fits_for_apod(
+        &self,
+        archive: &dyn ObservationArchive,
+    ) -> Result<EarendelFits, Box<dyn Error + Send + Sync>> {
+        let coords = self.resolve_apod_target().await?;
+        let first = self.search_archive(archive, &coords, 1).await?;
+        let pages = first.total_hits.div_ceil(PAGE_SIZE).min(MAX_PAGES);
+
+        let mut all = EarendelFits {
+            files: Vec::new(),
+            file_sizes: BTreeMap::new(),
+            observations: Vec::new(),
+            page: 0,
+            total_hits: first.total_hits,
+            page_size: 0,
+            total_pages: 1,
+            has_next: false,
+            has_prev: false,
+            target: None,
+        };
+        let mut files = HashSet::new();
+        let mut observations = HashSet::new();
+        let mut fits = first;
+        let mut page = 1;
+        loop {
+            let listed = fits.observations.len();
+            for file in fits.files {
+                if files.insert(file.to_owned()) {
+                    all.files.push(file);
+                }
+            }
+            for observation in fits.observations {
+                let key = (
+                    observation.archive.to_owned(),
+                    observation.obs_id.to_owned(),
+                );
+                if observations.insert(key) {
+                    all.observations.push(observation);
+                }
+            }
+            // the total may change while paging, so an empty page also ends the listing
+            page += 1;
+            if page > pages || listed == 0 {
+                break;
+            }
+            fits = self.search_archive(archive, &coords, page).await?;
+        }
+        all.page_size = all.observations.len();
+        all.target = self.apod_target_info().await;
+
+        Ok(all)
+    }
+
+    /// Gets the SIMBAD details of the current APOD's target. Failures are logged rather than returned, as the
+    /// observations are still useful without the target details.
+    async fn apod_target_info(&self) -> Option<TargetInfo> {
+        match self.get_target_info(self.apod_target_name()).await {
+            Ok(info) => Some(info),
+            Err(e) => {
+                warn!("failed to get SIMBAD details of the APOD target: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Requests the size of each listed FITS file with a `HEAD` request, with at most `max_concurrent` requests in
+    /// flight at once, recording it in `file_sizes` so that the size can be shown before a file is downloaded. Files
+    /// whose size cannot be determined are left out.
+    #[instrument(skip(self, fits), fields(files = fits.files.len()))]
+    pub async fn get_file_sizes(&self, fits: &mut EarendelFits, max_concurrent: usize) {
+        let sizes = stream::iter(fits.files.iter())
+            .map(|file| async move {
+                self.get_file_size(file)
+                    .await
+                    .map(|size| (file.to_owned(), size))
+            })
+            .buffer_unordered(max_concurrent.max(1))
+            .filter_map(future::ready)
+            .collect::<Vec<(String, u64)>>()
+            .await;
+        fits.file_sizes.extend(sizes);
+    }
+
+    /// Requests the size of the given FITS file with a `HEAD` request, if it can be determined.
+    async fn get_file_size(&self, file: &str) -> Option<u64> {
+        match self.send(Upstream::Download, self.client.head(file)).await {
+            // the length is read from the header, as the body of a HEAD response is always empty
+            Ok(resp) if resp.status().is_success() => resp
+                .headers()
+                .get(CONTENT_LENGTH)
+                .and_then(|length| length.to_str().ok())
+                .and_then(|length| length.parse::<u64>().ok()),
+            Ok(resp) => {
+                warn!("failed to get the size of {}: {}", file, resp.status());
+                None
+            }
+            Err(e) => {
+                warn!("failed to get the size of {}: {}", file, e);
+                None
+            }
+        }
+    }
+}
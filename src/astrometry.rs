@@ -0,0 +1,228 @@
+//! Plate solving of images through the nova.astrometry.net API.
+
+use reqwest::multipart::{Form, Part};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use tracing::instrument;
+
+use std::env;
+use std::error::Error;
+use std::time::Duration;
+
+use crate::fits;
+use crate::runtime::sleep;
+use crate::wcs::Wcs;
+use crate::{EarendelServer, Upstream};
+
+const ASTROMETRY_API_URL: &str = "https://nova.astrometry.net/api";
+const ASTROMETRY_WCS_URL: &str = "https://nova.astrometry.net/wcs_file";
+/// The environment variable holding the key for the astrometry.net API.
+const API_KEY_VAR: &str = "EARENDEL_ASTROMETRY_API_KEY";
+/// The interval between checks of a submission's progress.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// The number of progress checks made before giving up on a submission.
+const MAX_POLLS: usize = 120;
+
+/// The solution of an image by astrometry.net.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PlateSolution {
+    /// The astrometry.net job that solved the image.
+    pub job_id: u64,
+    /// The right ascension of the center of the image, in degrees.
+    pub ra: f64,
+    /// The declination of the center of the image, in degrees.
+    pub dec: f64,
+    /// The radius of the field, in degrees.
+    pub radius: f64,
+    /// The scale of the image, in arcseconds per pixel.
+    pub pixel_scale: f64,
+    /// The position angle of the image up direction, in degrees east of north.
+    pub orientation: f64,
+    /// The world coordinate system of the image.
+    pub wcs: Wcs,
+}
+
+#[derive(Debug, Deserialize)]
+struct LoginResponse {
+    session: Option<String>,
+    errormessage: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UploadResponse {
+    subid: Option<u64>,
+    errormessage: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubmissionResponse {
+    #[serde(default)]
+    jobs: Vec<Option<u64>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JobResponse {
+    status: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CalibrationResponse {
+    ra: f64,
+    dec: f64,
+    radius: f64,
+    pixscale: f64,
+    orientation: f64,
+}
+
+impl EarendelServer {
+    /// Uploads the given image to astrometry.net and waits for its plate solution. This may take several minutes.
+    /// Returns an error if the EARENDEL_ASTROMETRY_API_KEY environment variable is not set, if the image cannot be
+    /// solved, or if a web request fails.
+    #[instrument(skip(self, img))]
+    pub async fn solve_plate(
+        &self,
+        img: &[u8],
+    ) -> Result<PlateSolution, Box<dyn Error + Send + Sync>> {
+        let api_key = env::var(API_KEY_VAR)?;
+
+        let login = json!({ "apikey": api_key }).to_string();
+        let request = self
+            .client
+            .post([ASTROMETRY_API_URL, "/login"].concat())
+            .form(&[("request-json", login.as_str())]);
+        let body = self
+            .send(Upstream::Astrometry, request)
+            .await?
+            .text()
+            .await?;
+        let login = self.parse::<LoginResponse>(Upstream::Astrometry, &body)?;
+        let session = login.session.ok_or_else(|| {
+            format!(
+                "astrometry.net login failed: {}",
+                login.errormessage.unwrap_or_default()
+            )
+        })?;
+
+        let upload =
+            json!({ "session": session, "publicly_visible": "n", "allow_commercial_use": "n" });
+        let form = Form::new()
+            .text("request-json", upload.to_string())
+            .part("file", Part::bytes(img.to_vec()).file_name("image"));
+        let request = self
+            .client
+            .post([ASTROMETRY_API_URL, "/upload"].concat())
+            .multipart(form);
+        let body = self
+            .send(Upstream::Astrometry, request)
+            .await?
+            .text()
+            .await?;
+        let upload = self.parse::<UploadResponse>(Upstream::Astrometry, &body)?;
+        let submission_id = upload.subid.ok_or_else(|| {
+            format!(
+                "astrometry.net upload failed: {}",
+                upload.errormessage.unwrap_or_default()
+            )
+        })?;
+
+        let mut job_id = None;
+        for _ in 0..MAX_POLLS {
+            if job_id.is_none() {
+                let request = self.client.get(format!(
+                    "{}/submissions/{}",
+                    ASTROMETRY_API_URL, submission_id
+                ));
+                let body = self
+                    .send(Upstream::Astrometry, request)
+                    .await?
+                    .text()
+                    .await?;
+                job_id = self
+                    .parse::<SubmissionResponse>(Upstream::Astrometry, &body)?
+                    .jobs
+                    .into_iter()
+                    .flatten()
+                    .next();
+            }
+            if let Some(job_id) = job_id {
+                let request = self
+                    .client
+                    .get(format!("{}/jobs/{}", ASTROMETRY_API_URL, job_id));
+                let body = self
+                    .send(Upstream::Astrometry, request)
+                    .await?
+                    .text()
+                    .await?;
+                match self
+                    .parse::<JobResponse>(Upstream::Astrometry, &body)?
+                    .status
+                    .as_str()
+                {
+                    "success" => return self.get_plate_solution(job_id).await,
+                    "failure" => {
+                        return Err(format!("astrometry.net could not solve job {}", job_id).into())
+                    }
+                    _ => {}
+                }
+            }
+            sleep(POLL_INTERVAL).await;
+        }
+
+        Err(format!(
+            "astrometry.net did not solve submission {} in time",
+            submission_id
+        )
+        .into())
+    }
+
+    /// Gets the calibration and WCS of the given solved job.
+    async fn get_plate_solution(
+        &self,
+        job_id: u64,
+    ) -> Result<PlateSolution, Box<dyn Error + Send + Sync>> {
+        let request = self.client.get(format!(
+            "{}/jobs/{}/calibration/",
+            ASTROMETRY_API_URL, job_id
+        ));
+        let body = self
+            .send(Upstream::Astrometry, request)
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let calibration = self.parse::<CalibrationResponse>(Upstream::Astrometry, &body)?;
+
+        let request = self
+            .client
+            .get(format!("{}/{}", ASTROMETRY_WCS_URL, job_id));
+        let wcs_file = self
+            .send(Upstream::Astrometry, request)
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        let header = fits::read_primary_header(wcs_file.as_ref())?;
+
+        Ok(PlateSolution {
+            job_id,
+            ra: calibration.ra,
+            dec: calibration.dec,
+            radius: calibration.radius,
+            pixel_scale: calibration.pixscale,
+            orientation: calibration.orientation,
+            wcs: Wcs::from_header(&header)?,
+        })
+    }
+
+    /// Uploads the current APOD image to astrometry.net and waits for its plate solution, identifying the field even
+    /// when the APOD does not name a catalogued object. Returns an error if the APOD or the solution cannot be
+    /// retrieved.
+    #[cfg(feature = "apod")]
+    pub async fn solve_apod_plate(&self) -> Result<PlateSolution, Box<dyn Error + Send + Sync>> {
+        let apod = self.get_apod_image().await?;
+
+        self.solve_plate(apod.img()).await
+    }
+}
@@ -0,0 +1,131 @@
+//! Per-upstream circuit breakers, which fail requests fast while an upstream is down.
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::metrics::Metrics;
+use crate::{EarendelError, Upstream};
+
+/// The state of the circuit breaker of an upstream.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum CircuitState {
+    /// Requests are sent normally.
+    #[default]
+    Closed,
+    /// The upstream failed repeatedly, so requests fail immediately with `EarendelError::CircuitOpen`.
+    Open,
+    /// The open period has elapsed, and a single probe request is being sent to determine whether the upstream has
+    /// recovered. Other requests fail immediately until it completes.
+    HalfOpen,
+}
+
+/// Determines when the circuit breaker of an upstream opens, and for how long.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct CircuitBreakerConfig {
+    /// The number of consecutive failed requests that opens the circuit.
+    pub failure_threshold: u32,
+    /// How long the circuit stays open before a probe request is allowed.
+    pub open_duration: Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        CircuitBreakerConfig {
+            failure_threshold: 5,
+            open_duration: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Circuit {
+    state: CircuitState,
+    failures: u32,
+    opened_at: Option<Instant>,
+}
+
+/// The circuit breakers of every upstream contacted by a server.
+#[derive(Debug)]
+pub(crate) struct CircuitBreakers {
+    config: CircuitBreakerConfig,
+    circuits: Mutex<HashMap<Upstream, Circuit>>,
+}
+
+impl CircuitBreakers {
+    pub(crate) fn new(config: CircuitBreakerConfig) -> Self {
+        CircuitBreakers {
+            config,
+            circuits: Mutex::default(),
+        }
+    }
+
+    /// Determines whether a request may be sent to the given upstream, returning `EarendelError::CircuitOpen` if not.
+    /// Once the open period has elapsed, the first caller is allowed through as the probe.
+    pub(crate) fn acquire(
+        &self,
+        upstream: Upstream,
+        metrics: &Metrics,
+    ) -> Result<(), EarendelError> {
+        let mut circuits = self.lock();
+        let circuit = circuits.entry(upstream).or_default();
+        match circuit.state {
+            CircuitState::Closed => Ok(()),
+            CircuitState::Open => {
+                let elapsed = circuit.opened_at.map(|at| at.elapsed()).unwrap_or_default();
+                if elapsed >= self.config.open_duration {
+                    circuit.state = CircuitState::HalfOpen;
+                    metrics.record_circuit(upstream, CircuitState::HalfOpen);
+                    Ok(())
+                } else {
+                    Err(EarendelError::CircuitOpen {
+                        upstream,
+                        retry_after: self.config.open_duration - elapsed,
+                    })
+                }
+            }
+            CircuitState::HalfOpen => Err(EarendelError::CircuitOpen {
+                upstream,
+                retry_after: Duration::ZERO,
+            }),
+        }
+    }
+
+    /// Records the outcome of a request to the given upstream that was allowed by `acquire`.
+    pub(crate) fn record(&self, upstream: Upstream, success: bool, metrics: &Metrics) {
+        let mut circuits = self.lock();
+        let circuit = circuits.entry(upstream).or_default();
+        let state = if success {
+            circuit.failures = 0;
+            CircuitState::Closed
+        } else {
+            circuit.failures = circuit.failures.saturating_add(1);
+            if circuit.state == CircuitState::HalfOpen
+                || circuit.failures >= self.config.failure_threshold
+            {
+                circuit.opened_at = Some(Instant::now());
+                CircuitState::Open
+            } else {
+                circuit.state
+            }
+        };
+        if state != circuit.state {
+            circuit.state = state;
+            metrics.record_circuit(upstream, state);
+        }
+    }
+
+    /// Gets the state of the circuit breaker of each contacted upstream.
+    pub(crate) fn states(&self) -> BTreeMap<Upstream, CircuitState> {
+        self.lock()
+            .iter()
+            .map(|(upstream, circuit)| (*upstream, circuit.state))
+            .collect()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<Upstream, Circuit>> {
+        self.circuits.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
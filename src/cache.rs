@@ -0,0 +1,178 @@
+//! Storage of cached APODs that can be shared between servers, such as the instances of a deployment.
+
+use async_trait::async_trait;
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// A store of cached entries, by key. Entries may expire before their time to live, but never after it.
+#[async_trait]
+pub trait CacheBackend: Send + Sync {
+    /// Gets the entry with the given key, or None if there is none or it has expired.
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Box<dyn Error + Send + Sync>>;
+
+    /// Stores the given entry under the given key, replacing any previous entry, until the given time to live
+    /// elapses.
+    async fn set(
+        &self,
+        key: &str,
+        value: Vec<u8>,
+        ttl: Duration,
+    ) -> Result<(), Box<dyn Error + Send + Sync>>;
+}
+
+/// A cache held in memory. Clones share the same entries, so one cache can be used by several servers in the same
+/// process.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryCache {
+    entries: Arc<Mutex<HashMap<String, (Vec<u8>, SystemTime)>>>,
+}
+
+#[async_trait]
+impl CacheBackend for MemoryCache {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Box<dyn Error + Send + Sync>> {
+        let mut entries = self.entries.lock().map_err(|e| e.to_string())?;
+        match entries.get(key) {
+            Some((value, expires)) if *expires > SystemTime::now() => Ok(Some(value.to_owned())),
+            Some(_) => {
+                entries.remove(key);
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn set(
+        &self,
+        key: &str,
+        value: Vec<u8>,
+        ttl: Duration,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut entries = self.entries.lock().map_err(|e| e.to_string())?;
+        entries.insert(key.to_owned(), (value, SystemTime::now() + ttl));
+
+        Ok(())
+    }
+}
+
+/// A cache held in files in a directory, which can be shared by servers on the same host or on a shared volume.
+#[derive(Clone, Debug)]
+pub struct DiskCache {
+    dir: PathBuf,
+}
+
+impl DiskCache {
+    /// Creates a cache in the given directory, which is created if it does not exist.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        DiskCache { dir: dir.into() }
+    }
+
+    /// Gets the path of the file holding the entry with the given key. Keys are escaped, so that they cannot name
+    /// files outside the directory.
+    fn path(&self, key: &str) -> PathBuf {
+        let name = key
+            .chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c.to_string(),
+                _ => format!("%{:02X}", c as u32),
+            })
+            .collect::<String>();
+
+        self.dir.join(name)
+    }
+}
+
+#[async_trait]
+impl CacheBackend for DiskCache {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Box<dyn Error + Send + Sync>> {
+        let path = self.path(key);
+        let expired = match fs::metadata(&path) {
+            Ok(metadata) => metadata.modified()? <= SystemTime::now(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        if expired {
+            fs::remove_file(&path)?;
+            return Ok(None);
+        }
+
+        Ok(Some(fs::read(path)?))
+    }
+
+    async fn set(
+        &self,
+        key: &str,
+        value: Vec<u8>,
+        ttl: Duration,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(key);
+        // the entry is written to a temporary file first, so that readers never see a partial entry
+        let partial = path.with_extension("partial");
+        fs::write(&partial, value)?;
+        // the modification time of the file records when the entry expires
+        fs::File::options()
+            .write(true)
+            .open(&partial)?
+            .set_modified(SystemTime::now() + ttl)?;
+        fs::rename(partial, path)?;
+
+        Ok(())
+    }
+}
+
+/// A cache held in Redis, which can be shared by servers on different hosts.
+#[cfg(feature = "redis")]
+#[derive(Clone, Debug)]
+pub struct RedisCache {
+    client: redis::Client,
+    prefix: String,
+}
+
+#[cfg(feature = "redis")]
+impl RedisCache {
+    /// Creates a cache in the Redis server at the given URL, such as `redis://127.0.0.1/`, with keys prefixed by
+    /// `earendel:`. Returns an error if the URL is invalid.
+    pub fn new(url: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Ok(RedisCache {
+            client: redis::Client::open(url)?,
+            prefix: String::from("earendel:"),
+        })
+    }
+}
+
+#[cfg(feature = "redis")]
+#[async_trait]
+impl CacheBackend for RedisCache {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Box<dyn Error + Send + Sync>> {
+        let mut connection = self.client.get_multiplexed_async_connection().await?;
+        let value = redis::cmd("GET")
+            .arg([self.prefix.as_str(), key].concat())
+            .query_async::<_, Option<Vec<u8>>>(&mut connection)
+            .await?;
+
+        Ok(value)
+    }
+
+    async fn set(
+        &self,
+        key: &str,
+        value: Vec<u8>,
+        ttl: Duration,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut connection = self.client.get_multiplexed_async_connection().await?;
+        redis::cmd("SET")
+            .arg([self.prefix.as_str(), key].concat())
+            .arg(value)
+            .arg("PX")
+            .arg(ttl.as_millis().max(1) as u64)
+            .query_async::<_, ()>(&mut connection)
+            .await?;
+
+        Ok(())
+    }
+}
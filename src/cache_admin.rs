@@ -0,0 +1,123 @@
+//! Inspection and invalidation of the in-memory caches of a server, so that bad cached data can be dropped without
+//! restarting the process.
+
+use chrono::NaiveDate;
+
+use serde::{Deserialize, Serialize};
+
+use tracing::info;
+
+use std::time::Duration;
+
+use crate::EarendelServer;
+
+/// The cache holding an entry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum CacheKind {
+    /// The current APOD, keyed by its publication date.
+    Apod,
+    /// The latest EPIC images, keyed by the date they were taken.
+    Epic,
+    /// The current image of a daily image source, keyed by the name of the source.
+    DailyImage,
+}
+
+/// An entry of an in-memory cache.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CacheEntry {
+    /// The cache holding the entry.
+    pub kind: CacheKind,
+    /// The key of the entry, such as a date or the name of a daily image source.
+    pub key: String,
+    /// The time since the entry was fetched or last confirmed to be current, if known.
+    pub age: Option<Duration>,
+    /// The size of the images held by the entry, in bytes.
+    pub bytes: u64,
+}
+
+/// The entries of the in-memory caches of a server.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct CacheStats {
+    /// The cached entries.
+    pub entries: Vec<CacheEntry>,
+}
+
+impl CacheStats {
+    /// Gets the total size of the images held by the cached entries, in bytes.
+    pub fn total_bytes(&self) -> u64 {
+        self.entries.iter().map(|entry| entry.bytes).sum()
+    }
+}
+
+impl EarendelServer {
+    /// Lists the entries of the in-memory caches of this server, along with their ages and sizes.
+    pub fn cache_stats(&self) -> CacheStats {
+        let mut entries = Vec::new();
+        entries.extend(self.cached_apod().as_ref().map(|cached| cached.entry()));
+        entries.extend(self.cached_epic().as_ref().map(|cached| cached.entry()));
+        entries.extend(
+            self.daily_images()
+                .iter()
+                .map(|(source, cached)| cached.entry(source)),
+        );
+
+        CacheStats { entries }
+    }
+
+    /// Drops every entry of the in-memory caches of this server, so that the next requests fetch fresh data. Caches
+    /// shared with other servers, such as the HTTP cache and the cache backend, are kept.
+    pub fn clear_cache(&self) {
+        info!("clearing the in-memory caches");
+        self.cache_apod(None);
+        *self.cached_epic() = None;
+        self.daily_images().clear();
+    }
+
+    /// Drops the cached APOD, EPIC images, and daily images of the given date. Returns whether any entry was dropped.
+    pub fn invalidate_date(&self, date: NaiveDate) -> bool {
+        let mut invalidated = false;
+        let apod_cached = self
+            .cached_apod()
+            .as_ref()
+            .is_some_and(|cached| cached.date() == date);
+        if apod_cached {
+            self.cache_apod(None);
+            invalidated = true;
+        }
+        let mut cached_epic = self.cached_epic();
+        if cached_epic
+            .as_ref()
+            .is_some_and(|cached| cached.date() == date)
+        {
+            *cached_epic = None;
+            invalidated = true;
+        }
+        let mut daily_images = self.daily_images();
+        let cached_images = daily_images.len();
+        daily_images.retain(|_, cached| cached.date() != date);
+        invalidated |= daily_images.len() != cached_images;
+        if invalidated {
+            info!("invalidated the cached entries of {}", date);
+        }
+
+        invalidated
+    }
+
+    /// Drops the cached image of the given daily image source, such as `eso-potw`. Returns whether an entry was
+    /// dropped.
+    pub fn invalidate_daily_image(&self, source: &str) -> bool {
+        self.daily_images().remove(source).is_some()
+    }
+
+    /// Drops every response in the HTTP cache configured with `EarendelServerBuilder::http_cache`. Returns an error if
+    /// the cache directory cannot be cleared.
+    #[cfg(feature = "http-cache")]
+    pub async fn clear_http_cache(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if let Some(config) = self.http_cache.as_ref() {
+            info!("clearing the HTTP cache in {}", config.dir.display());
+            cacache::clear(&config.dir).await?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+//! A cheaply cloneable handle to a shared `EarendelServer`.
+
+#[cfg(feature = "apod")]
+use chrono::NaiveDate;
+
+#[cfg(feature = "apod")]
+use tokio::sync::broadcast;
+#[cfg(feature = "apod")]
+use tokio::task::JoinHandle;
+
+use std::collections::BTreeMap;
+#[cfg(feature = "apod")]
+use std::error::Error;
+#[cfg(feature = "apod")]
+use std::path::Path;
+use std::sync::Arc;
+
+#[cfg(feature = "mast")]
+use crate::EarendelFits;
+#[cfg(feature = "metrics")]
+use crate::MetricsSnapshot;
+#[cfg(feature = "apod")]
+use crate::{CacheSnapshot, CacheStats, DailyImage, EarendelApod, EpicImage, SnapshotFormat};
+use crate::{CircuitState, EarendelServer, HealthReport, Upstream};
+
+/// A handle to an `EarendelServer` that is cheap to clone and can be shared between request handlers and background
+/// tasks. All clones share the same server, and so the same caches, metrics, rate limits, and circuit breakers. Calls
+/// through the handle run concurrently; only the cached state of the server is locked, and never across a request.
+#[derive(Clone)]
+pub struct EarendelClient {
+    server: Arc<EarendelServer>,
+}
+
+impl EarendelClient {
+    /// Creates a handle to the given server.
+    pub fn new(server: EarendelServer) -> Self {
+        EarendelClient {
+            server: Arc::new(server),
+        }
+    }
+
+    /// Gets the server, for calls not exposed by the handle.
+    pub fn server(&self) -> &EarendelServer {
+        &self.server
+    }
+
+    /// Gets the shared server, for APIs that take an `Arc<EarendelServer>`, such as `EarendelServer::spawn_refresher`.
+    pub fn shared(&self) -> Arc<EarendelServer> {
+        Arc::clone(&self.server)
+    }
+
+    /// Gets the current APOD image data. See `EarendelServer::get_apod_image`.
+    #[cfg(feature = "apod")]
+    pub async fn get_apod_image(&self) -> Result<EarendelApod, Box<dyn Error + Send + Sync>> {
+        self.server.get_apod_image().await
+    }
+
+    /// Gets the APOD image data for the given date. See `EarendelServer::get_apod_image_for_date`.
+    #[cfg(feature = "apod")]
+    pub async fn get_apod_image_for_date(
+        &self,
+        date: NaiveDate,
+    ) -> Result<EarendelApod, Box<dyn Error + Send + Sync>> {
+        self.server.get_apod_image_for_date(date).await
+    }
+
+    /// Subscribes to newly cached APODs. See `EarendelServer::subscribe_apod`.
+    #[cfg(feature = "apod")]
+    pub fn subscribe_apod(&self) -> broadcast::Receiver<Arc<EarendelApod>> {
+        self.server.subscribe_apod()
+    }
+
+    /// Spawns a task refreshing the APOD cache of the server. See `EarendelServer::spawn_refresher`.
+    #[cfg(feature = "apod")]
+    pub fn spawn_refresher(&self) -> JoinHandle<()> {
+        EarendelServer::spawn_refresher(self.shared())
+    }
+
+    /// Refreshes the APOD cache of the server, for spawning onto an executor other than tokio. See
+    /// `EarendelServer::run_refresher`.
+    #[cfg(feature = "apod")]
+    pub async fn run_refresher(&self) {
+        EarendelServer::run_refresher(self.shared()).await
+    }
+
+    /// Gets the current image of the given daily image source. See `EarendelServer::get_daily_image`.
+    #[cfg(feature = "apod")]
+    pub async fn get_daily_image(
+        &self,
+        source: &str,
+    ) -> Result<DailyImage, Box<dyn Error + Send + Sync>> {
+        self.server.get_daily_image(source).await
+    }
+
+    /// Gets up to the given number of the latest EPIC images. See `EarendelServer::get_epic_images`.
+    #[cfg(feature = "apod")]
+    pub async fn get_epic_images(
+        &self,
+        limit: usize,
+    ) -> Result<Vec<EpicImage>, Box<dyn Error + Send + Sync>> {
+        self.server.get_epic_images(limit).await
+    }
+
+    /// Lists the entries of the in-memory caches of the server. See `EarendelServer::cache_stats`.
+    #[cfg(feature = "apod")]
+    pub fn cache_stats(&self) -> CacheStats {
+        self.server.cache_stats()
+    }
+
+    /// Drops every entry of the in-memory caches of the server. See `EarendelServer::clear_cache`.
+    #[cfg(feature = "apod")]
+    pub fn clear_cache(&self) {
+        self.server.clear_cache()
+    }
+
+    /// Drops the cached entries of the given date. See `EarendelServer::invalidate_date`.
+    #[cfg(feature = "apod")]
+    pub fn invalidate_date(&self, date: NaiveDate) -> bool {
+        self.server.invalidate_date(date)
+    }
+
+    /// Drops the cached image of the given daily image source. See `EarendelServer::invalidate_daily_image`.
+    #[cfg(feature = "apod")]
+    pub fn invalidate_daily_image(&self, source: &str) -> bool {
+        self.server.invalidate_daily_image(source)
+    }
+
+    /// Replaces the cached state of the server with the given snapshot. See `EarendelServer::import_cache`.
+    #[cfg(feature = "apod")]
+    pub fn import_cache(
+        &self,
+        snapshot: CacheSnapshot,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.server.import_cache(snapshot)
+    }
+
+    /// Restores the cached state of the server from the given file. See `EarendelServer::load_cache`.
+    #[cfg(feature = "apod")]
+    pub fn load_cache<P: AsRef<Path>>(
+        &self,
+        path: P,
+        format: SnapshotFormat,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.server.load_cache(path, format)
+    }
+
+    /// Gets a page of FITS observations of the current APOD target. See `EarendelServer::get_fits_for_apod`.
+    #[cfg(feature = "mast")]
+    pub async fn get_fits_for_apod(
+        &self,
+        page: usize,
+    ) -> Result<EarendelFits, Box<dyn Error + Send + Sync>> {
+        self.server.get_fits_for_apod(page).await
+    }
+
+    /// Gets a snapshot of the metrics recorded by the server.
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.server.metrics()
+    }
+
+    /// Gets the state of the circuit breaker of each contacted upstream.
+    pub fn circuit_states(&self) -> BTreeMap<Upstream, CircuitState> {
+        self.server.circuit_states()
+    }
+
+    /// Probes the main upstreams. See `EarendelServer::health`.
+    pub async fn health(&self) -> HealthReport {
+        self.server.health().await
+    }
+}
+
+impl From<EarendelServer> for EarendelClient {
+    fn from(server: EarendelServer) -> Self {
+        EarendelClient::new(server)
+    }
+}
+
+impl From<Arc<EarendelServer>> for EarendelClient {
+    fn from(server: Arc<EarendelServer>) -> Self {
+        EarendelClient { server }
+    }
+}
@@ -0,0 +1,422 @@
+//! Conversions between coordinate representations.
+
+use astro_rs::coordinates::{EquatorialCoord, Icrs};
+
+use serde::{Deserialize, Serialize};
+
+use tracing::field::Empty;
+use tracing::{debug, info_span, instrument, Instrument, Span};
+
+use uom::si::angle::degree;
+use uom::si::f64::Angle;
+
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::breaker::CircuitBreakers;
+use crate::metrics::Metrics;
+use crate::runtime::sleep;
+use crate::{rebase_url, EarendelServer, ErrorCategory, RetryPolicy, Upstream};
+
+/// The CDS Sesame name resolver, queried for ICRS coordinates in plain text when the base URL of the resolver is
+/// overridden.
+const SESAME_URL: &str = "https://cds.unistra.fr/cgi-bin/nph-sesame/-oI/A";
+
+/// Creates ICRS coordinates from a right ascension and declination, in degrees.
+pub(crate) fn icrs_from_degrees(ra: f64, dec: f64) -> Icrs {
+    Icrs {
+        coords: EquatorialCoord {
+            ra: Angle::new::<degree>(ra),
+            dec: Angle::new::<degree>(dec),
+        },
+    }
+}
+
+/// Gets the right ascension and declination of the given ICRS coordinates, in degrees.
+pub(crate) fn icrs_to_degrees(coords: &Icrs) -> (f64, f64) {
+    (
+        coords.coords.ra.get::<degree>(),
+        coords.coords.dec.get::<degree>(),
+    )
+}
+
+/// The rotation from ICRS to Galactic coordinates, as defined for the Hipparcos catalogue.
+const ICRS_TO_GALACTIC: [[f64; 3]; 3] = [
+    [
+        -0.054_875_560_416_215_4,
+        -0.873_437_090_234_885,
+        -0.483_835_015_548_713_2,
+    ],
+    [
+        0.494_109_427_875_583_7,
+        -0.444_829_629_960_011_2,
+        0.746_982_244_497_218_9,
+    ],
+    [
+        -0.867_666_149_019_004_7,
+        -0.198_076_373_431_201_5,
+        0.455_983_776_175_066_9,
+    ],
+];
+/// The frame bias from ICRS to the FK5 J2000 mean equator and equinox, to first order, from the IERS Conventions.
+const ICRS_TO_FK5: [[f64; 3]; 3] = {
+    const MAS: f64 = std::f64::consts::PI / (180.0 * 3_600_000.0);
+    let (d_alpha, xi, eta) = (-14.6 * MAS, -16.617 * MAS, -6.819_2 * MAS);
+    [[1.0, d_alpha, -xi], [-d_alpha, 1.0, -eta], [xi, eta, 1.0]]
+};
+
+/// Celestial coordinates in one of the frames commonly found in the literature, in degrees.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum SkyCoords {
+    /// ICRS right ascension and declination.
+    Icrs {
+        /// The right ascension.
+        ra: f64,
+        /// The declination.
+        dec: f64,
+    },
+    /// FK5 right ascension and declination, for the J2000 equinox.
+    Fk5 {
+        /// The right ascension.
+        ra: f64,
+        /// The declination.
+        dec: f64,
+    },
+    /// Galactic longitude and latitude.
+    Galactic {
+        /// The longitude.
+        l: f64,
+        /// The latitude.
+        b: f64,
+    },
+}
+
+impl SkyCoords {
+    /// Converts the coordinates to ICRS.
+    pub fn to_icrs(&self) -> Icrs {
+        let (ra, dec) = match *self {
+            SkyCoords::Icrs { ra, dec } => (ra, dec),
+            SkyCoords::Fk5 { ra, dec } => rotate_inverse(&ICRS_TO_FK5, ra, dec),
+            SkyCoords::Galactic { l, b } => rotate_inverse(&ICRS_TO_GALACTIC, l, b),
+        };
+
+        icrs_from_degrees(ra, dec)
+    }
+}
+
+impl From<SkyCoords> for Icrs {
+    fn from(coords: SkyCoords) -> Self {
+        coords.to_icrs()
+    }
+}
+
+/// Parses ICRS coordinates from a right ascension and declination, such as `12 42 32.0 -00 04 58`,
+/// `12h42m32s −00°04′58″`, `12:42:32 -0:04:58`, or decimal degrees such as `190.633 -0.083`. Sexagesimal right
+/// ascensions are in hours, and may omit the seconds along with the declination. Returns an error if the text is not
+/// a recognized form or a component is out of range.
+pub fn parse_coordinates(text: &str) -> Result<Icrs, Box<dyn Error + Send + Sync>> {
+    let normalized = text.replace(['\u{2212}', '\u{2013}'], "-").replace(
+        [
+            'h', 'H', 'm', 'M', 's', 'S', 'd', 'D', ':', ',', '\u{b0}', '\u{2032}', '\u{2033}',
+            '\'', '"',
+        ],
+        " ",
+    );
+    let tokens = normalized.split_whitespace().collect::<Vec<&str>>();
+    let invalid = || format!("unrecognized coordinates: {}", text);
+
+    let (ra, dec) = match tokens.len() {
+        2 => (parse_number(tokens[0])?, parse_number(tokens[1])?),
+        4 | 6 => {
+            let (ra, dec) = tokens.split_at(tokens.len() / 2);
+            (sexagesimal(ra, 24.0)? * 15.0, sexagesimal(dec, 90.0)?)
+        }
+        _ => return Err(invalid().into()),
+    };
+    if !(0.0..360.0).contains(&ra) || !(-90.0..=90.0).contains(&dec) {
+        return Err(invalid().into());
+    }
+
+    Ok(icrs_from_degrees(ra, dec))
+}
+
+fn parse_number(token: &str) -> Result<f64, Box<dyn Error + Send + Sync>> {
+    token
+        .parse::<f64>()
+        .map_err(|_| format!("invalid number in coordinates: {}", token).into())
+}
+
+/// Converts the given sexagesimal components, the first of which may be signed, to a decimal value, checking that
+/// the first component is within the given bound and the rest are below 60.
+fn sexagesimal(tokens: &[&str], bound: f64) -> Result<f64, Box<dyn Error + Send + Sync>> {
+    let negative = tokens[0].starts_with('-');
+    let mut value = 0.0;
+    for (i, token) in tokens.iter().enumerate() {
+        let component = parse_number(token)?.abs();
+        let limit = if i == 0 { bound } else { 60.0 };
+        if component >= limit && !(i == 0 && component == bound) {
+            return Err(format!("coordinate component out of range: {}", token).into());
+        }
+        value += component / 60f64.powi(i as i32);
+    }
+
+    Ok(if negative { -value } else { value })
+}
+
+/// Applies the inverse of the given rotation, from ICRS to another frame, to the given longitude and latitude in
+/// that frame, returning the ICRS right ascension and declination, in degrees.
+fn rotate_inverse(rotation: &[[f64; 3]; 3], lon: f64, lat: f64) -> (f64, f64) {
+    let (lon, lat) = (lon.to_radians(), lat.to_radians());
+    let v = [lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin()];
+    // the inverse of a rotation is its transpose
+    let mut icrs = [0.0; 3];
+    for (i, component) in icrs.iter_mut().enumerate() {
+        *component = (0..3).map(|j| rotation[j][i] * v[j]).sum();
+    }
+    let ra = icrs[1].atan2(icrs[0]).to_degrees().rem_euclid(360.0);
+    let dec = icrs[2].clamp(-1.0, 1.0).asin().to_degrees();
+
+    (ra, dec)
+}
+
+/// Resolves the given object name to ICRS coordinates with the CDS Sesame service, through the given client so that
+/// the configured proxy, User-Agent, and timeouts apply, retrying according to the given policy unless the circuit
+/// breaker of the resolver is open. If a base URL is given, the Sesame service at that URL is used instead.
+pub(crate) async fn resolve_name(
+    metrics: &Metrics,
+    retry_policy: &RetryPolicy,
+    breakers: &CircuitBreakers,
+    client: &reqwest::Client,
+    base_url: Option<&reqwest::Url>,
+    name: &str,
+) -> Result<Icrs, Box<dyn Error + Send + Sync>> {
+    let start = Instant::now();
+    let mut attempt = 1;
+    loop {
+        let span = info_span!(
+            "upstream_request",
+            upstream = ?Upstream::Resolver,
+            target = name,
+            attempt,
+        );
+
+        breakers.acquire(Upstream::Resolver, metrics)?;
+        let attempt_start = Instant::now();
+        let result = sesame_lookup(client, base_url, name).instrument(span).await;
+        metrics.record_request(Upstream::Resolver, attempt_start.elapsed());
+        breakers.record(Upstream::Resolver, result.is_ok(), metrics);
+
+        let e = match result {
+            Ok(coords) => return Ok(coords),
+            Err(e) => e,
+        };
+        let category = ErrorCategory::of(e.as_ref());
+        metrics.record_error(Upstream::Resolver, category);
+        match retry_policy.delay(attempt, start.elapsed()) {
+            Some(delay) if retry_policy.retries_error(category) => {
+                debug!(
+                    "retrying resolution of {} in {:?} after attempt {}",
+                    name, delay, attempt
+                );
+                metrics.record_retry(Upstream::Resolver);
+                sleep(delay).await;
+                attempt += 1;
+            }
+            _ => return Err(e),
+        }
+    }
+}
+
+/// Resolves the given object name to ICRS coordinates with the Sesame service, under the given base URL if any.
+async fn sesame_lookup(
+    client: &reqwest::Client,
+    base_url: Option<&reqwest::Url>,
+    name: &str,
+) -> Result<Icrs, Box<dyn Error + Send + Sync>> {
+    let sesame_url = reqwest::Url::parse(SESAME_URL)?;
+    let mut url = match base_url {
+        Some(base_url) => rebase_url(&sesame_url, base_url),
+        None => sesame_url,
+    };
+    url.set_query(Some(name));
+    let body = client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+
+    // the coordinates are reported in degrees on a line such as `%J 190.6333 -00.0021 = 12 42 32.0 -00 00 07`
+    let (ra, dec) = body
+        .lines()
+        .find_map(|line| {
+            let mut parts = line.strip_prefix("%J ")?.split_whitespace();
+            Some((parts.next()?.parse().ok()?, parts.next()?.parse().ok()?))
+        })
+        .ok_or_else(|| format!("the resolver does not know {}", name))?;
+
+    Ok(icrs_from_degrees(ra, dec))
+}
+
+impl EarendelServer {
+    /// Gets the name of the target of the current APOD.
+    pub(crate) fn apod_target_name(&self) -> &'static str {
+        // TODO: extract name from apod title
+        "NGC 4632"
+    }
+
+    /// Resolves the coordinates of the target of the current APOD.
+    #[instrument(skip(self), fields(target = Empty))]
+    pub(crate) async fn resolve_apod_target(&self) -> Result<Icrs, Box<dyn Error + Send + Sync>> {
+        let name = self.apod_target_name();
+        Span::current().record("target", name);
+
+        let metrics = Arc::clone(&self.metrics);
+        let retry_policy = self.retry_policy.clone();
+        let breakers = Arc::clone(&self.breakers);
+        let client = self
+            .upstream_clients
+            .get(&Upstream::Resolver)
+            .unwrap_or(&self.client)
+            .clone();
+        let base_url = self.base_urls.get(&Upstream::Resolver).cloned();
+        // only the title is needed, so the image download is skipped when the APOD is not cached
+        let (title, coords) = tokio::join!(
+            self.get_apod_title(),
+            resolve_name(
+                &metrics,
+                &retry_policy,
+                &breakers,
+                &client,
+                base_url.as_ref(),
+                name
+            )
+        );
+        let _title = title?;
+
+        coords
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The tolerance of the comparisons, in degrees, well under a milliarcsecond.
+    const TOLERANCE: f64 = 1e-8;
+
+    /// Determines whether the given coordinates are within the given tolerance of the given position, in degrees.
+    fn within(coords: &Icrs, ra: f64, dec: f64, tolerance: f64) -> bool {
+        let (actual_ra, actual_dec) = icrs_to_degrees(coords);
+        // right ascensions just below 360 are close to those just above 0
+        let ra_error = ((actual_ra - ra + 180.0).rem_euclid(360.0) - 180.0).abs();
+
+        ra_error < tolerance && (actual_dec - dec).abs() < tolerance
+    }
+
+    fn assert_degrees(coords: &Icrs, ra: f64, dec: f64) {
+        assert!(
+            within(coords, ra, dec, TOLERANCE),
+            "expected ({}, {}), got {:?}",
+            ra,
+            dec,
+            icrs_to_degrees(coords)
+        );
+    }
+
+    fn parse(text: &str) -> Icrs {
+        parse_coordinates(text).unwrap_or_else(|e| panic!("failed to parse {}: {}", text, e))
+    }
+
+    #[test]
+    fn parses_sexagesimal() {
+        let ra = (12.0 + 42.0 / 60.0 + 32.0 / 3600.0) * 15.0;
+        let dec = -(4.0 / 60.0 + 58.0 / 3600.0);
+        for text in [
+            "12 42 32.0 -00 04 58",
+            "12h42m32s \u{2212}00\u{b0}04\u{2032}58\u{2033}",
+            "12:42:32 -0:04:58",
+            "12h 42m 32s, -00d 04' 58\"",
+        ] {
+            assert_degrees(&parse(text), ra, dec);
+        }
+    }
+
+    #[test]
+    fn parses_sexagesimal_without_seconds() {
+        assert_degrees(&parse("12 42 +41 16"), 190.5, 41.0 + 16.0 / 60.0);
+    }
+
+    #[test]
+    fn parses_decimal_degrees() {
+        assert_degrees(&parse("190.633 -0.083"), 190.633, -0.083);
+        assert_degrees(&parse("0 90"), 0.0, 90.0);
+    }
+
+    #[test]
+    fn applies_declination_sign() {
+        assert_degrees(&parse("00 00 00 +10 30 00"), 0.0, 10.5);
+        assert_degrees(&parse("00 00 00 -10 30 00"), 0.0, -10.5);
+        // the sign of a zero degree component still applies to the minutes and seconds
+        assert_degrees(&parse("00 00 00 -00 30 00"), 0.0, -0.5);
+        assert_degrees(&parse("00 00 00 \u{2013}00 30 00"), 0.0, -0.5);
+    }
+
+    #[test]
+    fn rejects_out_of_range_values() {
+        for text in [
+            "24 00 00 +00 00 00",
+            "12 60 00 +00 00 00",
+            "12 00 60 +00 00 00",
+            "12 00 00 +91 00 00",
+            "12 00 00 +90 30 00",
+            "12 00 00 +00 60 00",
+            "360 0",
+            "-1 0",
+            "10 91",
+            "10 -90.5",
+        ] {
+            assert!(parse_coordinates(text).is_err(), "accepted {}", text);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_text() {
+        for text in ["", "190.633", "12 42 32", "12 42 32 -00 04 58 00", "ra dec"] {
+            assert!(parse_coordinates(text).is_err(), "accepted {}", text);
+        }
+    }
+
+    #[test]
+    fn keeps_icrs_coordinates() {
+        let coords = SkyCoords::Icrs {
+            ra: 190.633,
+            dec: -0.083,
+        };
+        assert_degrees(&coords.to_icrs(), 190.633, -0.083);
+    }
+
+    #[test]
+    fn converts_galactic_coordinates() {
+        // the Galactic center and north pole, as defined for the Hipparcos catalogue
+        let center = SkyCoords::Galactic { l: 0.0, b: 0.0 };
+        assert!(within(&center.to_icrs(), 266.404_995, -28.936_174, 1e-5));
+        let pole = SkyCoords::Galactic { l: 0.0, b: 90.0 };
+        assert!(within(&pole.to_icrs(), 192.859_48, 27.128_25, 1e-5));
+    }
+
+    #[test]
+    fn converts_fk5_coordinates() {
+        // the FK5 origin is offset from that of ICRS by the frame bias of about 15 milliarcseconds
+        let origin = Icrs::from(SkyCoords::Fk5 { ra: 0.0, dec: 0.0 });
+        assert!(within(
+            &origin,
+            -14.6 / 3_600_000.0,
+            16.617 / 3_600_000.0,
+            1e-9
+        ));
+        assert!(!within(&origin, 0.0, 0.0, 1e-6));
+    }
+}
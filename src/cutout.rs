@@ -0,0 +1,103 @@
+//! Sky image cutouts from the CDS hips2fits service.
+
+use astro_rs::coordinates::Icrs;
+
+use serde::{Deserialize, Serialize};
+
+use tracing::instrument;
+
+use uom::si::angle::degree;
+use uom::si::f64::Angle;
+
+use std::error::Error;
+
+use crate::coords::icrs_to_degrees;
+use crate::{EarendelServer, Upstream};
+
+/// The width and height of requested cutouts, in pixels.
+const CUTOUT_SIZE: u32 = 512;
+
+/// The image format of a cutout.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum CutoutFormat {
+    /// A FITS image, including WCS keywords.
+    Fits,
+    /// A PNG image.
+    Png,
+    /// A JPEG image.
+    Jpeg,
+}
+
+impl CutoutFormat {
+    pub(crate) fn as_param(&self) -> &'static str {
+        match self {
+            CutoutFormat::Fits => "fits",
+            CutoutFormat::Png => "png",
+            CutoutFormat::Jpeg => "jpg",
+        }
+    }
+}
+
+/// A sky image centered on a position.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct EarendelCutout {
+    /// The HiPS survey the cutout was taken from.
+    pub survey: String,
+    /// The format of the image.
+    pub format: CutoutFormat,
+    /// The binary representation of the image.
+    #[serde(with = "serde_bytes")]
+    pub img: Vec<u8>,
+}
+
+impl EarendelServer {
+    /// Gets a square cutout of the given survey centered on the given coordinates. The survey is a HiPS identifier,
+    /// such as `CDS/P/DSS2/color` or `CDS/P/2MASS/color`. Returns an error if the web request fails.
+    #[instrument(skip(self, coords, fov))]
+    pub async fn get_cutout(
+        &self,
+        coords: &Icrs,
+        fov: Angle,
+        survey: &str,
+        format: CutoutFormat,
+    ) -> Result<EarendelCutout, Box<dyn Error + Send + Sync>> {
+        let api_url = "https://alasky.cds.unistra.fr/hips-image-services/hips2fits";
+        let (ra, dec) = icrs_to_degrees(coords);
+
+        let request = self.client.get(api_url).query(&[
+            ("hips", survey.to_owned()),
+            ("ra", ra.to_string()),
+            ("dec", dec.to_string()),
+            ("fov", fov.get::<degree>().to_string()),
+            ("width", CUTOUT_SIZE.to_string()),
+            ("height", CUTOUT_SIZE.to_string()),
+            ("projection", String::from("TAN")),
+            ("coordsys", String::from("icrs")),
+            ("format", String::from(format.as_param())),
+        ]);
+        let resp = self
+            .send(Upstream::Hips2Fits, request)
+            .await?
+            .error_for_status()?;
+        let img = resp.bytes().await?;
+
+        Ok(EarendelCutout {
+            survey: survey.to_owned(),
+            format,
+            img: img.to_vec(),
+        })
+    }
+
+    /// Gets a square cutout of the given survey centered on the target of the current APOD. Returns an error if the
+    /// target cannot be resolved or if the web request fails.
+    pub async fn get_cutout_for_apod(
+        &self,
+        fov: Angle,
+        survey: &str,
+        format: CutoutFormat,
+    ) -> Result<EarendelCutout, Box<dyn Error + Send + Sync>> {
+        let coords = self.resolve_apod_target().await?;
+
+        self.get_cutout(&coords, fov, survey, format).await
+    }
+}
@@ -0,0 +1,195 @@
+//! A common interface for the sources of a picture of the day, such as the APOD, with shared caching.
+
+use async_trait::async_trait;
+
+use chrono::NaiveDate;
+
+use serde::{Deserialize, Serialize};
+
+use tracing::{instrument, warn};
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::apod::{apod_page_url, PENDING_RECHECK};
+use crate::{CacheEntry, CacheKind, EarendelApod, EarendelError, EarendelServer};
+
+/// The name of the APOD source, which is always registered.
+pub const APOD_SOURCE: &str = "apod";
+/// The time for which a daily image is served from the cache by default, before its source is asked for a newer one.
+const DEFAULT_MAX_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// A picture of the day from any source.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DailyImage {
+    /// The name of the source of the image, such as `apod`.
+    pub source: String,
+    /// The title of the image.
+    pub title: String,
+    /// The date the image was published.
+    pub date: NaiveDate,
+    /// The URL the image was downloaded from.
+    pub image_url: String,
+    /// The URL of the web page presenting the image, if any.
+    pub page_url: Option<String>,
+    /// The credit or copyright of the image, if any.
+    pub credit: Option<String>,
+    /// The license under which the image may be reused, such as `CC BY 4.0`, if known.
+    pub license: Option<String>,
+    /// The explanation of the image, if any.
+    pub explanation: Option<String>,
+    /// The binary representation of the image.
+    #[serde(with = "serde_bytes")]
+    pub img: Vec<u8>,
+}
+
+impl From<EarendelApod> for DailyImage {
+    fn from(apod: EarendelApod) -> Self {
+        DailyImage {
+            source: String::from(APOD_SOURCE),
+            title: apod.title,
+            date: apod.date,
+            image_url: apod.image_url,
+            page_url: Some(apod_page_url(apod.date)),
+            credit: apod.copyright,
+            // APOD images are either in the public domain or copyrighted by their credited authors
+            license: None,
+            explanation: apod.explanation,
+            img: apod.image.into_bytes().unwrap_or_default(),
+        }
+    }
+}
+
+/// A source of a picture of the day, registered with `EarendelServerBuilder::daily_image_provider`.
+#[async_trait]
+pub trait DailyImageProvider: Send + Sync {
+    /// Gets the name of the source, by which its images are requested.
+    fn name(&self) -> &str;
+
+    /// Gets the time for which a fetched image is served from the cache, before a newer one is fetched.
+    fn max_age(&self) -> Duration {
+        DEFAULT_MAX_AGE
+    }
+
+    /// Fetches the current image of the source, using the given server for web requests.
+    async fn fetch(
+        &self,
+        server: &EarendelServer,
+    ) -> Result<DailyImage, Box<dyn Error + Send + Sync>>;
+}
+
+/// The Astronomy Picture of the Day, fetched with `EarendelServer::get_apod_image`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ApodProvider;
+
+#[async_trait]
+impl DailyImageProvider for ApodProvider {
+    fn name(&self) -> &str {
+        APOD_SOURCE
+    }
+
+    fn max_age(&self) -> Duration {
+        // the APOD cache already expires at the daily rollover
+        PENDING_RECHECK
+    }
+
+    async fn fetch(
+        &self,
+        server: &EarendelServer,
+    ) -> Result<DailyImage, Box<dyn Error + Send + Sync>> {
+        Ok(server.get_apod_image().await?.into())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct CachedDailyImage {
+    image: DailyImage,
+    fetched: Instant,
+}
+
+impl CachedDailyImage {
+    /// Gets the date the image was published.
+    pub(crate) fn date(&self) -> NaiveDate {
+        self.image.date
+    }
+
+    pub(crate) fn entry(&self, source: &str) -> CacheEntry {
+        CacheEntry {
+            kind: CacheKind::DailyImage,
+            key: source.to_owned(),
+            age: Some(self.fetched.elapsed()),
+            bytes: self.image.img.len() as u64,
+        }
+    }
+}
+
+impl EarendelServer {
+    /// Gets the current image of the given source, such as `apod`. If the source fails, the last image fetched from it
+    /// is returned instead, if any. Returns an error if the source is not registered or if it fails without a
+    /// previously fetched image.
+    #[instrument(skip(self))]
+    pub async fn get_daily_image(
+        &self,
+        source: &str,
+    ) -> Result<DailyImage, Box<dyn Error + Send + Sync>> {
+        let provider = self.daily_providers.get(source).cloned().ok_or_else(|| {
+            EarendelError::UnknownDailyImageSource {
+                source: source.to_owned(),
+                available: self.daily_image_sources(),
+            }
+        })?;
+        let cached = self
+            .daily_images()
+            .get(source)
+            .filter(|cached| cached.fetched.elapsed() < provider.max_age())
+            .map(|cached| cached.image.to_owned());
+        if let Some(image) = cached {
+            self.metrics.record_cache(true);
+            return Ok(image);
+        }
+        self.metrics.record_cache(false);
+
+        match provider.fetch(self).await {
+            Ok(image) => {
+                self.daily_images().insert(
+                    source.to_owned(),
+                    CachedDailyImage {
+                        image: image.to_owned(),
+                        fetched: Instant::now(),
+                    },
+                );
+                Ok(image)
+            }
+            Err(e) => {
+                let cached = self
+                    .daily_images()
+                    .get(source)
+                    .map(|cached| cached.image.to_owned());
+                match cached {
+                    Some(image) => {
+                        warn!(
+                            "failed to fetch {} image, serving the cached image: {}",
+                            source, e
+                        );
+                        Ok(image)
+                    }
+                    None => Err(e),
+                }
+            }
+        }
+    }
+
+    /// Locks the cached daily images, keyed by the name of their source. The guard must not be held across an await.
+    pub(crate) fn daily_images(&self) -> MutexGuard<'_, HashMap<String, CachedDailyImage>> {
+        self.daily_images
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Gets the names of the registered daily image sources, in alphabetical order.
+    pub fn daily_image_sources(&self) -> Vec<String> {
+        self.daily_providers.keys().cloned().collect()
+    }
+}
@@ -0,0 +1,285 @@
+//! Space-weather events from the NASA DONKI API, such as solar flares, coronal mass ejections, and geomagnetic
+//! storms.
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+
+use serde::de::{DeserializeOwned, Error as _};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use tracing::instrument;
+
+use std::error::Error;
+
+use crate::{EarendelServer, Upstream};
+
+const DONKI_URL: &str = "https://api.nasa.gov/DONKI";
+/// The format of the event times reported by DONKI, which omit the seconds.
+const DONKI_TIME_FORMAT: &str = "%Y-%m-%dT%H:%MZ";
+
+/// A solar flare.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SolarFlare {
+    /// The DONKI identifier of the flare.
+    pub id: String,
+    /// When the flare began.
+    pub begin_time: DateTime<Utc>,
+    /// When the flare peaked, if known.
+    pub peak_time: Option<DateTime<Utc>>,
+    /// When the flare ended, if known.
+    pub end_time: Option<DateTime<Utc>>,
+    /// The X-ray class of the flare, such as `M1.2` or `X3.9`.
+    pub class_type: Option<String>,
+    /// The heliographic location of the flare on the Sun, such as `S17W29`.
+    pub source_location: Option<String>,
+    /// The NOAA number of the active region that produced the flare, if known.
+    pub active_region: Option<u32>,
+    /// The URL of the DONKI page of the flare.
+    pub link: String,
+}
+
+/// A coronal mass ejection.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CoronalMassEjection {
+    /// The DONKI identifier of the ejection.
+    pub id: String,
+    /// When the ejection was first observed.
+    pub start_time: DateTime<Utc>,
+    /// The heliographic location of the source of the ejection on the Sun, if known.
+    pub source_location: Option<String>,
+    /// The NOAA number of the active region that produced the ejection, if known.
+    pub active_region: Option<u32>,
+    /// The speed of the ejection given by its most accurate analysis, in kilometers per second, if analyzed.
+    pub speed_km_s: Option<f64>,
+    /// The notes of the forecasters on the ejection.
+    pub note: Option<String>,
+    /// The URL of the DONKI page of the ejection.
+    pub link: String,
+}
+
+/// A geomagnetic storm, which can cause aurorae at lower latitudes than usual.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GeomagneticStorm {
+    /// The DONKI identifier of the storm.
+    pub id: String,
+    /// When the storm started.
+    pub start_time: DateTime<Utc>,
+    /// The highest planetary K-index observed during the storm, from 0 to 9. A storm of index 5 or more is visible
+    /// as aurorae at mid latitudes.
+    pub max_kp_index: Option<f64>,
+    /// The URL of the DONKI page of the storm.
+    pub link: String,
+}
+
+/// The space-weather events of a date range.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct SpaceWeather {
+    /// The solar flares that began in the range.
+    pub flares: Vec<SolarFlare>,
+    /// The coronal mass ejections that started in the range.
+    pub coronal_mass_ejections: Vec<CoronalMassEjection>,
+    /// The geomagnetic storms that started in the range.
+    pub geomagnetic_storms: Vec<GeomagneticStorm>,
+}
+
+impl SpaceWeather {
+    /// Determines whether no event occurred in the range.
+    pub fn is_empty(&self) -> bool {
+        self.flares.is_empty()
+            && self.coronal_mass_ejections.is_empty()
+            && self.geomagnetic_storms.is_empty()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DonkiFlare {
+    #[serde(rename = "flrID")]
+    flr_id: String,
+    #[serde(deserialize_with = "donki_time")]
+    begin_time: DateTime<Utc>,
+    #[serde(default, deserialize_with = "optional_donki_time")]
+    peak_time: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "optional_donki_time")]
+    end_time: Option<DateTime<Utc>>,
+    class_type: Option<String>,
+    source_location: Option<String>,
+    active_region_num: Option<u32>,
+    link: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DonkiCme {
+    #[serde(rename = "activityID")]
+    activity_id: String,
+    #[serde(deserialize_with = "donki_time")]
+    start_time: DateTime<Utc>,
+    source_location: Option<String>,
+    active_region_num: Option<u32>,
+    note: Option<String>,
+    cme_analyses: Option<Vec<DonkiCmeAnalysis>>,
+    link: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DonkiCmeAnalysis {
+    is_most_accurate: bool,
+    speed: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DonkiStorm {
+    #[serde(rename = "gstID")]
+    gst_id: String,
+    #[serde(deserialize_with = "donki_time")]
+    start_time: DateTime<Utc>,
+    all_kp_index: Option<Vec<DonkiKpIndex>>,
+    link: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DonkiKpIndex {
+    kp_index: f64,
+}
+
+/// Deserializes a DONKI event time.
+fn donki_time<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+    let time = String::deserialize(deserializer)?;
+    NaiveDateTime::parse_from_str(&time, DONKI_TIME_FORMAT)
+        .map(|time| time.and_utc())
+        .map_err(D::Error::custom)
+}
+
+/// Deserializes a DONKI event time that may be null.
+fn optional_donki_time<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<DateTime<Utc>>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|time| {
+            NaiveDateTime::parse_from_str(&time, DONKI_TIME_FORMAT)
+                .map(|time| time.and_utc())
+                .map_err(D::Error::custom)
+        })
+        .transpose()
+}
+
+impl EarendelServer {
+    /// Gets the solar flares, coronal mass ejections, and geomagnetic storms of the given date, such as the
+    /// publication date of an APOD of an aurora or of the Sun. Returns an error if a web request fails or if
+    /// deserialization fails.
+    pub async fn get_space_weather(
+        &self,
+        date: NaiveDate,
+    ) -> Result<SpaceWeather, Box<dyn Error + Send + Sync>> {
+        self.get_space_weather_between(date, date).await
+    }
+
+    /// Gets the solar flares, coronal mass ejections, and geomagnetic storms between the given dates, inclusive, in
+    /// chronological order. Returns an error if the range is invalid, if a web request fails, or if deserialization
+    /// fails.
+    #[instrument(skip(self))]
+    pub async fn get_space_weather_between(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<SpaceWeather, Box<dyn Error + Send + Sync>> {
+        if end < start {
+            return Err("space weather range must not end before it starts".into());
+        }
+
+        let flares = self
+            .get_donki::<DonkiFlare>("FLR", start, end)
+            .await?
+            .into_iter()
+            .map(|flare| SolarFlare {
+                id: flare.flr_id,
+                begin_time: flare.begin_time,
+                peak_time: flare.peak_time,
+                end_time: flare.end_time,
+                class_type: flare.class_type,
+                source_location: flare
+                    .source_location
+                    .filter(|location| !location.is_empty()),
+                active_region: flare.active_region_num,
+                link: flare.link,
+            });
+        let coronal_mass_ejections = self
+            .get_donki::<DonkiCme>("CME", start, end)
+            .await?
+            .into_iter()
+            .map(|cme| CoronalMassEjection {
+                speed_km_s: cme.cme_analyses.and_then(|analyses| {
+                    analyses
+                        .into_iter()
+                        .find(|analysis| analysis.is_most_accurate)
+                        .and_then(|analysis| analysis.speed)
+                }),
+                id: cme.activity_id,
+                start_time: cme.start_time,
+                source_location: cme.source_location.filter(|location| !location.is_empty()),
+                active_region: cme.active_region_num,
+                note: cme.note.filter(|note| !note.is_empty()),
+                link: cme.link,
+            });
+        let geomagnetic_storms = self
+            .get_donki::<DonkiStorm>("GST", start, end)
+            .await?
+            .into_iter()
+            .map(|storm| GeomagneticStorm {
+                max_kp_index: storm.all_kp_index.and_then(|indices| {
+                    indices
+                        .into_iter()
+                        .map(|index| index.kp_index)
+                        .reduce(f64::max)
+                }),
+                id: storm.gst_id,
+                start_time: storm.start_time,
+                link: storm.link,
+            });
+
+        let mut weather = SpaceWeather {
+            flares: flares.collect(),
+            coronal_mass_ejections: coronal_mass_ejections.collect(),
+            geomagnetic_storms: geomagnetic_storms.collect(),
+        };
+        weather.flares.sort_by_key(|flare| flare.begin_time);
+        weather
+            .coronal_mass_ejections
+            .sort_by_key(|cme| cme.start_time);
+        weather
+            .geomagnetic_storms
+            .sort_by_key(|storm| storm.start_time);
+
+        Ok(weather)
+    }
+
+    /// Gets the events of the given DONKI endpoint, such as `FLR`, between the given dates, inclusive.
+    async fn get_donki<T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<Vec<T>, Box<dyn Error + Send + Sync>> {
+        let request = self
+            .nasa_api_get(format!("{}/{}", DONKI_URL, endpoint))?
+            .query(&[
+                ("startDate", start.to_string()),
+                ("endDate", end.to_string()),
+            ]);
+        let resp = self
+            .send(Upstream::Donki, request)
+            .await?
+            .error_for_status()?;
+        self.record_rate_limit(resp.headers());
+        let body = resp.text().await?;
+        // ranges without events are reported with an empty body rather than an empty array
+        if body.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+
+        self.parse::<Vec<T>>(Upstream::Donki, &body)
+    }
+}
@@ -0,0 +1,449 @@
+//! Resumable and concurrent downloads of FITS files.
+
+use futures::future::join_all;
+
+use md5::{Digest, Md5};
+
+use reqwest::header::{CONTENT_RANGE, RANGE};
+use reqwest::StatusCode;
+
+use serde::{Deserialize, Serialize};
+
+use tokio::sync::Semaphore;
+
+use tracing::{instrument, warn};
+
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+use crate::mast::download_url;
+use crate::redact::redact;
+use crate::runtime::fs::{self, File, OpenOptions};
+use crate::runtime::{AsyncReadExt, AsyncWriteExt};
+use crate::{EarendelError, EarendelServer, MastProduct, Observation, Upstream};
+
+/// The result of verifying a downloaded file against its published checksum.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ChecksumStatus {
+    /// The checksum of the file matched.
+    Verified,
+    /// The checksum of the file did not match, so the file is corrupt or incomplete.
+    Mismatch {
+        /// The published checksum.
+        expected: String,
+        /// The checksum computed from the file.
+        actual: String,
+    },
+}
+
+/// A file to download, and where to write it.
+struct FileRequest {
+    url: String,
+    path: PathBuf,
+    md5: Option<String>,
+}
+
+/// The outcome of downloading a single file with `EarendelServer::download_all`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct FileDownload {
+    /// The URL of the file.
+    pub url: String,
+    /// The path the file was written to, or would have been written to had it succeeded.
+    pub path: PathBuf,
+    /// The size of the written file, in bytes, including any part written by an earlier attempt.
+    pub bytes: u64,
+    /// The result of verifying the file against its published checksum, or None if it has none.
+    pub checksum: Option<ChecksumStatus>,
+    /// A description of the failure, if the download failed.
+    pub error: Option<String>,
+}
+
+/// The outcome of downloading a set of files with `EarendelServer::download_all`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct DownloadReport {
+    /// The outcome of each file, in the order the files were given.
+    pub files: Vec<FileDownload>,
+}
+
+impl DownloadReport {
+    /// Gets the files that were downloaded successfully.
+    pub fn succeeded(&self) -> impl Iterator<Item = &FileDownload> {
+        self.files.iter().filter(|file| file.error.is_none())
+    }
+
+    /// Gets the files that failed to download.
+    pub fn failed(&self) -> impl Iterator<Item = &FileDownload> {
+        self.files.iter().filter(|file| file.error.is_some())
+    }
+}
+
+/// The aggregated progress of `EarendelServer::download_all_with_progress`, reported as each file finishes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct DownloadProgress {
+    /// The total number of files being downloaded.
+    pub total: usize,
+    /// The number of files downloaded successfully so far.
+    pub completed: usize,
+    /// The number of files that failed so far.
+    pub failed: usize,
+    /// The number of bytes written so far.
+    pub bytes: u64,
+}
+
+impl EarendelServer {
+    /// Downloads the given files into the given directory, with at most `max_concurrent` downloads in flight at once.
+    /// Each file is named after the final segment of its URL, and partial files left by earlier attempts are resumed
+    /// as with `download_to_file`. A failed download does not affect the others; its error is recorded in the returned
+    /// report. Returns an error only if the directory cannot be created.
+    pub async fn download_all<S: AsRef<str> + Sync>(
+        &self,
+        files: &[S],
+        dest_dir: &Path,
+        max_concurrent: usize,
+    ) -> Result<DownloadReport, Box<dyn Error + Send + Sync>> {
+        self.download_all_with_progress(files, dest_dir, max_concurrent, |_| {})
+            .await
+    }
+
+    /// Downloads the given files as with `download_all`, calling `on_progress` with the aggregated progress each time
+    /// a file finishes.
+    #[instrument(skip(self, files, on_progress), fields(files = files.len()))]
+    pub async fn download_all_with_progress<S, F>(
+        &self,
+        files: &[S],
+        dest_dir: &Path,
+        max_concurrent: usize,
+        on_progress: F,
+    ) -> Result<DownloadReport, Box<dyn Error + Send + Sync>>
+    where
+        S: AsRef<str> + Sync,
+        F: Fn(&DownloadProgress) + Sync,
+    {
+        let names = files.iter().map(|url| url_file_name(url.as_ref()));
+        let requests = files
+            .iter()
+            .zip(dest_paths(names, dest_dir))
+            .map(|(url, path)| FileRequest {
+                url: url.as_ref().to_owned(),
+                path,
+                md5: None,
+            })
+            .collect();
+
+        self.download_set(requests, dest_dir, max_concurrent, false, on_progress)
+            .await
+    }
+
+    /// Downloads the given MAST products into the given directory as with `download_all`, naming each after its file
+    /// name. The MD5 checksum of each product is verified where MAST reports one, and the result is recorded in the
+    /// report. If `redownload_on_mismatch` is set, a product that fails verification, such as one resumed from a stale
+    /// partial file, is downloaded again in full before it is reported as failed.
+    #[instrument(skip(self, products), fields(products = products.len()))]
+    pub async fn download_products(
+        &self,
+        products: &[MastProduct],
+        dest_dir: &Path,
+        max_concurrent: usize,
+        redownload_on_mismatch: bool,
+    ) -> Result<DownloadReport, Box<dyn Error + Send + Sync>> {
+        let names = products
+            .iter()
+            .map(|product| Some(product.filename.as_str()));
+        let requests = products
+            .iter()
+            .zip(dest_paths(names, dest_dir))
+            .map(|(product, path)| FileRequest {
+                url: product.download_url(),
+                path,
+                md5: product.md5.to_owned(),
+            })
+            .collect();
+
+        self.download_set(
+            requests,
+            dest_dir,
+            max_concurrent,
+            redownload_on_mismatch,
+            |_| {},
+        )
+        .await
+    }
+
+    /// Downloads the preview images of the given observations, such as a page of `EarendelFits::observations`, with
+    /// at most `max_concurrent` downloads in flight at once, so that galleries can show the previews without
+    /// downloading them themselves. Returns the bytes of each preview keyed by the `obs_id` of its observation.
+    /// Observations without a preview are left out, as are previews that fail to download, whose errors are logged.
+    #[instrument(skip(self, observations), fields(observations = observations.len()))]
+    pub async fn download_previews(
+        &self,
+        observations: &[Observation],
+        max_concurrent: usize,
+    ) -> BTreeMap<String, Vec<u8>> {
+        let semaphore = Semaphore::new(max_concurrent.max(1));
+        let downloads = observations.iter().filter_map(|observation| {
+            let preview_url = observation.preview_url.as_deref()?;
+            // MAST lists data URIs, such as `mast:HST/product/<filename>`, rather than URLs
+            let url = if preview_url.starts_with("mast:") {
+                download_url(preview_url)
+            } else {
+                preview_url.to_owned()
+            };
+            let semaphore = &semaphore;
+            Some(async move {
+                let result = match semaphore.acquire().await {
+                    Ok(_permit) => self.download(&url).await,
+                    Err(e) => Err(e.into()),
+                };
+                match result {
+                    Ok(bytes) => Some((observation.obs_id.to_owned(), bytes)),
+                    Err(e) => {
+                        warn!("failed to download the preview {}: {}", redact(&url), e);
+                        None
+                    }
+                }
+            })
+        });
+
+        join_all(downloads).await.into_iter().flatten().collect()
+    }
+
+    async fn download_set<F: Fn(&DownloadProgress) + Sync>(
+        &self,
+        requests: Vec<FileRequest>,
+        dest_dir: &Path,
+        max_concurrent: usize,
+        redownload_on_mismatch: bool,
+        on_progress: F,
+    ) -> Result<DownloadReport, Box<dyn Error + Send + Sync>> {
+        fs::create_dir_all(dest_dir).await?;
+
+        let semaphore = Semaphore::new(max_concurrent.max(1));
+        let progress = Mutex::new(DownloadProgress {
+            total: requests.len(),
+            ..Default::default()
+        });
+        let downloads = requests.into_iter().map(|request| {
+            let (semaphore, progress, on_progress) = (&semaphore, &progress, &on_progress);
+            async move {
+                let result = match semaphore.acquire().await {
+                    Ok(_permit) => {
+                        self.download_verified(&request, redownload_on_mismatch)
+                            .await
+                    }
+                    Err(e) => Err(e.into()),
+                };
+                let file = match result {
+                    Ok((bytes, checksum)) => {
+                        let error = match checksum.as_ref() {
+                            Some(ChecksumStatus::Mismatch { expected, actual }) => {
+                                let e = EarendelError::DownloadChecksumMismatch {
+                                    url: request.url.to_owned(),
+                                    expected: expected.to_owned(),
+                                    actual: actual.to_owned(),
+                                };
+                                warn!("failed to download {}: {}", redact(&request.url), e);
+                                Some(e.to_string())
+                            }
+                            _ => None,
+                        };
+                        FileDownload {
+                            url: request.url,
+                            path: request.path,
+                            bytes,
+                            checksum,
+                            error,
+                        }
+                    }
+                    Err(e) => {
+                        let error = redact(&e.to_string());
+                        warn!("failed to download {}: {}", redact(&request.url), error);
+                        FileDownload {
+                            url: request.url,
+                            path: request.path,
+                            bytes: 0,
+                            checksum: None,
+                            error: Some(error),
+                        }
+                    }
+                };
+
+                let snapshot = {
+                    let mut progress = progress.lock().unwrap_or_else(PoisonError::into_inner);
+                    if file.error.is_none() {
+                        progress.completed += 1;
+                    } else {
+                        progress.failed += 1;
+                    }
+                    progress.bytes += file.bytes;
+                    *progress
+                };
+                on_progress(&snapshot);
+
+                file
+            }
+        });
+
+        Ok(DownloadReport {
+            files: join_all(downloads).await,
+        })
+    }
+
+    /// Downloads the given file, verifying it against its expected checksum if it has one.
+    async fn download_verified(
+        &self,
+        request: &FileRequest,
+        redownload_on_mismatch: bool,
+    ) -> Result<(u64, Option<ChecksumStatus>), Box<dyn Error + Send + Sync>> {
+        let mut bytes = self.download_to_file(&request.url, &request.path).await?;
+        let Some(expected) = request.md5.as_deref() else {
+            return Ok((bytes, None));
+        };
+
+        let mut actual = file_md5(&request.path).await?;
+        if redownload_on_mismatch && !actual.eq_ignore_ascii_case(expected) {
+            warn!(
+                "checksum mismatch for {}, downloading it again",
+                redact(&request.url)
+            );
+            fs::remove_file(&request.path).await?;
+            bytes = self.download_to_file(&request.url, &request.path).await?;
+            actual = file_md5(&request.path).await?;
+        }
+        let status = if actual.eq_ignore_ascii_case(expected) {
+            ChecksumStatus::Verified
+        } else {
+            ChecksumStatus::Mismatch {
+                expected: expected.to_owned(),
+                actual,
+            }
+        };
+
+        Ok((bytes, Some(status)))
+    }
+
+    /// Downloads the given URL to the given path, returning the size of the file. If the file already exists, only
+    /// the remainder is requested with a `Range` request, so that a download interrupted by a failure resumes where it
+    /// stopped rather than starting over; the partial file is kept when the download fails. If the server does not
+    /// support range requests, the file is downloaded again in full.
+    #[instrument(skip(self, url), fields(url = %redact(url)))]
+    pub async fn download_to_file(
+        &self,
+        url: &str,
+        path: &Path,
+    ) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        let existing = match fs::metadata(path).await {
+            Ok(metadata) => metadata.len(),
+            Err(e) if e.kind() == ErrorKind::NotFound => 0,
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut request = self.client.get(url);
+        if existing > 0 {
+            request = request.header(RANGE, format!("bytes={}-", existing));
+        }
+        let resp = self.send(Upstream::Download, request).await?;
+        // the range starts at the end of the file, so the file is already complete
+        if existing > 0 && resp.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+            return Ok(existing);
+        }
+        let mut resp = resp.error_for_status()?;
+
+        let resuming = resp.status() == StatusCode::PARTIAL_CONTENT;
+        if resuming && range_start(&resp) != Some(existing) {
+            return Err(format!("unexpected Content-Range in response from {}", url).into());
+        }
+        let mut file = if resuming {
+            OpenOptions::new().append(true).open(path).await?
+        } else {
+            File::create(path).await?
+        };
+        let mut written = if resuming { existing } else { 0 };
+
+        let too_large = |limit| EarendelError::DownloadTooLarge {
+            url: url.to_owned(),
+            limit,
+        };
+        if let (Some(limit), Some(length)) = (self.max_download_size, resp.content_length()) {
+            if written + length > limit {
+                return Err(too_large(limit).into());
+            }
+        }
+        while let Some(chunk) = resp.chunk().await? {
+            written += chunk.len() as u64;
+            if let Some(limit) = self.max_download_size.filter(|limit| written > *limit) {
+                return Err(too_large(limit).into());
+            }
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+
+        Ok(written)
+    }
+}
+
+/// Gets the first byte position of the `Content-Range` of the given partial response.
+fn range_start(resp: &reqwest::Response) -> Option<u64> {
+    resp.headers()
+        .get(CONTENT_RANGE)?
+        .to_str()
+        .ok()?
+        .strip_prefix("bytes ")?
+        .split('-')
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Gets the final segment of the path of the given URL, if any.
+fn url_file_name(url: &str) -> Option<&str> {
+    url.split(['?', '#'])
+        .next()
+        .and_then(|path| path.rsplit(['/', ':']).next())
+}
+
+/// Gets the path each file with the given name is written to. Only the final component of each name is kept, so that
+/// it cannot escape the directory, and names shared by more than one file are given a numeric prefix.
+fn dest_paths<'a, I: Iterator<Item = Option<&'a str>>>(names: I, dest_dir: &Path) -> Vec<PathBuf> {
+    let mut taken = HashSet::new();
+    names
+        .enumerate()
+        .map(|(i, name)| {
+            let name = name
+                .and_then(|name| Path::new(name).file_name())
+                .and_then(|name| name.to_str())
+                .map(str::to_owned)
+                .unwrap_or_else(|| format!("download-{}", i));
+            let mut candidate = name.to_owned();
+            let mut suffix = 1;
+            while !taken.insert(candidate.to_owned()) {
+                candidate = format!("{}-{}", suffix, name);
+                suffix += 1;
+            }
+
+            dest_dir.join(candidate)
+        })
+        .collect()
+}
+
+/// Computes the hex-encoded MD5 digest of the file at the given path.
+pub(crate) async fn file_md5(path: &Path) -> Result<String, Box<dyn Error + Send + Sync>> {
+    let mut file = File::open(path).await?;
+    let mut hasher = Md5::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buf).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+    }
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
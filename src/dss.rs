@@ -0,0 +1,81 @@
+//! Plate images from the STScI Digitized Sky Survey (DSS) server.
+
+use astro_rs::coordinates::Icrs;
+
+use serde::{Deserialize, Serialize};
+
+use tracing::instrument;
+
+use uom::si::angle::arcminute;
+use uom::si::f64::Angle;
+
+use std::error::Error;
+
+use crate::coords::icrs_to_degrees;
+use crate::{EarendelServer, Upstream};
+
+const DSS_URL: &str = "https://archive.stsci.edu/cgi-bin/dss_search";
+/// The all-sky red plates of the second-generation survey.
+const DSS_SURVEY: &str = "poss2ukstu_red";
+/// The largest field returned by the DSS server, in arcminutes.
+const MAX_FIELD_ARCMIN: f64 = 60.0;
+
+/// The image format of a DSS plate image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum DssFormat {
+    /// A FITS image, including WCS keywords.
+    Fits,
+    /// A GIF image.
+    Gif,
+}
+
+impl EarendelServer {
+    /// Gets a square DSS image centered on the given coordinates. The DSS covers the whole sky, making it a fallback
+    /// when other surveys have no coverage. Fields larger than 60 arcminutes are reduced to 60 arcminutes. Returns an
+    /// error if the web request fails.
+    #[instrument(skip(self, coords, fov))]
+    pub async fn get_dss_image(
+        &self,
+        coords: &Icrs,
+        fov: Angle,
+        format: DssFormat,
+    ) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        let (ra, dec) = icrs_to_degrees(coords);
+        let ra = ra.to_string();
+        let dec = dec.to_string();
+        let size = fov.get::<arcminute>().min(MAX_FIELD_ARCMIN).to_string();
+        let format_param = match format {
+            DssFormat::Fits => "fits",
+            DssFormat::Gif => "gif",
+        };
+
+        let request = self.client.get(DSS_URL).query(&[
+            ("v", DSS_SURVEY),
+            ("r", ra.as_str()),
+            ("d", dec.as_str()),
+            ("e", "J2000"),
+            ("h", size.as_str()),
+            ("w", size.as_str()),
+            ("f", format_param),
+            ("c", "none"),
+        ]);
+        let resp = self
+            .send(Upstream::Dss, request)
+            .await?
+            .error_for_status()?;
+
+        Ok(resp.bytes().await?.to_vec())
+    }
+
+    /// Gets a square DSS image centered on the target of the current APOD. Returns an error if the target cannot be
+    /// resolved or if the web request fails.
+    pub async fn get_dss_image_for_apod(
+        &self,
+        fov: Angle,
+        format: DssFormat,
+    ) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        let coords = self.resolve_apod_target().await?;
+
+        self.get_dss_image(&coords, fov, format).await
+    }
+}
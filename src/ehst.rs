@@ -0,0 +1,101 @@
+//! The ESA Hubble Science Archive (eHST), searched through its TAP service.
+
+use astro_rs::coordinates::Icrs;
+
+use async_trait::async_trait;
+
+use std::error::Error;
+
+use crate::archive::{EarendelFits, Observation, ObservationArchive, PAGE_SIZE, SEARCH_RADIUS_DEG};
+use crate::coords::icrs_to_degrees;
+use crate::tap::{TapFormat, TapRow};
+use crate::{EarendelServer, Upstream};
+
+const EHST_TAP_URL: &str = "https://hst.esac.esa.int/tap-server/tap";
+const EHST_DATA_URL: &str = "https://hst.esac.esa.int/ehst-sl-server/servlet/data-action";
+
+/// The ESA Hubble Science Archive (eHST), hosting European copies of Hubble observations.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EhstArchive;
+
+impl EhstArchive {
+    fn observation(row: &TapRow<'_>) -> Observation {
+        let obs_id = row.get_string("observation_id").unwrap_or_default();
+        let data_url = format!(
+            "{}?OBSERVATION_ID={}",
+            EHST_DATA_URL,
+            urlencoding::encode(&obs_id)
+        );
+
+        Observation {
+            archive: String::from("eHST"),
+            collection: row.get_string("collection"),
+            instrument: row.get_string("instrument_name"),
+            filters: row.get_string("filter"),
+            target_name: row.get_string("target_name"),
+            ra: row.get_f64("ra"),
+            dec: row.get_f64("dec"),
+            exposure_time: row.get_f64("exposure_duration"),
+            data_url: Some(data_url),
+            obs_id,
+            ..Default::default()
+        }
+    }
+}
+
+#[async_trait]
+impl ObservationArchive for EhstArchive {
+    fn name(&self) -> &str {
+        "eHST"
+    }
+
+    async fn search(
+        &self,
+        server: &EarendelServer,
+        coords: &Icrs,
+        page: usize,
+    ) -> Result<EarendelFits, Box<dyn Error + Send + Sync>> {
+        let (ra, dec) = icrs_to_degrees(coords);
+        let from_where = format!(
+            "FROM ehst.archive WHERE 1=CONTAINS(POINT('ICRS', ra, dec), CIRCLE('ICRS', {}, {}, {}))",
+            ra, dec, SEARCH_RADIUS_DEG
+        );
+        // ADQL 2.0 has no OFFSET, so earlier pages are fetched and skipped
+        let skip = page.saturating_sub(1) * PAGE_SIZE;
+        let query = format!(
+            "SELECT TOP {} observation_id, collection, instrument_name, filter, target_name, ra, dec, \
+             exposure_duration {} ORDER BY observation_id",
+            skip + PAGE_SIZE,
+            from_where
+        );
+
+        let table = server
+            .tap_query(Upstream::Ehst, EHST_TAP_URL, TapFormat::EsacJson, &query)
+            .await?;
+        let total_hits = server
+            .tap_count(
+                Upstream::Ehst,
+                EHST_TAP_URL,
+                TapFormat::EsacJson,
+                &from_where,
+            )
+            .await?;
+
+        let observations = table
+            .rows()
+            .skip(skip)
+            .map(|row| Self::observation(&row))
+            .collect::<Vec<Observation>>();
+
+        Ok(EarendelFits::new(
+            observations
+                .iter()
+                .filter_map(|observation| observation.data_url.to_owned())
+                .collect(),
+            observations,
+            page,
+            PAGE_SIZE,
+            total_hits,
+        ))
+    }
+}
@@ -0,0 +1,211 @@
+//! Rendering of APODs as HTML for embedding in static sites and newsletters.
+
+use crate::apod::apod_page_url;
+use crate::{ApodMetadata, EarendelApod};
+
+/// The longest description in the link preview meta tags, in characters, as longer ones are cut by most sites.
+const PREVIEW_DESCRIPTION_CHARS: usize = 200;
+
+impl EarendelApod {
+    /// Renders the APOD as an HTML snippet, with the image linked to the APOD web page, a caption holding the title,
+    /// date, and copyright, and the explanation. The snippet needs no stylesheet or script, and all text is escaped.
+    /// The image is referenced by its URL rather than embedded.
+    pub fn to_html(&self) -> String {
+        let page_url = html_escape(&apod_page_url(self.date));
+        let title = html_escape(&self.title);
+        let mut html = match self.language.as_ref() {
+            Some(language) => format!(
+                "<figure class=\"earendel-apod\" lang=\"{}\">\n",
+                html_escape(language)
+            ),
+            None => String::from("<figure class=\"earendel-apod\">\n"),
+        };
+
+        html.push_str(&format!(
+            "<a href=\"{}\"><img src=\"{}\" alt=\"{}\"",
+            page_url,
+            html_escape(&self.image_url),
+            title
+        ));
+        if let (Some(width), Some(height)) = (self.width, self.height) {
+            html.push_str(&format!(" width=\"{}\" height=\"{}\"", width, height));
+        }
+        html.push_str(" loading=\"lazy\"></a>\n<figcaption>");
+        html.push_str(&format!(
+            "<a href=\"{}\"><strong>{}</strong></a> <time datetime=\"{}\">{}</time>",
+            page_url,
+            title,
+            self.date.format("%Y-%m-%d"),
+            self.date.format("%B %-d, %Y")
+        ));
+        if let Some(copyright) = self
+            .copyright
+            .as_deref()
+            .map(str::trim)
+            .filter(|copyright| !copyright.is_empty())
+        {
+            // the API breaks long credits over several lines
+            let copyright = copyright
+                .split_whitespace()
+                .collect::<Vec<&str>>()
+                .join(" ");
+            html.push_str(&format!("<br>&copy; {}", html_escape(&copyright)));
+        }
+        html.push_str("</figcaption>\n</figure>\n");
+
+        for paragraph in self
+            .explanation
+            .as_deref()
+            .unwrap_or_default()
+            .split("\n\n")
+            .map(str::trim)
+            .filter(|paragraph| !paragraph.is_empty())
+        {
+            html.push_str(&format!("<p>{}</p>\n", html_escape(paragraph)));
+        }
+
+        html
+    }
+
+    /// Renders the OpenGraph and Twitter Card meta tags of the APOD, for the `<head>` of a page shared as a link
+    /// preview. The description is the explanation, cut at a word boundary. See `ApodMetadata::meta_tags`.
+    pub fn meta_tags(&self) -> String {
+        preview_meta_tags(
+            &self.title,
+            self.explanation.as_deref(),
+            &apod_page_url(self.date),
+            Some(preview_image_url(&self.image_url).as_str()),
+            self.width.zip(self.height),
+        )
+    }
+}
+
+impl ApodMetadata {
+    /// Gets the URL of the image best suited to link previews, or None if the APOD is not an image. The standard
+    /// image is preferred, as the high-resolution image is often too large for the crawlers of social sites, and the
+    /// APOD website is linked over HTTPS.
+    pub fn preview_image_url(&self) -> Option<String> {
+        if self.media_type != "image" {
+            return None;
+        }
+        let standard_url = match (self.image_url.as_ref(), self.hd_image_url.as_ref()) {
+            // the image URL is that of the high-resolution image if it was preferred, which leaves no standard URL
+            (Some(image_url), Some(hd_image_url)) if image_url == hd_image_url => None,
+            (image_url, _) => image_url,
+        };
+
+        standard_url
+            .or(self.hd_image_url.as_ref())
+            .map(|url| preview_image_url(url))
+    }
+
+    /// Renders the OpenGraph and Twitter Card meta tags of the APOD, for the `<head>` of a page shared as a link
+    /// preview. The description is the explanation, cut at a word boundary, and the image is chosen by
+    /// `ApodMetadata::preview_image_url`.
+    pub fn meta_tags(&self) -> String {
+        preview_meta_tags(
+            &self.title,
+            self.explanation.as_deref(),
+            &self.page_url,
+            self.preview_image_url().as_deref(),
+            None,
+        )
+    }
+}
+
+/// Renders the OpenGraph and Twitter Card meta tags of a link preview.
+fn preview_meta_tags(
+    title: &str,
+    explanation: Option<&str>,
+    page_url: &str,
+    image_url: Option<&str>,
+    image_size: Option<(u32, u32)>,
+) -> String {
+    let description = preview_description(explanation.unwrap_or_default());
+    let image_size = image_size.map(|(width, height)| (width.to_string(), height.to_string()));
+    let card = if image_url.is_some() {
+        "summary_large_image"
+    } else {
+        "summary"
+    };
+    let mut properties = vec![
+        ("og:type", "article"),
+        ("og:site_name", "Astronomy Picture of the Day"),
+        ("og:title", title),
+        ("og:description", description.as_str()),
+        ("og:url", page_url),
+    ];
+    let mut names = vec![
+        ("twitter:card", card),
+        ("twitter:title", title),
+        ("twitter:description", description.as_str()),
+    ];
+    if let Some(image_url) = image_url {
+        properties.push(("og:image", image_url));
+        if let Some((width, height)) = image_size.as_ref() {
+            properties.push(("og:image:width", width.as_str()));
+            properties.push(("og:image:height", height.as_str()));
+        }
+        properties.push(("og:image:alt", title));
+        names.push(("twitter:image", image_url));
+        names.push(("twitter:image:alt", title));
+    }
+
+    let mut tags = String::new();
+    for (property, content) in properties {
+        tags.push_str(&format!(
+            "<meta property=\"{}\" content=\"{}\">\n",
+            property,
+            html_escape(content)
+        ));
+    }
+    for (name, content) in names {
+        tags.push_str(&format!(
+            "<meta name=\"{}\" content=\"{}\">\n",
+            name,
+            html_escape(content)
+        ));
+    }
+
+    tags
+}
+
+/// Collapses the whitespace of the given explanation and cuts it at a word boundary to at most
+/// `PREVIEW_DESCRIPTION_CHARS` characters, ending with an ellipsis if it was cut.
+pub(crate) fn preview_description(explanation: &str) -> String {
+    let mut description = String::new();
+    for word in explanation.split_whitespace() {
+        let separator = usize::from(!description.is_empty());
+        // one character is kept for the ellipsis
+        if description.chars().count() + separator + word.chars().count()
+            >= PREVIEW_DESCRIPTION_CHARS
+        {
+            description.push('\u{2026}');
+            return description;
+        }
+        if separator == 1 {
+            description.push(' ');
+        }
+        description.push_str(word);
+    }
+
+    description
+}
+
+/// Gets the given image URL as it should be linked from a link preview, with the APOD website upgraded to HTTPS, as
+/// social sites reject insecure images.
+fn preview_image_url(url: &str) -> String {
+    match url.strip_prefix("http://apod.nasa.gov/") {
+        Some(path) => format!("https://apod.nasa.gov/{}", path),
+        None => url.to_owned(),
+    }
+}
+
+/// Escapes the given text for use in HTML content or a quoted attribute value.
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
@@ -0,0 +1,161 @@
+//! Retrieval and caching of the latest DSCOVR EPIC natural-color images of the Earth.
+
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+
+use serde::{Deserialize, Serialize};
+
+use tracing::instrument;
+
+use std::error::Error;
+use std::sync::{MutexGuard, PoisonError};
+
+use crate::{CacheEntry, CacheKind, EarendelServer, Upstream};
+
+const EPIC_API_URL: &str = "https://api.nasa.gov/EPIC/api/natural";
+const EPIC_ARCHIVE_URL: &str = "https://api.nasa.gov/EPIC/archive/natural";
+/// The format of image times reported by the EPIC API.
+const EPIC_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// A natural-color image of the Earth taken by the EPIC camera on DSCOVR.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct EpicImage {
+    /// The EPIC identifier of the image.
+    pub identifier: String,
+    /// The caption of the image.
+    pub caption: String,
+    /// The time the image was taken, in UTC.
+    pub date: NaiveDateTime,
+    /// The latitude of the center of the imaged disk, in degrees.
+    pub centroid_latitude: f64,
+    /// The longitude of the center of the imaged disk, in degrees.
+    pub centroid_longitude: f64,
+    /// The binary representation of the JPEG image.
+    #[serde(with = "serde_bytes")]
+    pub img: Vec<u8>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EpicMetadata {
+    identifier: String,
+    caption: String,
+    image: String,
+    date: String,
+    centroid_coordinates: EpicCoordinates,
+}
+
+#[derive(Debug, Deserialize)]
+struct EpicCoordinates {
+    lat: f64,
+    lon: f64,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct CachedEpic {
+    /// The date the latest images were taken, which EPIC publishes a day or more later.
+    date: NaiveDate,
+    /// The UTC date the images were fetched, after which the API is asked for newer images.
+    #[serde(default)]
+    fetched: NaiveDate,
+    /// Whether every image listed by the API was fetched, so that a request for more is answered from the cache.
+    #[serde(default)]
+    complete: bool,
+    images: Vec<EpicImage>,
+}
+
+impl CachedEpic {
+    /// Gets the date the latest images were taken.
+    pub(crate) fn date(&self) -> NaiveDate {
+        self.date
+    }
+
+    /// Determines whether the given number of images can be served from the cache on the given UTC date.
+    fn covers(&self, today: NaiveDate, limit: usize) -> bool {
+        self.fetched == today && (self.complete || self.images.len() >= limit)
+    }
+
+    pub(crate) fn entry(&self) -> CacheEntry {
+        CacheEntry {
+            kind: CacheKind::Epic,
+            key: self.date.to_string(),
+            age: None,
+            bytes: self.images.iter().map(|image| image.img.len() as u64).sum(),
+        }
+    }
+}
+
+impl EarendelServer {
+    /// Gets up to the given number of the latest EPIC images, in the order they were taken. Returns an error if a web
+    /// request fails or if deserialization fails.
+    #[instrument(skip(self))]
+    pub async fn get_epic_images(
+        &self,
+        limit: usize,
+    ) -> Result<Vec<EpicImage>, Box<dyn Error + Send + Sync>> {
+        let today = Utc::now().date_naive();
+        let cached = self
+            .cached_epic()
+            .as_ref()
+            .filter(|cached| cached.covers(today, limit))
+            .map(|cached| cached.images.iter().take(limit).cloned().collect());
+        if let Some(images) = cached {
+            self.metrics.record_cache(true);
+            return Ok(images);
+        }
+        self.metrics.record_cache(false);
+
+        let resp = self
+            .send(Upstream::Epic, self.nasa_api_get(EPIC_API_URL)?)
+            .await?
+            .error_for_status()?;
+        self.record_rate_limit(resp.headers());
+        let body = resp.text().await?;
+        let metadata = self.parse::<Vec<EpicMetadata>>(Upstream::Epic, &body)?;
+
+        let complete = metadata.len() <= limit;
+        let mut images = Vec::new();
+        for entry in metadata.into_iter().take(limit) {
+            let date = NaiveDateTime::parse_from_str(&entry.date, EPIC_TIME_FORMAT)?;
+            let image_url = format!(
+                "{}/{}/jpg/{}.jpg",
+                EPIC_ARCHIVE_URL,
+                date.format("%Y/%m/%d"),
+                entry.image
+            );
+            let resp = self
+                .send(Upstream::Epic, self.nasa_api_get(image_url)?)
+                .await?
+                .error_for_status()?;
+            self.record_rate_limit(resp.headers());
+            let img = self.read_limited(resp).await?;
+
+            images.push(EpicImage {
+                identifier: entry.identifier,
+                caption: entry.caption,
+                date,
+                centroid_latitude: entry.centroid_coordinates.lat,
+                centroid_longitude: entry.centroid_coordinates.lon,
+                img,
+            });
+        }
+
+        *self.cached_epic() = Some(CachedEpic {
+            date: images
+                .iter()
+                .map(|image| image.date.date())
+                .max()
+                .unwrap_or(today),
+            fetched: today,
+            complete,
+            images: images.to_owned(),
+        });
+
+        Ok(images)
+    }
+
+    /// Locks the cached EPIC images. The guard must not be held across an await.
+    pub(crate) fn cached_epic(&self) -> MutexGuard<'_, Option<CachedEpic>> {
+        self.cached_epic
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
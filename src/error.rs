@@ -0,0 +1,185 @@
+//! The specific errors produced by Earendel.
+//!
+//! Fallible functions return `Box<dyn Error + Send + Sync>`, so their futures can be spawned onto a multi-threaded
+//! runtime; failures described here can be recovered with `downcast_ref::<EarendelError>()`.
+
+use chrono::NaiveDate;
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+use crate::redact::redact;
+use crate::Upstream;
+
+/// A failure with a specific, actionable cause.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum EarendelError {
+    /// A FITS CHECKSUM or DATASUM keyword did not match the contents of its HDU.
+    FitsChecksumMismatch {
+        /// The zero-based index of the HDU.
+        hdu: usize,
+        /// The keyword that failed verification.
+        keyword: &'static str,
+        /// The value recorded in the header.
+        expected: String,
+        /// The value computed from the file contents.
+        actual: String,
+    },
+    /// A FITS data unit ended before the size given by its header, such as after a truncated transfer.
+    FitsTruncated {
+        /// The size of the data unit given by its header, in bytes.
+        expected: usize,
+        /// The number of bytes present.
+        actual: usize,
+    },
+    /// A download was aborted because its body exceeded the configured maximum size.
+    DownloadTooLarge {
+        /// The URL of the download.
+        url: String,
+        /// The maximum size, in bytes.
+        limit: u64,
+    },
+    /// The APOD is not an image, such as a video or an interactive page, so there is no image to download.
+    ApodNotImage {
+        /// The media type reported by the APOD API, such as `video` or `other`.
+        media_type: String,
+        /// The URL of the APOD web page, which can be linked to instead.
+        page_url: String,
+    },
+    /// The APOD did not include the URL of its image, which happens on some days that are not plain images.
+    ApodImageUrlMissing {
+        /// The date of the APOD.
+        date: NaiveDate,
+        /// The media type reported by the APOD API.
+        media_type: String,
+        /// The URL of the APOD web page, which can be linked to instead.
+        page_url: String,
+    },
+    /// An APOD was requested for a date on which none was published, either before the first APOD or in the future.
+    InvalidDate {
+        /// The requested date.
+        date: NaiveDate,
+        /// The date of the first APOD.
+        earliest: NaiveDate,
+        /// The date of the current APOD.
+        latest: NaiveDate,
+    },
+    /// A daily image was requested from a source that is not registered.
+    UnknownDailyImageSource {
+        /// The requested source.
+        source: String,
+        /// The names of the registered sources.
+        available: Vec<String>,
+    },
+    /// A downloaded file did not match its published checksum.
+    DownloadChecksumMismatch {
+        /// The URL of the download.
+        url: String,
+        /// The published checksum.
+        expected: String,
+        /// The checksum computed from the downloaded file.
+        actual: String,
+    },
+    /// A MAST query did not complete, such as when the query is malformed or MAST is overloaded.
+    MastQueryFailed {
+        /// The status reported by MAST, such as `ERROR`.
+        status: String,
+        /// The message reported by MAST.
+        msg: String,
+    },
+    /// A request was not sent because the circuit breaker of its upstream is open after repeated failures.
+    CircuitOpen {
+        /// The upstream whose circuit is open.
+        upstream: Upstream,
+        /// The time remaining until a request to the upstream is attempted again.
+        retry_after: Duration,
+    },
+}
+
+impl Display for EarendelError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EarendelError::FitsChecksumMismatch {
+                hdu,
+                keyword,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "FITS {} mismatch in HDU {}: expected {}, computed {}",
+                keyword, hdu, expected, actual
+            ),
+            EarendelError::FitsTruncated { expected, actual } => write!(
+                f,
+                "FITS data unit is truncated: expected {} bytes, found {}",
+                expected, actual
+            ),
+            EarendelError::DownloadTooLarge { url, limit } => {
+                write!(
+                    f,
+                    "download of {} exceeds the limit of {} bytes",
+                    redact(url),
+                    limit
+                )
+            }
+            EarendelError::ApodNotImage {
+                media_type,
+                page_url,
+            } => write!(
+                f,
+                "APOD is a {} rather than an image; see {}",
+                media_type, page_url
+            ),
+            EarendelError::ApodImageUrlMissing {
+                date,
+                media_type,
+                page_url,
+            } => write!(
+                f,
+                "APOD of {} ({}) has no image URL; see {}",
+                date, media_type, page_url
+            ),
+            EarendelError::InvalidDate {
+                date,
+                earliest,
+                latest,
+            } => write!(
+                f,
+                "no APOD exists for {}; dates must be between {} and {}",
+                date, earliest, latest
+            ),
+            EarendelError::UnknownDailyImageSource { source, available } => write!(
+                f,
+                "unknown daily image source {}; available sources are {}",
+                source,
+                available.join(", ")
+            ),
+            EarendelError::DownloadChecksumMismatch {
+                url,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "checksum mismatch in download of {}: expected {}, computed {}",
+                redact(url),
+                expected,
+                actual
+            ),
+            EarendelError::MastQueryFailed { status, msg } => {
+                write!(f, "MAST query failed with status {}: {}", status, msg)
+            }
+            EarendelError::CircuitOpen {
+                upstream,
+                retry_after,
+            } => write!(
+                f,
+                "{:?} is unavailable after repeated failures; retry in {:?}",
+                upstream, retry_after
+            ),
+        }
+    }
+}
+
+impl Error for EarendelError {}
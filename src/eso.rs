@@ -0,0 +1,38 @@
+//! The ESO Science Archive, searched through its ObsCore TAP service.
+
+use astro_rs::coordinates::Icrs;
+
+use async_trait::async_trait;
+
+use std::error::Error;
+
+use crate::archive::{EarendelFits, ObservationArchive};
+use crate::tap::ObsCoreArchive;
+use crate::{EarendelServer, Upstream};
+
+const ESO: ObsCoreArchive = ObsCoreArchive {
+    name: "ESO",
+    base_url: "https://archive.eso.org/tap_obs",
+    upstream: Upstream::Eso,
+    id_column: "dp_id",
+};
+
+/// The ESO Science Archive, hosting reduced observations from the VLT, La Silla, and other ESO facilities.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EsoArchive;
+
+#[async_trait]
+impl ObservationArchive for EsoArchive {
+    fn name(&self) -> &str {
+        ESO.name
+    }
+
+    async fn search(
+        &self,
+        server: &EarendelServer,
+        coords: &Icrs,
+        page: usize,
+    ) -> Result<EarendelFits, Box<dyn Error + Send + Sync>> {
+        ESO.search(server, coords, page).await
+    }
+}
@@ -0,0 +1,75 @@
+//! Queries against the NASA Exoplanet Archive for the parameters of known planets.
+
+use serde::{Deserialize, Serialize};
+
+use tracing::instrument;
+
+use std::error::Error;
+
+use crate::tap::{adql_escape, TapFormat};
+use crate::{EarendelServer, Upstream};
+
+const EXOPLANET_TAP_URL: &str = "https://exoplanetarchive.ipac.caltech.edu/TAP";
+
+/// The composite parameters of a confirmed exoplanet.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Exoplanet {
+    /// The name of the planet.
+    pub name: String,
+    /// The name of the host star.
+    pub host_name: Option<String>,
+    /// The orbital period, in days.
+    pub orbital_period: Option<f64>,
+    /// The radius, in Earth radii.
+    pub radius: Option<f64>,
+    /// The mass or minimum mass, in Earth masses.
+    pub mass: Option<f64>,
+    /// The equilibrium temperature, in kelvins.
+    pub equilibrium_temperature: Option<f64>,
+    /// The method by which the planet was discovered, such as Transit or Radial Velocity.
+    pub discovery_method: Option<String>,
+    /// The year the planet was discovered.
+    pub discovery_year: Option<u32>,
+    /// The distance to the planetary system, in parsecs.
+    pub distance: Option<f64>,
+}
+
+impl EarendelServer {
+    /// Gets the parameters of the confirmed exoplanet with the given name, such as `TRAPPIST-1 e`. The name is matched
+    /// without regard to case. Returns an error if the planet is unknown or if the web request fails.
+    #[instrument(skip(self))]
+    pub async fn lookup_exoplanet(
+        &self,
+        name: &str,
+    ) -> Result<Exoplanet, Box<dyn Error + Send + Sync>> {
+        let query = format!(
+            "SELECT pl_name, hostname, pl_orbper, pl_rade, pl_bmasse, pl_eqt, discoverymethod, disc_year, sy_dist \
+             FROM pscomppars WHERE LOWER(pl_name) = '{}'",
+            adql_escape(&name.trim().to_lowercase())
+        );
+        let table = self
+            .tap_query(
+                Upstream::Exoplanet,
+                EXOPLANET_TAP_URL,
+                TapFormat::Csv,
+                &query,
+            )
+            .await?;
+        let row = table
+            .rows()
+            .next()
+            .ok_or_else(|| format!("the Exoplanet Archive does not know the planet {}", name))?;
+
+        Ok(Exoplanet {
+            name: row.get_string("pl_name").unwrap_or_else(|| name.to_owned()),
+            host_name: row.get_string("hostname"),
+            orbital_period: row.get_f64("pl_orbper"),
+            radius: row.get_f64("pl_rade"),
+            mass: row.get_f64("pl_bmasse"),
+            equilibrium_temperature: row.get_f64("pl_eqt"),
+            discovery_method: row.get_string("discoverymethod"),
+            discovery_year: row.get_f64("disc_year").map(|year| year as u32),
+            distance: row.get_f64("sy_dist"),
+        })
+    }
+}
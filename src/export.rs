@@ -0,0 +1,116 @@
+//! Export of FITS search results to tabular formats understood by other tools.
+
+use crate::{EarendelFits, Observation};
+
+/// The name, VOTable datatype, and unit of each exported column, in order.
+const COLUMNS: [(&str, &str, Option<&str>); 13] = [
+    ("archive", "char", None),
+    ("obs_id", "char", None),
+    ("collection", "char", None),
+    ("instrument", "char", None),
+    ("filters", "char", None),
+    ("target_name", "char", None),
+    ("target_classification", "char", None),
+    ("dataproduct_type", "char", None),
+    ("ra", "double", Some("deg")),
+    ("dec", "double", Some("deg")),
+    ("exposure_time", "double", Some("s")),
+    ("preview_url", "char", None),
+    ("data_url", "char", None),
+];
+
+/// Gets the values of the exported columns of the given observation, in the order of `COLUMNS`.
+fn values(observation: &Observation) -> [Option<String>; 13] {
+    [
+        Some(observation.archive.to_owned()),
+        Some(observation.obs_id.to_owned()),
+        observation.collection.to_owned(),
+        observation.instrument.to_owned(),
+        observation.filters.to_owned(),
+        observation.target_name.to_owned(),
+        observation.target_classification.to_owned(),
+        observation.dataproduct_type.to_owned(),
+        observation.ra.map(|ra| ra.to_string()),
+        observation.dec.map(|dec| dec.to_string()),
+        observation.exposure_time.map(|time| time.to_string()),
+        observation.preview_url.to_owned(),
+        observation.data_url.to_owned(),
+    ]
+}
+
+impl EarendelFits {
+    /// Renders the observations as CSV, with a header row. Missing values are left empty.
+    pub fn to_csv(&self) -> String {
+        let mut csv = COLUMNS
+            .iter()
+            .map(|(name, _, _)| *name)
+            .collect::<Vec<&str>>()
+            .join(",");
+        csv.push_str("\r\n");
+        for observation in self.observations.iter() {
+            let row = values(observation)
+                .iter()
+                .map(|value| csv_field(value.as_deref().unwrap_or_default()))
+                .collect::<Vec<String>>()
+                .join(",");
+            csv.push_str(&row);
+            csv.push_str("\r\n");
+        }
+
+        csv
+    }
+
+    /// Renders the observations as a VOTable 1.4 document. Missing values are left empty.
+    pub fn to_votable(&self) -> String {
+        let mut xml = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <VOTABLE version=\"1.4\" xmlns=\"http://www.ivoa.net/xml/VOTable/v1.3\">\n\
+             <RESOURCE type=\"results\">\n\
+             <TABLE name=\"observations\">\n",
+        );
+        for (name, datatype, unit) in COLUMNS.iter() {
+            xml.push_str(&format!(
+                "<FIELD name=\"{}\" datatype=\"{}\"",
+                name, datatype
+            ));
+            if *datatype == "char" {
+                xml.push_str(" arraysize=\"*\"");
+            }
+            if let Some(unit) = unit {
+                xml.push_str(&format!(" unit=\"{}\"", unit));
+            }
+            xml.push_str("/>\n");
+        }
+        xml.push_str("<DATA>\n<TABLEDATA>\n");
+        for observation in self.observations.iter() {
+            xml.push_str("<TR>");
+            for value in values(observation) {
+                xml.push_str(&format!(
+                    "<TD>{}</TD>",
+                    xml_escape(value.as_deref().unwrap_or_default())
+                ));
+            }
+            xml.push_str("</TR>\n");
+        }
+        xml.push_str("</TABLEDATA>\n</DATA>\n</TABLE>\n</RESOURCE>\n</VOTABLE>\n");
+
+        xml
+    }
+}
+
+/// Quotes the given CSV field if it contains a delimiter, quote, or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
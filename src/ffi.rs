@@ -0,0 +1,176 @@
+//! UniFFI bindings for Kotlin and Swift consumers.
+//!
+//! The exported functions are asynchronous, running on the tokio runtime managed by UniFFI, and map to coroutines in
+//! Kotlin and async functions in Swift.
+
+use chrono::NaiveDate;
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+
+use crate::{EarendelApod, EarendelFits, EarendelServer, Observation};
+
+/// A failure reported across the binding layer.
+#[derive(Debug, uniffi::Error)]
+pub enum FfiError {
+    /// A request failed, or an argument was invalid.
+    Failed {
+        /// A description of the failure.
+        message: String,
+    },
+}
+
+impl Display for FfiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FfiError::Failed { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for FfiError {}
+
+impl From<Box<dyn Error + Send + Sync>> for FfiError {
+    fn from(error: Box<dyn Error + Send + Sync>) -> Self {
+        FfiError::Failed {
+            message: error.to_string(),
+        }
+    }
+}
+
+/// The APOD, as exposed to bindings.
+#[derive(Clone, Debug, uniffi::Record)]
+pub struct FfiApod {
+    /// The title of the APOD.
+    pub title: String,
+    /// The date the APOD was published, formatted as YYYY-MM-DD.
+    pub date: String,
+    /// The URL the image was downloaded from.
+    pub image_url: String,
+    /// The explanation of the APOD, if any.
+    pub explanation: Option<String>,
+    /// The copyright string.
+    pub copyright: Option<String>,
+    /// The width of the image, in pixels, if known.
+    pub width: Option<u32>,
+    /// The height of the image, in pixels, if known.
+    pub height: Option<u32>,
+    /// The binary representation of the image.
+    pub img: Vec<u8>,
+}
+
+impl From<EarendelApod> for FfiApod {
+    fn from(apod: EarendelApod) -> Self {
+        FfiApod {
+            title: apod.title,
+            date: apod.date.format("%Y-%m-%d").to_string(),
+            image_url: apod.image_url,
+            explanation: apod.explanation,
+            copyright: apod.copyright,
+            width: apod.width,
+            height: apod.height,
+            img: apod.image.into_bytes().unwrap_or_default(),
+        }
+    }
+}
+
+/// An observation listed by an archive, as exposed to bindings.
+#[derive(Clone, Debug, uniffi::Record)]
+pub struct FfiObservation {
+    /// The name of the archive that listed the observation.
+    pub archive: String,
+    /// The archive identifier of the observation.
+    pub obs_id: String,
+    /// The mission or collection of the observation.
+    pub collection: Option<String>,
+    /// The instrument that acquired the observation.
+    pub instrument: Option<String>,
+    /// The name of the observed target.
+    pub target_name: Option<String>,
+    /// The right ascension of the observation, in degrees.
+    pub ra: Option<f64>,
+    /// The declination of the observation, in degrees.
+    pub dec: Option<f64>,
+    /// The URL of a preview image of the observation.
+    pub preview_url: Option<String>,
+    /// The URL of the observation data.
+    pub data_url: Option<String>,
+}
+
+impl From<Observation> for FfiObservation {
+    fn from(observation: Observation) -> Self {
+        FfiObservation {
+            archive: observation.archive,
+            obs_id: observation.obs_id,
+            collection: observation.collection,
+            instrument: observation.instrument,
+            target_name: observation.target_name,
+            ra: observation.ra,
+            dec: observation.dec,
+            preview_url: observation.preview_url,
+            data_url: observation.data_url,
+        }
+    }
+}
+
+/// A page of FITS observations, as exposed to bindings.
+#[derive(Clone, Debug, uniffi::Record)]
+pub struct FfiFits {
+    /// The observations for the current page.
+    pub observations: Vec<FfiObservation>,
+    /// The current page number.
+    pub page: u64,
+    /// The total number of available FITS files.
+    pub total_hits: u64,
+}
+
+impl From<EarendelFits> for FfiFits {
+    fn from(fits: EarendelFits) -> Self {
+        FfiFits {
+            observations: fits.observations.into_iter().map(Into::into).collect(),
+            page: fits.page as u64,
+            total_hits: fits.total_hits as u64,
+        }
+    }
+}
+
+/// The manager of the Earendel functionality and state, as exposed to bindings.
+#[derive(uniffi::Object)]
+pub struct FfiEarendelServer {
+    server: EarendelServer,
+}
+
+#[uniffi::export(async_runtime = "tokio")]
+impl FfiEarendelServer {
+    /// Creates a server, optionally preferring high-resolution APOD images.
+    #[uniffi::constructor]
+    pub fn new(prefer_hd: bool) -> Arc<Self> {
+        Arc::new(FfiEarendelServer {
+            server: EarendelServer::builder().prefer_hd(prefer_hd).build(),
+        })
+    }
+
+    /// Gets the current APOD.
+    pub async fn get_apod(&self) -> Result<FfiApod, FfiError> {
+        Ok(self.server.get_apod_image().await?.into())
+    }
+
+    /// Gets the APOD of the given date, formatted as YYYY-MM-DD.
+    pub async fn get_apod_for_date(&self, date: String) -> Result<FfiApod, FfiError> {
+        let date = NaiveDate::parse_from_str(&date, "%Y-%m-%d").map_err(|e| FfiError::Failed {
+            message: format!("invalid date: {}", e),
+        })?;
+
+        Ok(self.server.get_apod_image_for_date(date).await?.into())
+    }
+
+    /// Gets a page of FITS observations of the current APOD target.
+    pub async fn get_fits(&self, page: u64) -> Result<FfiFits, FfiError> {
+        let page = usize::try_from(page).map_err(|_| FfiError::Failed {
+            message: String::from("page is out of range"),
+        })?;
+
+        Ok(self.server.get_fits_for_apod(page).await?.into())
+    }
+}
@@ -0,0 +1,878 @@
+//! Parsing of FITS files downloaded from the archives.
+
+use chrono::{NaiveDate, NaiveDateTime};
+
+use serde::{Deserialize, Serialize};
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, Cursor, ErrorKind, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::EarendelError;
+
+/// The size of a FITS block, in bytes.
+pub const BLOCK_SIZE: usize = 2880;
+/// The size of a FITS header card, in bytes.
+pub const CARD_SIZE: usize = 80;
+
+/// The value of a FITS header card.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum FitsValue {
+    /// A logical (T or F) value.
+    Logical(bool),
+    /// An integer value.
+    Integer(i64),
+    /// A floating-point value.
+    Float(f64),
+    /// A character string value.
+    String(String),
+}
+
+impl FitsValue {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "" => None,
+            "T" => Some(FitsValue::Logical(true)),
+            "F" => Some(FitsValue::Logical(false)),
+            _ => value
+                .parse::<i64>()
+                .map(FitsValue::Integer)
+                .or_else(|_| {
+                    value
+                        .replace(['D', 'd'], "E")
+                        .parse::<f64>()
+                        .map(FitsValue::Float)
+                })
+                .ok(),
+        }
+    }
+
+    /// Gets the value as a string slice, if it is a character string.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            FitsValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Gets the value as a float, if it is numeric.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            FitsValue::Integer(value) => Some(*value as f64),
+            FitsValue::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Gets the value as an integer, if it is an integer.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            FitsValue::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Gets the value as a boolean, if it is logical.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            FitsValue::Logical(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+/// A single 80-character FITS header card.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct FitsCard {
+    /// The keyword of the card.
+    pub keyword: String,
+    /// The value of the card, if it has one.
+    pub value: Option<FitsValue>,
+    /// The comment of the card, if it has one.
+    pub comment: Option<String>,
+}
+
+impl FitsCard {
+    fn parse(raw: &[u8]) -> Self {
+        let text = String::from_utf8_lossy(raw);
+        let text: &str = &text;
+        let keyword = text.get(..8).unwrap_or(text).trim_end().to_owned();
+        let rest = text.get(8..).unwrap_or_default();
+
+        let Some(field) = rest.strip_prefix("= ") else {
+            let comment = rest.trim();
+            return FitsCard {
+                keyword,
+                value: None,
+                comment: (!comment.is_empty()).then(|| comment.to_owned()),
+            };
+        };
+
+        let field = field.trim_start();
+        let (value, remainder) = if let Some(quoted) = field.strip_prefix('\'') {
+            let mut value = String::new();
+            let mut chars = quoted.char_indices().peekable();
+            let mut end = quoted.len();
+            while let Some((i, c)) = chars.next() {
+                if c == '\'' {
+                    if matches!(chars.peek(), Some((_, '\''))) {
+                        chars.next();
+                    } else {
+                        end = i + 1;
+                        break;
+                    }
+                }
+                value.push(c);
+            }
+            (
+                Some(FitsValue::String(value.trim_end().to_owned())),
+                &quoted[end..],
+            )
+        } else {
+            let (value, remainder) = field.split_at(field.find('/').unwrap_or(field.len()));
+            (FitsValue::parse(value.trim()), remainder)
+        };
+
+        let comment = remainder
+            .trim_start()
+            .strip_prefix('/')
+            .map(|comment| comment.trim().to_owned())
+            .filter(|comment| !comment.is_empty());
+
+        FitsCard {
+            keyword,
+            value,
+            comment,
+        }
+    }
+}
+
+/// The header of a FITS header-data unit.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct FitsHeader {
+    /// The cards of the header, in order, excluding the END card.
+    pub cards: Vec<FitsCard>,
+}
+
+impl FitsHeader {
+    /// Gets the value of the first card with the given keyword.
+    pub fn get(&self, keyword: &str) -> Option<&FitsValue> {
+        self.cards
+            .iter()
+            .find(|card| card.keyword == keyword)
+            .and_then(|card| card.value.as_ref())
+    }
+
+    /// Gets the string value of the given keyword.
+    pub fn get_str(&self, keyword: &str) -> Option<&str> {
+        self.get(keyword).and_then(FitsValue::as_str)
+    }
+
+    /// Gets the numeric value of the given keyword.
+    pub fn get_f64(&self, keyword: &str) -> Option<f64> {
+        self.get(keyword).and_then(FitsValue::as_f64)
+    }
+
+    /// Gets the integer value of the given keyword.
+    pub fn get_i64(&self, keyword: &str) -> Option<i64> {
+        self.get(keyword).and_then(FitsValue::as_i64)
+    }
+
+    /// Gets the telescope that acquired the data (TELESCOP).
+    pub fn telescope(&self) -> Option<&str> {
+        self.get_str("TELESCOP")
+    }
+
+    /// Gets the instrument that acquired the data (INSTRUME).
+    pub fn instrument(&self) -> Option<&str> {
+        self.get_str("INSTRUME")
+    }
+
+    /// Gets the exposure time in seconds (EXPTIME).
+    pub fn exposure_time(&self) -> Option<f64> {
+        self.get_f64("EXPTIME")
+    }
+
+    /// Gets the start of the observation (DATE-OBS).
+    pub fn date_obs(&self) -> Option<NaiveDateTime> {
+        let value = self.get_str("DATE-OBS")?;
+        NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f")
+            .ok()
+            .or_else(|| {
+                NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                    .ok()
+                    .and_then(|date| date.and_hms_opt(0, 0, 0))
+            })
+    }
+
+    /// Gets the size of the data unit following this header, in bytes, excluding padding.
+    pub fn data_size(&self) -> usize {
+        let naxis = self.get_i64("NAXIS").unwrap_or(0).max(0) as usize;
+        if naxis == 0 {
+            return 0;
+        }
+        let bitpix = self.get_i64("BITPIX").unwrap_or(8).unsigned_abs() as usize;
+        let pcount = self.get_i64("PCOUNT").unwrap_or(0).max(0) as usize;
+        let gcount = self.get_i64("GCOUNT").unwrap_or(1).max(1) as usize;
+        // random groups have NAXIS1 = 0, which does not contribute to the size
+        let first_axis = match self.get("GROUPS").and_then(FitsValue::as_bool) {
+            Some(true) => 2,
+            Some(false) | None => 1,
+        };
+        let elements = (first_axis..=naxis)
+            .map(|axis| self.get_i64(&format!("NAXIS{}", axis)).unwrap_or(0).max(0) as usize)
+            .product::<usize>();
+
+        bitpix / 8 * gcount * (pcount + elements)
+    }
+
+    /// Gets the size of the data unit following this header, in bytes, including padding.
+    pub fn padded_data_size(&self) -> usize {
+        self.data_size().div_ceil(BLOCK_SIZE) * BLOCK_SIZE
+    }
+}
+
+/// Reads the header of the next HDU from the given reader. Returns None if the reader is already exhausted.
+pub(crate) fn read_next_header<R: Read>(
+    reader: &mut R,
+) -> Result<Option<FitsHeader>, Box<dyn Error + Send + Sync>> {
+    let mut header = FitsHeader::default();
+    let mut block = [0; BLOCK_SIZE];
+    let mut first = true;
+    loop {
+        match reader.read_exact(&mut block) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof && first => return Ok(None),
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                return Err("FITS file ended before the END card".into())
+            }
+            Err(e) => return Err(e.into()),
+        }
+        if first && !block.starts_with(b"SIMPLE") && !block.starts_with(b"XTENSION") {
+            return Err("FITS header does not start with SIMPLE or XTENSION".into());
+        }
+        first = false;
+
+        for raw in block.chunks(CARD_SIZE) {
+            let card = FitsCard::parse(raw);
+            if card.keyword == "END" {
+                return Ok(Some(header));
+            }
+            if !card.keyword.is_empty() || card.comment.is_some() {
+                header.cards.push(card);
+            }
+        }
+    }
+}
+
+fn read_all_headers<R: Read + Seek>(
+    mut reader: R,
+) -> Result<Vec<FitsHeader>, Box<dyn Error + Send + Sync>> {
+    let mut headers = Vec::new();
+    while let Some(header) = read_next_header(&mut reader)? {
+        reader.seek(SeekFrom::Current(header.padded_data_size() as i64))?;
+        headers.push(header);
+    }
+
+    Ok(headers)
+}
+
+/// Reads the primary header from the given reader, without reading any data.
+pub fn read_primary_header<R: Read>(
+    mut reader: R,
+) -> Result<FitsHeader, Box<dyn Error + Send + Sync>> {
+    read_next_header(&mut reader)?.ok_or_else(|| "FITS file is empty".into())
+}
+
+/// Reads the headers of every HDU in the given FITS file contents.
+pub fn read_headers(bytes: &[u8]) -> Result<Vec<FitsHeader>, Box<dyn Error + Send + Sync>> {
+    read_all_headers(Cursor::new(bytes))
+}
+
+/// Reads the headers of every HDU in the FITS file at the given path, skipping over the data units.
+pub fn read_headers_from_path<P: AsRef<Path>>(
+    path: P,
+) -> Result<Vec<FitsHeader>, Box<dyn Error + Send + Sync>> {
+    read_all_headers(BufReader::new(File::open(path)?))
+}
+
+/// The pixel data of the first image HDU of a FITS file.
+#[derive(Clone, Debug)]
+pub struct FitsImage {
+    /// The header of the HDU containing the image.
+    pub header: FitsHeader,
+    /// The number of pixels along the first axis.
+    pub width: usize,
+    /// The number of pixels along the second axis.
+    pub height: usize,
+    /// The physical pixel values of the first image plane, row by row starting at the bottom. Undefined pixels are NaN.
+    pub data: Vec<f64>,
+}
+
+impl FitsImage {
+    fn is_image(header: &FitsHeader) -> bool {
+        let extension = header.get_str("XTENSION").map(str::trim);
+        let width = header.get_i64("NAXIS1").unwrap_or(0);
+        let height = header.get_i64("NAXIS2").unwrap_or(0);
+
+        matches!(extension, None | Some("IMAGE"))
+            && header.get_i64("NAXIS").unwrap_or(0) >= 2
+            && width > 0
+            && height > 0
+    }
+
+    fn decode(header: FitsHeader, raw: &[u8]) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let width = header.get_i64("NAXIS1").unwrap_or(0) as usize;
+        let height = header.get_i64("NAXIS2").unwrap_or(0) as usize;
+        let bitpix = header
+            .get_i64("BITPIX")
+            .ok_or("FITS image is missing BITPIX")?;
+        let bscale = header.get_f64("BSCALE").unwrap_or(1.0);
+        let bzero = header.get_f64("BZERO").unwrap_or(0.0);
+        let blank = header.get_i64("BLANK");
+
+        let bytes_per_pixel = bitpix.unsigned_abs() as usize / 8;
+        let size = width * height * bytes_per_pixel;
+        let raw = raw.get(..size).ok_or(EarendelError::FitsTruncated {
+            expected: size,
+            actual: raw.len(),
+        })?;
+        let physical = |value: f64| bzero + bscale * value;
+        let integer = |value: i64| {
+            if Some(value) == blank {
+                f64::NAN
+            } else {
+                physical(value as f64)
+            }
+        };
+
+        let data = match bitpix {
+            8 => raw.iter().map(|v| integer(i64::from(*v))).collect(),
+            16 => raw
+                .chunks_exact(2)
+                .map(|v| integer(i64::from(i16::from_be_bytes([v[0], v[1]]))))
+                .collect(),
+            32 => raw
+                .chunks_exact(4)
+                .map(|v| integer(i64::from(i32::from_be_bytes([v[0], v[1], v[2], v[3]]))))
+                .collect(),
+            64 => raw
+                .chunks_exact(8)
+                .map(|v| integer(i64::from_be_bytes(v.try_into().unwrap_or_default())))
+                .collect(),
+            -32 => raw
+                .chunks_exact(4)
+                .map(|v| physical(f64::from(f32::from_be_bytes([v[0], v[1], v[2], v[3]]))))
+                .collect(),
+            -64 => raw
+                .chunks_exact(8)
+                .map(|v| physical(f64::from_be_bytes(v.try_into().unwrap_or_default())))
+                .collect(),
+            _ => return Err(format!("unsupported FITS BITPIX value {}", bitpix).into()),
+        };
+
+        Ok(FitsImage {
+            header,
+            width,
+            height,
+            data,
+        })
+    }
+
+    /// Gets the physical value of the pixel at the given zero-based position, if it is within the image.
+    pub fn get(&self, x: usize, y: usize) -> Option<f64> {
+        if x < self.width && y < self.height {
+            self.data.get(y * self.width + x).copied()
+        } else {
+            None
+        }
+    }
+}
+
+/// Reads the first image plane of the first image HDU in the given FITS file contents.
+pub fn read_image(bytes: &[u8]) -> Result<FitsImage, Box<dyn Error + Send + Sync>> {
+    let mut reader = Cursor::new(bytes);
+    while let Some(header) = read_next_header(&mut reader)? {
+        let start = reader.position() as usize;
+        if FitsImage::is_image(&header) {
+            return FitsImage::decode(header, &bytes[start..]);
+        }
+        reader.set_position((start + header.padded_data_size()) as u64);
+    }
+
+    Err("FITS file does not contain an image".into())
+}
+
+/// A column of a FITS binary table.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FitsColumn {
+    /// The name of the column, from its TTYPE keyword.
+    pub name: String,
+    /// The data type code of the column, from its TFORM keyword, such as `E` for 32-bit or `D` for 64-bit floats.
+    pub format: char,
+    /// The number of values in each cell of the column.
+    pub repeat: usize,
+    /// The offset of the column within a row, in bytes.
+    offset: usize,
+}
+
+/// The rows of the first binary table HDU of a FITS file, such as a light curve.
+#[derive(Clone, Debug)]
+pub struct FitsTable {
+    /// The header of the HDU containing the table.
+    pub header: FitsHeader,
+    /// The columns of the table, in order.
+    pub columns: Vec<FitsColumn>,
+    /// The number of rows of the table.
+    pub rows: usize,
+    row_size: usize,
+    data: Vec<u8>,
+}
+
+impl FitsTable {
+    fn is_table(header: &FitsHeader) -> bool {
+        header.get_str("XTENSION").map(str::trim) == Some("BINTABLE")
+    }
+
+    fn decode(header: FitsHeader, raw: &[u8]) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let row_size = header.get_i64("NAXIS1").unwrap_or(0).max(0) as usize;
+        let rows = header.get_i64("NAXIS2").unwrap_or(0).max(0) as usize;
+        let fields = header.get_i64("TFIELDS").unwrap_or(0).max(0) as usize;
+
+        let mut columns = Vec::with_capacity(fields);
+        let mut offset = 0;
+        for index in 1..=fields {
+            let tform = header
+                .get_str(&format!("TFORM{}", index))
+                .map(str::trim)
+                .ok_or_else(|| format!("FITS table is missing TFORM{}", index))?;
+            let digits = tform
+                .find(|c: char| !c.is_ascii_digit())
+                .ok_or_else(|| format!("invalid FITS table format {}", tform))?;
+            let repeat = if digits == 0 {
+                1
+            } else {
+                tform[..digits].parse::<usize>()?
+            };
+            let format = tform[digits..].chars().next().unwrap_or_default();
+            let width = match format {
+                'L' | 'B' | 'A' => repeat,
+                'X' => repeat.div_ceil(8),
+                'I' => 2 * repeat,
+                'J' | 'E' => 4 * repeat,
+                'K' | 'D' | 'C' | 'P' => 8 * repeat,
+                'M' | 'Q' => 16 * repeat,
+                _ => return Err(format!("invalid FITS table format {}", tform).into()),
+            };
+            columns.push(FitsColumn {
+                name: header
+                    .get_str(&format!("TTYPE{}", index))
+                    .map(|name| name.trim().to_owned())
+                    .unwrap_or_default(),
+                format,
+                repeat,
+                offset,
+            });
+            offset += width;
+        }
+        if offset > row_size {
+            return Err("FITS table columns are wider than its rows".into());
+        }
+
+        let size = row_size * rows;
+        let data = raw
+            .get(..size)
+            .ok_or(EarendelError::FitsTruncated {
+                expected: size,
+                actual: raw.len(),
+            })?
+            .to_vec();
+
+        Ok(FitsTable {
+            header,
+            columns,
+            rows,
+            row_size,
+            data,
+        })
+    }
+
+    /// Gets the physical values of the numeric column with the given name, compared without regard to ASCII case, in
+    /// row order. Undefined values are NaN. Returns None if there is no such column or if its cells are not single
+    /// numbers.
+    pub fn column(&self, name: &str) -> Option<Vec<f64>> {
+        let (index, column) = self
+            .columns
+            .iter()
+            .enumerate()
+            .find(|(_, column)| column.name.eq_ignore_ascii_case(name))?;
+        if column.repeat != 1 {
+            return None;
+        }
+        let number = index + 1;
+        let scale = self
+            .header
+            .get_f64(&format!("TSCAL{}", number))
+            .unwrap_or(1.0);
+        let zero = self
+            .header
+            .get_f64(&format!("TZERO{}", number))
+            .unwrap_or(0.0);
+        let null = self.header.get_i64(&format!("TNULL{}", number));
+        let physical = |value: f64| zero + scale * value;
+        let integer = |value: i64| {
+            if Some(value) == null {
+                f64::NAN
+            } else {
+                physical(value as f64)
+            }
+        };
+        let size = match column.format {
+            'B' => 1,
+            'I' => 2,
+            'J' | 'E' => 4,
+            'K' | 'D' => 8,
+            _ => return None,
+        };
+
+        Some(
+            self.data
+                .chunks_exact(self.row_size)
+                .map(|row| {
+                    let v = &row[column.offset..column.offset + size];
+                    match column.format {
+                        'B' => integer(i64::from(v[0])),
+                        'I' => integer(i64::from(i16::from_be_bytes([v[0], v[1]]))),
+                        'J' => integer(i64::from(i32::from_be_bytes([v[0], v[1], v[2], v[3]]))),
+                        'K' => integer(i64::from_be_bytes(v.try_into().unwrap_or_default())),
+                        'E' => physical(f64::from(f32::from_be_bytes([v[0], v[1], v[2], v[3]]))),
+                        _ => physical(f64::from_be_bytes(v.try_into().unwrap_or_default())),
+                    }
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Reads the first binary table HDU in the given FITS file contents.
+pub fn read_table(bytes: &[u8]) -> Result<FitsTable, Box<dyn Error + Send + Sync>> {
+    let mut reader = Cursor::new(bytes);
+    while let Some(header) = read_next_header(&mut reader)? {
+        let start = reader.position() as usize;
+        if FitsTable::is_table(&header) {
+            return FitsTable::decode(header, &bytes[start..]);
+        }
+        reader.set_position((start + header.padded_data_size()) as u64);
+    }
+
+    Err("FITS file does not contain a binary table".into())
+}
+
+/// Adds the given bytes, as big-endian 32-bit words, to a ones' complement sum.
+fn ones_complement_sum(sum: u32, bytes: &[u8]) -> u32 {
+    let mut sum = u64::from(sum);
+    for word in bytes.chunks(4) {
+        let mut padded = [0; 4];
+        padded[..word.len()].copy_from_slice(word);
+        sum += u64::from(u32::from_be_bytes(padded));
+        sum = (sum & 0xFFFF_FFFF) + (sum >> 32);
+    }
+    sum as u32
+}
+
+/// Verifies the CHECKSUM and DATASUM keywords of every HDU in the given FITS file contents, when present.
+/// Returns an [EarendelError::FitsChecksumMismatch](crate::EarendelError::FitsChecksumMismatch) if the contents do not match,
+/// or an [EarendelError::FitsTruncated](crate::EarendelError::FitsTruncated) if a data unit is cut short, such as after a
+/// truncated transfer.
+pub fn verify_checksums(bytes: &[u8]) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut reader = Cursor::new(bytes);
+    let mut hdu = 0;
+    loop {
+        let header_start = reader.position() as usize;
+        let Some(header) = read_next_header(&mut reader)? else {
+            return Ok(());
+        };
+        let data_start = reader.position() as usize;
+        let data_end = data_start + header.padded_data_size();
+        let data = bytes
+            .get(data_start..data_end)
+            .ok_or(EarendelError::FitsTruncated {
+                expected: data_end - data_start,
+                actual: bytes.len().saturating_sub(data_start),
+            })?;
+        let data_sum = ones_complement_sum(0, data);
+
+        if let Some(expected) = header.get("DATASUM") {
+            let expected = match expected {
+                FitsValue::String(value) => value.trim().to_owned(),
+                other => other
+                    .as_i64()
+                    .map(|value| value.to_string())
+                    .unwrap_or_default(),
+            };
+            if expected.parse::<u32>().ok() != Some(data_sum) {
+                return Err(EarendelError::FitsChecksumMismatch {
+                    hdu,
+                    keyword: "DATASUM",
+                    expected,
+                    actual: data_sum.to_string(),
+                }
+                .into());
+            }
+        }
+
+        if let Some(expected) = header.get_str("CHECKSUM") {
+            // a valid checksum makes the sum of the whole HDU negative zero
+            let sum = ones_complement_sum(data_sum, &bytes[header_start..data_start]);
+            if sum != 0xFFFF_FFFF && sum != 0 {
+                return Err(EarendelError::FitsChecksumMismatch {
+                    hdu,
+                    keyword: "CHECKSUM",
+                    expected: expected.to_owned(),
+                    actual: format!("{:08X}", sum),
+                }
+                .into());
+            }
+        }
+
+        reader.set_position(data_end as u64);
+        hdu += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The position of the value of the CHECKSUM card, after `CHECKSUM= '`.
+    const CHECKSUM_VALUE: usize = 11;
+
+    /// Builds an HDU from the given header cards and data, padding each to whole blocks.
+    fn hdu(cards: &[&str], data: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for card in cards.iter().chain(["END"].iter()) {
+            bytes.extend_from_slice(format!("{:<80}", card).as_bytes());
+        }
+        bytes.resize(bytes.len().div_ceil(BLOCK_SIZE) * BLOCK_SIZE, b' ');
+        bytes.extend_from_slice(data);
+        bytes.resize(bytes.len().div_ceil(BLOCK_SIZE) * BLOCK_SIZE, 0);
+        bytes
+    }
+
+    /// Encodes the complement of the given ones' complement sum as the value of a CHECKSUM card, as described by the
+    /// FITS checksum convention.
+    fn encode_checksum(sum: u32) -> String {
+        const EXCLUDED: &[u8] = b":;<=>?@[\\]^_`";
+        let mut ascii = [0; 16];
+        for (i, byte) in (!sum).to_be_bytes().into_iter().enumerate() {
+            let mut chars = [byte / 4 + b'0'; 4];
+            chars[0] += byte % 4;
+            let mut adjusted = true;
+            while adjusted {
+                adjusted = false;
+                for excluded in EXCLUDED {
+                    for j in [0, 2] {
+                        if chars[j] == *excluded || chars[j + 1] == *excluded {
+                            chars[j] += 1;
+                            chars[j + 1] -= 1;
+                            adjusted = true;
+                        }
+                    }
+                }
+            }
+            for (j, c) in chars.into_iter().enumerate() {
+                ascii[4 * j + i] = c;
+            }
+        }
+
+        // the value starts one byte before a word boundary
+        (0..16).map(|i| char::from(ascii[(i + 15) % 16])).collect()
+    }
+
+    /// Builds a primary HDU with valid DATASUM and CHECKSUM cards.
+    fn checksummed_hdu() -> Vec<u8> {
+        let data = (0..200).map(|i| (i * 7) as u8).collect::<Vec<u8>>();
+        let mut padded = data.to_owned();
+        padded.resize(BLOCK_SIZE, 0);
+        let data_sum = ones_complement_sum(0, &padded);
+        let datasum = format!("DATASUM = '{}'", data_sum);
+        let mut bytes = hdu(
+            &[
+                "SIMPLE  =                    T",
+                "BITPIX  =                    8",
+                "NAXIS   =                    1",
+                "NAXIS1  =                  200",
+                "COMMENT checksummed test data",
+                "CHECKSUM= '0000000000000000'",
+                &datasum,
+            ],
+            &data,
+        );
+
+        let card = 5 * CARD_SIZE + CHECKSUM_VALUE;
+        let sum = ones_complement_sum(data_sum, &bytes[..BLOCK_SIZE]);
+        bytes[card..card + 16].copy_from_slice(encode_checksum(sum).as_bytes());
+        bytes
+    }
+
+    fn mismatched_keyword(bytes: &[u8]) -> Option<&'static str> {
+        match verify_checksums(bytes)
+            .err()?
+            .downcast_ref::<EarendelError>()
+        {
+            Some(EarendelError::FitsChecksumMismatch { keyword, .. }) => Some(*keyword),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn checksum_encoding_matches_convention() {
+        // the example of the FITS checksum convention, whose complement encodes as shown
+        assert_eq!(encode_checksum(868229149), "hcHjjc9ghcEghc9g");
+    }
+
+    #[test]
+    fn checksums_verify_intact_hdu() {
+        verify_checksums(&checksummed_hdu()).unwrap();
+    }
+
+    #[test]
+    fn checksums_reject_flipped_data_byte() {
+        let mut bytes = checksummed_hdu();
+        bytes[BLOCK_SIZE + 17] ^= 0x01;
+        assert_eq!(mismatched_keyword(&bytes), Some("DATASUM"));
+    }
+
+    #[test]
+    fn checksums_reject_flipped_header_byte() {
+        let mut bytes = checksummed_hdu();
+        // a character of the COMMENT card, which only the CHECKSUM covers
+        bytes[4 * CARD_SIZE + 10] ^= 0x01;
+        assert_eq!(mismatched_keyword(&bytes), Some("CHECKSUM"));
+    }
+
+    #[test]
+    fn checksums_report_truncated_data() {
+        let bytes = checksummed_hdu();
+        let error = verify_checksums(&bytes[..BLOCK_SIZE + 100]).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<EarendelError>(),
+            Some(EarendelError::FitsTruncated {
+                expected: BLOCK_SIZE,
+                actual: 100
+            })
+        ));
+    }
+
+    fn card(text: &str) -> FitsCard {
+        FitsCard::parse(format!("{:<80}", text).as_bytes())
+    }
+
+    fn header(cards: &[&str]) -> FitsHeader {
+        read_next_header(&mut Cursor::new(hdu(cards, &[])))
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn card_unescapes_quoted_string() {
+        let card = card("OBJECT  = 'O''Brien''s nebula  ' / the target");
+        assert_eq!(card.keyword, "OBJECT");
+        assert_eq!(
+            card.value,
+            Some(FitsValue::String("O'Brien's nebula".to_owned()))
+        );
+        assert_eq!(card.comment.as_deref(), Some("the target"));
+    }
+
+    #[test]
+    fn card_keeps_slash_inside_quoted_string() {
+        let card = card("FILTER  = 'F606W/F814W'");
+        assert_eq!(
+            card.value.as_ref().and_then(FitsValue::as_str),
+            Some("F606W/F814W")
+        );
+        assert_eq!(card.comment, None);
+    }
+
+    #[test]
+    fn card_reads_string_filling_whole_card() {
+        let value = "abcdefghij".repeat(7)[..68].to_owned();
+        let text = format!("LONGSTR = '{}'", value);
+        assert_eq!(text.len(), CARD_SIZE);
+
+        let card = card(&text);
+        assert_eq!(card.value, Some(FitsValue::String(value)));
+        assert_eq!(card.comment, None);
+    }
+
+    #[test]
+    fn card_parses_typed_values() {
+        assert_eq!(
+            card("SIMPLE  =                    T").value,
+            Some(FitsValue::Logical(true))
+        );
+        assert_eq!(
+            card("EXTEND  =                    F").value,
+            Some(FitsValue::Logical(false))
+        );
+        assert_eq!(
+            card("NAXIS1  =                 -512 / width").value,
+            Some(FitsValue::Integer(-512))
+        );
+        assert_eq!(
+            card("EXPTIME =              1.5D+03").value,
+            Some(FitsValue::Float(1500.0))
+        );
+        assert_eq!(card("BLANK   =").value, None);
+    }
+
+    #[test]
+    fn card_without_value_keeps_commentary() {
+        let card = card("HISTORY reprocessed = twice");
+        assert_eq!(card.keyword, "HISTORY");
+        assert_eq!(card.value, None);
+        assert_eq!(card.comment.as_deref(), Some("reprocessed = twice"));
+    }
+
+    #[test]
+    fn data_size_of_float_image() {
+        let header = header(&[
+            "SIMPLE  =                    T",
+            "BITPIX  =                  -32",
+            "NAXIS   =                    2",
+            "NAXIS1  =                  100",
+            "NAXIS2  =                   50",
+        ]);
+        assert_eq!(header.data_size(), 20000);
+        assert_eq!(header.padded_data_size(), 7 * BLOCK_SIZE);
+    }
+
+    #[test]
+    fn data_size_of_binary_table_includes_heap() {
+        let header = header(&[
+            "XTENSION= 'BINTABLE'",
+            "BITPIX  =                    8",
+            "NAXIS   =                    2",
+            "NAXIS1  =                   24",
+            "NAXIS2  =                   10",
+            "PCOUNT  =                  100",
+            "GCOUNT  =                    1",
+            "TFIELDS =                    1",
+        ]);
+        assert_eq!(header.data_size(), 340);
+        assert_eq!(header.padded_data_size(), BLOCK_SIZE);
+    }
+
+    #[test]
+    fn data_size_without_axes_is_empty() {
+        let header = header(&[
+            "SIMPLE  =                    T",
+            "BITPIX  =                   16",
+            "NAXIS   =                    0",
+        ]);
+        assert_eq!(header.data_size(), 0);
+        assert_eq!(header.padded_data_size(), 0);
+    }
+}
@@ -0,0 +1,237 @@
+//! An on-disk cache of downloaded FITS products, so that repeated analysis of the same products does not download
+//! gigabytes from the archives again.
+
+use md5::{Digest, Md5};
+
+use serde::{Deserialize, Serialize};
+
+use tracing::{instrument, warn};
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::download::file_md5;
+use crate::redact::redact;
+use crate::runtime::fs;
+use crate::{EarendelServer, MastProduct};
+
+/// The name of the file recording the contents of the cache, within the cache directory.
+const INDEX_FILE: &str = "index.json";
+
+/// The configuration of the on-disk cache of downloaded FITS products.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FitsCacheConfig {
+    /// The directory holding the cache.
+    pub dir: PathBuf,
+    /// The total size of the cached files, in bytes, beyond which the least recently used files are evicted.
+    pub max_size: u64,
+}
+
+impl FitsCacheConfig {
+    /// Creates a configuration that caches up to 10 GiB of files in the given directory.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        FitsCacheConfig {
+            dir: dir.into(),
+            max_size: 10 * 1024 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct CacheIndex {
+    /// The digest of the contents downloaded from each URL.
+    urls: BTreeMap<String, String>,
+    /// Each cached file, by the digest of its contents.
+    files: BTreeMap<String, CachedFile>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+struct CachedFile {
+    /// The size of the file, in bytes.
+    size: u64,
+    /// When the file was last downloaded or reused, in milliseconds since the Unix epoch.
+    last_used: u128,
+}
+
+fn now() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+}
+
+/// Computes the hex-encoded MD5 digest of the given bytes.
+fn md5_hex(bytes: &[u8]) -> String {
+    Md5::digest(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// The cached files and the URLs they were downloaded from. Files are named after the MD5 digest of their contents,
+/// so that a product listed under several URLs is stored once.
+pub(crate) struct FitsCache {
+    config: FitsCacheConfig,
+    index: Mutex<CacheIndex>,
+}
+
+impl FitsCache {
+    /// Opens the cache described by the given configuration, reading the index left by earlier runs, if any.
+    pub(crate) fn open(config: FitsCacheConfig) -> Self {
+        let index = std::fs::read(config.dir.join(INDEX_FILE))
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+
+        FitsCache {
+            config,
+            index: Mutex::new(index),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, CacheIndex> {
+        self.index.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Gets the path of the file with the given digest.
+    fn path(&self, digest: &str) -> PathBuf {
+        self.config.dir.join(digest)
+    }
+
+    /// Gets the path a download from the given URL is written to until it completes, which stays the same across
+    /// attempts so that an interrupted download resumes.
+    fn partial_path(&self, url: &str) -> PathBuf {
+        self.config
+            .dir
+            .join(format!("{}.part", md5_hex(url.as_bytes())))
+    }
+
+    /// Gets the path of the file downloaded from the given URL, marking it as used, or None if it is not cached.
+    fn lookup(&self, url: &str) -> Option<PathBuf> {
+        let mut index = self.lock();
+        let digest = index.urls.get(url)?.to_owned();
+        let path = self.path(&digest);
+        // the file may have been deleted outside of the cache
+        if !path.is_file() {
+            index.files.remove(&digest);
+            index.urls.retain(|_, cached| *cached != digest);
+            self.save(&index);
+            return None;
+        }
+        if let Some(file) = index.files.get_mut(&digest) {
+            file.last_used = now();
+        }
+        self.save(&index);
+
+        Some(path)
+    }
+
+    /// Records that the given URL was downloaded to the file with the given digest and size, then evicts the least
+    /// recently used files until the cache fits within its maximum size. Returns the paths of the evicted files.
+    fn insert(&self, url: &str, digest: &str, size: u64) -> Vec<PathBuf> {
+        let mut index = self.lock();
+        index.urls.insert(url.to_owned(), digest.to_owned());
+        index.files.insert(
+            digest.to_owned(),
+            CachedFile {
+                size,
+                last_used: now(),
+            },
+        );
+
+        let mut total = index.files.values().map(|file| file.size).sum::<u64>();
+        let mut evicted = Vec::new();
+        while total > self.config.max_size {
+            // the file just downloaded is kept even if it alone exceeds the maximum size
+            let Some(oldest) = index
+                .files
+                .iter()
+                .filter(|(cached, _)| *cached != digest)
+                .min_by_key(|(_, file)| file.last_used)
+                .map(|(cached, _)| cached.to_owned())
+            else {
+                break;
+            };
+            if let Some(file) = index.files.remove(&oldest) {
+                total -= file.size;
+            }
+            index.urls.retain(|_, cached| *cached != oldest);
+            evicted.push(self.path(&oldest));
+        }
+        self.save(&index);
+
+        evicted
+    }
+
+    /// Writes the given index to the cache directory. Failures are logged, as the cached files remain usable for the
+    /// rest of the run.
+    fn save(&self, index: &CacheIndex) {
+        let result = serde_json::to_vec(index)
+            .map_err(Box::<dyn Error + Send + Sync>::from)
+            .and_then(|bytes| {
+                std::fs::write(self.config.dir.join(INDEX_FILE), bytes).map_err(Box::from)
+            });
+        if let Err(e) = result {
+            warn!("failed to save the FITS cache index: {}", e);
+        }
+    }
+}
+
+impl EarendelServer {
+    /// Downloads the file at the given URL into the configured FITS cache, returning the path of the cached file.
+    /// Files already cached are reused without a request, and interrupted downloads are resumed as with
+    /// `download_to_file`. Once the file is cached, the least recently used files are evicted until the cache fits
+    /// within its maximum size. Returns an error if no FITS cache is configured, if the web request fails, or if the
+    /// file cannot be written.
+    #[instrument(skip(self, url), fields(url = %redact(url)))]
+    pub async fn download_cached(
+        &self,
+        url: &str,
+    ) -> Result<PathBuf, Box<dyn Error + Send + Sync>> {
+        let cache = self
+            .fits_cache
+            .as_ref()
+            .ok_or("no FITS cache is configured")?;
+        if let Some(path) = cache.lookup(url) {
+            return Ok(path);
+        }
+
+        fs::create_dir_all(&cache.config.dir).await?;
+        let partial = cache.partial_path(url);
+        let size = self.download_to_file(url, &partial).await?;
+        let digest = file_md5(&partial).await?;
+        let path = cache.path(&digest);
+        fs::rename(&partial, &path).await?;
+
+        for evicted in cache.insert(url, &digest, size) {
+            if let Err(e) = fs::remove_file(&evicted).await {
+                warn!(
+                    "failed to evict {} from the FITS cache: {}",
+                    evicted.display(),
+                    e
+                );
+            }
+        }
+
+        Ok(path)
+    }
+
+    /// Downloads the given MAST product into the configured FITS cache, as with `download_cached`, returning the path
+    /// of the cached file.
+    pub async fn download_product_cached(
+        &self,
+        product: &MastProduct,
+    ) -> Result<PathBuf, Box<dyn Error + Send + Sync>> {
+        self.download_cached(&product.download_url()).await
+    }
+
+    /// Gets the directory of the configured FITS cache, if any.
+    pub fn fits_cache_dir(&self) -> Option<&Path> {
+        self.fits_cache
+            .as_ref()
+            .map(|cache| cache.config.dir.as_path())
+    }
+}
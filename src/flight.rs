@@ -0,0 +1,124 @@
+//! Coalescing of concurrent identical upstream requests.
+
+use tokio::sync::broadcast;
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::future::Future;
+use std::sync::Mutex;
+
+use crate::EarendelError;
+
+/// The failure of a shared request, as received by the callers that did not make it.
+#[derive(Clone, Debug)]
+enum SharedError {
+    Earendel(EarendelError),
+    Other(String),
+}
+
+impl SharedError {
+    fn new(e: &(dyn Error + Send + Sync)) -> Self {
+        match e.downcast_ref::<EarendelError>() {
+            Some(e) => SharedError::Earendel(e.to_owned()),
+            None => SharedError::Other(e.to_string()),
+        }
+    }
+
+    fn into_error(self) -> Box<dyn Error + Send + Sync> {
+        match self {
+            SharedError::Earendel(e) => e.into(),
+            SharedError::Other(msg) => msg.into(),
+        }
+    }
+}
+
+type Sender<T> = broadcast::Sender<Result<T, SharedError>>;
+
+/// The in-flight requests of one kind, by key. Callers that make a request while an identical one is in flight wait
+/// for its result instead of sending their own.
+pub(crate) struct SingleFlight<T> {
+    in_flight: Mutex<HashMap<String, Sender<T>>>,
+}
+
+impl<T> Default for SingleFlight<T> {
+    fn default() -> Self {
+        SingleFlight {
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+/// Removes the entry of a request from the in-flight requests if the request is dropped before completing, so that
+/// its waiting callers make it again.
+struct Flight<'a, T> {
+    single_flight: &'a SingleFlight<T>,
+    key: &'a str,
+    done: bool,
+}
+
+impl<T> Drop for Flight<'_, T> {
+    fn drop(&mut self) {
+        if !self.done {
+            self.single_flight.lock().remove(self.key);
+        }
+    }
+}
+
+impl<T: Clone> SingleFlight<T> {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Sender<T>>> {
+        // the map is never left inconsistent, so a poisoned lock is still usable
+        self.in_flight
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Makes the given request, unless one with the same key is already in flight, in which case its result is
+    /// returned instead.
+    pub(crate) async fn run<F>(
+        &self,
+        key: &str,
+        request: F,
+    ) -> Result<T, Box<dyn Error + Send + Sync>>
+    where
+        F: Future<Output = Result<T, Box<dyn Error + Send + Sync>>>,
+    {
+        loop {
+            let receiver = {
+                let mut in_flight = self.lock();
+                match in_flight.get(key) {
+                    Some(sender) => Some(sender.subscribe()),
+                    None => {
+                        in_flight.insert(key.to_owned(), broadcast::channel(1).0);
+                        None
+                    }
+                }
+            };
+            match receiver {
+                Some(mut receiver) => {
+                    if let Ok(result) = receiver.recv().await {
+                        return result.map_err(SharedError::into_error);
+                    }
+                    // the request was dropped before completing, so it is made again
+                }
+                None => {
+                    let mut flight = Flight {
+                        single_flight: self,
+                        key,
+                        done: false,
+                    };
+                    let result = request.await;
+                    flight.done = true;
+                    if let Some(sender) = self.lock().remove(key) {
+                        // an error only means that no other callers are waiting
+                        let _ = sender.send(match &result {
+                            Ok(value) => Ok(value.to_owned()),
+                            Err(e) => Err(SharedError::new(e.as_ref())),
+                        });
+                    }
+
+                    return result;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,270 @@
+//! Observation footprints parsed from STC-S region strings, such as the MAST `s_region` column.
+
+use serde::{Deserialize, Serialize};
+
+use std::error::Error;
+
+use crate::{EarendelFits, Observation};
+
+/// The region of the sky covered by an observation, with positions in degrees.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum Footprint {
+    /// A polygon with the given vertices, as right ascension and declination pairs. The edges are great circle arcs.
+    Polygon(Vec<(f64, f64)>),
+    /// A circle with the given center and radius.
+    Circle {
+        /// The right ascension of the center.
+        ra: f64,
+        /// The declination of the center.
+        dec: f64,
+        /// The radius.
+        radius: f64,
+    },
+    /// The union of the given regions, such as the detectors of a mosaic camera.
+    Union(Vec<Footprint>),
+}
+
+impl Footprint {
+    /// Parses the given STC-S region, such as `POLYGON ICRS 10.1 20.2 10.3 20.2 10.3 20.4`. Frame and reference
+    /// position flags are ignored, and consecutive shapes are treated as a union. Returns an error if the region
+    /// contains no shapes, an unsupported shape, or a shape with the wrong number of coordinates.
+    pub fn parse(region: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let mut shapes = Vec::new();
+        let mut tokens = region.split_whitespace().peekable();
+        while let Some(token) = tokens.next() {
+            let mut values = Vec::new();
+            while let Some(value) = tokens.peek() {
+                if is_shape(value) {
+                    break;
+                }
+                // frame and reference position flags, such as ICRS or TOPOCENTER, are skipped
+                if let Ok(value) = value.parse::<f64>() {
+                    values.push(value);
+                }
+                tokens.next();
+            }
+
+            let shape = match token.to_ascii_uppercase().as_str() {
+                "POLYGON" if values.len() >= 6 && values.len() % 2 == 0 => Footprint::Polygon(
+                    values
+                        .chunks_exact(2)
+                        .map(|vertex| (vertex[0], vertex[1]))
+                        .collect(),
+                ),
+                "CIRCLE" if values.len() == 3 => Footprint::Circle {
+                    ra: values[0],
+                    dec: values[1],
+                    radius: values[2],
+                },
+                "POLYGON" | "CIRCLE" => {
+                    return Err(format!("malformed {} in STC-S region", token).into())
+                }
+                _ => return Err(format!("unsupported STC-S shape {}", token).into()),
+            };
+            shapes.push(shape);
+        }
+
+        match shapes.len() {
+            0 => Err("STC-S region is empty".into()),
+            1 => Ok(shapes.remove(0)),
+            _ => Ok(Footprint::Union(shapes)),
+        }
+    }
+
+    /// Determines whether the given position, in degrees, falls inside the footprint.
+    pub fn contains(&self, ra: f64, dec: f64) -> bool {
+        match self {
+            Footprint::Polygon(vertices) => polygon_contains(vertices, ra, dec),
+            Footprint::Circle {
+                ra: center_ra,
+                dec: center_dec,
+                radius,
+            } => angular_distance(*center_ra, *center_dec, ra, dec) <= *radius,
+            Footprint::Union(shapes) => shapes.iter().any(|shape| shape.contains(ra, dec)),
+        }
+    }
+}
+
+impl Observation {
+    /// Parses the footprint of the observation, if the archive reports one.
+    pub fn footprint(&self) -> Option<Footprint> {
+        Footprint::parse(self.s_region.as_deref()?).ok()
+    }
+}
+
+impl EarendelFits {
+    /// Removes the observations whose footprint does not contain the given position, in degrees, along with their
+    /// files. Cone searches also list observations that only overlap the search radius at their edges, which rarely
+    /// show the target. Observations without a known footprint are kept.
+    pub fn retain_containing(&mut self, ra: f64, dec: f64) {
+        self.retain_observations(|observation| {
+            observation
+                .footprint()
+                .is_none_or(|footprint| footprint.contains(ra, dec))
+        });
+    }
+}
+
+fn is_shape(token: &str) -> bool {
+    [
+        "POLYGON",
+        "CIRCLE",
+        "BOX",
+        "POSITION",
+        "UNION",
+        "INTERSECTION",
+        "NOT",
+    ]
+    .iter()
+    .any(|shape| token.eq_ignore_ascii_case(shape))
+}
+
+/// Gets the angle between the given positions, in degrees.
+fn angular_distance(ra1: f64, dec1: f64, ra2: f64, dec2: f64) -> f64 {
+    let (ra1, dec1, ra2, dec2) = (
+        ra1.to_radians(),
+        dec1.to_radians(),
+        ra2.to_radians(),
+        dec2.to_radians(),
+    );
+    // the haversine formula, which is accurate for the small separations of footprints
+    let a = ((dec2 - dec1) / 2.0).sin().powi(2)
+        + dec1.cos() * dec2.cos() * ((ra2 - ra1) / 2.0).sin().powi(2);
+
+    (2.0 * a.sqrt().min(1.0).asin()).to_degrees()
+}
+
+/// Determines whether the given position falls inside the given polygon, by projecting the vertices onto the plane
+/// tangent to the sky at the position and counting the edges crossed by a ray from it.
+fn polygon_contains(vertices: &[(f64, f64)], ra: f64, dec: f64) -> bool {
+    if vertices.len() < 3 {
+        return false;
+    }
+    let (ra0, dec0) = (ra.to_radians(), dec.to_radians());
+    let mut projected = Vec::with_capacity(vertices.len());
+    for (vertex_ra, vertex_dec) in vertices {
+        let (ra, dec) = (vertex_ra.to_radians(), vertex_dec.to_radians());
+        let cos_c = dec0.sin() * dec.sin() + dec0.cos() * dec.cos() * (ra - ra0).cos();
+        // a vertex on the far hemisphere cannot belong to a footprint around the position
+        if cos_c <= 0.0 {
+            return false;
+        }
+        let x = dec.cos() * (ra - ra0).sin() / cos_c;
+        let y = (dec0.cos() * dec.sin() - dec0.sin() * dec.cos() * (ra - ra0).cos()) / cos_c;
+        projected.push((x, y));
+    }
+
+    // the gnomonic projection maps great circles to straight lines, so the test is exact
+    let mut inside = false;
+    let mut previous = projected[projected.len() - 1];
+    for current in projected.iter().copied() {
+        let ((x1, y1), (x2, y2)) = (previous, current);
+        if (y1 > 0.0) != (y2 > 0.0) && x1 - y1 * (x2 - x1) / (y2 - y1) > 0.0 {
+            inside = !inside;
+        }
+        previous = current;
+    }
+
+    inside
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square() -> Footprint {
+        Footprint::Polygon(vec![(10.0, 20.0), (11.0, 20.0), (11.0, 21.0), (10.0, 21.0)])
+    }
+
+    #[test]
+    fn polygon_contains_inner_point() {
+        assert!(square().contains(10.5, 20.5));
+    }
+
+    #[test]
+    fn polygon_excludes_outer_points() {
+        let square = square();
+        assert!(!square.contains(12.0, 20.5));
+        assert!(!square.contains(10.5, 21.5));
+        assert!(!square.contains(9.9, 19.9));
+    }
+
+    #[test]
+    fn polygon_crossing_ra_zero() {
+        let footprint =
+            Footprint::Polygon(vec![(359.5, -0.5), (0.5, -0.5), (0.5, 0.5), (359.5, 0.5)]);
+        assert!(footprint.contains(0.0, 0.0));
+        assert!(footprint.contains(359.8, 0.2));
+        assert!(footprint.contains(0.3, -0.3));
+        assert!(!footprint.contains(1.0, 0.0));
+        assert!(!footprint.contains(359.0, 0.0));
+        assert!(!footprint.contains(180.0, 0.0));
+    }
+
+    #[test]
+    fn polygon_excludes_far_hemisphere() {
+        // the projection of the antipode would otherwise fall inside the polygon
+        assert!(!square().contains(190.5, -20.5));
+    }
+
+    #[test]
+    fn circle_contains_points_within_radius() {
+        let circle = Footprint::Circle {
+            ra: 10.0,
+            dec: 20.0,
+            radius: 0.5,
+        };
+        assert!(circle.contains(10.2, 20.2));
+        assert!(!circle.contains(11.0, 20.0));
+    }
+
+    #[test]
+    fn parses_polygon_with_flags() {
+        let footprint =
+            Footprint::parse("Polygon ICRS TOPOCENTER 10 20 11 20 11 21 10 21").unwrap();
+        assert_eq!(footprint, square());
+    }
+
+    #[test]
+    fn parses_circle() {
+        assert_eq!(
+            Footprint::parse("CIRCLE ICRS 10 20 0.5").unwrap(),
+            Footprint::Circle {
+                ra: 10.0,
+                dec: 20.0,
+                radius: 0.5,
+            }
+        );
+    }
+
+    #[test]
+    fn parses_consecutive_shapes_as_union() {
+        let footprint =
+            Footprint::parse("POLYGON ICRS 10 20 11 20 11 21 10 21 CIRCLE ICRS 30 40 1").unwrap();
+        let Footprint::Union(shapes) = &footprint else {
+            panic!("expected a union, got {:?}", footprint);
+        };
+        assert_eq!(shapes.len(), 2);
+        assert!(footprint.contains(10.5, 20.5));
+        assert!(footprint.contains(30.0, 40.0));
+        assert!(!footprint.contains(20.0, 30.0));
+    }
+
+    #[test]
+    fn rejects_unsupported_regions() {
+        for region in [
+            "",
+            "ICRS",
+            "UNION ICRS ( POLYGON 10 20 11 20 11 21 POLYGON 12 20 13 20 13 21 )",
+            "INTERSECTION ICRS ( CIRCLE 10 20 1 CIRCLE 10.5 20 1 )",
+            "NOT ( CIRCLE ICRS 10 20 1 )",
+            "BOX ICRS 10 20 1 1",
+            "POSITION ICRS 10 20",
+            "POLYGON ICRS 10 20 11 20",
+            "POLYGON ICRS 10 20 11 20 11",
+            "CIRCLE ICRS 10 20",
+        ] {
+            assert!(Footprint::parse(region).is_err(), "accepted {:?}", region);
+        }
+    }
+}
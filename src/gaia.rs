@@ -0,0 +1,113 @@
+//! Queries against the Gaia archive for the stars in a field.
+
+use astro_rs::coordinates::Icrs;
+
+use serde::{Deserialize, Serialize};
+
+use tracing::instrument;
+
+use uom::si::angle::degree;
+use uom::si::f64::Angle;
+
+use std::error::Error;
+
+use crate::coords::icrs_to_degrees;
+use crate::tap::{TapFormat, TapRow};
+use crate::{EarendelServer, Upstream};
+
+const GAIA_TAP_URL: &str = "https://gea.esac.esa.int/tap-server/tap";
+/// The maximum number of stars listed in a field, brightest first.
+const MAX_STARS: usize = 500;
+
+/// A star from the Gaia DR3 source catalog.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GaiaStar {
+    /// The Gaia DR3 source identifier.
+    pub source_id: String,
+    /// The right ascension of the star, in degrees.
+    pub ra: f64,
+    /// The declination of the star, in degrees.
+    pub dec: f64,
+    /// The parallax of the star, in milliarcseconds.
+    pub parallax: Option<f64>,
+    /// The uncertainty of the parallax, in milliarcseconds.
+    pub parallax_error: Option<f64>,
+    /// The proper motion in right ascension, multiplied by the cosine of the declination, in milliarcseconds per year.
+    pub pmra: Option<f64>,
+    /// The proper motion in declination, in milliarcseconds per year.
+    pub pmdec: Option<f64>,
+    /// The mean magnitude in the G band.
+    pub g_mag: Option<f64>,
+    /// The mean magnitude in the blue photometer (BP) band.
+    pub bp_mag: Option<f64>,
+    /// The mean magnitude in the red photometer (RP) band.
+    pub rp_mag: Option<f64>,
+}
+
+impl GaiaStar {
+    fn from_row(row: &TapRow<'_>) -> Option<Self> {
+        Some(GaiaStar {
+            source_id: row.get_string("source_id")?,
+            ra: row.get_f64("ra")?,
+            dec: row.get_f64("dec")?,
+            parallax: row.get_f64("parallax"),
+            parallax_error: row.get_f64("parallax_error"),
+            pmra: row.get_f64("pmra"),
+            pmdec: row.get_f64("pmdec"),
+            g_mag: row.get_f64("phot_g_mean_mag"),
+            bp_mag: row.get_f64("phot_bp_mean_mag"),
+            rp_mag: row.get_f64("phot_rp_mean_mag"),
+        })
+    }
+
+    /// Gets the distance to the star estimated by inverting its parallax, in parsecs. Returns None if the parallax is
+    /// missing or not positive.
+    pub fn distance_pc(&self) -> Option<f64> {
+        self.parallax
+            .filter(|parallax| *parallax > 0.0)
+            .map(|parallax| 1000.0 / parallax)
+    }
+}
+
+impl EarendelServer {
+    /// Gets the Gaia DR3 stars within the given radius of the given coordinates, brightest first. Returns an error if
+    /// the web request fails.
+    #[instrument(skip(self, coords, radius))]
+    pub async fn get_gaia_stars(
+        &self,
+        coords: &Icrs,
+        radius: Angle,
+    ) -> Result<Vec<GaiaStar>, Box<dyn Error + Send + Sync>> {
+        let (ra, dec) = icrs_to_degrees(coords);
+        // the identifier exceeds the precision of a JSON number, so it is returned as a string
+        let query = format!(
+            "SELECT TOP {} CAST(source_id AS VARCHAR(20)) AS source_id, ra, dec, parallax, parallax_error, pmra, \
+             pmdec, phot_g_mean_mag, phot_bp_mean_mag, phot_rp_mean_mag FROM gaiadr3.gaia_source WHERE \
+             1=CONTAINS(POINT('ICRS', ra, dec), CIRCLE('ICRS', {}, {}, {})) ORDER BY phot_g_mean_mag",
+            MAX_STARS,
+            ra,
+            dec,
+            radius.get::<degree>()
+        );
+
+        let table = self
+            .tap_query(Upstream::Gaia, GAIA_TAP_URL, TapFormat::EsacJson, &query)
+            .await?;
+
+        Ok(table
+            .rows()
+            .filter_map(|row| GaiaStar::from_row(&row))
+            .collect())
+    }
+
+    /// Gets the Gaia DR3 stars within the given radius of the target of the current APOD. Returns an error if the
+    /// target cannot be resolved or if the web request fails.
+    pub async fn get_gaia_stars_for_apod(
+        &self,
+        radius: Angle,
+    ) -> Result<Vec<GaiaStar>, Box<dyn Error + Send + Sync>> {
+        let coords = self.resolve_apod_target().await?;
+
+        self.get_gaia_stars(&coords, radius).await
+    }
+}
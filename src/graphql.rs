@@ -0,0 +1,244 @@
+//! A GraphQL schema exposing the APOD and its FITS observations.
+//!
+//! The schema provides `apod(date)` and `fitsResults(page, filters)` queries. A schema created by `schema` can be
+//! executed directly or mounted with any of the async-graphql web integrations.
+
+use async_graphql::{
+    Context, EmptyMutation, EmptySubscription, InputObject, Object, Result, Schema, SimpleObject,
+};
+
+use chrono::NaiveDate;
+
+use std::sync::Arc;
+
+use crate::{EarendelApod, EarendelFits, EarendelServer, ImageMetadata, Observation, TargetInfo};
+
+/// The GraphQL schema of Earendel.
+pub type EarendelSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// Creates the GraphQL schema, resolving queries with the given shared server.
+pub fn schema(server: Arc<EarendelServer>) -> EarendelSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(server)
+        .finish()
+}
+
+/// The root of GraphQL queries.
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// The APOD for the given date, or the current APOD if no date is given.
+    async fn apod(&self, ctx: &Context<'_>, date: Option<NaiveDate>) -> Result<Apod> {
+        let server = ctx.data::<Arc<EarendelServer>>()?;
+        let apod = match date {
+            Some(date) => server.get_apod_image_for_date(date).await?,
+            None => server.get_apod_image().await?,
+        };
+
+        Ok(Apod(apod))
+    }
+
+    /// A page of FITS observations of the current APOD target, keeping only the observations matching the given
+    /// filters.
+    async fn fits_results(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default)] page: usize,
+        filters: Option<ObservationFilter>,
+    ) -> Result<FitsResults> {
+        let server = ctx.data::<Arc<EarendelServer>>()?;
+        let mut fits = server.get_fits_for_apod(page).await?;
+        if let Some(filters) = filters {
+            fits.observations
+                .retain(|observation| filters.matches(observation));
+        }
+
+        Ok(FitsResults(fits))
+    }
+}
+
+/// The Astronomy Picture of the Day.
+pub struct Apod(EarendelApod);
+
+#[Object]
+impl Apod {
+    /// The title of the APOD.
+    async fn title(&self) -> &str {
+        &self.0.title
+    }
+
+    /// The date the APOD was published.
+    async fn date(&self) -> NaiveDate {
+        self.0.date
+    }
+
+    /// The URL the image was downloaded from.
+    async fn image_url(&self) -> &str {
+        &self.0.image_url
+    }
+
+    /// The copyright string.
+    async fn copyright(&self) -> Option<&str> {
+        self.0.copyright.as_deref()
+    }
+
+    /// The width of the image, in pixels, if known.
+    async fn width(&self) -> Option<u32> {
+        self.0.width
+    }
+
+    /// The height of the image, in pixels, if known.
+    async fn height(&self) -> Option<u32> {
+        self.0.height
+    }
+
+    /// The EXIF and XMP metadata embedded in the image, if any.
+    async fn metadata(&self) -> Option<&ImageMetadata> {
+        self.0.metadata.as_ref()
+    }
+}
+
+/// Criteria that observations must match. Each given criterion must match exactly.
+#[derive(Debug, Default, InputObject)]
+pub struct ObservationFilter {
+    /// The name of the archive that listed the observation.
+    pub archive: Option<String>,
+    /// The mission or collection of the observation, such as HST or JWST.
+    pub collection: Option<String>,
+    /// The instrument that acquired the observation.
+    pub instrument: Option<String>,
+    /// The type of the data product, such as image or spectrum.
+    pub dataproduct_type: Option<String>,
+}
+
+impl ObservationFilter {
+    fn matches(&self, observation: &Observation) -> bool {
+        let matches = |criterion: &Option<String>, value: Option<&str>| {
+            criterion
+                .as_deref()
+                .is_none_or(|criterion| value == Some(criterion))
+        };
+
+        matches(&self.archive, Some(&observation.archive))
+            && matches(&self.collection, observation.collection.as_deref())
+            && matches(&self.instrument, observation.instrument.as_deref())
+            && matches(
+                &self.dataproduct_type,
+                observation.dataproduct_type.as_deref(),
+            )
+    }
+}
+
+/// A page of FITS observations.
+pub struct FitsResults(EarendelFits);
+
+#[Object]
+impl FitsResults {
+    /// The names of the FITS files for the current page.
+    async fn files(&self) -> &[String] {
+        &self.0.files
+    }
+
+    /// The observations for the current page that match the filters.
+    async fn observations(&self) -> &[Observation] {
+        &self.0.observations
+    }
+
+    /// The current page number.
+    async fn page(&self) -> usize {
+        self.0.page
+    }
+
+    /// The total number of available FITS files.
+    async fn total_hits(&self) -> usize {
+        self.0.total_hits
+    }
+
+    /// The largest number of observations listed per page.
+    async fn page_size(&self) -> usize {
+        self.0.page_size
+    }
+
+    /// The number of pages needed to list every available observation.
+    async fn total_pages(&self) -> usize {
+        self.0.total_pages
+    }
+
+    /// Whether a page follows the current page.
+    async fn has_next(&self) -> bool {
+        self.0.has_next
+    }
+
+    /// Whether a page precedes the current page.
+    async fn has_prev(&self) -> bool {
+        self.0.has_prev
+    }
+
+    /// The SIMBAD details of the searched target, if known.
+    async fn target(&self) -> Option<Target> {
+        self.0.target.to_owned().map(Target)
+    }
+}
+
+/// The SIMBAD details of an astronomical object.
+pub struct Target(TargetInfo);
+
+#[Object]
+impl Target {
+    /// The main SIMBAD identifier of the object.
+    async fn main_id(&self) -> &str {
+        &self.0.main_id
+    }
+
+    /// The SIMBAD object type, such as G for galaxy or PN for planetary nebula.
+    async fn object_type(&self) -> Option<&str> {
+        self.0.object_type.as_deref()
+    }
+
+    /// All identifiers of the object across catalogs.
+    async fn identifiers(&self) -> &[String] {
+        &self.0.identifiers
+    }
+
+    /// The right ascension of the object, in degrees.
+    async fn ra(&self) -> Option<f64> {
+        self.0.ra
+    }
+
+    /// The declination of the object, in degrees.
+    async fn dec(&self) -> Option<f64> {
+        self.0.dec
+    }
+
+    /// The radial velocity of the object, in kilometers per second.
+    async fn radial_velocity(&self) -> Option<f64> {
+        self.0.radial_velocity
+    }
+
+    /// The redshift of the object.
+    async fn redshift(&self) -> Option<f64> {
+        self.0.redshift
+    }
+
+    /// The magnitudes of the object.
+    async fn fluxes(&self) -> Vec<Flux> {
+        self.0
+            .fluxes
+            .iter()
+            .map(|(filter, magnitude)| Flux {
+                filter: filter.to_owned(),
+                magnitude: *magnitude,
+            })
+            .collect()
+    }
+}
+
+/// The magnitude of an object in a filter.
+#[derive(Debug, SimpleObject)]
+pub struct Flux {
+    /// The name of the filter.
+    pub filter: String,
+    /// The magnitude of the object in the filter.
+    pub magnitude: f64,
+}
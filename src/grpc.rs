@@ -0,0 +1,154 @@
+//! A gRPC service exposing the APOD and its FITS observations, as defined in `proto/earendel.proto`.
+//!
+//! Building this module requires `protoc` to be available.
+
+use chrono::NaiveDate;
+
+use tokio_stream::Stream;
+
+use tonic::{Request, Response, Status};
+
+use std::error::Error;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use crate::metadata::content_type;
+use crate::{EarendelApod, EarendelServer};
+
+/// The types generated from `proto/earendel.proto`.
+#[allow(missing_docs, clippy::all)]
+pub mod proto {
+    tonic::include_proto!("earendel");
+}
+
+use proto::earendel_service_server::{EarendelService, EarendelServiceServer};
+use proto::{ApodRequest, FitsPage, FitsRequest, ImageChunk};
+
+/// The size of the chunks in which images are streamed, in bytes.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// The gRPC service, backed by an `EarendelServer` shared between requests.
+#[derive(Clone)]
+pub struct GrpcService {
+    server: Arc<EarendelServer>,
+}
+
+impl GrpcService {
+    /// Creates a new service backed by the given server.
+    pub fn new(server: EarendelServer) -> Self {
+        GrpcService {
+            server: Arc::new(server),
+        }
+    }
+
+    /// Wraps this service for use with a tonic `Server`.
+    pub fn into_server(self) -> EarendelServiceServer<Self> {
+        EarendelServiceServer::new(self)
+    }
+
+    async fn get_apod_image(&self, request: &ApodRequest) -> Result<EarendelApod, Status> {
+        let date = request
+            .date
+            .as_deref()
+            .map(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d"))
+            .transpose()
+            .map_err(|e| Status::invalid_argument(format!("invalid date: {}", e)))?;
+
+        match date {
+            Some(date) => self.server.get_apod_image_for_date(date).await,
+            None => self.server.get_apod_image().await,
+        }
+        .map_err(unavailable)
+    }
+}
+
+fn unavailable(error: Box<dyn Error + Send + Sync>) -> Status {
+    Status::unavailable(error.to_string())
+}
+
+#[tonic::async_trait]
+impl EarendelService for GrpcService {
+    type StreamApodImageStream = Pin<Box<dyn Stream<Item = Result<ImageChunk, Status>> + Send>>;
+
+    async fn get_apod(
+        &self,
+        request: Request<ApodRequest>,
+    ) -> Result<Response<proto::Apod>, Status> {
+        let apod = self.get_apod_image(request.get_ref()).await?;
+
+        Ok(Response::new(proto::Apod {
+            content_type: content_type(apod.img()).to_owned(),
+            title: apod.title,
+            copyright: apod.copyright,
+            width: apod.width,
+            height: apod.height,
+        }))
+    }
+
+    async fn stream_apod_image(
+        &self,
+        request: Request<ApodRequest>,
+    ) -> Result<Response<Self::StreamApodImageStream>, Status> {
+        let apod = self.get_apod_image(request.get_ref()).await?;
+        let chunks = apod
+            .img()
+            .chunks(CHUNK_SIZE)
+            .map(|data| {
+                Ok(ImageChunk {
+                    data: data.to_vec(),
+                })
+            })
+            .collect::<Vec<Result<ImageChunk, Status>>>();
+
+        Ok(Response::new(Box::pin(tokio_stream::iter(chunks))))
+    }
+
+    #[cfg(feature = "mast")]
+    async fn list_fits(&self, request: Request<FitsRequest>) -> Result<Response<FitsPage>, Status> {
+        let page = usize::try_from(request.get_ref().page)
+            .map_err(|_| Status::invalid_argument("page is out of range"))?;
+        let fits = self
+            .server
+            .get_fits_for_apod(page)
+            .await
+            .map_err(unavailable)?;
+
+        Ok(Response::new(FitsPage {
+            files: fits.files,
+            observations: fits
+                .observations
+                .into_iter()
+                .map(|observation| proto::Observation {
+                    em_min_nm: observation.em_min_nm(),
+                    em_max_nm: observation.em_max_nm(),
+                    archive: observation.archive,
+                    obs_id: observation.obs_id,
+                    collection: observation.collection,
+                    instrument: observation.instrument,
+                    filters: observation.filters,
+                    target_name: observation.target_name,
+                    target_classification: observation.target_classification,
+                    dataproduct_type: observation.dataproduct_type,
+                    ra: observation.ra,
+                    dec: observation.dec,
+                    exposure_time: observation.exposure_time,
+                    preview_url: observation.preview_url,
+                    data_url: observation.data_url,
+                    distance: observation.distance,
+                })
+                .collect(),
+            page: fits.page as u64,
+            total_hits: fits.total_hits as u64,
+        }))
+    }
+
+    #[cfg(not(feature = "mast"))]
+    async fn list_fits(
+        &self,
+        _request: Request<FitsRequest>,
+    ) -> Result<Response<FitsPage>, Status> {
+        Err(Status::unimplemented(
+            "FITS listing requires the mast feature",
+        ))
+    }
+}
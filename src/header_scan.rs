@@ -0,0 +1,175 @@
+//! Concurrent reading of the primary headers of many FITS files, for inspecting their metadata without downloading
+//! the files in full.
+
+use chrono::NaiveDateTime;
+
+use futures::future::join_all;
+
+use reqwest::header::RANGE;
+
+use serde::{Deserialize, Serialize};
+
+use tokio::sync::Semaphore;
+
+use tracing::{instrument, warn};
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, Cursor};
+
+use crate::fits::{self, FitsHeader, BLOCK_SIZE, CARD_SIZE};
+use crate::mast::download_url;
+use crate::redact::redact;
+use crate::runtime::spawn_blocking;
+use crate::{EarendelServer, Upstream};
+
+/// The number of blocks first requested from a remote file, which holds the primary header of most files.
+const INITIAL_HEADER_BLOCKS: usize = 4;
+/// The size of the largest primary header read from a remote file, in bytes.
+const MAX_HEADER_BYTES: usize = 256 * BLOCK_SIZE;
+
+/// The key metadata of a FITS file, read from its primary header.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct FitsHeaderSummary {
+    /// The URL or path of the file, as given.
+    pub source: String,
+    /// The telescope that acquired the data (TELESCOP).
+    pub telescope: Option<String>,
+    /// The instrument that acquired the data (INSTRUME).
+    pub instrument: Option<String>,
+    /// The filter used for the observation (FILTER, or FILTER1 for instruments with two filter wheels).
+    pub filter: Option<String>,
+    /// The observed object (OBJECT, or TARGNAME as used by HST).
+    pub object: Option<String>,
+    /// The start of the observation (DATE-OBS).
+    pub date_obs: Option<NaiveDateTime>,
+    /// The exposure time in seconds (EXPTIME).
+    pub exposure_time: Option<f64>,
+    /// The full primary header, for keywords not summarized above, or None if it could not be read.
+    pub header: Option<FitsHeader>,
+    /// A description of the failure, if the header could not be read.
+    pub error: Option<String>,
+}
+
+impl FitsHeaderSummary {
+    fn new(source: &str, result: Result<FitsHeader, Box<dyn Error + Send + Sync>>) -> Self {
+        let header = match result {
+            Ok(header) => header,
+            Err(e) => {
+                warn!(
+                    "failed to read the FITS header of {}: {}",
+                    redact(source),
+                    e
+                );
+                return FitsHeaderSummary {
+                    source: source.to_owned(),
+                    error: Some(e.to_string()),
+                    ..Default::default()
+                };
+            }
+        };
+        let get = |keywords: &[&str]| {
+            keywords
+                .iter()
+                .find_map(|keyword| header.get_str(keyword))
+                .map(|value| value.trim().to_owned())
+        };
+
+        FitsHeaderSummary {
+            source: source.to_owned(),
+            telescope: get(&["TELESCOP"]),
+            instrument: get(&["INSTRUME"]),
+            filter: get(&["FILTER", "FILTER1"]),
+            object: get(&["OBJECT", "TARGNAME"]),
+            date_obs: header.date_obs(),
+            exposure_time: header.exposure_time(),
+            header: Some(header),
+            error: None,
+        }
+    }
+}
+
+/// Determines whether the given bytes, read from the start of a FITS file, include the END card of the primary
+/// header.
+fn has_end_card(bytes: &[u8]) -> bool {
+    bytes
+        .chunks_exact(CARD_SIZE)
+        .any(|card| card.starts_with(b"END") && card[3..].iter().all(|byte| *byte == b' '))
+}
+
+impl EarendelServer {
+    /// Reads the primary headers of the given FITS files, which may be URLs, MAST data URIs, or local paths, with at
+    /// most `max_concurrent` reads in flight at once. Only the start of each remote file is requested, with a `Range`
+    /// request, so that inspecting metadata such as DATE-OBS and FILTER does not download whole files. Returns a
+    /// summary of each file in the order the files were given; a file that cannot be read does not affect the others,
+    /// and its error is recorded in its summary.
+    #[instrument(skip(self, sources), fields(sources = sources.len()))]
+    pub async fn scan_fits_headers<S: AsRef<str> + Sync>(
+        &self,
+        sources: &[S],
+        max_concurrent: usize,
+    ) -> Vec<FitsHeaderSummary> {
+        let semaphore = Semaphore::new(max_concurrent.max(1));
+        let scans = sources.iter().map(|source| {
+            let (source, semaphore) = (source.as_ref(), &semaphore);
+            async move {
+                let result = match semaphore.acquire().await {
+                    Ok(_permit) => self.read_fits_header(source).await,
+                    Err(e) => Err(e.into()),
+                };
+                FitsHeaderSummary::new(source, result)
+            }
+        });
+
+        join_all(scans).await
+    }
+
+    /// Reads the primary header of the FITS file at the given URL, MAST data URI, or local path.
+    async fn read_fits_header(
+        &self,
+        source: &str,
+    ) -> Result<FitsHeader, Box<dyn Error + Send + Sync>> {
+        let url = if source.starts_with("mast:") {
+            download_url(source)
+        } else if source.starts_with("http://") || source.starts_with("https://") {
+            source.to_owned()
+        } else {
+            // reading a header from disk is brief, but blocking
+            let path = source.to_owned();
+            return spawn_blocking(move || {
+                fits::read_primary_header(BufReader::new(File::open(path)?))
+            })
+            .await?;
+        };
+
+        let mut wanted = INITIAL_HEADER_BLOCKS * BLOCK_SIZE;
+        loop {
+            let request = self
+                .client
+                .get(&url)
+                .header(RANGE, format!("bytes=0-{}", wanted - 1));
+            let mut resp = self
+                .send(Upstream::Download, request)
+                .await?
+                .error_for_status()?;
+            // servers that ignore the range send the whole file, so reading stops once enough has arrived
+            let mut bytes = Vec::with_capacity(wanted);
+            while bytes.len() < wanted {
+                match resp.chunk().await? {
+                    Some(chunk) => bytes.extend_from_slice(&chunk),
+                    None => break,
+                }
+            }
+            let complete = bytes.len() < wanted;
+            bytes.truncate(wanted);
+
+            if has_end_card(&bytes) || complete {
+                return fits::read_primary_header(Cursor::new(bytes));
+            }
+            if wanted >= MAX_HEADER_BYTES {
+                return Err(format!("FITS header of {} is too large", redact(&url)).into());
+            }
+            wanted = (wanted * 4).min(MAX_HEADER_BYTES);
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! Active health checks of the main upstreams, for readiness probes.
+
+use reqwest::RequestBuilder;
+
+use serde::{Deserialize, Serialize};
+
+use tracing::{instrument, warn};
+
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+use crate::{EarendelServer, Upstream};
+
+/// The URL probed for the NASA API, with the configured key so that an invalid or missing key is reported.
+#[cfg(feature = "apod")]
+const NASA_API_PROBE_URL: &str = "https://api.nasa.gov/planetary/apod";
+/// The URL probed for MAST.
+#[cfg(feature = "mast")]
+const MAST_PROBE_URL: &str = "https://mast.stsci.edu/api/v0/invoke";
+/// The time after which a probe is considered failed.
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The outcome of probing an upstream.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct UpstreamHealth {
+    /// The probed upstream.
+    pub upstream: Upstream,
+    /// Whether the upstream answered with a successful or redirection status.
+    pub healthy: bool,
+    /// The HTTP status of the answer, if any.
+    pub status: Option<u16>,
+    /// The time taken by the probe.
+    pub latency: Duration,
+    /// The reason the probe failed, if it did.
+    pub error: Option<String>,
+}
+
+/// The outcome of `EarendelServer::health`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct HealthReport {
+    /// The outcome of probing each upstream.
+    pub upstreams: Vec<UpstreamHealth>,
+}
+
+impl HealthReport {
+    /// Determines whether every probed upstream is healthy.
+    pub fn is_healthy(&self) -> bool {
+        self.upstreams.iter().all(|upstream| upstream.healthy)
+    }
+}
+
+impl EarendelServer {
+    /// Probes the NASA API and, with the `mast` feature, MAST concurrently, reporting whether each answered with a
+    /// successful or redirection status and how long it took. The NASA API is probed with the configured key, and
+    /// counts against its rate limit; MAST is probed with a lightweight `HEAD` request. Probes are sent once, bypassing
+    /// the retry policy, rate limits, and circuit breakers, so that they reflect the current state of each upstream.
+    #[instrument(skip(self))]
+    pub async fn health(&self) -> HealthReport {
+        #[cfg(feature = "mast")]
+        let upstreams = {
+            let (nasa_api, mast) = tokio::join!(
+                self.probe(Upstream::Apod, self.nasa_api_get(NASA_API_PROBE_URL)),
+                self.probe(Upstream::Mast, Ok(self.client.head(MAST_PROBE_URL)))
+            );
+            vec![nasa_api, mast]
+        };
+        #[cfg(all(feature = "apod", not(feature = "mast")))]
+        let upstreams = vec![
+            self.probe(Upstream::Apod, self.nasa_api_get(NASA_API_PROBE_URL))
+                .await,
+        ];
+        // the `mast` feature implies `apod`, so there is no upstream to probe
+        #[cfg(not(feature = "apod"))]
+        let upstreams = Vec::new();
+
+        HealthReport { upstreams }
+    }
+
+    #[cfg_attr(not(feature = "apod"), allow(dead_code))]
+    async fn probe(
+        &self,
+        upstream: Upstream,
+        request: Result<RequestBuilder, Box<dyn Error + Send + Sync>>,
+    ) -> UpstreamHealth {
+        let start = Instant::now();
+        let result = self.send_probe(upstream, request).await;
+        let latency = start.elapsed();
+
+        match result {
+            Ok(resp) => UpstreamHealth {
+                upstream,
+                healthy: resp.status().is_success() || resp.status().is_redirection(),
+                status: Some(resp.status().as_u16()),
+                latency,
+                error: None,
+            },
+            Err(e) => {
+                warn!("health probe of {:?} failed: {}", upstream, e);
+                UpstreamHealth {
+                    upstream,
+                    healthy: false,
+                    status: None,
+                    latency,
+                    error: Some(e.to_string()),
+                }
+            }
+        }
+    }
+
+    /// Sends the given probe once, with a NASA API key if it is a NASA API request.
+    #[cfg_attr(not(feature = "apod"), allow(dead_code, unused_mut))]
+    async fn send_probe(
+        &self,
+        upstream: Upstream,
+        request: Result<RequestBuilder, Box<dyn Error + Send + Sync>>,
+    ) -> Result<reqwest::Response, Box<dyn Error + Send + Sync>> {
+        let mut request = request?.timeout(HEALTH_TIMEOUT).build()?;
+        #[cfg(feature = "apod")]
+        let api_key = self.api_keys.assign(&mut request);
+        let resp = self.send_once(upstream, request, 1).await?;
+        #[cfg(feature = "apod")]
+        if let Some(index) = api_key {
+            self.api_keys.record(index, &resp);
+        }
+
+        Ok(resp)
+    }
+}
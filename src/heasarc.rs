@@ -0,0 +1,39 @@
+//! The HEASARC archive of high-energy observations, searched through its ObsCore TAP service.
+
+use astro_rs::coordinates::Icrs;
+
+use async_trait::async_trait;
+
+use std::error::Error;
+
+use crate::archive::{EarendelFits, ObservationArchive};
+use crate::tap::ObsCoreArchive;
+use crate::{EarendelServer, Upstream};
+
+const HEASARC: ObsCoreArchive = ObsCoreArchive {
+    name: "HEASARC",
+    base_url: "https://heasarc.gsfc.nasa.gov/xamin/vo/tap",
+    upstream: Upstream::Heasarc,
+    id_column: "obs_id",
+};
+
+/// The High Energy Astrophysics Science Archive Research Center (HEASARC), hosting X-ray and gamma-ray observations
+/// from missions such as Chandra, XMM-Newton, Swift, and Fermi.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HeasarcArchive;
+
+#[async_trait]
+impl ObservationArchive for HeasarcArchive {
+    fn name(&self) -> &str {
+        HEASARC.name
+    }
+
+    async fn search(
+        &self,
+        server: &EarendelServer,
+        coords: &Icrs,
+        page: usize,
+    ) -> Result<EarendelFits, Box<dyn Error + Send + Sync>> {
+        HEASARC.search(server, coords, page).await
+    }
+}
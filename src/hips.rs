@@ -0,0 +1,192 @@
+//! HiPS tiles covering a region of the sky, for feeding pan and zoom sky viewers.
+
+use astro_rs::coordinates::Icrs;
+
+use serde::{Deserialize, Serialize};
+
+use tracing::instrument;
+
+use uom::si::angle::degree;
+use uom::si::f64::Angle;
+
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::f64::consts::{FRAC_PI_2, PI};
+
+use crate::coords::icrs_to_degrees;
+use crate::wcs::{Projection, Wcs};
+use crate::{CutoutFormat, EarendelServer, Upstream};
+
+/// The deepest HEALPix order supported.
+pub const MAX_ORDER: u8 = 29;
+/// The maximum number of tiles fetched for a single region.
+const MAX_TILES: usize = 256;
+/// The number of sample points per tile width used to find the covering tiles.
+const SAMPLES_PER_TILE: f64 = 4.0;
+
+/// A HiPS tile of a survey.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct HipsTile {
+    /// The HEALPix order of the tile.
+    pub order: u8,
+    /// The NESTED HEALPix index of the tile.
+    pub ipix: u64,
+    /// The format of the image.
+    pub format: CutoutFormat,
+    /// The binary representation of the image.
+    #[serde(with = "serde_bytes")]
+    pub img: Vec<u8>,
+}
+
+/// Gets the approximate width of a tile of the given order, in degrees.
+pub fn tile_width(order: u8) -> f64 {
+    (4.0 * PI / 12.0).sqrt().to_degrees() / f64::from(1u32 << order.min(31))
+}
+
+/// Gets the NESTED HEALPix index of the given position at the given order. The position is given in degrees.
+pub fn ang2pix_nest(order: u8, ra: f64, dec: f64) -> u64 {
+    let nside = 1i64 << order.min(MAX_ORDER);
+    let z = dec.to_radians().sin();
+    let za = z.abs();
+    let tt = ra.rem_euclid(360.0).to_radians() / FRAC_PI_2;
+
+    let (face, ix, iy) = if za <= 2.0 / 3.0 {
+        let temp1 = nside as f64 * (0.5 + tt);
+        let temp2 = nside as f64 * z * 0.75;
+        let jp = (temp1 - temp2) as i64;
+        let jm = (temp1 + temp2) as i64;
+        let ifp = jp / nside;
+        let ifm = jm / nside;
+        let face = if ifp == ifm {
+            ifp | 4
+        } else if ifp < ifm {
+            ifp
+        } else {
+            ifm + 8
+        };
+        (face, jm & (nside - 1), nside - (jp & (nside - 1)) - 1)
+    } else {
+        let ntt = (tt as i64).min(3);
+        let tp = tt - ntt as f64;
+        let tmp = nside as f64 * (3.0 * (1.0 - za)).sqrt();
+        let jp = ((tp * tmp) as i64).min(nside - 1);
+        let jm = (((1.0 - tp) * tmp) as i64).min(nside - 1);
+        if z >= 0.0 {
+            (ntt, nside - jm - 1, nside - jp - 1)
+        } else {
+            (ntt + 8, jp, jm)
+        }
+    };
+
+    (face as u64) * (nside as u64).pow(2) + interleave(ix as u64, iy as u64)
+}
+
+/// Interleaves the bits of the given coordinates within a base pixel, with x in the even bits.
+fn interleave(x: u64, y: u64) -> u64 {
+    let mut result = 0;
+    for bit in 0..32 {
+        result |= ((x >> bit) & 1) << (2 * bit);
+        result |= ((y >> bit) & 1) << (2 * bit + 1);
+    }
+    result
+}
+
+/// Gets the NESTED HEALPix indices of the tiles of the given order covering the disc of the given radius around the
+/// given coordinates.
+pub fn tiles_covering(coords: &Icrs, radius: Angle, order: u8) -> Vec<u64> {
+    let (ra, dec) = icrs_to_degrees(coords);
+    let radius = radius.get::<degree>();
+    let step = tile_width(order) / SAMPLES_PER_TILE;
+    let sampler = Wcs {
+        crpix: [0.0, 0.0],
+        crval: [ra, dec],
+        cd: [[-step, 0.0], [0.0, step]],
+        projection: Projection::Tan,
+    };
+
+    let steps = (radius / step).ceil() as i64;
+    let mut tiles = BTreeSet::new();
+    tiles.insert(ang2pix_nest(order, ra, dec));
+    for y in -steps..=steps {
+        for x in -steps..=steps {
+            if ((x * x + y * y) as f64).sqrt() * step > radius + step {
+                continue;
+            }
+            let (ra, dec) = sampler.pixel_to_world(x as f64, y as f64);
+            tiles.insert(ang2pix_nest(order, ra, dec));
+        }
+    }
+
+    tiles.into_iter().collect()
+}
+
+impl EarendelServer {
+    /// Gets the tiles of the HiPS survey at the given base URL, such as `https://alasky.cds.unistra.fr/DSS/DSSColor`,
+    /// covering the disc of the given radius around the given coordinates. Returns an error if too many tiles are
+    /// needed at the given order or if a web request fails.
+    #[instrument(skip(self, coords, radius))]
+    pub async fn get_hips_tiles(
+        &self,
+        hips_url: &str,
+        coords: &Icrs,
+        radius: Angle,
+        order: u8,
+        format: CutoutFormat,
+    ) -> Result<Vec<HipsTile>, Box<dyn Error + Send + Sync>> {
+        if order > MAX_ORDER {
+            return Err(
+                format!("HiPS order {} exceeds the maximum of {}", order, MAX_ORDER).into(),
+            );
+        }
+        let indices = tiles_covering(coords, radius, order);
+        if indices.len() > MAX_TILES {
+            return Err(format!(
+                "{} HiPS tiles are needed to cover the region at order {}, more than the maximum of {}",
+                indices.len(),
+                order,
+                MAX_TILES
+            )
+            .into());
+        }
+
+        let mut tiles = Vec::with_capacity(indices.len());
+        for ipix in indices {
+            let url = format!(
+                "{}/Norder{}/Dir{}/Npix{}.{}",
+                hips_url.trim_end_matches('/'),
+                order,
+                ipix / 10_000 * 10_000,
+                ipix,
+                format.as_param()
+            );
+            let resp = self
+                .send(Upstream::Hips, self.client.get(url))
+                .await?
+                .error_for_status()?;
+            tiles.push(HipsTile {
+                order,
+                ipix,
+                format,
+                img: resp.bytes().await?.to_vec(),
+            });
+        }
+
+        Ok(tiles)
+    }
+
+    /// Gets the tiles of the HiPS survey at the given base URL covering the disc of the given radius around the target
+    /// of the current APOD. Returns an error if the target cannot be resolved, if too many tiles are needed, or if a
+    /// web request fails.
+    pub async fn get_hips_tiles_for_apod(
+        &self,
+        hips_url: &str,
+        radius: Angle,
+        order: u8,
+        format: CutoutFormat,
+    ) -> Result<Vec<HipsTile>, Box<dyn Error + Send + Sync>> {
+        let coords = self.resolve_apod_target().await?;
+
+        self.get_hips_tiles(hips_url, &coords, radius, order, format)
+            .await
+    }
+}
@@ -0,0 +1,277 @@
+//! A SQLite store of the history of fetched APODs.
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+
+use serde::{Deserialize, Serialize};
+
+use std::error::Error;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+#[cfg(feature = "mirror")]
+use crate::ApodMetadata;
+use crate::EarendelApod;
+
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS apod (
+    date TEXT PRIMARY KEY,
+    title TEXT NOT NULL,
+    explanation TEXT,
+    copyright TEXT,
+    image_url TEXT NOT NULL,
+    width INTEGER,
+    height INTEGER,
+    fetched_at TEXT NOT NULL,
+    image BLOB
+)";
+
+/// The batches of dates recorded by `EarendelServer::mirror_archive`, and whether their images were stored.
+const MIRROR_SCHEMA: &str = "CREATE TABLE IF NOT EXISTS mirrored (
+    start_date TEXT PRIMARY KEY,
+    end_date TEXT NOT NULL,
+    images INTEGER NOT NULL
+)";
+
+const ENTRY_COLUMNS: &str =
+    "date, title, explanation, copyright, image_url, width, height, fetched_at, image IS NOT NULL";
+
+/// A fetched APOD recorded in the history.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct HistoryEntry {
+    /// The date the APOD was published.
+    pub date: NaiveDate,
+    /// The title of the APOD.
+    pub title: String,
+    /// The explanation of the APOD, if any.
+    pub explanation: Option<String>,
+    /// The copyright string.
+    pub copyright: Option<String>,
+    /// The URL the image was downloaded from.
+    pub image_url: String,
+    /// The width of the image, in pixels, if known.
+    pub width: Option<u32>,
+    /// The height of the image, in pixels, if known.
+    pub height: Option<u32>,
+    /// When the APOD was most recently fetched.
+    pub fetched_at: DateTime<Utc>,
+    /// Whether the image itself is stored.
+    pub has_image: bool,
+}
+
+impl HistoryEntry {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(HistoryEntry {
+            date: row.get(0)?,
+            title: row.get(1)?,
+            explanation: row.get(2)?,
+            copyright: row.get(3)?,
+            image_url: row.get(4)?,
+            width: row.get(5)?,
+            height: row.get(6)?,
+            fetched_at: row.get(7)?,
+            has_image: row.get(8)?,
+        })
+    }
+}
+
+/// A SQLite database recording every APOD fetched by a server, and optionally their images.
+#[derive(Debug)]
+pub struct ApodHistory {
+    connection: Mutex<Connection>,
+    store_images: bool,
+}
+
+impl ApodHistory {
+    /// Opens or creates the history database at the given path. Images are only stored if `store_images` is set.
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        store_images: bool,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Self::with_connection(Connection::open(path)?, store_images)
+    }
+
+    /// Creates a history database held in memory, which is lost when the history is dropped.
+    pub fn open_in_memory(store_images: bool) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Self::with_connection(Connection::open_in_memory()?, store_images)
+    }
+
+    fn with_connection(
+        connection: Connection,
+        store_images: bool,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        connection.execute(SCHEMA, [])?;
+        connection.execute(MIRROR_SCHEMA, [])?;
+
+        Ok(ApodHistory {
+            connection: Mutex::new(connection),
+            store_images,
+        })
+    }
+
+    /// Records the given APOD, replacing any previous record of the same date.
+    pub fn record(&self, apod: &EarendelApod) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let image = self.store_images.then_some(apod.img());
+        self.lock().execute(
+            "INSERT INTO apod (date, title, explanation, copyright, image_url, width, height, fetched_at, image)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+             ON CONFLICT (date) DO UPDATE SET title = ?2, explanation = ?3, copyright = ?4, image_url = ?5,
+             width = ?6, height = ?7, fetched_at = ?8, image = COALESCE(?9, image)",
+            params![
+                apod.date,
+                apod.title,
+                apod.explanation,
+                apod.copyright,
+                apod.image_url,
+                apod.width,
+                apod.height,
+                Utc::now(),
+                image,
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// Records the given APOD, fetched without its image, unless the APOD of the same date is already recorded.
+    #[cfg(feature = "mirror")]
+    pub(crate) fn record_metadata(
+        &self,
+        apod: &ApodMetadata,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.lock().execute(
+            "INSERT INTO apod (date, title, explanation, copyright, image_url, fetched_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT (date) DO NOTHING",
+            params![
+                apod.date,
+                apod.title,
+                apod.explanation,
+                apod.copyright,
+                apod.image_url.as_deref().unwrap_or_default(),
+                Utc::now(),
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// Stores the given image of the recorded APOD of the given date.
+    #[cfg(feature = "mirror")]
+    pub(crate) fn store_image(
+        &self,
+        date: NaiveDate,
+        image: &[u8],
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.lock().execute(
+            "UPDATE apod SET image = ?2 WHERE date = ?1",
+            params![date, image],
+        )?;
+
+        Ok(())
+    }
+
+    /// Determines whether the batch of dates starting on the given date has been mirrored, including the images if
+    /// `images` is set.
+    #[cfg(feature = "mirror")]
+    pub(crate) fn is_mirrored(
+        &self,
+        start: NaiveDate,
+        images: bool,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let mirrored = self
+            .lock()
+            .query_row(
+                "SELECT 1 FROM mirrored WHERE start_date = ?1 AND (images OR NOT ?2)",
+                params![start, images],
+                |_| Ok(()),
+            )
+            .optional()?;
+
+        Ok(mirrored.is_some())
+    }
+
+    /// Records that the given batch of dates has been mirrored, including the images if `images` is set.
+    #[cfg(feature = "mirror")]
+    pub(crate) fn mark_mirrored(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+        images: bool,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.lock().execute(
+            "INSERT INTO mirrored (start_date, end_date, images) VALUES (?1, ?2, ?3)
+             ON CONFLICT (start_date) DO UPDATE SET end_date = ?2, images = images OR ?3",
+            params![start, end, images],
+        )?;
+
+        Ok(())
+    }
+
+    /// Gets the recorded APOD of the given date, if any.
+    pub fn get(
+        &self,
+        date: NaiveDate,
+    ) -> Result<Option<HistoryEntry>, Box<dyn Error + Send + Sync>> {
+        let query = format!("SELECT {} FROM apod WHERE date = ?1", ENTRY_COLUMNS);
+        let entry = self
+            .lock()
+            .query_row(&query, [date], HistoryEntry::from_row)
+            .optional()?;
+
+        Ok(entry)
+    }
+
+    /// Gets the stored image of the APOD of the given date, if any.
+    pub fn image(&self, date: NaiveDate) -> Result<Option<Vec<u8>>, Box<dyn Error + Send + Sync>> {
+        let image = self
+            .lock()
+            .query_row("SELECT image FROM apod WHERE date = ?1", [date], |row| {
+                row.get::<_, Option<Vec<u8>>>(0)
+            })
+            .optional()?;
+
+        Ok(image.flatten())
+    }
+
+    /// Lists every recorded APOD, most recent first.
+    pub fn entries(&self) -> Result<Vec<HistoryEntry>, Box<dyn Error + Send + Sync>> {
+        let query = format!("SELECT {} FROM apod ORDER BY date DESC", ENTRY_COLUMNS);
+        let connection = self.lock();
+        let mut statement = connection.prepare(&query)?;
+        let entries = statement
+            .query_map([], HistoryEntry::from_row)?
+            .collect::<rusqlite::Result<Vec<HistoryEntry>>>()?;
+
+        Ok(entries)
+    }
+
+    /// Lists the recorded APODs whose title or explanation contains the given text, ignoring ASCII case, most
+    /// recent first.
+    pub fn search(&self, text: &str) -> Result<Vec<HistoryEntry>, Box<dyn Error + Send + Sync>> {
+        let pattern = format!(
+            "%{}%",
+            text.replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_")
+        );
+        let query = format!(
+            "SELECT {} FROM apod WHERE title LIKE ?1 ESCAPE '\\' OR explanation LIKE ?1 ESCAPE '\\' \
+             ORDER BY date DESC",
+            ENTRY_COLUMNS
+        );
+        let connection = self.lock();
+        let mut statement = connection.prepare(&query)?;
+        let entries = statement
+            .query_map([pattern], HistoryEntry::from_row)?
+            .collect::<rusqlite::Result<Vec<HistoryEntry>>>()?;
+
+        Ok(entries)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Connection> {
+        self.connection
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
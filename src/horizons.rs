@@ -0,0 +1,138 @@
+//! Ephemerides of solar system bodies from the JPL Horizons system.
+
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+
+use serde::{Deserialize, Serialize};
+
+use tracing::instrument;
+
+use std::error::Error;
+
+use crate::archive::{EarendelFits, ObservationArchive};
+use crate::coords::icrs_from_degrees;
+use crate::{EarendelServer, Upstream};
+
+const HORIZONS_API_URL: &str = "https://ssd.jpl.nasa.gov/api/horizons.api";
+/// The format of times sent to Horizons.
+const REQUEST_TIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+/// The format of times in Horizons ephemerides.
+const EPHEMERIS_TIME_FORMAT: &str = "%Y-%b-%d %H:%M";
+
+/// The apparent position of a body at a point in time, as seen from the geocenter.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct EphemerisPoint {
+    /// The time of the position, in UTC.
+    pub time: NaiveDateTime,
+    /// The right ascension of the body, in degrees.
+    pub ra: f64,
+    /// The declination of the body, in degrees.
+    pub dec: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct HorizonsResponse {
+    result: String,
+}
+
+/// Parses the rows between the `$$SOE` and `$$EOE` markers of a CSV observer ephemeris.
+fn parse_ephemeris(result: &str) -> Vec<EphemerisPoint> {
+    result
+        .lines()
+        .skip_while(|line| !line.starts_with("$$SOE"))
+        .skip(1)
+        .take_while(|line| !line.starts_with("$$EOE"))
+        .filter_map(|line| {
+            let fields = line.split(',').map(str::trim).collect::<Vec<&str>>();
+            // the date is followed by the solar and lunar presence flags
+            Some(EphemerisPoint {
+                time: NaiveDateTime::parse_from_str(fields.first()?, EPHEMERIS_TIME_FORMAT).ok()?,
+                ra: fields.get(3)?.parse().ok()?,
+                dec: fields.get(4)?.parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+impl EarendelServer {
+    /// Gets the geocentric ephemeris of the given body between the given times. The body is a Horizons identifier,
+    /// such as `499` for Mars or `DES=1P;` for Halley's Comet. Returns an error if Horizons cannot identify a single
+    /// body or if the web request fails.
+    #[instrument(skip(self))]
+    pub async fn get_ephemeris(
+        &self,
+        body: &str,
+        start: DateTime<Utc>,
+        stop: DateTime<Utc>,
+        step: Duration,
+    ) -> Result<Vec<EphemerisPoint>, Box<dyn Error + Send + Sync>> {
+        let quote = |value: String| format!("'{}'", value);
+        let request = self.client.get(HORIZONS_API_URL).query(&[
+            ("format", String::from("json")),
+            ("COMMAND", quote(body.to_owned())),
+            ("OBJ_DATA", quote(String::from("NO"))),
+            ("MAKE_EPHEM", quote(String::from("YES"))),
+            ("EPHEM_TYPE", quote(String::from("OBSERVER"))),
+            ("CENTER", quote(String::from("500@399"))),
+            (
+                "START_TIME",
+                quote(start.format(REQUEST_TIME_FORMAT).to_string()),
+            ),
+            (
+                "STOP_TIME",
+                quote(stop.format(REQUEST_TIME_FORMAT).to_string()),
+            ),
+            (
+                "STEP_SIZE",
+                quote(format!("{} m", step.num_minutes().max(1))),
+            ),
+            ("QUANTITIES", quote(String::from("1"))),
+            ("ANG_FORMAT", quote(String::from("DEG"))),
+            ("CSV_FORMAT", quote(String::from("YES"))),
+        ]);
+        let resp = self
+            .send(Upstream::Horizons, request)
+            .await?
+            .error_for_status()?;
+        let body_text = resp.text().await?;
+        let result = self
+            .parse::<HorizonsResponse>(Upstream::Horizons, &body_text)?
+            .result;
+
+        let points = parse_ephemeris(&result);
+        if points.is_empty() {
+            // Horizons reports ambiguous or unknown bodies in the result text
+            return Err(format!(
+                "Horizons returned no ephemeris for {}: {}",
+                body,
+                result.trim()
+            )
+            .into());
+        }
+
+        Ok(points)
+    }
+
+    /// Gets a page of observations from the given archive at the position of the given body at the given time, since
+    /// a cone search at a fixed position cannot follow a moving target. Returns an error if the ephemeris cannot be
+    /// computed or if a web request fails.
+    pub async fn search_archive_for_body(
+        &self,
+        archive: &dyn ObservationArchive,
+        body: &str,
+        time: DateTime<Utc>,
+        page: usize,
+    ) -> Result<EarendelFits, Box<dyn Error + Send + Sync>> {
+        let points = self
+            .get_ephemeris(
+                body,
+                time,
+                time + Duration::minutes(1),
+                Duration::minutes(1),
+            )
+            .await?;
+        let point = points.first().ok_or("Horizons returned no ephemeris")?;
+        let coords = icrs_from_degrees(point.ra, point.dec);
+
+        self.search_archive(archive, &coords, page).await
+    }
+}
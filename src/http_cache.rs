@@ -0,0 +1,182 @@
+//! An on-disk cache of upstream responses, so that repeated queries and downloads during development do not hammer
+//! the archives.
+
+use reqwest::header::{CACHE_CONTROL, IF_MODIFIED_SINCE, IF_NONE_MATCH, RANGE};
+use reqwest::{Method, Request, Response, StatusCode};
+
+use serde::{Deserialize, Serialize};
+
+use tracing::warn;
+
+use std::error::Error;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{EarendelServer, Upstream};
+
+/// The configuration of the on-disk cache of upstream responses.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HttpCacheConfig {
+    /// The directory holding the cache.
+    pub dir: PathBuf,
+    /// The time for which a response is fresh when its `Cache-Control` header does not give a max age.
+    pub default_ttl: Duration,
+    /// The size of the largest cached response body, in bytes.
+    pub max_entry_size: u64,
+}
+
+impl HttpCacheConfig {
+    /// Creates a configuration that caches responses of up to 64 MiB in the given directory, for an hour unless their
+    /// `Cache-Control` header says otherwise.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        HttpCacheConfig {
+            dir: dir.into(),
+            default_ttl: Duration::from_secs(60 * 60),
+            max_entry_size: 64 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct CachedResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    /// When the response was stored, in seconds since the Unix epoch.
+    stored_at: u64,
+    /// The time for which the response is fresh, in seconds.
+    max_age: u64,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Gets the cache key of the given request to the given upstream, or None if its response must not be cached.
+pub(crate) fn cache_key(upstream: Upstream, request: &Request) -> Option<String> {
+    // partial and conditional responses depend on what the caller already has
+    if [RANGE, IF_NONE_MATCH, IF_MODIFIED_SINCE]
+        .iter()
+        .any(|header| request.headers().contains_key(header))
+    {
+        return None;
+    }
+
+    match *request.method() {
+        Method::GET => Some(format!("GET {}", request.url())),
+        // queries to these services are sent as POST requests, but have no side effects
+        Method::POST if matches!(upstream, Upstream::Mast | Upstream::Ned) => {
+            let body = request.body()?.as_bytes()?;
+            Some(format!(
+                "POST {} {}",
+                request.url(),
+                String::from_utf8_lossy(body)
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Gets the fresh cached response for the given key, if any.
+pub(crate) async fn lookup(config: &HttpCacheConfig, key: &str) -> Option<Response> {
+    let meta = cacache::read(&config.dir, ["meta:", key].concat())
+        .await
+        .ok()?;
+    let meta = serde_json::from_slice::<CachedResponse>(&meta).ok()?;
+    if now().saturating_sub(meta.stored_at) >= meta.max_age {
+        return None;
+    }
+    let body = cacache::read(&config.dir, ["body:", key].concat())
+        .await
+        .ok()?;
+
+    build_response(&meta, body)
+}
+
+/// Caches the given response to the given upstream under the given key if it may be cached. As the body is read to
+/// cache it, an equivalent response is returned in its place. The body is read with the download size limit of the
+/// given server, and responses larger than it or than the largest cached entry are returned unread.
+pub(crate) async fn store(
+    server: &EarendelServer,
+    config: &HttpCacheConfig,
+    key: &str,
+    upstream: Upstream,
+    resp: Response,
+) -> Result<Response, Box<dyn Error + Send + Sync>> {
+    let Some(max_age) = max_age(config, &resp) else {
+        return Ok(resp);
+    };
+    let limit = config
+        .max_entry_size
+        .min(server.max_download_size.unwrap_or(u64::MAX));
+    // bodies of unknown length are only read into memory when they are expected to be small
+    let fits = match resp.content_length() {
+        Some(length) => length <= limit,
+        None => upstream != Upstream::Download,
+    };
+    if resp.status() != StatusCode::OK || !fits {
+        return Ok(resp);
+    }
+
+    let meta = CachedResponse {
+        status: resp.status().as_u16(),
+        headers: resp
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_owned())))
+            .collect(),
+        stored_at: now(),
+        max_age: max_age.as_secs(),
+    };
+    let body = server.read_limited(resp).await?;
+    if body.len() as u64 <= config.max_entry_size {
+        let written = async {
+            cacache::write(
+                &config.dir,
+                ["meta:", key].concat(),
+                serde_json::to_vec(&meta)?,
+            )
+            .await?;
+            cacache::write(&config.dir, ["body:", key].concat(), &body).await?;
+            Ok::<(), Box<dyn Error + Send + Sync>>(())
+        };
+        if let Err(e) = written.await {
+            warn!("failed to cache response: {}", e);
+        }
+    }
+
+    build_response(&meta, body).ok_or_else(|| "failed to rebuild the cached response".into())
+}
+
+/// Gets the time for which the given response is fresh, or None if it must not be cached.
+fn max_age(config: &HttpCacheConfig, resp: &Response) -> Option<Duration> {
+    let Some(cache_control) = resp
+        .headers()
+        .get(CACHE_CONTROL)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return Some(config.default_ttl);
+    };
+    let mut max_age = config.default_ttl;
+    for directive in cache_control.split(',').map(str::trim) {
+        if directive.eq_ignore_ascii_case("no-store") {
+            return None;
+        }
+        if let Some(seconds) = directive.strip_prefix("max-age=") {
+            max_age = Duration::from_secs(seconds.parse().ok()?);
+        }
+    }
+
+    (!max_age.is_zero()).then_some(max_age)
+}
+
+fn build_response(meta: &CachedResponse, body: Vec<u8>) -> Option<Response> {
+    let mut builder = http::Response::builder().status(meta.status);
+    for (name, value) in meta.headers.iter() {
+        builder = builder.header(name, value);
+    }
+
+    Some(Response::from(builder.body(body).ok()?))
+}
@@ -0,0 +1,103 @@
+//! Rendering of APODs as an iCalendar feed, for subscribing to the picture of the day in calendar apps.
+
+use chrono::{Duration, NaiveDate};
+
+use tracing::instrument;
+
+use std::error::Error;
+
+use crate::embed::preview_description;
+use crate::{ApodMetadata, EarendelServer};
+
+/// The longest line of an iCalendar file, in bytes, excluding the line break. Longer lines are folded.
+const MAX_LINE_BYTES: usize = 75;
+
+/// Renders the given APODs as an iCalendar file, with an all-day event on the date of each APOD. Each event carries
+/// the title, the start of the explanation, the image URL, and a link to the APOD web page. Events are identified by
+/// their date, so that calendar apps update rather than duplicate them when the feed is refreshed.
+pub fn apod_calendar(apods: &[ApodMetadata]) -> String {
+    let mut lines = vec![
+        String::from("BEGIN:VCALENDAR"),
+        String::from("VERSION:2.0"),
+        String::from("PRODID:-//earendel//APOD//EN"),
+        String::from("CALSCALE:GREGORIAN"),
+        String::from("METHOD:PUBLISH"),
+        String::from("X-WR-CALNAME:Astronomy Picture of the Day"),
+    ];
+    for apod in apods {
+        let mut description = preview_description(apod.explanation.as_deref().unwrap_or_default());
+        if let Some(image_url) = apod.image_url.as_ref() {
+            description.push_str("\n\n");
+            description.push_str(image_url);
+        }
+        let end = apod.date + Duration::days(1);
+
+        lines.push(String::from("BEGIN:VEVENT"));
+        lines.push(format!("UID:apod-{}@earendel", apod.date.format("%Y%m%d")));
+        // the feed is derived from the APODs alone, so that it renders identically each time
+        lines.push(format!("DTSTAMP:{}T000000Z", apod.date.format("%Y%m%d")));
+        lines.push(format!("DTSTART;VALUE=DATE:{}", apod.date.format("%Y%m%d")));
+        lines.push(format!("DTEND;VALUE=DATE:{}", end.format("%Y%m%d")));
+        lines.push(format!("SUMMARY:{}", ical_escape(&apod.title)));
+        lines.push(format!("DESCRIPTION:{}", ical_escape(&description)));
+        lines.push(format!("URL:{}", apod.page_url));
+        if let Some(image_url) = apod
+            .image_url
+            .as_ref()
+            .filter(|_| apod.media_type == "image")
+        {
+            lines.push(format!("ATTACH:{}", image_url));
+        }
+        lines.push(String::from("END:VEVENT"));
+    }
+    lines.push(String::from("END:VCALENDAR"));
+
+    lines
+        .iter()
+        .map(|line| fold_line(line))
+        .collect::<Vec<String>>()
+        .concat()
+}
+
+impl EarendelServer {
+    /// Renders the APODs published between the given dates, inclusive, as an iCalendar file. See `apod_calendar`.
+    /// Returns an Error if either date is outside the archive, if a web request fails, or if deserialization fails.
+    #[instrument(skip(self))]
+    pub async fn get_apod_calendar(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let apods = self.fetch_apod_range(start, end).await?;
+
+        Ok(apod_calendar(&apods))
+    }
+}
+
+/// Escapes the given text for use in an iCalendar property value.
+fn ical_escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Folds the given line into lines of at most `MAX_LINE_BYTES` bytes, without splitting characters, each ending with
+/// a CRLF. Continuation lines start with a space.
+fn fold_line(line: &str) -> String {
+    let mut folded = String::new();
+    let mut line_bytes = 0;
+    for c in line.chars() {
+        if line_bytes + c.len_utf8() > MAX_LINE_BYTES {
+            folded.push_str("\r\n ");
+            // the leading space counts towards the length of the continuation line
+            line_bytes = 1;
+        }
+        folded.push(c);
+        line_bytes += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+
+    folded
+}
@@ -0,0 +1,440 @@
+//! Processing of downloaded images, such as the APOD, for serving to clients.
+
+#[cfg(feature = "avif")]
+use image::codecs::avif::AvifEncoder;
+#[cfg(feature = "webp")]
+use image::codecs::webp::{WebPEncoder, WebPQuality};
+use image::imageops::FilterType;
+#[cfg(any(feature = "webp", feature = "avif"))]
+use image::{ColorType, ImageEncoder};
+use image::{DynamicImage, GrayImage, ImageFormat, ImageOutputFormat, Rgb, RgbImage};
+
+use serde::{Deserialize, Serialize};
+
+use sha2::{Digest, Sha256};
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::f64::consts::PI;
+use std::io::Cursor;
+
+/// The quality of JPEG output.
+const JPEG_QUALITY: u8 = 85;
+/// The width and height to which images are reduced before their palette is computed, in pixels.
+const PALETTE_SAMPLE_SIZE: u32 = 64;
+/// The number of significant bits per channel used to group similar colors.
+const PALETTE_BITS: u8 = 3;
+/// The width and height to which images are reduced before their BlurHash is computed, in pixels.
+const BLURHASH_SAMPLE_SIZE: u32 = 64;
+/// The digits of the base 83 encoding used by BlurHash.
+const BASE83_DIGITS: &[u8] =
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz#$%*+,-.:;=?@[]^_{|}~";
+/// The largest number of differing bits between the perceptual hashes of two images considered the same picture.
+pub const SAME_IMAGE_DISTANCE: u32 = 5;
+/// The encoding speed of AVIF output, from 1 (slowest, smallest) to 10 (fastest).
+#[cfg(feature = "avif")]
+const AVIF_SPEED: u8 = 6;
+
+/// Resizes the given encoded image to fit within the given dimensions, keeping its aspect ratio, and encodes the
+/// result in the same format as the original. Images already within the dimensions are only re-encoded.
+pub fn thumbnail(
+    img: &[u8],
+    max_width: u32,
+    max_height: u32,
+) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    if max_width == 0 || max_height == 0 {
+        return Err("thumbnail dimensions must be positive".into());
+    }
+    let format = image::guess_format(img)?;
+    let decoded = image::load_from_memory_with_format(img, format)?;
+    let resized = if decoded.width() > max_width || decoded.height() > max_height {
+        decoded.resize(max_width, max_height, FilterType::Lanczos3)
+    } else {
+        decoded
+    };
+
+    let output_format = match format {
+        ImageFormat::Jpeg => ImageOutputFormat::Jpeg(JPEG_QUALITY),
+        other => ImageOutputFormat::from(other),
+    };
+    let mut output = Vec::new();
+    resized.write_to(&mut Cursor::new(&mut output), output_format)?;
+
+    Ok(output)
+}
+
+/// How an image is fitted to a resolution of a different aspect ratio.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum WallpaperFit {
+    /// The image is scaled to cover the resolution, and the overflowing side is cropped around its most detailed
+    /// region, so that the subject stays in frame.
+    #[default]
+    Crop,
+    /// The image is scaled to fit within the resolution, and centered on black bars filling the remaining space, so
+    /// that nothing is cut off.
+    Letterbox,
+}
+
+/// Scales the given encoded image to exactly the given resolution, such as 3840 by 2160, fitting it as described by
+/// the given fit, and encodes the result in the same format as the original, ready to be set as a wallpaper. Returns an
+/// error if either dimension is zero or if the image cannot be decoded or encoded.
+pub fn wallpaper(
+    img: &[u8],
+    width: u32,
+    height: u32,
+    fit: WallpaperFit,
+) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    if width == 0 || height == 0 {
+        return Err("wallpaper dimensions must be positive".into());
+    }
+    let format = image::guess_format(img)?;
+    let decoded = image::load_from_memory_with_format(img, format)?;
+    if decoded.width() == 0 || decoded.height() == 0 {
+        return Err("image has no pixels".into());
+    }
+
+    let fitted = match fit {
+        WallpaperFit::Crop => {
+            let scale = f64::max(
+                f64::from(width) / f64::from(decoded.width()),
+                f64::from(height) / f64::from(decoded.height()),
+            );
+            let scaled_width = ((f64::from(decoded.width()) * scale).round() as u32).max(width);
+            let scaled_height = ((f64::from(decoded.height()) * scale).round() as u32).max(height);
+            let scaled = decoded.resize_exact(scaled_width, scaled_height, FilterType::Lanczos3);
+            let (column_detail, row_detail) = detail_profile(&scaled.to_luma8());
+            let x = most_detailed_window(&column_detail, width);
+            let y = most_detailed_window(&row_detail, height);
+            scaled.crop_imm(x, y, width, height)
+        }
+        WallpaperFit::Letterbox => {
+            let scaled = decoded
+                .resize(width, height, FilterType::Lanczos3)
+                .to_rgb8();
+            let mut canvas = RgbImage::from_pixel(width, height, Rgb([0, 0, 0]));
+            image::imageops::overlay(
+                &mut canvas,
+                &scaled,
+                i64::from((width - scaled.width().min(width)) / 2),
+                i64::from((height - scaled.height().min(height)) / 2),
+            );
+            DynamicImage::ImageRgb8(canvas)
+        }
+    };
+
+    let output_format = match format {
+        ImageFormat::Jpeg => ImageOutputFormat::Jpeg(JPEG_QUALITY),
+        other => ImageOutputFormat::from(other),
+    };
+    let mut output = Vec::new();
+    fitted.write_to(&mut Cursor::new(&mut output), output_format)?;
+
+    Ok(output)
+}
+
+/// The format an image is transcoded to.
+#[cfg(any(feature = "webp", feature = "avif"))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum TranscodeFormat {
+    /// A lossy WebP image.
+    #[cfg(feature = "webp")]
+    WebP,
+    /// An AVIF image.
+    #[cfg(feature = "avif")]
+    Avif,
+}
+
+/// The options used to transcode an image.
+#[cfg(any(feature = "webp", feature = "avif"))]
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct TranscodeOptions {
+    /// The format of the output.
+    pub format: TranscodeFormat,
+    /// The quality of the output, from 1 to 100.
+    pub quality: u8,
+}
+
+/// Transcodes the given encoded image to the format and quality described by the given options.
+#[cfg(any(feature = "webp", feature = "avif"))]
+pub fn transcode(
+    img: &[u8],
+    options: &TranscodeOptions,
+) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    let decoded = image::load_from_memory(img)?;
+    let quality = options.quality.clamp(1, 100);
+    let mut output = Vec::new();
+
+    match options.format {
+        #[cfg(feature = "webp")]
+        TranscodeFormat::WebP => {
+            let rgba = decoded.to_rgba8();
+            WebPEncoder::new_with_quality(&mut output, WebPQuality::lossy(quality)).write_image(
+                rgba.as_raw(),
+                rgba.width(),
+                rgba.height(),
+                ColorType::Rgba8,
+            )?;
+        }
+        #[cfg(feature = "avif")]
+        TranscodeFormat::Avif => {
+            let rgba = decoded.to_rgba8();
+            AvifEncoder::new_with_speed_quality(&mut output, AVIF_SPEED, quality).write_image(
+                rgba.as_raw(),
+                rgba.width(),
+                rgba.height(),
+                ColorType::Rgba8,
+            )?;
+        }
+    }
+
+    Ok(output)
+}
+
+/// A color that makes up part of an image.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct PaletteColor {
+    /// The red, green, and blue channels of the color.
+    pub rgb: [u8; 3],
+    /// The fraction of the image covered by the color, from 0 to 1.
+    pub proportion: f64,
+}
+
+/// The colors that make up an image.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Palette {
+    /// The average color of the image.
+    pub average: [u8; 3],
+    /// The dominant colors of the image, most common first.
+    pub dominant: Vec<PaletteColor>,
+}
+
+/// Computes the average color and up to the given number of dominant colors of the given encoded image.
+pub fn palette(img: &[u8], count: usize) -> Result<Palette, Box<dyn Error + Send + Sync>> {
+    let pixels = image::load_from_memory(img)?
+        .resize(
+            PALETTE_SAMPLE_SIZE,
+            PALETTE_SAMPLE_SIZE,
+            FilterType::Triangle,
+        )
+        .to_rgb8();
+    let total = u64::from(pixels.width()) * u64::from(pixels.height());
+    if total == 0 {
+        return Err("image has no pixels".into());
+    }
+
+    // colors are grouped into buckets by their most significant bits, then each bucket is averaged
+    let shift = 8 - PALETTE_BITS;
+    let mut buckets = HashMap::<[u8; 3], ([u64; 3], u64)>::new();
+    let mut sum = [0u64; 3];
+    for pixel in pixels.pixels() {
+        let key = pixel.0.map(|channel| channel >> shift);
+        let (bucket_sum, bucket_count) = buckets.entry(key).or_default();
+        for (channel, value) in pixel.0.iter().enumerate() {
+            bucket_sum[channel] += u64::from(*value);
+            sum[channel] += u64::from(*value);
+        }
+        *bucket_count += 1;
+    }
+
+    let mut dominant = buckets
+        .into_values()
+        .map(|(bucket_sum, bucket_count)| PaletteColor {
+            rgb: bucket_sum.map(|channel| (channel / bucket_count) as u8),
+            proportion: bucket_count as f64 / total as f64,
+        })
+        .collect::<Vec<PaletteColor>>();
+    dominant.sort_by(|a, b| b.proportion.total_cmp(&a.proportion));
+    dominant.truncate(count);
+
+    Ok(Palette {
+        average: sum.map(|channel| (channel / total) as u8),
+        dominant,
+    })
+}
+
+/// Computes the BlurHash of the given encoded image with the given number of horizontal and vertical components, each
+/// from 1 to 9, so that clients can render a blurred placeholder while the image loads. Four components by three suit
+/// most landscape images. Returns an error if the image cannot be decoded or if a component count is out of range.
+pub fn blurhash(
+    img: &[u8],
+    components_x: u32,
+    components_y: u32,
+) -> Result<String, Box<dyn Error + Send + Sync>> {
+    if !(1..=9).contains(&components_x) || !(1..=9).contains(&components_y) {
+        return Err("BlurHash components must be between 1 and 9".into());
+    }
+    let pixels = image::load_from_memory(img)?
+        .resize(
+            BLURHASH_SAMPLE_SIZE,
+            BLURHASH_SAMPLE_SIZE,
+            FilterType::Triangle,
+        )
+        .to_rgb8();
+    let (width, height) = (pixels.width(), pixels.height());
+    if width == 0 || height == 0 {
+        return Err("image has no pixels".into());
+    }
+
+    let linear = pixels
+        .pixels()
+        .map(|pixel| pixel.0.map(srgb_to_linear))
+        .collect::<Vec<[f64; 3]>>();
+    let mut factors = Vec::new();
+    for j in 0..components_y {
+        for i in 0..components_x {
+            let normalization = if i == 0 && j == 0 { 1.0 } else { 2.0 };
+            let mut factor = [0.0; 3];
+            for (index, color) in linear.iter().enumerate() {
+                let x = index as u32 % width;
+                let y = index as u32 / width;
+                let basis = normalization
+                    * (PI * f64::from(i) * f64::from(x) / f64::from(width)).cos()
+                    * (PI * f64::from(j) * f64::from(y) / f64::from(height)).cos();
+                for (sum, value) in factor.iter_mut().zip(color) {
+                    *sum += basis * value;
+                }
+            }
+            factors.push(factor.map(|value| value / f64::from(width * height)));
+        }
+    }
+
+    let mut hash = String::new();
+    encode_base83((components_x - 1) + (components_y - 1) * 9, 1, &mut hash);
+    let (dc, ac) = factors.split_first().ok_or("BlurHash has no components")?;
+    let max_value = if ac.is_empty() {
+        encode_base83(0, 1, &mut hash);
+        1.0
+    } else {
+        let actual_max = ac
+            .iter()
+            .flatten()
+            .map(|value| value.abs())
+            .fold(0.0, f64::max);
+        let quantized_max = (actual_max * 166.0 - 0.5).floor().clamp(0.0, 82.0) as u32;
+        encode_base83(quantized_max, 1, &mut hash);
+        f64::from(quantized_max + 1) / 166.0
+    };
+    let [r, g, b] = dc.map(linear_to_srgb);
+    encode_base83((r << 16) + (g << 8) + b, 4, &mut hash);
+    for factor in ac {
+        let [r, g, b] = factor.map(|value| {
+            let normalized = value / max_value;
+            let root = normalized.signum() * normalized.abs().sqrt();
+            (root * 9.0 + 9.5).floor().clamp(0.0, 18.0) as u32
+        });
+        encode_base83(r * 19 * 19 + g * 19 + b, 2, &mut hash);
+    }
+
+    Ok(hash)
+}
+
+/// Computes the 64-bit perceptual hash of the given encoded image, which changes little when the image is re-encoded,
+/// resized, or slightly retouched, unlike a cryptographic hash. The hash compares the brightness of neighbouring
+/// cells of a 9 by 8 grid. Compare hashes with `hash_distance`. Returns an error if the image cannot be decoded.
+pub fn perceptual_hash(img: &[u8]) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    let pixels = image::load_from_memory(img)?
+        .resize_exact(9, 8, FilterType::Triangle)
+        .to_luma8();
+
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let brighter = pixels.get_pixel(x, y).0[0] > pixels.get_pixel(x + 1, y).0[0];
+            hash = (hash << 1) | u64::from(brighter);
+        }
+    }
+
+    Ok(hash)
+}
+
+/// Counts the bits that differ between the given perceptual hashes. Images whose hashes differ by at most
+/// `SAME_IMAGE_DISTANCE` bits are almost certainly the same picture.
+pub fn hash_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// Computes the SHA-256 digest of the given bytes, as lowercase hexadecimal.
+pub fn sha256(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>()
+}
+
+/// Sums the brightness differences between neighbouring pixels of the given image over each column and each row, as a
+/// measure of where the detail of the image lies.
+fn detail_profile(luma: &GrayImage) -> (Vec<u64>, Vec<u64>) {
+    let mut columns = vec![0u64; luma.width() as usize];
+    let mut rows = vec![0u64; luma.height() as usize];
+    for (x, y, pixel) in luma.enumerate_pixels() {
+        let value = i32::from(pixel.0[0]);
+        let right = luma
+            .get_pixel(x.saturating_add(1).min(luma.width() - 1), y)
+            .0[0];
+        let below = luma
+            .get_pixel(x, y.saturating_add(1).min(luma.height() - 1))
+            .0[0];
+        let detail =
+            (value - i32::from(right)).unsigned_abs() + (value - i32::from(below)).unsigned_abs();
+        columns[x as usize] += u64::from(detail);
+        rows[y as usize] += u64::from(detail);
+    }
+
+    (columns, rows)
+}
+
+/// Finds the offset of the window of the given length with the most detail in the given profile. Profiles without a
+/// more detailed window are centered.
+fn most_detailed_window(profile: &[u64], length: u32) -> u32 {
+    let length = length as usize;
+    if profile.len() <= length {
+        return 0;
+    }
+
+    let mut best_offset = (profile.len() - length) / 2;
+    let mut best_detail = profile[best_offset..best_offset + length]
+        .iter()
+        .sum::<u64>();
+    let mut detail = profile[..length].iter().sum::<u64>();
+    for offset in 0..=profile.len() - length {
+        if offset > 0 {
+            detail = detail + profile[offset + length - 1] - profile[offset - 1];
+        }
+        if detail > best_detail {
+            best_offset = offset;
+            best_detail = detail;
+        }
+    }
+
+    best_offset as u32
+}
+
+/// Converts the given sRGB channel to a linear intensity from 0 to 1.
+fn srgb_to_linear(value: u8) -> f64 {
+    let value = f64::from(value) / 255.0;
+    if value <= 0.040_45 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Converts the given linear intensity to an sRGB channel.
+fn linear_to_srgb(value: f64) -> u32 {
+    let value = value.clamp(0.0, 1.0);
+    let srgb = if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+
+    (srgb * 255.0 + 0.5) as u32
+}
+
+/// Appends the given value to the given string as the given number of base 83 digits.
+fn encode_base83(value: u32, digits: u32, output: &mut String) {
+    for digit in (0..digits).rev() {
+        let index = value / 83u32.pow(digit) % 83;
+        output.push(char::from(BASE83_DIGITS[index as usize]));
+    }
+}
@@ -0,0 +1,82 @@
+//! Retrieval of the NASA Image of the Day, an alternative daily image source.
+
+use async_trait::async_trait;
+
+use chrono::{DateTime, Utc};
+
+use std::error::Error;
+
+use crate::potw::{attribute, element, text};
+use crate::{DailyImage, DailyImageProvider, EarendelServer, Upstream};
+
+const IOTD_FEED_URL: &str = "https://www.nasa.gov/feeds/iotd-feed/";
+
+/// The NASA Image of the Day, published on weekdays, served under the `nasa-iotd` source.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NasaIotdProvider;
+
+#[async_trait]
+impl DailyImageProvider for NasaIotdProvider {
+    fn name(&self) -> &str {
+        "nasa-iotd"
+    }
+
+    async fn fetch(
+        &self,
+        server: &EarendelServer,
+    ) -> Result<DailyImage, Box<dyn Error + Send + Sync>> {
+        let resp = server
+            .send(Upstream::NasaIotd, server.client.get(IOTD_FEED_URL))
+            .await?
+            .error_for_status()?;
+        let feed = resp.text().await?;
+        let item = element(&feed, "item").ok_or("NASA Image of the Day feed has no items")?;
+
+        let title = element(item, "title")
+            .map(text)
+            .ok_or("NASA Image of the Day has no title")?;
+        let date = element(item, "pubDate")
+            .and_then(|date| DateTime::parse_from_rfc2822(date.trim()).ok())
+            .map(|date| date.with_timezone(&Utc).date_naive())
+            .unwrap_or_else(|| Utc::now().date_naive());
+        let image_url =
+            attribute(item, "enclosure", "url").ok_or("NASA Image of the Day has no enclosure")?;
+        let caption = element(item, "description").map(text).unwrap_or_default();
+        let (explanation, credit) = split_credit(&caption);
+
+        let resp = server
+            .send(Upstream::NasaIotd, server.client.get(&image_url))
+            .await?
+            .error_for_status()?;
+        let img = server.read_limited(resp).await?;
+
+        Ok(DailyImage {
+            source: self.name().to_owned(),
+            title,
+            date,
+            image_url,
+            page_url: element(item, "link").map(text),
+            credit: Some(credit.unwrap_or("NASA").to_owned()),
+            license: None,
+            explanation: (!explanation.is_empty()).then(|| explanation.to_owned()),
+            img,
+        })
+    }
+}
+
+/// Splits the given caption into its explanation and the trailing credit line, such as `Image credit: NASA/JPL`.
+fn split_credit(caption: &str) -> (&str, Option<&str>) {
+    let lowercase = caption.to_ascii_lowercase();
+    let Some((start, length)) = ["image credit:", "credit:"]
+        .iter()
+        .find_map(|label| lowercase.rfind(label).map(|start| (start, label.len())))
+    else {
+        return (caption.trim(), None);
+    };
+    let credit = caption[start + length..].trim();
+
+    (
+        caption[..start].trim(),
+        (!credit.is_empty()).then_some(credit),
+    )
+}
@@ -0,0 +1,39 @@
+//! The IRSA archive of infrared observations, searched through its ObsCore TAP service.
+
+use astro_rs::coordinates::Icrs;
+
+use async_trait::async_trait;
+
+use std::error::Error;
+
+use crate::archive::{EarendelFits, ObservationArchive};
+use crate::tap::ObsCoreArchive;
+use crate::{EarendelServer, Upstream};
+
+const IRSA: ObsCoreArchive = ObsCoreArchive {
+    name: "IRSA",
+    base_url: "https://irsa.ipac.caltech.edu/TAP",
+    upstream: Upstream::Irsa,
+    id_column: "obs_id",
+};
+
+/// The NASA/IPAC Infrared Science Archive (IRSA), hosting infrared observations from missions such as WISE, Spitzer,
+/// and 2MASS.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct IrsaArchive;
+
+#[async_trait]
+impl ObservationArchive for IrsaArchive {
+    fn name(&self) -> &str {
+        IRSA.name
+    }
+
+    async fn search(
+        &self,
+        server: &EarendelServer,
+        coords: &Icrs,
+        page: usize,
+    ) -> Result<EarendelFits, Box<dyn Error + Send + Sync>> {
+        IRSA.search(server, coords, page).await
+    }
+}
@@ -0,0 +1,314 @@
+//! The current position of the International Space Station (ISS) and predictions of its passes over an observer.
+
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+
+use serde::{Deserialize, Serialize};
+
+use tracing::instrument;
+
+use std::error::Error;
+use std::f64::consts::{PI, TAU};
+
+use crate::{EarendelServer, Upstream};
+
+const ISS_API_URL: &str = "https://api.wheretheiss.at/v1/satellites/25544";
+/// The gravitational parameter of the Earth, in cubic kilometers per square second.
+const EARTH_MU: f64 = 398_600.4418;
+/// The equatorial radius of the Earth, in kilometers.
+const EARTH_RADIUS_KM: f64 = 6378.137;
+/// The flattening of the WGS84 ellipsoid.
+const EARTH_FLATTENING: f64 = 1.0 / 298.257_223_563;
+/// The second zonal harmonic of the Earth's gravity field.
+const EARTH_J2: f64 = 1.082_63e-3;
+/// The interval between positions checked for passes, in seconds.
+const PASS_STEP_SECONDS: i64 = 10;
+
+/// The position of the ISS over the Earth.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct IssPosition {
+    /// The geodetic latitude of the point below the ISS, in degrees.
+    pub latitude: f64,
+    /// The longitude of the point below the ISS, in degrees.
+    pub longitude: f64,
+    /// The altitude of the ISS, in kilometers.
+    pub altitude: f64,
+    /// The speed of the ISS, in kilometers per hour.
+    pub velocity: f64,
+    /// The time of the position.
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WhereTheIssPosition {
+    latitude: f64,
+    longitude: f64,
+    altitude: f64,
+    velocity: f64,
+    timestamp: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct WhereTheIssTle {
+    line1: String,
+    line2: String,
+}
+
+/// The mean orbital elements of a satellite, parsed from a two-line element set (TLE).
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct Tle {
+    /// The epoch of the elements.
+    pub epoch: DateTime<Utc>,
+    /// The inclination, in degrees.
+    pub inclination: f64,
+    /// The right ascension of the ascending node, in degrees.
+    pub raan: f64,
+    /// The eccentricity.
+    pub eccentricity: f64,
+    /// The argument of perigee, in degrees.
+    pub argument_of_perigee: f64,
+    /// The mean anomaly, in degrees.
+    pub mean_anomaly: f64,
+    /// The mean motion, in revolutions per day.
+    pub mean_motion: f64,
+}
+
+impl Tle {
+    /// Parses the two lines of a TLE. Returns an error if a field is missing or malformed.
+    pub fn parse(line1: &str, line2: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let field =
+            |line: &str, start: usize, end: usize| -> Result<f64, Box<dyn Error + Send + Sync>> {
+                Ok(line
+                    .get(start..end)
+                    .ok_or("TLE line is too short")?
+                    .trim()
+                    .parse::<f64>()?)
+            };
+
+        let year = field(line1, 18, 20)? as i32;
+        let year = if year < 57 { 2000 + year } else { 1900 + year };
+        let day = field(line1, 20, 32)?;
+        let start = NaiveDate::from_ymd_opt(year, 1, 1)
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .ok_or("TLE epoch is invalid")?;
+        let epoch = Utc.from_utc_datetime(&start)
+            + Duration::microseconds(((day - 1.0) * 86_400_000_000.0).round() as i64);
+
+        Ok(Tle {
+            epoch,
+            inclination: field(line2, 8, 16)?,
+            raan: field(line2, 17, 25)?,
+            eccentricity: field(line2, 26, 33)? * 1e-7,
+            argument_of_perigee: field(line2, 34, 42)?,
+            mean_anomaly: field(line2, 43, 51)?,
+            mean_motion: field(line2, 52, 63)?,
+        })
+    }
+
+    /// Gets the position of the satellite in Earth-fixed coordinates at the given time, in kilometers. The orbit is
+    /// propagated with the secular J2 perturbations only, which is adequate for predictions within a few days of the
+    /// epoch.
+    fn position_ecef(&self, time: DateTime<Utc>) -> [f64; 3] {
+        let elapsed = (time - self.epoch).num_milliseconds() as f64 / 1000.0;
+        let n = self.mean_motion * TAU / 86_400.0;
+        let e = self.eccentricity;
+        let i = self.inclination.to_radians();
+        let a = (EARTH_MU / (n * n)).cbrt();
+        let p = a * (1.0 - e * e);
+        let j2_rate = n * EARTH_J2 * (EARTH_RADIUS_KM / p).powi(2);
+
+        let raan = self.raan.to_radians() - 1.5 * j2_rate * i.cos() * elapsed;
+        let argp = self.argument_of_perigee.to_radians()
+            + 0.75 * j2_rate * (5.0 * i.cos().powi(2) - 1.0) * elapsed;
+        let mean_anomaly = (self.mean_anomaly.to_radians() + n * elapsed).rem_euclid(TAU);
+
+        let mut eccentric_anomaly = mean_anomaly;
+        for _ in 0..10 {
+            eccentric_anomaly -= (eccentric_anomaly - e * eccentric_anomaly.sin() - mean_anomaly)
+                / (1.0 - e * eccentric_anomaly.cos());
+        }
+        let true_anomaly = 2.0
+            * ((1.0 + e).sqrt() * (eccentric_anomaly / 2.0).sin())
+                .atan2((1.0 - e).sqrt() * (eccentric_anomaly / 2.0).cos());
+        let r = a * (1.0 - e * eccentric_anomaly.cos());
+        let u = argp + true_anomaly;
+
+        let x = r * (raan.cos() * u.cos() - raan.sin() * u.sin() * i.cos());
+        let y = r * (raan.sin() * u.cos() + raan.cos() * u.sin() * i.cos());
+        let z = r * u.sin() * i.sin();
+
+        let theta = gmst(time);
+        [
+            x * theta.cos() + y * theta.sin(),
+            -x * theta.sin() + y * theta.cos(),
+            z,
+        ]
+    }
+}
+
+/// Gets the Greenwich mean sidereal time at the given time, in radians.
+pub(crate) fn gmst(time: DateTime<Utc>) -> f64 {
+    let julian_date = time.timestamp_millis() as f64 / 86_400_000.0 + 2_440_587.5;
+    (280.460_618_37 + 360.985_647_366_29 * (julian_date - 2_451_545.0))
+        .to_radians()
+        .rem_euclid(TAU)
+}
+
+/// A location on the Earth from which passes and the sky are observed.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct Observer {
+    /// The geodetic latitude, in degrees.
+    pub latitude: f64,
+    /// The longitude, in degrees east.
+    pub longitude: f64,
+    /// The altitude above the WGS84 ellipsoid, in meters.
+    pub altitude: f64,
+}
+
+impl Observer {
+    /// Gets the elevation of the given Earth-fixed position above the horizon of the observer, in degrees.
+    fn elevation(&self, position: [f64; 3]) -> f64 {
+        let latitude = self.latitude.to_radians();
+        let longitude = self.longitude.to_radians();
+        let altitude = self.altitude / 1000.0;
+        let e2 = EARTH_FLATTENING * (2.0 - EARTH_FLATTENING);
+        let normal = EARTH_RADIUS_KM / (1.0 - e2 * latitude.sin().powi(2)).sqrt();
+
+        let observer = [
+            (normal + altitude) * latitude.cos() * longitude.cos(),
+            (normal + altitude) * latitude.cos() * longitude.sin(),
+            (normal * (1.0 - e2) + altitude) * latitude.sin(),
+        ];
+        let up = [
+            latitude.cos() * longitude.cos(),
+            latitude.cos() * longitude.sin(),
+            latitude.sin(),
+        ];
+        let range = [
+            position[0] - observer[0],
+            position[1] - observer[1],
+            position[2] - observer[2],
+        ];
+        let distance = range.iter().map(|value| value * value).sum::<f64>().sqrt();
+        let dot = range.iter().zip(up).map(|(r, u)| r * u).sum::<f64>();
+
+        (dot / distance).clamp(-1.0, 1.0).asin() * 180.0 / PI
+    }
+}
+
+/// A pass of a satellite above the horizon of an observer.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct IssPass {
+    /// The time the satellite rises above the minimum elevation.
+    pub rise: DateTime<Utc>,
+    /// The time of the highest elevation.
+    pub culmination: DateTime<Utc>,
+    /// The time the satellite sets below the minimum elevation.
+    pub set: DateTime<Utc>,
+    /// The highest elevation, in degrees.
+    pub max_elevation: f64,
+}
+
+/// Predicts the passes of the satellite described by the given TLE above the given minimum elevation, in degrees,
+/// between the given start time and the given number of hours later. Passes in progress at either end are omitted.
+pub fn predict_passes(
+    tle: &Tle,
+    observer: &Observer,
+    start: DateTime<Utc>,
+    hours: u32,
+    min_elevation: f64,
+) -> Vec<IssPass> {
+    let steps = i64::from(hours) * 3600 / PASS_STEP_SECONDS;
+    let mut passes = Vec::new();
+    let mut current: Option<IssPass> = None;
+    let mut was_above = true;
+
+    for step in 0..=steps {
+        let time = start + Duration::seconds(step * PASS_STEP_SECONDS);
+        let elevation = observer.elevation(tle.position_ecef(time));
+        let above = elevation >= min_elevation;
+
+        match (&mut current, above) {
+            (None, true) if !was_above => {
+                current = Some(IssPass {
+                    rise: time,
+                    culmination: time,
+                    set: time,
+                    max_elevation: elevation,
+                });
+            }
+            (Some(pass), true) => {
+                if elevation > pass.max_elevation {
+                    pass.max_elevation = elevation;
+                    pass.culmination = time;
+                }
+            }
+            (Some(pass), false) => {
+                pass.set = time;
+                passes.extend(current.take());
+            }
+            _ => {}
+        }
+        was_above = above;
+    }
+
+    passes
+}
+
+impl EarendelServer {
+    /// Gets the current position of the ISS. Returns an error if the web request fails.
+    #[instrument(skip(self))]
+    pub async fn get_iss_position(&self) -> Result<IssPosition, Box<dyn Error + Send + Sync>> {
+        let resp = self
+            .send(Upstream::Iss, self.client.get(ISS_API_URL))
+            .await?
+            .error_for_status()?;
+        let body = resp.text().await?;
+        let position = self.parse::<WhereTheIssPosition>(Upstream::Iss, &body)?;
+
+        Ok(IssPosition {
+            latitude: position.latitude,
+            longitude: position.longitude,
+            altitude: position.altitude,
+            velocity: position.velocity,
+            timestamp: Utc
+                .timestamp_opt(position.timestamp, 0)
+                .single()
+                .ok_or("ISS position has an invalid timestamp")?,
+        })
+    }
+
+    /// Gets the current TLE of the ISS. Returns an error if the web request fails or if the TLE is malformed.
+    #[instrument(skip(self))]
+    pub async fn get_iss_tle(&self) -> Result<Tle, Box<dyn Error + Send + Sync>> {
+        let resp = self
+            .send(
+                Upstream::Iss,
+                self.client.get([ISS_API_URL, "/tles"].concat()),
+            )
+            .await?
+            .error_for_status()?;
+        let body = resp.text().await?;
+        let tle = self.parse::<WhereTheIssTle>(Upstream::Iss, &body)?;
+
+        Tle::parse(&tle.line1, &tle.line2)
+    }
+
+    /// Predicts the passes of the ISS above the given minimum elevation, in degrees, over the given observer within
+    /// the given number of hours. Returns an error if the TLE cannot be retrieved.
+    pub async fn get_iss_passes(
+        &self,
+        observer: &Observer,
+        hours: u32,
+        min_elevation: f64,
+    ) -> Result<Vec<IssPass>, Box<dyn Error + Send + Sync>> {
+        let tle = self.get_iss_tle().await?;
+
+        Ok(predict_passes(
+            &tle,
+            observer,
+            Utc::now(),
+            hours,
+            min_elevation,
+        ))
+    }
+}
@@ -0,0 +1,218 @@
+//! Queries against the JWST instrument keyword services of MAST, which expose JWST-specific fields that the CAOM
+//! cone search does not.
+
+use astro_rs::coordinates::Icrs;
+
+use async_trait::async_trait;
+
+use serde::{Deserialize, Serialize};
+
+use std::error::Error;
+
+use crate::archive::{EarendelFits, Observation, ObservationArchive};
+use crate::mast::{
+    download_url, invoke, MastFilter, MastFilterValue, MastRequest, MastRequestParams,
+    MastResponsePaging,
+};
+use crate::mjd::mjd_to_datetime;
+use crate::{EarendelServer, FitsFileFilter};
+
+/// A JWST instrument, each of which has its own keyword service.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum JwstInstrument {
+    /// The Near Infrared Camera.
+    #[default]
+    Nircam,
+    /// The Near Infrared Imager and Slitless Spectrograph.
+    Niriss,
+    /// The Near Infrared Spectrograph.
+    Nirspec,
+    /// The Mid-Infrared Instrument.
+    Miri,
+    /// The Fine Guidance Sensor.
+    Fgs,
+}
+
+impl JwstInstrument {
+    /// Gets the name of the MAST service listing the exposures of the instrument.
+    fn service(&self) -> &'static str {
+        match self {
+            JwstInstrument::Nircam => "Mast.Jwst.Filtered.Nircam",
+            JwstInstrument::Niriss => "Mast.Jwst.Filtered.Niriss",
+            JwstInstrument::Nirspec => "Mast.Jwst.Filtered.Nirspec",
+            JwstInstrument::Miri => "Mast.Jwst.Filtered.Miri",
+            JwstInstrument::Fgs => "Mast.Jwst.Filtered.Fgs",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct JwstResponse {
+    data: Vec<JwstResponseEntry>,
+    paging: MastResponsePaging,
+}
+
+#[derive(Debug, Deserialize)]
+struct JwstResponseEntry {
+    filename: Option<String>,
+    #[serde(rename = "fileSetName")]
+    file_set_name: Option<String>,
+    instrume: Option<String>,
+    filter: Option<String>,
+    pupil: Option<String>,
+    readpatt: Option<String>,
+    apername: Option<String>,
+    targprop: Option<String>,
+    targ_ra: Option<f64>,
+    targ_dec: Option<f64>,
+    effexptm: Option<f64>,
+    expstart: Option<f64>,
+    expend: Option<f64>,
+}
+
+impl JwstResponseEntry {
+    /// Gets the URL the file of the exposure can be downloaded from.
+    fn data_url(&self) -> Option<String> {
+        self.filename
+            .as_ref()
+            .map(|filename| download_url(&format!("mast:JWST/product/{}", filename)))
+    }
+}
+
+impl From<&JwstResponseEntry> for Observation {
+    fn from(entry: &JwstResponseEntry) -> Self {
+        // listed the way the CAOM search lists the filters of JWST observations
+        let filters = match (entry.filter.as_deref(), entry.pupil.as_deref()) {
+            (Some(filter), Some(pupil)) => Some(format!("{};{}", filter, pupil)),
+            (filter, pupil) => filter.or(pupil).map(str::to_owned),
+        };
+
+        Observation {
+            archive: String::from("MAST"),
+            obs_id: entry
+                .file_set_name
+                .to_owned()
+                .or_else(|| entry.filename.to_owned())
+                .unwrap_or_default(),
+            collection: Some(String::from("JWST")),
+            instrument: entry.instrume.to_owned(),
+            filters,
+            target_name: entry.targprop.to_owned(),
+            ra: entry.targ_ra,
+            dec: entry.targ_dec,
+            exposure_time: entry.effexptm,
+            start_time: entry.expstart.and_then(mjd_to_datetime),
+            end_time: entry.expend.and_then(mjd_to_datetime),
+            data_url: entry.data_url(),
+            ..Default::default()
+        }
+    }
+}
+
+/// The exposures of a JWST instrument in MAST, searched through the instrument keyword service so that they can be
+/// narrowed by JWST-specific fields, such as the readout pattern, aperture, filter, and pupil. Each observation is a
+/// single exposure file.
+#[derive(Clone, Debug, Default)]
+pub struct JwstArchive {
+    instrument: JwstInstrument,
+    readout_pattern: Option<String>,
+    aperture: Option<String>,
+    filter: Option<String>,
+    pupil: Option<String>,
+}
+
+impl JwstArchive {
+    /// Creates an archive of the exposures of the given instrument.
+    pub fn new(instrument: JwstInstrument) -> Self {
+        JwstArchive {
+            instrument,
+            ..Default::default()
+        }
+    }
+
+    /// Only lists exposures with the given readout pattern, such as `SHALLOW4` or `FASTR1`.
+    pub fn readout_pattern(mut self, readout_pattern: &str) -> Self {
+        self.readout_pattern = Some(readout_pattern.to_owned());
+        self
+    }
+
+    /// Only lists exposures through the given aperture, such as `NRCA5_FULL`.
+    pub fn aperture(mut self, aperture: &str) -> Self {
+        self.aperture = Some(aperture.to_owned());
+        self
+    }
+
+    /// Only lists exposures through the given filter, such as `F444W`.
+    pub fn filter(mut self, filter: &str) -> Self {
+        self.filter = Some(filter.to_owned());
+        self
+    }
+
+    /// Only lists exposures through the given pupil element, such as `CLEAR`.
+    pub fn pupil(mut self, pupil: &str) -> Self {
+        self.pupil = Some(pupil.to_owned());
+        self
+    }
+
+    /// Gets the filters applied by MAST to the search.
+    fn filters(&self) -> Vec<MastFilter> {
+        [
+            ("readpatt", &self.readout_pattern),
+            ("apername", &self.aperture),
+            ("filter", &self.filter),
+            ("pupil", &self.pupil),
+        ]
+        .into_iter()
+        .filter_map(|(param_name, value)| {
+            value.as_ref().map(|value| MastFilter {
+                param_name: String::from(param_name),
+                values: vec![MastFilterValue::Exact(value.to_owned())],
+                free_text: None,
+            })
+        })
+        .collect()
+    }
+}
+
+#[async_trait]
+impl ObservationArchive for JwstArchive {
+    fn name(&self) -> &str {
+        "MAST"
+    }
+
+    async fn search(
+        &self,
+        server: &EarendelServer,
+        coords: &Icrs,
+        page: usize,
+    ) -> Result<EarendelFits, Box<dyn Error + Send + Sync>> {
+        let request = MastRequest::filtered(
+            self.instrument.service(),
+            MastRequestParams::from(coords),
+            self.filters(),
+            page,
+        );
+        let jwst = invoke::<JwstResponse>(server, &request.to_urlencoded()).await?;
+
+        let fits_filter = FitsFileFilter::default();
+        let fits_files = jwst
+            .data
+            .iter()
+            .filter(|entry| {
+                entry
+                    .filename
+                    .as_ref()
+                    .is_some_and(|filename| fits_filter.matches(filename, None))
+            })
+            .filter_map(JwstResponseEntry::data_url)
+            .collect::<Vec<String>>();
+
+        Ok(EarendelFits::new(
+            fits_files,
+            jwst.data.iter().map(Observation::from).collect(),
+            page,
+            jwst.paging.page_size,
+            jwst.paging.rows_total,
+        ))
+    }
+}
@@ -0,0 +1,192 @@
+//! Discovery of Kepler and K2 data products, such as target pixel files and light curves, by KIC or EPIC identifier.
+
+use serde::{Deserialize, Serialize};
+
+use tracing::instrument;
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+use crate::archive::Observation;
+use crate::mast::{invoke, MastFilter, MastFilterValue, MastRequest, MastResponse};
+use crate::{EarendelServer, MastProduct};
+
+/// The Kepler mission, or its extended K2 mission, each with its own catalog of target identifiers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum KeplerMission {
+    /// The original Kepler mission, whose targets are identified in the Kepler Input Catalog (KIC).
+    Kepler,
+    /// The K2 mission, whose targets are identified in the Ecliptic Plane Input Catalog (EPIC).
+    K2,
+}
+
+impl KeplerMission {
+    /// Gets the name of the catalog identifying the targets of the mission.
+    pub fn catalog(&self) -> &'static str {
+        match self {
+            KeplerMission::Kepler => "KIC",
+            KeplerMission::K2 => "EPIC",
+        }
+    }
+
+    /// Gets the name of the MAST collection of the mission.
+    fn collection(&self) -> &'static str {
+        match self {
+            KeplerMission::Kepler => "Kepler",
+            KeplerMission::K2 => "K2",
+        }
+    }
+}
+
+/// The identifier of a Kepler or K2 target, such as `KIC 8462852` or `EPIC 201367065`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct KeplerId {
+    /// The mission that observed the target.
+    pub mission: KeplerMission,
+    /// The number of the target in the catalog of the mission.
+    pub id: u64,
+}
+
+impl KeplerId {
+    /// Gets the name MAST lists the observations of the target under, such as `kplr008462852` or `ktwo201367065`.
+    fn mast_target_name(&self) -> String {
+        match self.mission {
+            KeplerMission::Kepler => format!("kplr{:09}", self.id),
+            KeplerMission::K2 => format!("ktwo{:09}", self.id),
+        }
+    }
+}
+
+impl Display for KeplerId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.mission.catalog(), self.id)
+    }
+}
+
+/// The Kepler or K2 data products of a target.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct KeplerProducts {
+    /// The identifier of the target.
+    pub target: KeplerId,
+    /// The observations of the target.
+    pub observations: Vec<Observation>,
+    /// The target pixel files of the target, which hold the pixels around the target at each cadence.
+    pub target_pixel_files: Vec<MastProduct>,
+    /// The light curve files of the target.
+    pub light_curves: Vec<MastProduct>,
+}
+
+/// Extracts a Kepler or K2 target identifier from text such as an APOD title. KIC (`KIC 8462852`) and EPIC
+/// (`EPIC 201367065`) identifiers are recognized, with or without a space before the number.
+pub fn extract_kepler_id(text: &str) -> Option<KeplerId> {
+    let words = text.split_whitespace().collect::<Vec<&str>>();
+    for (index, word) in words.iter().enumerate() {
+        for mission in [KeplerMission::Kepler, KeplerMission::K2] {
+            let Some(rest) = word.strip_prefix(mission.catalog()) else {
+                continue;
+            };
+            let number = if rest.is_empty() {
+                words.get(index + 1).copied().unwrap_or_default()
+            } else {
+                rest
+            };
+            let number = number.trim_end_matches(|c: char| !c.is_ascii_alphanumeric());
+            if !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()) {
+                if let Ok(id) = number.parse() {
+                    return Some(KeplerId { mission, id });
+                }
+            }
+        }
+    }
+
+    None
+}
+
+impl EarendelServer {
+    /// Resolves the Kepler or K2 identifier of the target with the given name. Names containing a KIC or EPIC
+    /// identifier are used as is; other names, such as `Kepler-186` or `K2-18`, are looked up in SIMBAD. Returns an
+    /// error if the target has no known identifier or if the web request fails.
+    #[instrument(skip(self))]
+    pub async fn resolve_kepler_id(
+        &self,
+        name: &str,
+    ) -> Result<KeplerId, Box<dyn Error + Send + Sync>> {
+        if let Some(id) = extract_kepler_id(name) {
+            return Ok(id);
+        }
+        let info = self.get_target_info(name).await?;
+
+        // targets observed by both missions are listed under Kepler
+        let ids = info
+            .identifiers
+            .iter()
+            .filter_map(|identifier| extract_kepler_id(identifier))
+            .collect::<Vec<KeplerId>>();
+        ids.iter()
+            .find(|id| id.mission == KeplerMission::Kepler)
+            .or_else(|| ids.first())
+            .copied()
+            .ok_or_else(|| format!("{} has no KIC or EPIC identifier", name).into())
+    }
+
+    /// Lists the Kepler or K2 target pixel files and light curves of the target with the given name or identifier.
+    /// See `EarendelServer::resolve_kepler_id`. Returns an error if the target has no known identifier or if a web
+    /// request fails.
+    #[instrument(skip(self))]
+    pub async fn get_kepler_products(
+        &self,
+        name: &str,
+    ) -> Result<KeplerProducts, Box<dyn Error + Send + Sync>> {
+        let target = self.resolve_kepler_id(name).await?;
+        let filters = vec![
+            MastFilter {
+                param_name: String::from("obs_collection"),
+                values: vec![MastFilterValue::Exact(String::from(
+                    target.mission.collection(),
+                ))],
+                free_text: None,
+            },
+            MastFilter {
+                param_name: String::from("target_name"),
+                values: vec![MastFilterValue::Exact(target.mast_target_name())],
+                free_text: None,
+            },
+        ];
+        let request = MastRequest::unpositioned("Mast.Caom.Filtered", filters, 1);
+        let mast = invoke::<MastResponse>(self, &request.to_urlencoded()).await?;
+        let observations = mast
+            .data
+            .iter()
+            .map(Observation::from)
+            .collect::<Vec<Observation>>();
+
+        let mut products = KeplerProducts {
+            target,
+            observations: Vec::new(),
+            target_pixel_files: Vec::new(),
+            light_curves: Vec::new(),
+        };
+        for observation in observations {
+            for product in self.get_mast_products(&observation).await? {
+                let filename = product.filename.to_ascii_lowercase();
+                if filename.contains("-targ.fits") {
+                    products.target_pixel_files.push(product);
+                } else if filename.ends_with("lc.fits") {
+                    products.light_curves.push(product);
+                }
+            }
+            products.observations.push(observation);
+        }
+
+        Ok(products)
+    }
+
+    /// Lists the Kepler or K2 target pixel files and light curves of the current APOD target. See
+    /// `EarendelServer::get_kepler_products`.
+    #[instrument(skip(self))]
+    pub async fn get_kepler_products_for_apod(
+        &self,
+    ) -> Result<KeplerProducts, Box<dyn Error + Send + Sync>> {
+        self.get_kepler_products(self.apod_target_name()).await
+    }
+}
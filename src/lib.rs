@@ -2,174 +2,784 @@
 #![deny(clippy::all)]
 #![doc = include_str!("../README.md")]
 
-use astro_rs::coordinates::Icrs;
-use chrono::{NaiveDate, Utc};
-
-use reqwest::header::HeaderMap;
-use reqwest::header::{ACCEPT, CONTENT_TYPE};
-
+#[cfg(feature = "mast")]
+mod alerce;
+#[cfg(feature = "apod")]
+mod api_keys;
+#[cfg(feature = "apod")]
+mod apod;
+#[cfg(feature = "mast")]
+mod archive;
+mod astrometry;
+mod breaker;
+#[cfg(feature = "apod")]
+mod cache;
+#[cfg(feature = "apod")]
+mod cache_admin;
+mod client;
+#[cfg(feature = "mast")]
+mod coords;
+#[cfg(feature = "mast")]
+mod cutout;
+#[cfg(feature = "apod")]
+mod daily;
+#[cfg(feature = "apod")]
+mod donki;
+#[cfg(feature = "mast")]
+mod download;
+#[cfg(feature = "mast")]
+mod dss;
+#[cfg(feature = "mast")]
+mod ehst;
+#[cfg(feature = "apod")]
+mod embed;
+#[cfg(feature = "apod")]
+mod epic;
+mod error;
+#[cfg(feature = "mast")]
+mod eso;
+#[cfg(feature = "mast")]
+mod exoplanet;
+#[cfg(feature = "mast")]
+mod export;
+#[cfg(feature = "ffi")]
+mod ffi;
+pub mod fits;
+#[cfg(feature = "mast")]
+mod fits_cache;
+mod flight;
+#[cfg(feature = "mast")]
+mod footprint;
+#[cfg(feature = "mast")]
+mod gaia;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "mast")]
+mod header_scan;
+mod health;
+#[cfg(feature = "mast")]
+mod heasarc;
+#[cfg(feature = "mast")]
+pub mod hips;
+#[cfg(feature = "history")]
+mod history;
+#[cfg(feature = "mast")]
+mod horizons;
+#[cfg(feature = "http-cache")]
+mod http_cache;
+#[cfg(feature = "apod")]
+mod ical;
+#[cfg(feature = "imaging")]
+pub mod imaging;
+#[cfg(feature = "apod")]
+mod iotd;
+#[cfg(feature = "mast")]
+mod irsa;
+mod iss;
+#[cfg(feature = "mast")]
+mod jwst;
+#[cfg(feature = "mast")]
+mod kepler;
+mod library;
+mod limiter;
+#[cfg(feature = "apod")]
+mod mars;
+#[cfg(feature = "mast")]
+mod mast;
+mod metadata;
+mod metrics;
+#[cfg(feature = "mirror")]
+mod mirror;
+mod mjd;
+mod moon;
+#[cfg(feature = "mast")]
+mod mpc;
+#[cfg(feature = "mast")]
+mod ned;
+#[cfg(feature = "apod")]
+mod neo;
+mod observability;
+#[cfg(feature = "overlay")]
+pub mod overlay;
+#[cfg(feature = "mast")]
+mod panstarrs;
+#[cfg(feature = "apod")]
+mod potw;
+#[cfg(feature = "mast")]
+mod product_name;
+#[cfg(feature = "py")]
+mod py;
+mod redact;
+#[cfg(feature = "apod")]
+mod refresh;
+#[cfg(feature = "render")]
+pub mod render;
+mod retry;
+mod runtime;
+#[cfg(feature = "mast")]
+pub mod sdss;
+#[cfg(feature = "search")]
+mod search;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "mast")]
+mod simbad;
+#[cfg(feature = "apod")]
+mod snapshot;
+#[cfg(feature = "stream")]
+mod stream;
+mod sun;
+#[cfg(feature = "mast")]
+mod tap;
+#[cfg(feature = "mast")]
+mod tess;
+#[cfg(feature = "mast")]
+mod vizier;
+pub mod wcs;
+#[cfg(feature = "webhook")]
+mod webhook;
+
+#[cfg(feature = "mast")]
+pub use alerce::Transient;
+#[cfg(feature = "apod")]
+pub use api_keys::KeyRotation;
+#[cfg(feature = "apod")]
+pub use apod::{
+    ApodImage, ApodLink, ApodMetadata, ApodMirror, ApodPageDetails, EarendelApod, RateLimitStatus,
+};
+#[cfg(feature = "mast")]
+pub use archive::{EarendelFits, Observation, ObservationArchive};
+pub use astrometry::PlateSolution;
+pub use breaker::{CircuitBreakerConfig, CircuitState};
+#[cfg(feature = "redis")]
+pub use cache::RedisCache;
+#[cfg(feature = "apod")]
+pub use cache::{CacheBackend, DiskCache, MemoryCache};
+#[cfg(feature = "apod")]
+pub use cache_admin::{CacheEntry, CacheKind, CacheStats};
+pub use client::EarendelClient;
+#[cfg(feature = "mast")]
+pub use coords::{parse_coordinates, SkyCoords};
+#[cfg(feature = "mast")]
+pub use cutout::{CutoutFormat, EarendelCutout};
+#[cfg(feature = "apod")]
+pub use daily::{ApodProvider, DailyImage, DailyImageProvider, APOD_SOURCE};
+#[cfg(feature = "apod")]
+pub use donki::{CoronalMassEjection, GeomagneticStorm, SolarFlare, SpaceWeather};
+#[cfg(feature = "mast")]
+pub use download::{ChecksumStatus, DownloadProgress, DownloadReport, FileDownload};
+#[cfg(feature = "mast")]
+pub use dss::DssFormat;
+#[cfg(feature = "mast")]
+pub use ehst::EhstArchive;
+#[cfg(feature = "apod")]
+pub use epic::EpicImage;
+pub use error::EarendelError;
+#[cfg(feature = "mast")]
+pub use eso::EsoArchive;
+#[cfg(feature = "mast")]
+pub use exoplanet::Exoplanet;
+#[cfg(feature = "ffi")]
+pub use ffi::{FfiApod, FfiEarendelServer, FfiError, FfiFits, FfiObservation};
+#[cfg(feature = "mast")]
+pub use fits_cache::FitsCacheConfig;
+#[cfg(feature = "mast")]
+pub use footprint::Footprint;
+#[cfg(feature = "mast")]
+pub use gaia::GaiaStar;
+#[cfg(feature = "mast")]
+pub use header_scan::FitsHeaderSummary;
+pub use health::{HealthReport, UpstreamHealth};
+#[cfg(feature = "mast")]
+pub use heasarc::HeasarcArchive;
+#[cfg(feature = "history")]
+pub use history::{ApodHistory, HistoryEntry};
+#[cfg(feature = "mast")]
+pub use horizons::EphemerisPoint;
+#[cfg(feature = "http-cache")]
+pub use http_cache::HttpCacheConfig;
+#[cfg(feature = "apod")]
+pub use ical::apod_calendar;
+#[cfg(feature = "apod")]
+pub use iotd::NasaIotdProvider;
+#[cfg(feature = "mast")]
+pub use irsa::IrsaArchive;
+pub use iss::{predict_passes, IssPass, IssPosition, Observer, Tle};
+#[cfg(feature = "mast")]
+pub use jwst::{JwstArchive, JwstInstrument};
+#[cfg(feature = "mast")]
+pub use kepler::{extract_kepler_id, KeplerId, KeplerMission, KeplerProducts};
+pub use library::{Library, LibraryEntry, LibraryKind};
+pub use limiter::RateLimit;
+#[cfg(feature = "apod")]
+pub use mars::{MarsPhoto, Rover, RoverDate};
+#[cfg(feature = "mast")]
+pub use mast::{DataProductType, FitsFileFilter, MastArchive, MastProduct};
+pub use metadata::ImageMetadata;
+pub use metrics::ErrorCategory;
+#[cfg(feature = "metrics")]
+pub use metrics::{LatencyHistogram, MetricsSnapshot, UpstreamMetrics};
+#[cfg(feature = "mirror")]
+pub use mirror::{MirrorOptions, MirrorReport};
+pub use mjd::{datetime_to_mjd, mjd_to_datetime};
+pub use moon::{moon_phase, moon_phase_at, MoonPhase, MoonPhaseName};
+#[cfg(feature = "mast")]
+pub use mpc::{extract_designation, OrbitalElements, SmallBody};
+#[cfg(feature = "mast")]
+pub use ned::NedObject;
+#[cfg(feature = "apod")]
+pub use neo::CloseApproach;
+pub use observability::{observability, ObservabilityReport, SkyPosition};
+#[cfg(feature = "mast")]
+pub use panstarrs::{Ps1Bands, Ps1Filter};
+#[cfg(feature = "apod")]
+pub use potw::{EsaHubblePotwProvider, EsaWebbPotmProvider, EsoPotwProvider};
+#[cfg(feature = "mast")]
+pub use product_name::ProductName;
+pub use retry::RetryPolicy;
+#[cfg(feature = "search")]
+pub use search::ApodIndex;
+#[cfg(feature = "mast")]
+pub use simbad::TargetInfo;
+#[cfg(feature = "apod")]
+pub use snapshot::{CacheSnapshot, SnapshotFormat};
+#[cfg(feature = "stream")]
+pub use stream::ByteStream;
+pub use sun::{sun_times, SunTimes};
+#[cfg(feature = "mast")]
+pub use tess::{LightCurve, TessLightCurves};
+#[cfg(feature = "mast")]
+pub use vizier::{VizierCatalog, VizierRow};
+#[cfg(feature = "webhook")]
+pub use webhook::{WebhookNotifier, WebhookPayload, SIGNATURE_HEADER};
+
+#[cfg(feature = "apod")]
+use api_keys::ApiKeys;
+#[cfg(feature = "apod")]
+use apod::{nasa_api_keys, CachedApod};
+use breaker::CircuitBreakers;
+#[cfg(feature = "apod")]
+use daily::CachedDailyImage;
+#[cfg(feature = "apod")]
+use epic::CachedEpic;
+#[cfg(feature = "mast")]
+use fits_cache::FitsCache;
+use flight::SingleFlight;
+#[cfg(any(feature = "webp", feature = "avif"))]
+use imaging::TranscodeOptions;
+use limiter::RateLimiters;
+use metrics::Metrics;
+#[cfg(feature = "apod")]
+use redact::register_secrets;
+use redact::{redact, redact_headers};
+use runtime::sleep;
+
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
-use tracing::instrument;
+#[cfg(feature = "apod")]
+use tokio::sync::{broadcast, watch};
 
-use uom::si::angle::degree;
+use tracing::field::Empty;
+use tracing::{debug, info_span, instrument, Instrument};
 
-use std::env;
+use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
+use std::sync::Arc;
+#[cfg(feature = "apod")]
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "ffi")]
+uniffi::setup_scaffolding!();
+
+/// The `User-Agent` sent with requests unless one is configured.
+const DEFAULT_USER_AGENT: &str = concat!("earendel/", env!("CARGO_PKG_VERSION"));
+
+/// The timeouts applied to requests unless others are configured.
+const DEFAULT_TIMEOUTS: Timeouts = Timeouts {
+    connect: Duration::from_secs(10),
+    total: Duration::from_secs(60),
+};
+/// The timeouts applied to `EarendelServer::download` unless others are configured, allowing for large FITS products.
+const DOWNLOAD_TIMEOUTS: Timeouts = Timeouts {
+    connect: Duration::from_secs(10),
+    total: Duration::from_secs(30 * 60),
+};
+/// The rate of MAST requests unless another is configured, as MAST asks clients to keep their request rates modest.
+const MAST_RATE_LIMIT: RateLimit = RateLimit {
+    requests_per_second: 2.0,
+    burst: 5,
+};
+/// The number of characters of response bodies logged when debugging requests.
+const DEBUG_BODY_CHARS: usize = 2048;
+
+/// The client-side timeouts of requests to an upstream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Timeouts {
+    /// The maximum time to establish a connection.
+    pub connect: Duration,
+    /// The maximum time for a whole request, from connecting until the response body has been read.
+    pub total: Duration,
+}
 
-/// Information used to display the APOD.
-#[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct EarendelApod {
-    /// The title of the APOD.
-    pub title: String,
-    /// The binary representation of the image.
-    pub img: Vec<u8>,
-    /// The copyright string.
-    pub copyright: Option<String>,
+impl Default for Timeouts {
+    fn default() -> Self {
+        DEFAULT_TIMEOUTS
+    }
 }
 
-/// Information used to display FITS files available for the APOD.
-#[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct EarendelFits {
-    /// The names of the FITS files for the current page.
-    pub files: Vec<String>,
-    /// The current page number.
-    pub page: usize,
-    /// The total number of available FITS files.
-    pub total_hits: usize,
+/// The upstream services contacted by Earendel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[non_exhaustive]
+pub enum Upstream {
+    /// The NASA APOD API.
+    Apod,
+    /// The APOD website, which serves the APOD pages and images.
+    ApodWebsite,
+    /// The translation mirror of the APOD website configured with `EarendelServerBuilder::locale`.
+    ApodMirror,
+    /// The astronomical object name resolver.
+    Resolver,
+    /// The MAST archive API.
+    Mast,
+    /// The CDS hips2fits cutout service.
+    Hips2Fits,
+    /// The ESA Hubble Science Archive.
+    Ehst,
+    /// The ESO Science Archive.
+    Eso,
+    /// The SDSS SkyServer.
+    Sdss,
+    /// The Gaia archive.
+    Gaia,
+    /// The CDS VizieR catalog service.
+    Vizier,
+    /// The CDS SIMBAD database.
+    Simbad,
+    /// The NASA/IPAC Extragalactic Database.
+    Ned,
+    /// The ALeRCE alert broker API.
+    Alerce,
+    /// The Pan-STARRS1 image server.
+    PanStarrs,
+    /// The STScI Digitized Sky Survey server.
+    Dss,
+    /// The servers hosting HiPS surveys.
+    Hips,
+    /// The JPL Horizons ephemeris system.
+    Horizons,
+    /// The Minor Planet Center API.
+    Mpc,
+    /// The ISS position API.
+    Iss,
+    /// The NASA EPIC API and archive.
+    Epic,
+    /// The NASA Mars Rover Photos API and the hosts serving its images.
+    MarsPhotos,
+    /// The NASA NeoWs API.
+    Neo,
+    /// The NASA DONKI space-weather API.
+    Donki,
+    /// The NASA Exoplanet Archive.
+    Exoplanet,
+    /// The HEASARC archive.
+    Heasarc,
+    /// The NASA/IPAC Infrared Science Archive.
+    Irsa,
+    /// The nova.astrometry.net plate-solving API.
+    Astrometry,
+    /// The ESO Picture of the Week feed and the host serving its images.
+    EsoPotw,
+    /// The ESA/Hubble picture feeds and the host serving their images.
+    EsaHubble,
+    /// The ESA/Webb picture feeds and the host serving their images.
+    EsaWebb,
+    /// The NASA Image of the Day feed and the host serving its images.
+    NasaIotd,
+    /// Any other host, downloaded from on request.
+    Download,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-struct Apod {
-    id: Option<u32>,
-    copyright: Option<String>,
-    date: String,
-    explanation: Option<String>,
-    hdurl: Option<String>,
-    media_type: String,
-    service_version: Option<String>,
-    title: String,
-    url: Option<String>,
+/// The manager of the Earendel functionality and state.
+pub struct EarendelServer {
+    #[cfg(feature = "apod")]
+    cached_state: Mutex<Option<CachedApod>>,
+    #[cfg(feature = "apod")]
+    rate_limit: Mutex<Option<RateLimitStatus>>,
+    #[cfg(feature = "apod")]
+    api_keys: ApiKeys,
+    #[cfg(feature = "apod")]
+    cached_epic: Mutex<Option<CachedEpic>>,
+    #[cfg(feature = "apod")]
+    prefer_hd: bool,
+    #[cfg(feature = "apod")]
+    page_details: bool,
+    #[cfg(feature = "apod")]
+    concept_tags: bool,
+    #[cfg(feature = "apod")]
+    locale: Option<ApodMirror>,
+    #[cfg(feature = "apod")]
+    cache_backend: Option<Arc<dyn CacheBackend>>,
+    #[cfg(feature = "apod")]
+    apod_published: broadcast::Sender<Arc<EarendelApod>>,
+    #[cfg(feature = "apod")]
+    apod_current: watch::Sender<Option<Arc<EarendelApod>>>,
+    #[cfg(feature = "apod")]
+    daily_providers: BTreeMap<String, Arc<dyn DailyImageProvider>>,
+    #[cfg(feature = "apod")]
+    daily_images: Mutex<HashMap<String, CachedDailyImage>>,
+    #[cfg(feature = "history")]
+    history: Option<ApodHistory>,
+    #[cfg(any(feature = "webp", feature = "avif"))]
+    transcode: Option<TranscodeOptions>,
+    max_download_size: Option<u64>,
+    #[cfg(feature = "http-cache")]
+    http_cache: Option<HttpCacheConfig>,
+    #[cfg(feature = "mast")]
+    fits_cache: Option<FitsCache>,
+    client: reqwest::Client,
+    upstream_clients: HashMap<Upstream, reqwest::Client>,
+    base_urls: HashMap<Upstream, reqwest::Url>,
+    retry_policy: RetryPolicy,
+    rate_limiters: RateLimiters,
+    breakers: Arc<CircuitBreakers>,
+    downloads: SingleFlight<Vec<u8>>,
+    #[cfg(feature = "mast")]
+    product_lists: SingleFlight<Vec<MastProduct>>,
+    metrics: Arc<Metrics>,
+    debug_requests: bool,
 }
 
-#[derive(Debug, Serialize)]
-struct MastRequestParams {
-    ra: f64,
-    dec: f64,
-    radius: f64,
+/// A builder for an EarendelServer with non-default configuration.
+#[derive(Default)]
+pub struct EarendelServerBuilder {
+    #[cfg(feature = "apod")]
+    prefer_hd: bool,
+    #[cfg(feature = "apod")]
+    page_details: bool,
+    #[cfg(feature = "apod")]
+    concept_tags: bool,
+    #[cfg(feature = "apod")]
+    locale: Option<ApodMirror>,
+    #[cfg(feature = "apod")]
+    cache_backend: Option<Arc<dyn CacheBackend>>,
+    #[cfg(feature = "apod")]
+    daily_providers: Vec<Arc<dyn DailyImageProvider>>,
+    #[cfg(feature = "apod")]
+    nasa_api_keys: Option<Vec<String>>,
+    #[cfg(feature = "apod")]
+    key_rotation: KeyRotation,
+    #[cfg(feature = "history")]
+    history: Option<ApodHistory>,
+    #[cfg(any(feature = "webp", feature = "avif"))]
+    transcode: Option<TranscodeOptions>,
+    max_download_size: Option<u64>,
+    #[cfg(feature = "http-cache")]
+    http_cache: Option<HttpCacheConfig>,
+    #[cfg(feature = "mast")]
+    fits_cache: Option<FitsCacheConfig>,
+    proxies: Vec<reqwest::Proxy>,
+    no_proxy: bool,
+    user_agent: Option<String>,
+    timeouts: Option<Timeouts>,
+    upstream_timeouts: HashMap<Upstream, Timeouts>,
+    base_urls: HashMap<Upstream, reqwest::Url>,
+    retry_policy: Option<RetryPolicy>,
+    rate_limits: HashMap<Upstream, RateLimit>,
+    circuit_breaker: Option<CircuitBreakerConfig>,
+    debug_requests: bool,
 }
 
-impl From<Icrs> for MastRequestParams {
-    fn from(value: Icrs) -> Self {
-        MastRequestParams {
-            ra: value.coords.ra.get::<degree>(),
-            dec: value.coords.dec.get::<degree>(),
-            radius: 0.2,
-        }
+impl EarendelServerBuilder {
+    /// Downloads the high-resolution APOD image when one is available, instead of the standard-resolution image.
+    #[cfg(feature = "apod")]
+    pub fn prefer_hd(mut self, prefer_hd: bool) -> Self {
+        self.prefer_hd = prefer_hd;
+        self
     }
-}
 
-#[derive(Debug, Serialize)]
-struct MastRequest {
-    service: String,
-    params: MastRequestParams,
-    format: String,
-    pagesize: usize,
-    page: usize,
-    removenullcolumns: bool,
-    timeout: u32,
-}
+    /// Fetches the APOD web page along with each APOD, to extract the full credit line and the links omitted by the
+    /// NASA API into `EarendelApod::page_details`.
+    #[cfg(feature = "apod")]
+    pub fn page_details(mut self, page_details: bool) -> Self {
+        self.page_details = page_details;
+        self
+    }
 
-impl MastRequest {
-    pub fn new(params: MastRequestParams, page: usize) -> Self {
-        MastRequest {
-            service: String::from("Mast.Caom.Cone"),
-            params,
-            format: String::from("json"),
-            pagesize: 25,
-            page,
-            removenullcolumns: true,
-            timeout: 30,
-        }
+    /// Requests the concept tags of each APOD from the NASA API, into `EarendelApod::concepts` and
+    /// `ApodMetadata::concepts`, for browsing and filtering APODs by subject.
+    #[cfg(feature = "apod")]
+    pub fn concept_tags(mut self, concept_tags: bool) -> Self {
+        self.concept_tags = concept_tags;
+        self
     }
 
-    pub fn to_urlencoded(&self) -> String {
-        let result = serde_json::to_string(self).unwrap();
+    /// Fetches the title and explanation of each APOD from the given translation mirror, falling back to English when
+    /// the mirror has not translated the APOD or cannot be reached.
+    #[cfg(feature = "apod")]
+    pub fn locale(mut self, mirror: ApodMirror) -> Self {
+        self.locale = Some(mirror);
+        self
+    }
 
-        urlencoding::encode(&result).into_owned()
+    /// Shares fetched APODs with other servers through the given cache backend, so that only one server of a
+    /// deployment fetches each APOD. The APOD is still cached in memory by each server.
+    #[cfg(feature = "apod")]
+    pub fn cache_backend<B: CacheBackend + 'static>(mut self, backend: B) -> Self {
+        self.cache_backend = Some(Arc::new(backend));
+        self
     }
-}
 
-#[derive(Debug, Deserialize)]
-struct MastResponse {
-    status: String,
-    msg: String,
-    data: Vec<MastResponseEntry>,
-    paging: MastResponsePaging,
-}
+    /// Registers the given source of daily images, served by `EarendelServer::get_daily_image` under its name. A source
+    /// with the same name as a previously registered source, including the built-in `apod`, `eso-potw`,
+    /// `esa-hubble-potw`, `esa-webb-potm`, and `nasa-iotd` sources, replaces it.
+    #[cfg(feature = "apod")]
+    pub fn daily_image_provider<P: DailyImageProvider + 'static>(mut self, provider: P) -> Self {
+        self.daily_providers.push(Arc::new(provider));
+        self
+    }
 
-#[derive(Debug, Deserialize)]
-struct MastResponseEntry {
-    #[serde(rename = "intentType")]
-    intent_type: Option<String>,
-    obs_collection: Option<String>,
-    provenance_name: Option<String>,
-    instrument_name: Option<String>,
-    project: Option<String>,
-    filters: Option<String>,
-    wavelength_region: Option<String>,
-    target_name: Option<String>,
-    target_classification: Option<String>,
-    obs_id: Option<String>,
-    s_ra: Option<f64>,
-    s_dec: Option<f64>,
-    dataproduct_type: Option<String>,
-    proposal_pi: Option<String>,
-    calib_level: Option<i64>,
-    t_min: Option<f64>,
-    t_max: Option<f64>,
-    t_exptime: Option<f64>,
-    em_min: Option<f64>,
-    em_max: Option<f64>,
-    obs_title: Option<String>,
-    t_obs_release: Option<f64>,
-    proposal_id: Option<String>,
-    proposal_type: Option<String>,
-    sequence_number: Option<i64>,
-    s_region: Option<String>,
-    #[serde(rename = "jpegURL")]
-    jpeg_url: Option<String>,
-    #[serde(rename = "dataURL")]
-    data_url: Option<String>,
-    #[serde(rename = "dataRights")]
-    data_rights: Option<String>,
-    #[serde(rename = "mtFlag")]
-    mt_flag: Option<bool>,
-    #[serde(rename = "srcDen")]
-    src_den: Option<f64>,
-    distance: Option<f64>,
-    #[serde(rename = "_selected_")]
-    selected: Option<bool>,
-}
+    /// Authenticates NASA API requests with the given keys, chosen according to the given rotation, instead of the
+    /// keys in the `EARENDEL_APOD_API_KEY` environment variable. Keys that are rate limited are skipped until their
+    /// hourly limit resets, unless every key is rate limited, so that busy deployments stay within the limit of each
+    /// key.
+    #[cfg(feature = "apod")]
+    pub fn nasa_api_keys(mut self, keys: Vec<String>, rotation: KeyRotation) -> Self {
+        self.nasa_api_keys = Some(keys);
+        self.key_rotation = rotation;
+        self
+    }
+
+    /// Records every fetched APOD in the given history.
+    #[cfg(feature = "history")]
+    pub fn history(mut self, history: ApodHistory) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    /// Transcodes downloaded APOD images with the given options before they are cached and returned.
+    #[cfg(any(feature = "webp", feature = "avif"))]
+    pub fn transcode(mut self, options: TranscodeOptions) -> Self {
+        self.transcode = Some(options);
+        self
+    }
+
+    /// Aborts image downloads whose bodies exceed the given number of bytes. When a high-resolution APOD image is too
+    /// large, the standard-resolution image is downloaded instead.
+    pub fn max_download_size(mut self, bytes: u64) -> Self {
+        self.max_download_size = Some(bytes);
+        self
+    }
+
+    /// Caches upstream responses on disk according to the given configuration, so that repeated queries and downloads
+    /// are answered from the cache while fresh. Only GET requests and MAST and NED queries are cached.
+    #[cfg(feature = "http-cache")]
+    pub fn http_cache(mut self, config: HttpCacheConfig) -> Self {
+        self.http_cache = Some(config);
+        self
+    }
+
+    /// Caches downloaded FITS products on disk according to the given configuration, so that
+    /// `EarendelServer::download_cached` reuses files downloaded before, even by an earlier run.
+    #[cfg(feature = "mast")]
+    pub fn fits_cache(mut self, config: FitsCacheConfig) -> Self {
+        self.fits_cache = Some(config);
+        self
+    }
+
+    /// Sends requests through the given proxy. May be called more than once, in which case the first proxy that
+    /// intercepts a request is used. Without any proxies, the `HTTP_PROXY`, `HTTPS_PROXY`, and `ALL_PROXY` environment
+    /// variables are honored. SOCKS proxies require the `socks` feature.
+    pub fn proxy(mut self, proxy: reqwest::Proxy) -> Self {
+        self.proxies.push(proxy);
+        self
+    }
+
+    /// Ignores the proxy environment variables, connecting directly unless a proxy is given with `proxy`.
+    pub fn no_proxy(mut self) -> Self {
+        self.no_proxy = true;
+        self
+    }
+
+    /// Identifies requests with the given `User-Agent`, such as one including contact details requested by an
+    /// archive, instead of the default `earendel/<version>`.
+    pub fn user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = Some(user_agent.to_owned());
+        self
+    }
+
+    /// Applies the given timeouts to requests to every upstream without its own timeouts, instead of a 10 second
+    /// connect timeout and a 60 second total timeout.
+    pub fn timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = Some(timeouts);
+        self
+    }
+
+    /// Applies the given timeouts to requests to the given upstream. Downloads with `EarendelServer::download` default
+    /// to a total timeout of 30 minutes.
+    pub fn upstream_timeouts(mut self, upstream: Upstream, timeouts: Timeouts) -> Self {
+        self.upstream_timeouts.insert(upstream, timeouts);
+        self
+    }
+
+    /// Sends the requests to the given upstream to the given base URL instead, such as an API gateway or a mock server
+    /// in tests. The path and query of each request are kept, following the path of the base URL, so that
+    /// `http://localhost:8080` receives MAST queries at `http://localhost:8080/api/v0/invoke`. The base URL of
+    /// `Upstream::Resolver` must serve the CDS Sesame protocol.
+    pub fn upstream_base_url(mut self, upstream: Upstream, base_url: reqwest::Url) -> Self {
+        self.base_urls.insert(upstream, base_url);
+        self
+    }
+
+    /// Retries failed requests according to the given policy, instead of the default `RetryPolicy`.
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Limits the rate of requests to the given upstream, including retries. MAST requests default to 2 per second,
+    /// with bursts of 5; other upstreams are not limited unless configured.
+    pub fn upstream_rate_limit(mut self, upstream: Upstream, limit: RateLimit) -> Self {
+        self.rate_limits.insert(upstream, limit);
+        self
+    }
+
+    /// Opens the circuit breaker of each upstream according to the given configuration, instead of the default
+    /// `CircuitBreakerConfig`.
+    pub fn circuit_breaker(mut self, config: CircuitBreakerConfig) -> Self {
+        self.circuit_breaker = Some(config);
+        self
+    }
+
+    /// Logs the URL and headers of every request and response, and the start of every parsed response body, at the
+    /// DEBUG level, for troubleshooting upstream queries. API keys and credentials are redacted.
+    pub fn debug_requests(mut self, debug_requests: bool) -> Self {
+        self.debug_requests = debug_requests;
+        self
+    }
+
+    /// Creates the configured EarendelServer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the HTTP client cannot be initialized, as with `reqwest::Client::new`.
+    pub fn build(self) -> EarendelServer {
+        let user_agent = self
+            .user_agent
+            .unwrap_or_else(|| String::from(DEFAULT_USER_AGENT));
+        let client = |timeouts: Timeouts| {
+            let mut client = reqwest::Client::builder()
+                .user_agent(user_agent.as_str())
+                .connect_timeout(timeouts.connect)
+                .timeout(timeouts.total);
+            if self.no_proxy {
+                client = client.no_proxy();
+            }
+            for proxy in self.proxies.iter() {
+                client = client.proxy(proxy.clone());
+            }
+
+            client
+                .build()
+                .expect("failed to initialize the HTTP client")
+        };
+        let mut upstream_timeouts = self.upstream_timeouts;
+        upstream_timeouts
+            .entry(Upstream::Download)
+            .or_insert(DOWNLOAD_TIMEOUTS);
+        let upstream_clients = upstream_timeouts
+            .into_iter()
+            .map(|(upstream, timeouts)| (upstream, client(timeouts)))
+            .collect();
+        let mut rate_limits = self.rate_limits;
+        rate_limits.entry(Upstream::Mast).or_insert(MAST_RATE_LIMIT);
+        #[cfg(feature = "apod")]
+        let built_in_providers: [Arc<dyn DailyImageProvider>; 5] = [
+            Arc::new(ApodProvider),
+            Arc::new(EsoPotwProvider),
+            Arc::new(EsaHubblePotwProvider),
+            Arc::new(EsaWebbPotmProvider),
+            Arc::new(NasaIotdProvider),
+        ];
+        #[cfg(feature = "apod")]
+        let nasa_api_keys = self.nasa_api_keys.unwrap_or_else(nasa_api_keys);
+        // keys given in code are not in the environment, where `redact` finds the others
+        #[cfg(feature = "apod")]
+        register_secrets(&nasa_api_keys);
+
+        EarendelServer {
+            #[cfg(feature = "apod")]
+            cached_state: Mutex::new(None),
+            #[cfg(feature = "apod")]
+            rate_limit: Mutex::new(None),
+            #[cfg(feature = "apod")]
+            api_keys: ApiKeys::new(nasa_api_keys, self.key_rotation),
+            #[cfg(feature = "apod")]
+            cached_epic: Mutex::new(None),
+            #[cfg(feature = "apod")]
+            prefer_hd: self.prefer_hd,
+            #[cfg(feature = "apod")]
+            page_details: self.page_details,
+            #[cfg(feature = "apod")]
+            concept_tags: self.concept_tags,
+            #[cfg(feature = "apod")]
+            locale: self.locale,
+            #[cfg(feature = "apod")]
+            cache_backend: self.cache_backend,
+            #[cfg(feature = "apod")]
+            apod_published: broadcast::channel(apod::PUBLISH_CAPACITY).0,
+            #[cfg(feature = "apod")]
+            apod_current: watch::channel(None).0,
+            #[cfg(feature = "apod")]
+            daily_providers: built_in_providers
+                .into_iter()
+                .chain(self.daily_providers)
+                .map(|provider| (provider.name().to_owned(), provider))
+                .collect(),
+            #[cfg(feature = "apod")]
+            daily_images: Mutex::new(HashMap::new()),
+            #[cfg(feature = "history")]
+            history: self.history,
+            #[cfg(any(feature = "webp", feature = "avif"))]
+            transcode: self.transcode,
+            max_download_size: self.max_download_size,
+            #[cfg(feature = "http-cache")]
+            http_cache: self.http_cache,
+            #[cfg(feature = "mast")]
+            fits_cache: self.fits_cache.map(FitsCache::open),
+            client: client(self.timeouts.unwrap_or_default()),
+            upstream_clients,
+            base_urls: self.base_urls,
+            retry_policy: self.retry_policy.unwrap_or_default(),
+            rate_limiters: RateLimiters::new(rate_limits),
+            breakers: Arc::new(CircuitBreakers::new(
+                self.circuit_breaker.unwrap_or_default(),
+            )),
+            downloads: SingleFlight::default(),
+            #[cfg(feature = "mast")]
+            product_lists: SingleFlight::default(),
+            metrics: Arc::default(),
+            debug_requests: self.debug_requests,
+        }
+    }
 
-#[derive(Debug, Deserialize)]
-struct MastResponsePaging {
-    page: usize,
-    #[serde(rename = "pageSize")]
-    page_size: usize,
-    #[serde(rename = "pagesFiltered")]
-    pages_filtered: usize,
-    rows: usize,
-    #[serde(rename = "rowsFiltered")]
-    rows_filtered: usize,
-    #[serde(rename = "rowsTotal")]
-    rows_total: usize,
+    /// Creates the configured EarendelServer behind a cloneable `EarendelClient` handle.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the HTTP client cannot be initialized, as with `reqwest::Client::new`.
+    pub fn build_client(self) -> EarendelClient {
+        EarendelClient::new(self.build())
+    }
 }
 
-/// The manager of the Earendel functionality and state.
-#[derive(Default)]
-pub struct EarendelServer {
-    cached_state: Option<(NaiveDate, EarendelApod)>,
+impl Default for EarendelServer {
+    fn default() -> Self {
+        Self::builder().build()
+    }
 }
 
 impl EarendelServer {
@@ -178,96 +788,233 @@ impl EarendelServer {
         Self::default()
     }
 
-    /// Gets the current APOD image data. Returns an Error if the web request fails or if deserialization fails.
-    pub async fn get_apod_image(&mut self) -> Result<EarendelApod, Box<dyn Error>> {
-        let today = Utc::now().date_naive();
-        let new_state = match self.cached_state.as_ref() {
-            Some((date, apod)) if date == &today => apod.to_owned(),
-            Some(_) | None => Self::fetch_apod_image().await?,
-        };
-        self.cached_state = Some((today, new_state.to_owned()));
+    /// Creates a builder for an EarendelServer with non-default configuration.
+    pub fn builder() -> EarendelServerBuilder {
+        EarendelServerBuilder::default()
+    }
 
-        Ok(new_state)
+    /// Gets a snapshot of the metrics recorded by this server.
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.metrics.snapshot()
     }
 
-    async fn fetch_apod_image() -> Result<EarendelApod, Box<dyn Error>> {
-        let api_url = "https://api.nasa.gov/planetary/apod";
-        let api_key = env::var("EARENDEL_APOD_API_KEY")?;
-        let request_url = [api_url, "?api_key=", &api_key].concat();
+    /// Gets the state of the circuit breaker of each upstream contacted by this server. Upstreams that have not been
+    /// contacted are omitted.
+    pub fn circuit_states(&self) -> BTreeMap<Upstream, CircuitState> {
+        self.breakers.states()
+    }
 
-        let resp = reqwest::get(request_url).await?;
-        let body = resp.text().await?;
-        let apod = serde_json::from_str::<Apod>(&body)?;
+    /// Downloads the body of the given URL, such as the data URL of an observation, aborting if it exceeds the
+    /// configured maximum download size. Concurrent downloads of the same URL share one request. Returns an error if
+    /// the web request fails.
+    #[instrument(skip(self, url), fields(url = %redact(url)))]
+    pub async fn download(&self, url: &str) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        self.downloads
+            .run(url, async {
+                let resp = self
+                    .send(Upstream::Download, self.client.get(url))
+                    .await?
+                    .error_for_status()?;
+
+                self.read_limited(resp).await
+            })
+            .await
+    }
 
-        let resp = reqwest::get(apod.url.ok_or("APOD did not contain image URL")?).await?;
-        let img = resp.bytes().await?;
+    /// Sends the given request, retrying it according to the configured retry policy. Fails immediately with
+    /// `EarendelError::CircuitOpen` if the circuit breaker of the upstream is open.
+    async fn send(
+        &self,
+        upstream: Upstream,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, Box<dyn Error + Send + Sync>> {
+        let request = request.build()?;
+        #[cfg(feature = "http-cache")]
+        if let Some(config) = self.http_cache.as_ref() {
+            if let Some(key) = http_cache::cache_key(upstream, &request) {
+                if let Some(resp) = http_cache::lookup(config, &key).await {
+                    debug!("answered request to {:?} from the HTTP cache", upstream);
+                    return Ok(resp);
+                }
+                let resp = self.send_with_retries(upstream, request).await?;
+                return http_cache::store(self, config, &key, upstream, resp).await;
+            }
+        }
 
-        Ok(EarendelApod {
-            title: apod.title,
-            img: img.to_vec(),
-            copyright: apod.copyright,
-        })
+        self.send_with_retries(upstream, request).await
     }
 
-    /// Gets FITS files for the current APOD. Returns an error if the web request fails.
-    ///
-    /// ```
-    /// use earendel::*;
-    ///
-    /// # tokio_test::block_on(async {
-    /// let server = EarendelServer::new();
-    /// server.get_fits_for_apod().await.unwrap();
-    /// # });
-    /// ```
-    #[instrument(skip(self))]
-    pub async fn get_fits_for_apod(&mut self, page: usize) -> Result<EarendelFits, Box<dyn Error>> {
-        let apod = self.get_apod_image().await?;
-        // TODO: extract name from apod title
-        let name = "NGC 4632";
-        let api_url = "https://mast.stsci.edu/api/v0/invoke";
-
-        let coords = astro_rs::coordinates::lookup_by_name(name).await?;
-
-        let params = MastRequestParams::from(coords);
-        let request = MastRequest::new(params, page);
-        let encoded_request = ["request=", &request.to_urlencoded()].concat();
-
-        let client = reqwest::Client::new();
-
-        let mut headers = HeaderMap::new();
-        headers.insert(
-            CONTENT_TYPE,
-            "application/x-www-form-urlencoded".parse().unwrap(),
+    async fn send_with_retries(
+        &self,
+        upstream: Upstream,
+        mut request: reqwest::Request,
+    ) -> Result<reqwest::Response, Box<dyn Error + Send + Sync>> {
+        let start = Instant::now();
+        let mut attempt = 1;
+        loop {
+            // requests with streamed bodies cannot be replayed, so they are only attempted once
+            let replay = request.try_clone();
+            self.rate_limiters.acquire(upstream).await;
+            self.breakers.acquire(upstream, &self.metrics)?;
+            #[cfg(feature = "apod")]
+            let api_key = self.api_keys.assign(&mut request);
+            let result = self.send_once(upstream, request, attempt).await;
+            #[cfg(feature = "apod")]
+            if let (Some(index), Ok(resp)) = (api_key, &result) {
+                self.api_keys.record(index, resp);
+            }
+            // client errors are the fault of the request rather than the upstream
+            let success = result
+                .as_ref()
+                .is_ok_and(|resp| !resp.status().is_server_error());
+            self.breakers.record(upstream, success, &self.metrics);
+            let retryable = match &result {
+                Ok(resp) => self.retry_policy.retries_status(resp.status().as_u16()),
+                Err(e) => self.retry_policy.retries_error(ErrorCategory::of(e)),
+            };
+            let delay = self.retry_policy.delay(attempt, start.elapsed());
+            match (retryable, replay, delay) {
+                (true, Some(replay), Some(delay)) => {
+                    debug!(
+                        "retrying request to {:?} in {:?} after attempt {}",
+                        upstream, delay, attempt
+                    );
+                    self.metrics.record_retry(upstream);
+                    sleep(delay).await;
+                    request = replay;
+                    attempt += 1;
+                }
+                _ => return Ok(result?),
+            }
+        }
+    }
+
+    async fn send_once(
+        &self,
+        upstream: Upstream,
+        mut request: reqwest::Request,
+        attempt: u32,
+    ) -> reqwest::Result<reqwest::Response> {
+        if let Some(base_url) = self.base_urls.get(&upstream) {
+            *request.url_mut() = rebase_url(request.url(), base_url);
+        }
+        let span = info_span!(
+            "upstream_request",
+            upstream = ?upstream,
+            method = %request.method(),
+            host = request.url().host_str().unwrap_or_default(),
+            attempt,
+            status = Empty,
+            bytes = Empty,
+            elapsed_ms = Empty,
         );
-        headers.insert(ACCEPT, "text/plain".parse().unwrap());
-
-        let resp = client
-            .post(api_url)
-            .headers(headers)
-            .body(encoded_request)
-            .send()
-            .await?;
-        let body = resp.text().await?;
-        let mast = serde_json::from_str::<MastResponse>(&body)?;
-
-        let fits_files = mast
-            .data
-            .iter()
-            .filter_map(|entry| {
-                entry.data_url.as_ref().and_then(|file| {
-                    if file.contains("fits") {
-                        Some(file.to_owned())
-                    } else {
-                        None
-                    }
-                })
-            })
-            .collect::<Vec<String>>();
 
-        Ok(EarendelFits {
-            files: fits_files,
-            page,
-            total_hits: mast.paging.rows_total,
+        if self.debug_requests {
+            debug!(
+                "sending {} {} to {:?}\n{}",
+                request.method(),
+                redact(request.url().as_str()),
+                upstream,
+                redact_headers(request.headers())
+            );
+        }
+
+        let start = Instant::now();
+        let client = self.upstream_clients.get(&upstream).unwrap_or(&self.client);
+        let result = client.execute(request).instrument(span.clone()).await;
+        let elapsed = start.elapsed();
+        span.record("elapsed_ms", elapsed.as_millis() as u64);
+        self.metrics.record_request(upstream, elapsed);
+        match &result {
+            Ok(resp) => {
+                if self.debug_requests {
+                    debug!(
+                        "received {} from {:?} in {:?}\n{}",
+                        resp.status(),
+                        upstream,
+                        elapsed,
+                        redact_headers(resp.headers())
+                    );
+                }
+                span.record("status", resp.status().as_u16());
+                if let Some(bytes) = resp.content_length() {
+                    span.record("bytes", bytes);
+                }
+                if resp.status().is_client_error() || resp.status().is_server_error() {
+                    self.metrics.record_error(upstream, ErrorCategory::Status);
+                }
+            }
+            Err(e) => self.metrics.record_error(upstream, ErrorCategory::of(e)),
+        }
+
+        // reqwest reports the URL in its errors, which must not leak a credential passed in the query
+        result.map_err(|e| {
+            let url = e
+                .url()
+                .map(|url| reqwest::Url::parse(&redact(url.as_str())));
+            match url {
+                Some(Ok(url)) => e.with_url(url),
+                _ => e.without_url(),
+            }
         })
     }
+
+    /// Reads the body of the given response, aborting if it exceeds the configured maximum download size.
+    async fn read_limited(
+        &self,
+        mut resp: reqwest::Response,
+    ) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        let Some(limit) = self.max_download_size else {
+            return Ok(resp.bytes().await?.to_vec());
+        };
+        let too_large = |resp: &reqwest::Response| EarendelError::DownloadTooLarge {
+            url: resp.url().to_string(),
+            limit,
+        };
+        if resp.content_length().is_some_and(|length| length > limit) {
+            return Err(too_large(&resp).into());
+        }
+
+        let mut body = Vec::new();
+        while let Some(chunk) = resp.chunk().await? {
+            if (body.len() + chunk.len()) as u64 > limit {
+                return Err(too_large(&resp).into());
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        Ok(body)
+    }
+
+    #[cfg_attr(not(feature = "apod"), allow(dead_code))]
+    fn parse<T: DeserializeOwned>(
+        &self,
+        upstream: Upstream,
+        body: &str,
+    ) -> Result<T, Box<dyn Error + Send + Sync>> {
+        if self.debug_requests {
+            let truncated = body.chars().take(DEBUG_BODY_CHARS).collect::<String>();
+            debug!(
+                "parsing {} bytes from {:?}: {}",
+                body.len(),
+                upstream,
+                redact(&truncated)
+            );
+        }
+        serde_json::from_str::<T>(body).map_err(|e| {
+            self.metrics
+                .record_error(upstream, ErrorCategory::Deserialization);
+            e.into()
+        })
+    }
+}
+
+/// Moves the given URL under the given base URL, keeping its path, after the path of the base URL, and its query.
+pub(crate) fn rebase_url(url: &reqwest::Url, base_url: &reqwest::Url) -> reqwest::Url {
+    let mut rebased = base_url.clone();
+    rebased.set_path(&[base_url.path().trim_end_matches('/'), url.path()].concat());
+    rebased.set_query(url.query());
+
+    rebased
 }
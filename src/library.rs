@@ -0,0 +1,259 @@
+//! A local directory tree of saved APOD images and FITS products.
+
+use serde::{Deserialize, Serialize};
+
+use std::error::Error;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+#[cfg(feature = "apod")]
+use crate::metadata::content_type;
+#[cfg(feature = "apod")]
+use crate::EarendelApod;
+
+/// The directory holding APOD images, under the library root.
+const APOD_DIR: &str = "apod";
+/// The directory holding FITS products, under the library root.
+const FITS_DIR: &str = "fits";
+/// The maximum length of a file or directory name derived from a title or target.
+const MAX_SLUG_LEN: usize = 60;
+
+/// The kind of file saved in a library.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum LibraryKind {
+    /// An APOD image, saved under `apod/<year>/`.
+    Apod,
+    /// A FITS product, saved under `fits/<target>/`.
+    Fits,
+}
+
+/// A file saved in a library.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct LibraryEntry {
+    /// The kind of file.
+    pub kind: LibraryKind,
+    /// The path of the file.
+    pub path: PathBuf,
+    /// The size of the file, in bytes.
+    pub size: u64,
+    /// When the file was last modified.
+    pub modified: SystemTime,
+}
+
+/// A directory tree of saved files. APOD images are saved by date and FITS products by target. Saving a file whose
+/// contents are already in the library returns the existing path instead of writing a copy, and names already taken
+/// by different contents are given a numeric suffix.
+#[derive(Clone, Debug)]
+pub struct Library {
+    root: PathBuf,
+}
+
+impl Library {
+    /// Creates a library rooted at the given directory. The directory is created when the first file is saved.
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Library { root: root.into() }
+    }
+
+    /// Gets the root directory of the library.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Saves the image of the given APOD as `apod/<year>/<month>-<day>-<title>.<extension>`, returning its path.
+    #[cfg(feature = "apod")]
+    pub fn save_apod(&self, apod: &EarendelApod) -> Result<PathBuf, Box<dyn Error + Send + Sync>> {
+        let extension = match content_type(apod.img()) {
+            "image/png" => "png",
+            "image/gif" => "gif",
+            "image/webp" => "webp",
+            "image/avif" => "avif",
+            "image/jpeg" => "jpg",
+            _ => "bin",
+        };
+        let dir = self
+            .root
+            .join(APOD_DIR)
+            .join(apod.date.format("%Y").to_string());
+        let name = format!(
+            "{}-{}.{}",
+            apod.date.format("%m-%d"),
+            slug(&apod.title),
+            extension
+        );
+
+        self.save(LibraryKind::Apod, &dir, &name, apod.img())
+    }
+
+    /// Saves the given FITS product of the given target as `fits/<target>/<name>`, returning its path.
+    pub fn save_fits(
+        &self,
+        target: &str,
+        name: &str,
+        bytes: &[u8],
+    ) -> Result<PathBuf, Box<dyn Error + Send + Sync>> {
+        let dir = self.root.join(FITS_DIR).join(slug(target));
+        // only the final component of the name is kept, so that it cannot escape the target directory
+        let name = Path::new(name)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or("FITS product name is not a valid file name")?;
+
+        self.save(LibraryKind::Fits, &dir, name, bytes)
+    }
+
+    fn save(
+        &self,
+        kind: LibraryKind,
+        dir: &Path,
+        name: &str,
+        bytes: &[u8],
+    ) -> Result<PathBuf, Box<dyn Error + Send + Sync>> {
+        let hash = content_hash(bytes);
+        for entry in self
+            .entries()?
+            .into_iter()
+            .filter(|entry| entry.kind == kind && entry.size == bytes.len() as u64)
+        {
+            let existing = fs::read(&entry.path)?;
+            if content_hash(&existing) == hash && existing == bytes {
+                return Ok(entry.path);
+            }
+        }
+
+        fs::create_dir_all(dir)?;
+        let (stem, extension) = match name.rsplit_once('.') {
+            Some((stem, extension)) if !stem.is_empty() => (stem, Some(extension)),
+            _ => (name, None),
+        };
+        let mut path = dir.join(name);
+        let mut suffix = 1;
+        while path.exists() {
+            let candidate = match extension {
+                Some(extension) => format!("{}-{}.{}", stem, suffix, extension),
+                None => format!("{}-{}", stem, suffix),
+            };
+            path = dir.join(candidate);
+            suffix += 1;
+        }
+        fs::write(&path, bytes)?;
+
+        Ok(path)
+    }
+
+    /// Lists every file in the library, oldest first.
+    pub fn entries(&self) -> Result<Vec<LibraryEntry>, Box<dyn Error + Send + Sync>> {
+        let mut entries = Vec::new();
+        for (kind, dir) in [(LibraryKind::Apod, APOD_DIR), (LibraryKind::Fits, FITS_DIR)] {
+            for group in read_dir(&self.root.join(dir))? {
+                for file in read_dir(&group)? {
+                    let metadata = fs::metadata(&file)?;
+                    if metadata.is_file() {
+                        entries.push(LibraryEntry {
+                            kind,
+                            path: file,
+                            size: metadata.len(),
+                            modified: metadata.modified()?,
+                        });
+                    }
+                }
+            }
+        }
+        entries.sort_by_key(|entry| entry.modified);
+
+        Ok(entries)
+    }
+
+    /// Removes the files last modified longer ago than the given age, returning their paths.
+    pub fn prune_older_than(
+        &self,
+        age: Duration,
+    ) -> Result<Vec<PathBuf>, Box<dyn Error + Send + Sync>> {
+        let cutoff = SystemTime::now()
+            .checked_sub(age)
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        let expired = self
+            .entries()?
+            .into_iter()
+            .filter(|entry| entry.modified < cutoff)
+            .collect::<Vec<LibraryEntry>>();
+
+        remove(expired)
+    }
+
+    /// Removes the oldest files until the library holds at most the given number of bytes, returning their paths.
+    pub fn prune_to_size(
+        &self,
+        max_bytes: u64,
+    ) -> Result<Vec<PathBuf>, Box<dyn Error + Send + Sync>> {
+        let entries = self.entries()?;
+        let mut total = entries.iter().map(|entry| entry.size).sum::<u64>();
+        let excess = entries
+            .into_iter()
+            .take_while(|entry| {
+                let over = total > max_bytes;
+                total = total.saturating_sub(entry.size);
+                over
+            })
+            .collect::<Vec<LibraryEntry>>();
+
+        remove(excess)
+    }
+}
+
+fn remove(entries: Vec<LibraryEntry>) -> Result<Vec<PathBuf>, Box<dyn Error + Send + Sync>> {
+    let mut removed = Vec::with_capacity(entries.len());
+    for entry in entries {
+        fs::remove_file(&entry.path)?;
+        if let Some(parent) = entry.path.parent() {
+            // the directory of a date or target is only removed once it is empty
+            let _ = fs::remove_dir(parent);
+        }
+        removed.push(entry.path);
+    }
+
+    Ok(removed)
+}
+
+/// Lists the paths in the given directory, or nothing if it does not exist.
+fn read_dir(dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error + Send + Sync>> {
+    match fs::read_dir(dir) {
+        Ok(entries) => Ok(entries
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<PathBuf>, _>>()?),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Converts the given text to a lowercase name of letters, digits, and hyphens.
+fn slug(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug
+        .chars()
+        .take(MAX_SLUG_LEN)
+        .collect::<String>()
+        .trim_end_matches('-')
+        .to_owned();
+
+    if slug.is_empty() {
+        String::from("untitled")
+    } else {
+        slug
+    }
+}
+
+/// Computes the 64-bit FNV-1a hash of the given bytes, which is stable across builds.
+fn content_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
@@ -0,0 +1,92 @@
+//! Client-side rate limiting of upstream requests, so that bulk operations stay within the request rates asked of
+//! clients by the archives.
+
+use serde::{Deserialize, Serialize};
+
+use tracing::debug;
+
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::runtime::sleep;
+use crate::Upstream;
+
+/// The rate of requests to an upstream, enforced with a token bucket.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct RateLimit {
+    /// The sustained number of requests per second. An infinite rate lifts the limit.
+    pub requests_per_second: f64,
+    /// The number of requests that can be sent at once after a quiet period.
+    pub burst: u32,
+}
+
+impl RateLimit {
+    /// Creates a limit of the given number of requests per second, without bursts.
+    pub fn per_second(requests_per_second: f64) -> Self {
+        RateLimit {
+            requests_per_second,
+            burst: 1,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// The number of requests that can be sent immediately, which is negative while requests wait for their turn.
+    tokens: f64,
+    updated: Instant,
+}
+
+/// The token buckets of every rate-limited upstream of a server.
+#[derive(Debug)]
+pub(crate) struct RateLimiters {
+    limits: HashMap<Upstream, RateLimit>,
+    buckets: Mutex<HashMap<Upstream, Bucket>>,
+}
+
+impl RateLimiters {
+    pub(crate) fn new(limits: HashMap<Upstream, RateLimit>) -> Self {
+        RateLimiters {
+            limits,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<Upstream, Bucket>> {
+        self.buckets.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Waits until a request can be sent to the given upstream. Waiting requests are sent in the order they arrived.
+    pub(crate) async fn acquire(&self, upstream: Upstream) {
+        let Some(limit) = self.limits.get(&upstream) else {
+            return;
+        };
+        let burst = f64::from(limit.burst.max(1));
+        let wait = {
+            let mut buckets = self.lock();
+            let now = Instant::now();
+            let bucket = buckets.entry(upstream).or_insert(Bucket {
+                tokens: burst,
+                updated: now,
+            });
+            let refilled =
+                now.duration_since(bucket.updated).as_secs_f64() * limit.requests_per_second;
+            bucket.tokens = burst.min(bucket.tokens + refilled);
+            bucket.updated = now;
+            // the token is taken even if the bucket is empty, which reserves the next one for this request
+            bucket.tokens -= 1.0;
+            if bucket.tokens < 0.0 && limit.requests_per_second > 0.0 {
+                Duration::try_from_secs_f64(-bucket.tokens / limit.requests_per_second)
+                    .unwrap_or_default()
+            } else {
+                Duration::ZERO
+            }
+        };
+
+        if !wait.is_zero() {
+            debug!("rate limiting request to {:?} for {:?}", upstream, wait);
+            sleep(wait).await;
+        }
+    }
+}
@@ -0,0 +1,99 @@
+//! The earendel command-line interface.
+
+use chrono::NaiveDate;
+
+use clap::{Parser, Subcommand};
+
+use earendel::EarendelServer;
+
+use std::error::Error;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// Retrieves the Astronomy Picture of the Day and related astronomical data.
+#[derive(Parser)]
+#[command(name = "earendel", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Prints the title and copyright of the APOD, optionally saving its image.
+    Apod {
+        /// The date of the APOD, formatted as YYYY-MM-DD. Defaults to the current APOD.
+        #[arg(long)]
+        date: Option<NaiveDate>,
+        /// Downloads the high-resolution image when one is available.
+        #[arg(long)]
+        hd: bool,
+        /// The file to write the image to.
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Prints a page of FITS observations of the APOD target as JSON.
+    Fits {
+        /// The zero-based page number.
+        #[arg(long, default_value_t = 0)]
+        page: usize,
+        /// Requests the size of each listed FITS file.
+        #[arg(long)]
+        sizes: bool,
+        /// The largest number of size requests in flight at once.
+        #[arg(long, default_value_t = 4)]
+        max_concurrent: usize,
+    },
+    /// Downloads the given URL.
+    Download {
+        /// The URL to download.
+        url: String,
+        /// The file to write the body to. Defaults to standard output.
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Command::Apod { date, hd, out } => {
+            let server = EarendelServer::builder().prefer_hd(hd).build();
+            let apod = match date {
+                Some(date) => server.get_apod_image_for_date(date).await?,
+                None => server.get_apod_image().await?,
+            };
+            println!("{}", apod.title);
+            if let Some(copyright) = apod.copyright.as_ref() {
+                println!("© {}", copyright.trim());
+            }
+            if let Some(out) = out {
+                fs::write(out, apod.img())?;
+            }
+        }
+        Command::Fits {
+            page,
+            sizes,
+            max_concurrent,
+        } => {
+            let server = EarendelServer::new();
+            let mut fits = server.get_fits_for_apod(page).await?;
+            if sizes {
+                server.get_file_sizes(&mut fits, max_concurrent).await;
+            }
+            println!("{}", serde_json::to_string_pretty(&fits)?);
+        }
+        Command::Download { url, out } => {
+            let body = EarendelServer::new().download(&url).await?;
+            match out {
+                Some(out) => fs::write(out, &body)?,
+                None => io::stdout().write_all(&body)?,
+            }
+        }
+    }
+
+    Ok(())
+}
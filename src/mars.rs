@@ -0,0 +1,146 @@
+//! Retrieval of photos taken by the NASA Mars rovers.
+
+use chrono::NaiveDate;
+
+use serde::{Deserialize, Serialize};
+
+use tracing::instrument;
+
+use std::error::Error;
+
+use crate::{EarendelServer, Upstream};
+
+const MARS_PHOTOS_API_URL: &str = "https://api.nasa.gov/mars-photos/api/v1/rovers";
+
+/// A Mars rover whose photos are available.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum Rover {
+    /// The Curiosity rover.
+    Curiosity,
+    /// The Opportunity rover.
+    Opportunity,
+    /// The Spirit rover.
+    Spirit,
+    /// The Perseverance rover.
+    Perseverance,
+}
+
+impl Rover {
+    fn as_param(&self) -> &'static str {
+        match self {
+            Rover::Curiosity => "curiosity",
+            Rover::Opportunity => "opportunity",
+            Rover::Spirit => "spirit",
+            Rover::Perseverance => "perseverance",
+        }
+    }
+}
+
+/// The day on which rover photos were taken.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum RoverDate {
+    /// The Martian solar day, counted from the rover's landing.
+    Sol(u32),
+    /// The Earth date.
+    EarthDate(NaiveDate),
+}
+
+/// A photo taken by a Mars rover.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MarsPhoto {
+    /// The identifier of the photo.
+    pub id: u64,
+    /// The rover that took the photo.
+    pub rover: Rover,
+    /// The Martian solar day the photo was taken.
+    pub sol: u32,
+    /// The Earth date the photo was taken.
+    pub earth_date: NaiveDate,
+    /// The abbreviated name of the camera, such as FHAZ or NAVCAM.
+    pub camera: String,
+    /// The full name of the camera.
+    pub camera_full_name: String,
+    /// The URL of the image.
+    pub img_src: String,
+    /// The binary representation of the image.
+    #[serde(with = "serde_bytes")]
+    pub img: Vec<u8>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MarsPhotosResponse {
+    photos: Vec<MarsPhotoEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MarsPhotoEntry {
+    id: u64,
+    sol: u32,
+    earth_date: NaiveDate,
+    camera: MarsCamera,
+    img_src: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct MarsCamera {
+    name: String,
+    full_name: String,
+}
+
+impl EarendelServer {
+    /// Gets up to the given number of photos taken by the given rover on the given day, optionally restricted to the
+    /// camera with the given abbreviated name. Returns an error if a web request fails or if deserialization fails.
+    #[instrument(skip(self))]
+    pub async fn get_mars_rover_photos(
+        &self,
+        rover: Rover,
+        date: RoverDate,
+        camera: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<MarsPhoto>, Box<dyn Error + Send + Sync>> {
+        let mut query = Vec::new();
+        match date {
+            RoverDate::Sol(sol) => query.push((String::from("sol"), sol.to_string())),
+            RoverDate::EarthDate(earth_date) => {
+                query.push((String::from("earth_date"), earth_date.to_string()))
+            }
+        }
+        if let Some(camera) = camera {
+            query.push((String::from("camera"), camera.to_lowercase()));
+        }
+
+        let request_url = format!("{}/{}/photos", MARS_PHOTOS_API_URL, rover.as_param());
+        let request = self.nasa_api_get(request_url)?.query(&query);
+        let resp = self
+            .send(Upstream::MarsPhotos, request)
+            .await?
+            .error_for_status()?;
+        self.record_rate_limit(resp.headers());
+        let body = resp.text().await?;
+        let entries = self
+            .parse::<MarsPhotosResponse>(Upstream::MarsPhotos, &body)?
+            .photos;
+
+        let mut photos = Vec::new();
+        for entry in entries.into_iter().take(limit) {
+            // the API lists some image URLs over plain HTTP, which the image hosts redirect
+            let resp = self
+                .send(Upstream::MarsPhotos, self.client.get(&entry.img_src))
+                .await?
+                .error_for_status()?;
+
+            photos.push(MarsPhoto {
+                id: entry.id,
+                rover,
+                sol: entry.sol,
+                earth_date: entry.earth_date,
+                camera: entry.camera.name,
+                camera_full_name: entry.camera.full_name,
+                img_src: entry.img_src,
+                img: resp.bytes().await?.to_vec(),
+            });
+        }
+
+        Ok(photos)
+    }
+}
@@ -0,0 +1,806 @@
+//! Queries against the MAST archive for observations of the APOD target.
+
+use astro_rs::coordinates::Icrs;
+
+use async_trait::async_trait;
+
+use chrono::{DateTime, Days, NaiveDate, Utc};
+
+use reqwest::header::HeaderMap;
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use tracing::instrument;
+
+use uom::si::f64::Length;
+use uom::si::length::nanometer;
+
+use std::error::Error;
+
+use crate::archive::{EarendelFits, Observation, ObservationArchive, PAGE_SIZE, SEARCH_RADIUS_DEG};
+use crate::coords::icrs_to_degrees;
+use crate::mjd::{datetime_to_mjd, mjd_to_datetime};
+use crate::{EarendelError, EarendelServer, Upstream};
+
+#[derive(Debug, Serialize)]
+pub(crate) struct MastRequestParams {
+    ra: f64,
+    dec: f64,
+    radius: f64,
+}
+
+impl From<&Icrs> for MastRequestParams {
+    fn from(value: &Icrs) -> Self {
+        let (ra, dec) = icrs_to_degrees(value);
+        MastRequestParams {
+            ra,
+            dec,
+            radius: SEARCH_RADIUS_DEG,
+        }
+    }
+}
+
+/// The URL of the MAST API service invocation endpoint.
+const MAST_INVOKE_URL: &str = "https://mast.stsci.edu/api/v0/invoke";
+/// The URL of the MAST endpoint that downloads a product by its data URI.
+const MAST_DOWNLOAD_URL: &str = "https://mast.stsci.edu/api/v0.1/Download/file";
+
+/// The file name suffixes of HST drizzled products, which combine the exposures of an observation into one
+/// calibrated image corrected for distortion, without (`_drz`) and with (`_drc`) charge transfer efficiency correction.
+const DRIZZLED_SUFFIXES: [&str; 2] = ["_drz.fits", "_drc.fits"];
+
+/// The modified Julian date used for the lower bound of a time filter without one.
+const MIN_MJD: f64 = 0.0;
+/// The modified Julian date used for the upper bound of a time filter without one.
+const MAX_MJD: f64 = 100_000.0;
+
+#[derive(Debug, Serialize)]
+struct MastFilteredParams {
+    columns: String,
+    filters: Vec<MastFilter>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    position: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct MastFilter {
+    #[serde(rename = "paramName")]
+    pub(crate) param_name: String,
+    pub(crate) values: Vec<MastFilterValue>,
+    // matched against string columns, with `%` as a wildcard
+    #[serde(rename = "freeText", skip_serializing_if = "Option::is_none")]
+    pub(crate) free_text: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct MastRange {
+    pub(crate) min: f64,
+    pub(crate) max: f64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub(crate) enum MastFilterValue {
+    Range(MastRange),
+    // matched exactly against string columns
+    Exact(String),
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum MastParams {
+    Cone(MastRequestParams),
+    Filtered(MastFilteredParams),
+    Raw(serde_json::Value),
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct MastRequest {
+    service: String,
+    params: MastParams,
+    format: String,
+    pagesize: usize,
+    page: usize,
+    removenullcolumns: bool,
+    timeout: u32,
+}
+
+impl MastRequest {
+    /// Creates a cone search, which is filtered by MAST if any filters are given.
+    pub fn new(params: MastRequestParams, filters: Vec<MastFilter>, page: usize) -> Self {
+        if filters.is_empty() {
+            Self::with_params("Mast.Caom.Cone", MastParams::Cone(params), page)
+        } else {
+            Self::filtered("Mast.Caom.Filtered.Position", params, filters, page)
+        }
+    }
+
+    /// Creates a search of the given filtered service, such as `Mast.Caom.Filtered.Position`, around the given
+    /// position.
+    pub(crate) fn filtered(
+        service: &str,
+        params: MastRequestParams,
+        filters: Vec<MastFilter>,
+        page: usize,
+    ) -> Self {
+        let position = format!("{}, {}, {}", params.ra, params.dec, params.radius);
+        let params = MastFilteredParams {
+            columns: String::from("*"),
+            filters,
+            position: Some(position),
+        };
+
+        Self::with_params(service, MastParams::Filtered(params), page)
+    }
+
+    /// Creates a search of the given filtered service, such as `Mast.Caom.Filtered`, over the whole sky.
+    pub(crate) fn unpositioned(service: &str, filters: Vec<MastFilter>, page: usize) -> Self {
+        let params = MastFilteredParams {
+            columns: String::from("*"),
+            filters,
+            position: None,
+        };
+
+        Self::with_params(service, MastParams::Filtered(params), page)
+    }
+
+    /// Creates a request of the given service with the given parameters as is, for services not otherwise wrapped.
+    fn raw(service: &str, params: serde_json::Value, page: usize, pagesize: usize) -> Self {
+        MastRequest {
+            pagesize,
+            ..Self::with_params(service, MastParams::Raw(params), page)
+        }
+    }
+
+    fn with_params(service: &str, params: MastParams, page: usize) -> Self {
+        MastRequest {
+            service: String::from(service),
+            params,
+            format: String::from("json"),
+            pagesize: PAGE_SIZE,
+            page,
+            removenullcolumns: true,
+            timeout: 30,
+        }
+    }
+
+    pub fn to_urlencoded(&self) -> String {
+        let result = serde_json::to_string(self).unwrap();
+
+        urlencoding::encode(&result).into_owned()
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct MastProductsParams {
+    obsid: String,
+}
+
+#[derive(Debug, Serialize)]
+struct MastProductsRequest {
+    service: String,
+    params: MastProductsParams,
+    format: String,
+}
+
+impl MastProductsRequest {
+    fn new(obsid: &str) -> Self {
+        MastProductsRequest {
+            service: String::from("Mast.Caom.Products"),
+            params: MastProductsParams {
+                obsid: obsid.to_owned(),
+            },
+            format: String::from("json"),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct MastProductsResponse {
+    data: Vec<MastProductEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MastProductEntry {
+    obs_id: Option<String>,
+    #[serde(rename = "productFilename")]
+    product_filename: Option<String>,
+    #[serde(rename = "dataURI")]
+    data_uri: Option<String>,
+    #[serde(rename = "productType")]
+    product_type: Option<String>,
+    size: Option<u64>,
+    #[serde(alias = "md5sum")]
+    md5: Option<String>,
+}
+
+/// A data product of a MAST observation.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MastProduct {
+    /// The identifier of the observation the product belongs to.
+    pub obs_id: Option<String>,
+    /// The file name of the product.
+    pub filename: String,
+    /// The MAST data URI of the product, such as `mast:HST/product/<filename>`.
+    pub data_uri: String,
+    /// The type of the product, such as `SCIENCE` or `PREVIEW`.
+    pub product_type: Option<String>,
+    /// The size of the product, in bytes, if known.
+    pub size: Option<u64>,
+    /// The hex-encoded MD5 checksum of the product, if known.
+    pub md5: Option<String>,
+}
+
+impl MastProduct {
+    /// Creates a product from the given entry, or None if it has no data URI.
+    fn from_entry(entry: MastProductEntry) -> Option<Self> {
+        let data_uri = entry.data_uri?;
+        let filename = entry
+            .product_filename
+            .or_else(|| data_uri.rsplit('/').next().map(str::to_owned))?;
+
+        Some(MastProduct {
+            obs_id: entry.obs_id,
+            filename,
+            data_uri,
+            product_type: entry.product_type,
+            size: entry.size,
+            md5: entry.md5.filter(|md5| !md5.is_empty()),
+        })
+    }
+
+    /// Determines whether the product is an HST drizzled product, which combines the exposures of its observation
+    /// into one calibrated image.
+    pub fn is_drizzled(&self) -> bool {
+        let filename = self.filename.to_ascii_lowercase();
+        DRIZZLED_SUFFIXES
+            .iter()
+            .any(|suffix| filename.ends_with(suffix))
+    }
+
+    /// Gets the URL the product can be downloaded from.
+    pub fn download_url(&self) -> String {
+        download_url(&self.data_uri)
+    }
+}
+
+/// Gets the URL the product with the given MAST data URI can be downloaded from.
+pub(crate) fn download_url(data_uri: &str) -> String {
+    format!(
+        "{}?uri={}",
+        MAST_DOWNLOAD_URL,
+        urlencoding::encode(data_uri)
+    )
+}
+
+/// The status reported with every MAST response, which is checked before the rest of the response is parsed.
+#[derive(Debug, Deserialize)]
+struct MastStatus {
+    status: String,
+    #[serde(default)]
+    msg: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct MastResponse {
+    pub(crate) data: Vec<MastResponseEntry>,
+    paging: MastResponsePaging,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct MastResponseEntry {
+    // reported as a string or a number, depending on the service version
+    obsid: Option<serde_json::Value>,
+    #[serde(rename = "intentType")]
+    intent_type: Option<String>,
+    obs_collection: Option<String>,
+    provenance_name: Option<String>,
+    instrument_name: Option<String>,
+    project: Option<String>,
+    filters: Option<String>,
+    wavelength_region: Option<String>,
+    target_name: Option<String>,
+    target_classification: Option<String>,
+    obs_id: Option<String>,
+    s_ra: Option<f64>,
+    s_dec: Option<f64>,
+    dataproduct_type: Option<String>,
+    proposal_pi: Option<String>,
+    calib_level: Option<i64>,
+    t_min: Option<f64>,
+    t_max: Option<f64>,
+    t_exptime: Option<f64>,
+    em_min: Option<f64>,
+    em_max: Option<f64>,
+    obs_title: Option<String>,
+    t_obs_release: Option<f64>,
+    proposal_id: Option<String>,
+    proposal_type: Option<String>,
+    sequence_number: Option<i64>,
+    s_region: Option<String>,
+    #[serde(rename = "jpegURL")]
+    jpeg_url: Option<String>,
+    #[serde(rename = "dataURL")]
+    data_url: Option<String>,
+    #[serde(rename = "dataRights")]
+    data_rights: Option<String>,
+    #[serde(rename = "mtFlag")]
+    mt_flag: Option<bool>,
+    #[serde(rename = "srcDen")]
+    src_den: Option<f64>,
+    distance: Option<f64>,
+    #[serde(rename = "_selected_")]
+    selected: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct MastResponsePaging {
+    page: usize,
+    #[serde(rename = "pageSize")]
+    pub(crate) page_size: usize,
+    #[serde(rename = "pagesFiltered")]
+    pages_filtered: usize,
+    rows: usize,
+    #[serde(rename = "rowsFiltered")]
+    rows_filtered: usize,
+    #[serde(rename = "rowsTotal")]
+    pub(crate) rows_total: usize,
+}
+
+impl From<&MastResponseEntry> for Observation {
+    fn from(entry: &MastResponseEntry) -> Self {
+        Observation {
+            archive: String::from("MAST"),
+            obs_id: entry.obs_id.to_owned().unwrap_or_default(),
+            collection: entry.obs_collection.to_owned(),
+            instrument: entry.instrument_name.to_owned(),
+            filters: entry.filters.to_owned(),
+            target_name: entry.target_name.to_owned(),
+            target_classification: entry.target_classification.to_owned(),
+            dataproduct_type: entry.dataproduct_type.to_owned(),
+            ra: entry.s_ra,
+            dec: entry.s_dec,
+            exposure_time: entry.t_exptime,
+            start_time: entry.t_min.and_then(mjd_to_datetime),
+            end_time: entry.t_max.and_then(mjd_to_datetime),
+            release_date: entry.t_obs_release.and_then(mjd_to_datetime),
+            preview_url: entry.jpeg_url.to_owned(),
+            data_url: entry.data_url.to_owned(),
+            s_region: entry.s_region.to_owned(),
+            product_group: entry.obsid.as_ref().map(|obsid| match obsid {
+                serde_json::Value::String(obsid) => obsid.to_owned(),
+                obsid => obsid.to_string(),
+            }),
+            // reported in arcseconds
+            distance: entry.distance.map(|distance| distance / 60.0),
+            // reported in nanometers
+            em_min: entry.em_min.map(Length::new::<nanometer>),
+            em_max: entry.em_max.map(Length::new::<nanometer>),
+        }
+    }
+}
+
+/// Selects which data URLs of MAST observations are listed as FITS files.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct FitsFileFilter {
+    /// The file name suffixes that are accepted, compared without regard to ASCII case.
+    pub extensions: Vec<String>,
+    /// The data product types that are accepted, such as `image` or `spectrum`. Any type is accepted if empty.
+    pub product_types: Vec<String>,
+}
+
+impl Default for FitsFileFilter {
+    /// Accepts `.fits`, `.fits.gz`, and `.fit` files of any product type.
+    fn default() -> Self {
+        FitsFileFilter {
+            extensions: vec![
+                String::from(".fits"),
+                String::from(".fits.gz"),
+                String::from(".fit"),
+            ],
+            product_types: Vec::new(),
+        }
+    }
+}
+
+impl FitsFileFilter {
+    /// Accepts only HST drizzled products (`_drz.fits` and `_drc.fits`), leaving out the raw and intermediate files
+    /// that make up most of the files of Hubble observations.
+    pub fn drizzled() -> Self {
+        FitsFileFilter {
+            extensions: DRIZZLED_SUFFIXES.map(String::from).to_vec(),
+            product_types: Vec::new(),
+        }
+    }
+
+    /// Determines whether the file at the given URL, of the given data product type, is accepted. The query and
+    /// fragment of the URL are ignored.
+    pub fn matches(&self, url: &str, product_type: Option<&str>) -> bool {
+        let path = url
+            .split(['?', '#'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let extension_matches = self
+            .extensions
+            .iter()
+            .any(|extension| path.ends_with(&extension.to_ascii_lowercase()));
+        let product_type_matches = self.product_types.is_empty()
+            || product_type.is_some_and(|product_type| {
+                self.product_types
+                    .iter()
+                    .any(|accepted| accepted.eq_ignore_ascii_case(product_type))
+            });
+
+        extension_matches && product_type_matches
+    }
+}
+
+/// The kind of data product of an observation, as classified by the archive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DataProductType {
+    /// A two-dimensional image.
+    Image,
+    /// A spectrum.
+    Spectrum,
+    /// A series of measurements over time, such as a light curve or an event list.
+    Timeseries,
+    /// A data cube, such as an integral field spectrum.
+    Cube,
+}
+
+impl DataProductType {
+    /// Gets the name of the type used by the archives.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DataProductType::Image => "image",
+            DataProductType::Spectrum => "spectrum",
+            DataProductType::Timeseries => "timeseries",
+            DataProductType::Cube => "cube",
+        }
+    }
+}
+
+/// The Mikulski Archive for Space Telescopes (MAST), searched through its CAOM cone search.
+#[derive(Clone, Debug, Default)]
+pub struct MastArchive {
+    filter: FitsFileFilter,
+    observed_after: Option<DateTime<Utc>>,
+    observed_before: Option<DateTime<Utc>>,
+    min_exposure_time: Option<f64>,
+    target_classification: Option<String>,
+    dataproduct_types: Vec<DataProductType>,
+    collection: Option<String>,
+}
+
+impl MastArchive {
+    /// Creates a MAST archive that lists the data URLs accepted by the given filter as FITS files.
+    pub fn with_filter(filter: FitsFileFilter) -> Self {
+        MastArchive {
+            filter,
+            ..Default::default()
+        }
+    }
+
+    /// Creates a MAST archive that only lists images, as searched for the APOD by default.
+    pub fn images() -> Self {
+        MastArchive::default().dataproduct_types(&[DataProductType::Image])
+    }
+
+    /// Only lists observations that started no earlier than `after` and ended no later than `before`, such as only
+    /// observations after the launch of JWST. Either bound may be omitted.
+    pub fn observed_between(
+        mut self,
+        after: Option<DateTime<Utc>>,
+        before: Option<DateTime<Utc>>,
+    ) -> Self {
+        self.observed_after = after;
+        self.observed_before = before;
+        self
+    }
+
+    /// Only lists observations that started and ended within the given number of days of the given date, such as the
+    /// date of an APOD, to find the data behind a picture rather than every visit to its field.
+    pub fn observed_near(self, date: NaiveDate, days: u64) -> Self {
+        let start_of_day = |date: NaiveDate| date.and_hms_opt(0, 0, 0).map(|time| time.and_utc());
+        let after = date
+            .checked_sub_days(Days::new(days))
+            .and_then(start_of_day);
+        // the window includes the whole of its last day
+        let before = date
+            .checked_add_days(Days::new(days + 1))
+            .and_then(start_of_day);
+
+        self.observed_between(after, before)
+    }
+
+    /// Only lists observations with an exposure time of at least the given number of seconds, excluding shallow
+    /// snapshots.
+    pub fn min_exposure_time(mut self, seconds: f64) -> Self {
+        self.min_exposure_time = Some(seconds);
+        self
+    }
+
+    /// Only lists observations whose target classification contains the given text, such as `GALAXY` or `STAR`,
+    /// excluding calibration and serendipitous targets that happen to fall in the cone.
+    pub fn target_classification(mut self, classification: &str) -> Self {
+        self.target_classification = Some(classification.to_owned());
+        self
+    }
+
+    /// Only lists observations of the given data product types, such as only images, excluding the spectra and event
+    /// lists that otherwise crowd the results of imaging-oriented searches. Any type is listed if none are given.
+    pub fn dataproduct_types(mut self, types: &[DataProductType]) -> Self {
+        self.dataproduct_types = types.to_vec();
+        self
+    }
+
+    /// Only lists observations of the given mission or collection, such as `HST` or `TESS`.
+    pub fn collection(mut self, collection: &str) -> Self {
+        self.collection = Some(collection.to_owned());
+        self
+    }
+
+    /// Gets the filters applied by MAST to the search.
+    fn filters(&self) -> Vec<MastFilter> {
+        let mut filters = Vec::new();
+        if let Some(after) = self.observed_after {
+            filters.push(MastFilter {
+                param_name: String::from("t_min"),
+                values: vec![MastFilterValue::Range(MastRange {
+                    min: datetime_to_mjd(after),
+                    max: MAX_MJD,
+                })],
+                free_text: None,
+            });
+        }
+        if let Some(seconds) = self.min_exposure_time {
+            filters.push(MastFilter {
+                param_name: String::from("t_exptime"),
+                values: vec![MastFilterValue::Range(MastRange {
+                    min: seconds,
+                    max: f64::MAX,
+                })],
+                free_text: None,
+            });
+        }
+        if let Some(before) = self.observed_before {
+            filters.push(MastFilter {
+                param_name: String::from("t_max"),
+                values: vec![MastFilterValue::Range(MastRange {
+                    min: MIN_MJD,
+                    max: datetime_to_mjd(before),
+                })],
+                free_text: None,
+            });
+        }
+        if let Some(classification) = self.target_classification.as_ref() {
+            filters.push(MastFilter {
+                param_name: String::from("target_classification"),
+                values: Vec::new(),
+                free_text: Some(format!("%{}%", classification)),
+            });
+        }
+        if let Some(collection) = self.collection.as_ref() {
+            filters.push(MastFilter {
+                param_name: String::from("obs_collection"),
+                values: vec![MastFilterValue::Exact(collection.to_owned())],
+                free_text: None,
+            });
+        }
+        if !self.dataproduct_types.is_empty() {
+            filters.push(MastFilter {
+                param_name: String::from("dataproduct_type"),
+                values: self
+                    .dataproduct_types
+                    .iter()
+                    .map(|product_type| MastFilterValue::Exact(product_type.as_str().to_owned()))
+                    .collect(),
+                free_text: None,
+            });
+        }
+
+        filters
+    }
+}
+
+#[async_trait]
+impl ObservationArchive for MastArchive {
+    fn name(&self) -> &str {
+        "MAST"
+    }
+
+    async fn search(
+        &self,
+        server: &EarendelServer,
+        coords: &Icrs,
+        page: usize,
+    ) -> Result<EarendelFits, Box<dyn Error + Send + Sync>> {
+        let params = MastRequestParams::from(coords);
+        let request = MastRequest::new(params, self.filters(), page);
+        let mast = invoke::<MastResponse>(server, &request.to_urlencoded()).await?;
+
+        let fits_files = mast
+            .data
+            .iter()
+            .filter_map(|entry| {
+                entry
+                    .data_url
+                    .as_ref()
+                    .filter(|file| self.filter.matches(file, entry.dataproduct_type.as_deref()))
+            })
+            .cloned()
+            .collect::<Vec<String>>();
+
+        Ok(EarendelFits::new(
+            fits_files,
+            mast.data.iter().map(Observation::from).collect(),
+            page,
+            mast.paging.page_size,
+            mast.paging.rows_total,
+        ))
+    }
+}
+
+/// Invokes a MAST API service with the given URL-encoded request. Returns `EarendelError::MastQueryFailed` if MAST
+/// responds with an error status or reports that the query did not complete.
+pub(crate) async fn invoke<T: DeserializeOwned>(
+    server: &EarendelServer,
+    encoded_request: &str,
+) -> Result<T, Box<dyn Error + Send + Sync>> {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        CONTENT_TYPE,
+        "application/x-www-form-urlencoded".parse().unwrap(),
+    );
+    headers.insert(ACCEPT, "text/plain".parse().unwrap());
+
+    let request = server
+        .client
+        .post(MAST_INVOKE_URL)
+        .headers(headers)
+        .body(["request=", encoded_request].concat());
+    let resp = server.send(Upstream::Mast, request).await?;
+    let http_status = resp.status();
+    let body = resp.text().await?;
+    if !http_status.is_success() {
+        // an overloaded or failing MAST answers with an error page rather than a MAST response
+        return Err(EarendelError::MastQueryFailed {
+            status: http_status.to_string(),
+            msg: body.trim().to_owned(),
+        }
+        .into());
+    }
+    let status = server.parse::<MastStatus>(Upstream::Mast, &body)?;
+    if status.status != "COMPLETE" {
+        return Err(EarendelError::MastQueryFailed {
+            status: status.status,
+            msg: status.msg,
+        }
+        .into());
+    }
+
+    server.parse::<T>(Upstream::Mast, &body)
+}
+
+impl EarendelServer {
+    /// Lists the data products of the given MAST observation, including their sizes and MD5 checksums where MAST
+    /// reports them. Concurrent requests for the same observation share one query. Returns an error if the observation
+    /// is not from MAST or if the web request fails.
+    #[instrument(skip(self, observation), fields(obs_id = observation.obs_id))]
+    pub async fn get_mast_products(
+        &self,
+        observation: &Observation,
+    ) -> Result<Vec<MastProduct>, Box<dyn Error + Send + Sync>> {
+        let obsid = observation
+            .product_group
+            .as_deref()
+            .filter(|_| observation.archive == "MAST")
+            .ok_or("observation is not from MAST")?;
+        let request = serde_json::to_string(&MastProductsRequest::new(obsid))?;
+
+        self.product_lists
+            .run(obsid, async {
+                let products =
+                    invoke::<MastProductsResponse>(self, &urlencoding::encode(&request)).await?;
+
+                Ok(products
+                    .data
+                    .into_iter()
+                    .filter_map(MastProduct::from_entry)
+                    .collect())
+            })
+            .await
+    }
+
+    /// Lists the HST drizzled products of the given MAST observation, which combine its exposures into calibrated
+    /// images, leaving out raw and intermediate files. See `EarendelServer::get_mast_products`. Returns an error if
+    /// the observation is not from MAST or if the web request fails.
+    #[instrument(skip(self, observation), fields(obs_id = observation.obs_id))]
+    pub async fn get_drizzled_products(
+        &self,
+        observation: &Observation,
+    ) -> Result<Vec<MastProduct>, Box<dyn Error + Send + Sync>> {
+        let mut products = self.get_mast_products(observation).await?;
+        products.retain(MastProduct::is_drizzled);
+
+        Ok(products)
+    }
+
+    /// Gets FITS files of images of the current APOD, excluding spectra, time series, and cubes. Returns an error if
+    /// the web request fails.
+    ///
+    /// ```
+    /// use earendel::*;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let server = EarendelServer::new();
+    /// server.get_fits_for_apod(1).await.unwrap();
+    /// # });
+    /// ```
+    #[instrument(skip(self))]
+    pub async fn get_fits_for_apod(
+        &self,
+        page: usize,
+    ) -> Result<EarendelFits, Box<dyn Error + Send + Sync>> {
+        self.get_archive_fits_for_apod(&MastArchive::images(), page)
+            .await
+    }
+
+    /// Gets FITS files of images of the current APOD target taken within the given number of days of the date of the
+    /// APOD, such as the observations behind a recently published picture. Returns an error if the web request fails.
+    #[instrument(skip(self))]
+    pub async fn get_fits_near_apod_date(
+        &self,
+        days: u64,
+        page: usize,
+    ) -> Result<EarendelFits, Box<dyn Error + Send + Sync>> {
+        let date = self.get_apod_date().await?;
+        let archive = MastArchive::images().observed_near(date, days);
+
+        self.get_archive_fits_for_apod(&archive, page).await
+    }
+
+    /// Gets every page of FITS files of images of the current APOD, combined into one page with duplicates removed. MAST
+    /// requests are subject to the configured rate limit. Returns an error if a web request fails.
+    #[instrument(skip(self))]
+    pub async fn get_all_fits_for_apod(
+        &self,
+    ) -> Result<EarendelFits, Box<dyn Error + Send + Sync>> {
+        self.get_all_archive_fits_for_apod(&MastArchive::images())
+            .await
+    }
+
+    /// Invokes the given MAST service, such as `Mast.Name.Lookup`, with the given parameters, for services earendel
+    /// does not otherwise wrap. The response is parsed as the given type, such as `serde_json::Value`. The request is
+    /// subject to the configured retries and rate limit. Returns `EarendelError::MastQueryFailed` if MAST reports that
+    /// the query did not complete, or an error if the web request fails or the response cannot be parsed.
+    ///
+    /// ```
+    /// use earendel::*;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let server = EarendelServer::new();
+    /// let params = serde_json::json!({ "input": "M101", "format": "json" });
+    /// let lookup = server
+    ///     .mast_invoke::<serde_json::Value>("Mast.Name.Lookup", params, 1, 10)
+    ///     .await
+    ///     .unwrap();
+    /// # });
+    /// ```
+    #[instrument(skip(self, params))]
+    pub async fn mast_invoke<T: DeserializeOwned>(
+        &self,
+        service: &str,
+        params: serde_json::Value,
+        page: usize,
+        pagesize: usize,
+    ) -> Result<T, Box<dyn Error + Send + Sync>> {
+        let request = MastRequest::raw(service, params, page, pagesize);
+
+        invoke::<T>(self, &request.to_urlencoded()).await
+    }
+}
@@ -0,0 +1,220 @@
+//! Extraction of embedded EXIF and XMP metadata from downloaded images.
+
+#[cfg(feature = "exif")]
+use exif::{Exif, In, Reader, Tag, Value};
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "exif")]
+use std::io::Cursor;
+
+/// The camera, exposure, location, and author metadata embedded in an image.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct ImageMetadata {
+    /// The manufacturer of the camera.
+    pub camera_make: Option<String>,
+    /// The model of the camera.
+    pub camera_model: Option<String>,
+    /// The model of the lens.
+    pub lens_model: Option<String>,
+    /// The exposure time, in seconds.
+    pub exposure_time: Option<f64>,
+    /// The f-number of the aperture.
+    pub f_number: Option<f64>,
+    /// The ISO sensitivity.
+    pub iso: Option<u32>,
+    /// The focal length, in millimeters.
+    pub focal_length: Option<f64>,
+    /// The date and time the image was taken, as recorded by the camera.
+    pub date_taken: Option<String>,
+    /// The latitude where the image was taken, in degrees north.
+    pub latitude: Option<f64>,
+    /// The longitude where the image was taken, in degrees east.
+    pub longitude: Option<f64>,
+    /// The author of the image.
+    pub author: Option<String>,
+}
+
+impl ImageMetadata {
+    fn is_empty(&self) -> bool {
+        self == &ImageMetadata::default()
+    }
+}
+
+/// Reads the metadata embedded in the given encoded image. EXIF is only read when the `exif` feature is enabled.
+/// Returns None if the image has no recognized metadata.
+#[cfg_attr(not(feature = "apod"), allow(dead_code))]
+pub(crate) fn read_metadata(img: &[u8]) -> Option<ImageMetadata> {
+    #[cfg_attr(not(feature = "exif"), allow(unused_mut))]
+    let mut metadata = ImageMetadata::default();
+
+    #[cfg(feature = "exif")]
+    if let Ok(exif) = Reader::new().read_from_container(&mut Cursor::new(img)) {
+        read_exif(&exif, &mut metadata);
+    }
+    if metadata.author.is_none() {
+        metadata.author = xmp_packet(img).and_then(xmp_creator);
+    }
+
+    (!metadata.is_empty()).then_some(metadata)
+}
+
+#[cfg(feature = "exif")]
+fn read_exif(exif: &Exif, metadata: &mut ImageMetadata) {
+    let ascii = |tag: Tag| match &exif.get_field(tag, In::PRIMARY)?.value {
+        Value::Ascii(values) => values
+            .first()
+            .map(|value| String::from_utf8_lossy(value).trim().to_owned())
+            .filter(|value| !value.is_empty()),
+        _ => None,
+    };
+    let rationals = |tag: Tag| match &exif.get_field(tag, In::PRIMARY)?.value {
+        Value::Rational(values) => Some(
+            values
+                .iter()
+                .map(|value| value.to_f64())
+                .collect::<Vec<f64>>(),
+        ),
+        _ => None,
+    };
+    let rational = |tag: Tag| rationals(tag)?.first().copied();
+    let coordinate = |tag: Tag, reference: Tag, negative: &str| {
+        let parts = rationals(tag)?;
+        let degrees = parts.first()?
+            + parts.get(1).unwrap_or(&0.0) / 60.0
+            + parts.get(2).unwrap_or(&0.0) / 3600.0;
+        Some(if ascii(reference).as_deref() == Some(negative) {
+            -degrees
+        } else {
+            degrees
+        })
+    };
+
+    metadata.camera_make = ascii(Tag::Make);
+    metadata.camera_model = ascii(Tag::Model);
+    metadata.lens_model = ascii(Tag::LensModel);
+    metadata.exposure_time = rational(Tag::ExposureTime);
+    metadata.f_number = rational(Tag::FNumber);
+    metadata.iso = exif
+        .get_field(Tag::PhotographicSensitivity, In::PRIMARY)
+        .and_then(|field| field.value.get_uint(0));
+    metadata.focal_length = rational(Tag::FocalLength);
+    metadata.date_taken = ascii(Tag::DateTimeOriginal).or_else(|| ascii(Tag::DateTime));
+    metadata.latitude = coordinate(Tag::GPSLatitude, Tag::GPSLatitudeRef, "S");
+    metadata.longitude = coordinate(Tag::GPSLongitude, Tag::GPSLongitudeRef, "W");
+    metadata.author = ascii(Tag::Artist);
+}
+
+/// Finds the XMP packet embedded in the given encoded image.
+fn xmp_packet(img: &[u8]) -> Option<&str> {
+    let start = find(img, b"<x:xmpmeta")?;
+    let end = start + find(&img[start..], b"</x:xmpmeta>")? + b"</x:xmpmeta>".len();
+
+    std::str::from_utf8(&img[start..end]).ok()
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Gets the first creator listed in the given XMP packet.
+fn xmp_creator(xmp: &str) -> Option<String> {
+    let creator = &xmp[xmp.find("<dc:creator")?..];
+    let creator = &creator[..creator.find("</dc:creator>")?];
+    let item = &creator[creator.find("<rdf:li")?..];
+    let text = &item[item.find('>')? + 1..item.find("</rdf:li>")?];
+
+    Some(text.trim().to_owned()).filter(|text| !text.is_empty())
+}
+
+/// Reads the width and height of the given encoded image from its header, without decoding the image. JPEG, PNG,
+/// GIF, and WebP images are supported.
+#[cfg_attr(not(feature = "apod"), allow(dead_code))]
+pub(crate) fn image_dimensions(img: &[u8]) -> Option<(u32, u32)> {
+    let u16_be = |offset: usize| {
+        Some(u16::from_be_bytes(
+            img.get(offset..offset + 2)?.try_into().ok()?,
+        ))
+    };
+    let u16_le = |offset: usize| {
+        Some(u16::from_le_bytes(
+            img.get(offset..offset + 2)?.try_into().ok()?,
+        ))
+    };
+    let u24_le = |offset: usize| {
+        let bytes = img.get(offset..offset + 3)?;
+        Some(u32::from(bytes[0]) | u32::from(bytes[1]) << 8 | u32::from(bytes[2]) << 16)
+    };
+    let u32_be = |offset: usize| {
+        Some(u32::from_be_bytes(
+            img.get(offset..offset + 4)?.try_into().ok()?,
+        ))
+    };
+    let u32_le = |offset: usize| {
+        Some(u32::from_le_bytes(
+            img.get(offset..offset + 4)?.try_into().ok()?,
+        ))
+    };
+
+    if img.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some((u32_be(16)?, u32_be(20)?))
+    } else if img.starts_with(b"GIF8") {
+        Some((u32::from(u16_le(6)?), u32::from(u16_le(8)?)))
+    } else if img.starts_with(b"RIFF") && img.get(8..12) == Some(b"WEBP") {
+        match img.get(12..16)? {
+            b"VP8 " => Some((
+                u32::from(u16_le(26)? & 0x3fff),
+                u32::from(u16_le(28)? & 0x3fff),
+            )),
+            b"VP8L" => {
+                let bits = u32_le(21)?;
+                Some((1 + (bits & 0x3fff), 1 + ((bits >> 14) & 0x3fff)))
+            }
+            b"VP8X" => Some((1 + u24_le(24)?, 1 + u24_le(27)?)),
+            _ => None,
+        }
+    } else if img.starts_with(&[0xff, 0xd8]) {
+        // walk the JPEG segments until a start-of-frame marker
+        let mut offset = 2;
+        loop {
+            while *img.get(offset)? == 0xff && *img.get(offset + 1)? == 0xff {
+                offset += 1;
+            }
+            if *img.get(offset)? != 0xff {
+                return None;
+            }
+            let marker = *img.get(offset + 1)?;
+            let is_frame = matches!(marker, 0xc0..=0xcf) && !matches!(marker, 0xc4 | 0xc8 | 0xcc);
+            if is_frame {
+                return Some((
+                    u32::from(u16_be(offset + 7)?),
+                    u32::from(u16_be(offset + 5)?),
+                ));
+            }
+            offset += 2 + usize::from(u16_be(offset + 2)?);
+        }
+    } else {
+        None
+    }
+}
+
+/// Guesses the MIME type of the given encoded image from its leading bytes.
+#[cfg_attr(not(feature = "apod"), allow(dead_code))]
+pub(crate) fn content_type(img: &[u8]) -> &'static str {
+    if img.starts_with(b"\x89PNG\r\n\x1a\n") {
+        "image/png"
+    } else if img.starts_with(b"GIF8") {
+        "image/gif"
+    } else if img.starts_with(b"RIFF") && img.get(8..12) == Some(b"WEBP") {
+        "image/webp"
+    } else if img.get(4..12) == Some(b"ftypavif") {
+        "image/avif"
+    } else if img.starts_with(&[0xff, 0xd8]) {
+        "image/jpeg"
+    } else {
+        "application/octet-stream"
+    }
+}
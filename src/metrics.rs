@@ -0,0 +1,344 @@
+//! Optional collection of request, error, cache, and latency metrics.
+
+#[cfg(feature = "metrics")]
+use std::collections::BTreeMap;
+use std::error::Error;
+#[cfg(feature = "metrics")]
+use std::fmt::Write;
+#[cfg(feature = "metrics")]
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{CircuitState, Upstream};
+
+/// The broad category of a failed upstream interaction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+pub enum ErrorCategory {
+    /// The connection to the upstream could not be established.
+    Connection,
+    /// The upstream did not respond in time.
+    Timeout,
+    /// The upstream responded with an error status code.
+    Status,
+    /// The upstream response could not be deserialized.
+    Deserialization,
+    /// Any other failure.
+    Other,
+}
+
+impl ErrorCategory {
+    /// Determines the category of the given error.
+    pub fn of(error: &(dyn Error + 'static)) -> Self {
+        if let Some(error) = error.downcast_ref::<reqwest::Error>() {
+            if error.is_timeout() {
+                ErrorCategory::Timeout
+            } else if error.is_connect() {
+                ErrorCategory::Connection
+            } else if error.is_status() {
+                ErrorCategory::Status
+            } else if error.is_decode() {
+                ErrorCategory::Deserialization
+            } else {
+                ErrorCategory::Other
+            }
+        } else if error.is::<serde_json::Error>() {
+            ErrorCategory::Deserialization
+        } else {
+            ErrorCategory::Other
+        }
+    }
+}
+
+/// The inclusive upper bounds of the latency histogram buckets, in milliseconds.
+#[cfg(feature = "metrics")]
+const LATENCY_BUCKETS_MS: [u64; 10] = [10, 25, 50, 100, 250, 500, 1_000, 2_500, 5_000, 10_000];
+
+/// A histogram of request latencies.
+#[cfg(feature = "metrics")]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct LatencyHistogram {
+    /// The inclusive upper bound of each bucket, in milliseconds.
+    pub bounds_ms: Vec<u64>,
+    /// The number of observations in each bucket. The final entry counts observations above the last bound.
+    pub counts: Vec<u64>,
+    /// The sum of all observed latencies, in milliseconds.
+    pub sum_ms: u64,
+}
+
+#[cfg(feature = "metrics")]
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        LatencyHistogram {
+            bounds_ms: LATENCY_BUCKETS_MS.to_vec(),
+            counts: vec![0; LATENCY_BUCKETS_MS.len() + 1],
+            sum_ms: 0,
+        }
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl LatencyHistogram {
+    /// Gets the total number of observations.
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    fn observe(&mut self, elapsed: Duration) {
+        let ms = u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX);
+        let bucket = self
+            .bounds_ms
+            .iter()
+            .position(|bound| ms <= *bound)
+            .unwrap_or(self.bounds_ms.len());
+        self.counts[bucket] += 1;
+        self.sum_ms = self.sum_ms.saturating_add(ms);
+    }
+}
+
+/// Metrics recorded for a single upstream.
+#[cfg(feature = "metrics")]
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct UpstreamMetrics {
+    /// The number of requests sent.
+    pub requests: u64,
+    /// The number of failed operations that were retried.
+    pub retries: u64,
+    /// The number of errors, by category.
+    pub errors: BTreeMap<ErrorCategory, u64>,
+    /// The request latencies.
+    pub latency: LatencyHistogram,
+    /// The state of the circuit breaker.
+    pub circuit: CircuitState,
+}
+
+/// A point-in-time copy of the recorded metrics.
+#[cfg(feature = "metrics")]
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct MetricsSnapshot {
+    /// The metrics recorded for each contacted upstream.
+    pub upstreams: BTreeMap<Upstream, UpstreamMetrics>,
+    /// The number of requests served from the cache.
+    pub cache_hits: u64,
+    /// The number of requests that could not be served from the cache.
+    pub cache_misses: u64,
+}
+
+#[cfg(feature = "metrics")]
+impl MetricsSnapshot {
+    /// Gets the ratio of cache hits to total cache lookups, or None if the cache has not been used.
+    pub fn cache_hit_ratio(&self) -> Option<f64> {
+        let total = self.cache_hits + self.cache_misses;
+        if total == 0 {
+            None
+        } else {
+            Some(self.cache_hits as f64 / total as f64)
+        }
+    }
+
+    /// Renders the metrics in the Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let mut text = String::new();
+        let header = |text: &mut String, name: &str, kind: &str, help: &str| {
+            let _ = writeln!(text, "# HELP {} {}", name, help);
+            let _ = writeln!(text, "# TYPE {} {}", name, kind);
+        };
+
+        let name = "earendel_cache_hits_total";
+        header(
+            &mut text,
+            name,
+            "counter",
+            "The number of requests served from the cache.",
+        );
+        let _ = writeln!(text, "{} {}", name, self.cache_hits);
+
+        let name = "earendel_cache_misses_total";
+        header(
+            &mut text,
+            name,
+            "counter",
+            "The number of requests that could not be served from the cache.",
+        );
+        let _ = writeln!(text, "{} {}", name, self.cache_misses);
+
+        let name = "earendel_upstream_requests_total";
+        header(
+            &mut text,
+            name,
+            "counter",
+            "The number of requests sent to each upstream.",
+        );
+        for (upstream, metrics) in self.upstreams.iter() {
+            let _ = writeln!(
+                text,
+                "{}{{upstream=\"{:?}\"}} {}",
+                name, upstream, metrics.requests
+            );
+        }
+
+        let name = "earendel_upstream_retries_total";
+        header(
+            &mut text,
+            name,
+            "counter",
+            "The number of failed operations against each upstream that were retried.",
+        );
+        for (upstream, metrics) in self.upstreams.iter() {
+            let _ = writeln!(
+                text,
+                "{}{{upstream=\"{:?}\"}} {}",
+                name, upstream, metrics.retries
+            );
+        }
+
+        let name = "earendel_upstream_errors_total";
+        header(
+            &mut text,
+            name,
+            "counter",
+            "The number of errors from each upstream, by category.",
+        );
+        for (upstream, metrics) in self.upstreams.iter() {
+            for (category, count) in metrics.errors.iter() {
+                let _ = writeln!(
+                    text,
+                    "{}{{upstream=\"{:?}\",category=\"{:?}\"}} {}",
+                    name, upstream, category, count
+                );
+            }
+        }
+
+        let name = "earendel_upstream_circuit_state";
+        header(
+            &mut text,
+            name,
+            "gauge",
+            "The state of the circuit breaker of each upstream: 0 if closed, 1 if open, or 2 if half-open.",
+        );
+        for (upstream, metrics) in self.upstreams.iter() {
+            let state = match metrics.circuit {
+                CircuitState::Closed => 0,
+                CircuitState::Open => 1,
+                CircuitState::HalfOpen => 2,
+            };
+            let _ = writeln!(text, "{}{{upstream=\"{:?}\"}} {}", name, upstream, state);
+        }
+
+        let name = "earendel_upstream_latency_seconds";
+        header(
+            &mut text,
+            name,
+            "histogram",
+            "The latency of requests to each upstream.",
+        );
+        for (upstream, metrics) in self.upstreams.iter() {
+            // Prometheus buckets are cumulative, unlike those of the histogram
+            let mut cumulative = 0;
+            for (bound, count) in metrics
+                .latency
+                .bounds_ms
+                .iter()
+                .zip(&metrics.latency.counts)
+            {
+                cumulative += count;
+                let _ = writeln!(
+                    text,
+                    "{}_bucket{{upstream=\"{:?}\",le=\"{}\"}} {}",
+                    name,
+                    upstream,
+                    *bound as f64 / 1000.0,
+                    cumulative
+                );
+            }
+            let count = metrics.latency.count();
+            let _ = writeln!(
+                text,
+                "{}_bucket{{upstream=\"{:?}\",le=\"+Inf\"}} {}",
+                name, upstream, count
+            );
+            let _ = writeln!(
+                text,
+                "{}_sum{{upstream=\"{:?}\"}} {}",
+                name,
+                upstream,
+                metrics.latency.sum_ms as f64 / 1000.0
+            );
+            let _ = writeln!(
+                text,
+                "{}_count{{upstream=\"{:?}\"}} {}",
+                name, upstream, count
+            );
+        }
+
+        text
+    }
+}
+
+/// The recorder used throughout the crate. Recording is a no-op unless the `metrics` feature is enabled.
+#[derive(Debug, Default)]
+pub(crate) struct Metrics {
+    #[cfg(feature = "metrics")]
+    state: Mutex<MetricsSnapshot>,
+}
+
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+impl Metrics {
+    pub(crate) fn record_request(&self, upstream: Upstream, elapsed: Duration) {
+        #[cfg(feature = "metrics")]
+        {
+            let mut state = self.lock();
+            let entry = state.upstreams.entry(upstream).or_default();
+            entry.requests += 1;
+            entry.latency.observe(elapsed);
+        }
+    }
+
+    pub(crate) fn record_error(&self, upstream: Upstream, category: ErrorCategory) {
+        #[cfg(feature = "metrics")]
+        {
+            let mut state = self.lock();
+            let entry = state.upstreams.entry(upstream).or_default();
+            *entry.errors.entry(category).or_default() += 1;
+        }
+    }
+
+    pub(crate) fn record_retry(&self, upstream: Upstream) {
+        #[cfg(feature = "metrics")]
+        {
+            let mut state = self.lock();
+            state.upstreams.entry(upstream).or_default().retries += 1;
+        }
+    }
+
+    pub(crate) fn record_circuit(&self, upstream: Upstream, circuit: CircuitState) {
+        #[cfg(feature = "metrics")]
+        {
+            let mut state = self.lock();
+            state.upstreams.entry(upstream).or_default().circuit = circuit;
+        }
+    }
+
+    pub(crate) fn record_cache(&self, hit: bool) {
+        #[cfg(feature = "metrics")]
+        {
+            let mut state = self.lock();
+            if hit {
+                state.cache_hits += 1;
+            } else {
+                state.cache_misses += 1;
+            }
+        }
+    }
+
+    #[cfg(feature = "metrics")]
+    pub(crate) fn snapshot(&self) -> MetricsSnapshot {
+        self.lock().clone()
+    }
+
+    #[cfg(feature = "metrics")]
+    fn lock(&self) -> MutexGuard<'_, MetricsSnapshot> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
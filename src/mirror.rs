@@ -0,0 +1,140 @@
+//! Mirroring of the whole APOD archive into a local history, for offline browsing.
+
+use chrono::{Duration, NaiveDate};
+
+use serde::{Deserialize, Serialize};
+
+use tracing::{info, instrument, warn};
+
+use std::error::Error;
+
+use crate::apod::{apod_today, FIRST_APOD_DATE};
+use crate::runtime::sleep;
+use crate::{ApodHistory, ApodMetadata, EarendelServer, Upstream};
+
+/// The number of days of APODs mirrored at once. Each batch is fetched with a single NASA API request, and is recorded
+/// as mirrored once complete, so that an interrupted mirror resumes from the first incomplete batch.
+const MIRROR_BATCH_DAYS: i64 = 100;
+
+/// The options of `EarendelServer::mirror_archive`.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct MirrorOptions {
+    /// Whether the images are stored as well as the information of the APODs. Videos and other media are never
+    /// stored.
+    pub images: bool,
+    /// The delay between consecutive web requests, to spread the load on the upstreams.
+    pub delay: std::time::Duration,
+    /// The number of remaining NASA API requests below which mirroring stops, so that the key is not exhausted. The
+    /// mirror can be resumed once the rate limit resets.
+    pub min_remaining_requests: u32,
+}
+
+impl Default for MirrorOptions {
+    fn default() -> Self {
+        MirrorOptions {
+            images: false,
+            delay: std::time::Duration::from_secs(1),
+            min_remaining_requests: 10,
+        }
+    }
+}
+
+/// The outcome of `EarendelServer::mirror_archive`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct MirrorReport {
+    /// The number of APODs recorded.
+    pub apods: usize,
+    /// The number of images stored.
+    pub images: usize,
+    /// The dates of the APODs whose image failed to download, which are retried when the mirror is resumed.
+    pub failed_images: Vec<NaiveDate>,
+    /// Whether the whole archive has been mirrored. Mirroring stops early when the NASA API rate limit runs low.
+    pub complete: bool,
+}
+
+impl EarendelServer {
+    /// Records every APOD in the archive in the given history, and optionally their images, so that they can be
+    /// browsed offline. Batches mirrored by a previous call are skipped, so an interrupted mirror can be resumed by
+    /// calling this again. Returns an Error if a web request for the APOD information fails or if the history cannot
+    /// be written; images that fail to download are reported instead.
+    #[instrument(skip(self, history))]
+    pub async fn mirror_archive(
+        &self,
+        history: &ApodHistory,
+        options: &MirrorOptions,
+    ) -> Result<MirrorReport, Box<dyn Error + Send + Sync>> {
+        let today = apod_today();
+        let mut report = MirrorReport::default();
+        let mut start = FIRST_APOD_DATE;
+        while start <= today {
+            let end = today.min(start + Duration::days(MIRROR_BATCH_DAYS - 1));
+            if !history.is_mirrored(start, options.images)? {
+                if self
+                    .rate_limit_status()
+                    .is_some_and(|status| status.remaining < options.min_remaining_requests)
+                {
+                    info!("stopping the mirror at {} as the rate limit is low", start);
+                    return Ok(report);
+                }
+                let apods = self.fetch_apod_range(start, end).await?;
+                for apod in apods.iter() {
+                    history.record_metadata(apod)?;
+                }
+                report.apods += apods.len();
+                sleep(options.delay).await;
+
+                let failed = report.failed_images.len();
+                if options.images {
+                    self.mirror_images(history, &apods, options, &mut report)
+                        .await?;
+                }
+                // the batch holding today is mirrored again, as its later APODs are not published yet
+                if end < today && report.failed_images.len() == failed {
+                    history.mark_mirrored(start, end, options.images)?;
+                }
+            }
+            start = end + Duration::days(1);
+        }
+        report.complete = report.failed_images.is_empty();
+
+        Ok(report)
+    }
+
+    /// Stores the images of the given APODs that are not already stored in the given history.
+    async fn mirror_images(
+        &self,
+        history: &ApodHistory,
+        apods: &[ApodMetadata],
+        options: &MirrorOptions,
+        report: &mut MirrorReport,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        for apod in apods.iter().filter(|apod| apod.media_type == "image") {
+            let Some(image_url) = apod.image_url.as_ref() else {
+                continue;
+            };
+            if history.get(apod.date)?.is_some_and(|entry| entry.has_image) {
+                continue;
+            }
+            let img = async {
+                let resp = self
+                    .send(Upstream::ApodWebsite, self.client.get(image_url))
+                    .await?
+                    .error_for_status()?;
+                self.read_limited(resp).await
+            };
+            match img.await {
+                Ok(img) => {
+                    history.store_image(apod.date, &img)?;
+                    report.images += 1;
+                }
+                Err(e) => {
+                    warn!("failed to mirror the APOD image of {}: {}", apod.date, e);
+                    report.failed_images.push(apod.date);
+                }
+            }
+            sleep(options.delay).await;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,57 @@
+//! Conversions between times and modified Julian dates (MJD), the day count used by MAST and most astronomical
+//! archives.
+
+use chrono::{DateTime, Utc};
+
+/// The modified Julian date of the Unix epoch.
+const UNIX_EPOCH_MJD: f64 = 40_587.0;
+const SECONDS_PER_DAY: f64 = 86_400.0;
+
+/// Converts the given time to a modified Julian date, with millisecond precision.
+pub fn datetime_to_mjd(time: DateTime<Utc>) -> f64 {
+    time.timestamp_millis() as f64 / (SECONDS_PER_DAY * 1000.0) + UNIX_EPOCH_MJD
+}
+
+/// Converts the given modified Julian date to a time, rounded to the nearest millisecond, or None if it is not finite
+/// or out of range.
+pub fn mjd_to_datetime(mjd: f64) -> Option<DateTime<Utc>> {
+    let millis = (mjd - UNIX_EPOCH_MJD) * SECONDS_PER_DAY * 1000.0;
+    if !millis.is_finite() {
+        return None;
+    }
+
+    DateTime::from_timestamp_millis(millis.round() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::TimeZone;
+
+    #[test]
+    fn converts_j2000_epoch() {
+        let j2000 = Utc.with_ymd_and_hms(2000, 1, 1, 12, 0, 0).unwrap();
+        assert_eq!(datetime_to_mjd(j2000), 51_544.5);
+        assert_eq!(mjd_to_datetime(51_544.5), Some(j2000));
+    }
+
+    #[test]
+    fn converts_unix_epoch() {
+        assert_eq!(datetime_to_mjd(DateTime::UNIX_EPOCH), UNIX_EPOCH_MJD);
+        assert_eq!(mjd_to_datetime(UNIX_EPOCH_MJD), Some(DateTime::UNIX_EPOCH));
+    }
+
+    #[test]
+    fn round_trips_to_the_millisecond() {
+        let time = Utc.with_ymd_and_hms(2024, 2, 29, 23, 59, 59).unwrap()
+            + chrono::Duration::milliseconds(123);
+        assert_eq!(mjd_to_datetime(datetime_to_mjd(time)), Some(time));
+    }
+
+    #[test]
+    fn rejects_non_finite_dates() {
+        assert_eq!(mjd_to_datetime(f64::NAN), None);
+        assert_eq!(mjd_to_datetime(f64::INFINITY), None);
+    }
+}
@@ -0,0 +1,160 @@
+//! Local computation of the phase and illumination of the Moon.
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use serde::{Deserialize, Serialize};
+
+/// The mean length of a lunation, from new moon to new moon, in days.
+const SYNODIC_MONTH_DAYS: f64 = 29.530_588;
+
+/// The named phase of the Moon, each spanning an eighth of a lunation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum MoonPhaseName {
+    /// The Moon is between the Earth and the Sun, and is not visible.
+    NewMoon,
+    /// Less than half of the Moon is lit, and the lit part is growing.
+    WaxingCrescent,
+    /// Half of the Moon is lit, and the lit part is growing.
+    FirstQuarter,
+    /// More than half of the Moon is lit, and the lit part is growing.
+    WaxingGibbous,
+    /// The Moon is opposite the Sun, and is fully lit.
+    FullMoon,
+    /// More than half of the Moon is lit, and the lit part is shrinking.
+    WaningGibbous,
+    /// Half of the Moon is lit, and the lit part is shrinking.
+    LastQuarter,
+    /// Less than half of the Moon is lit, and the lit part is shrinking.
+    WaningCrescent,
+}
+
+impl MoonPhaseName {
+    /// Gets the name of the phase for display, such as `Waxing Crescent`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MoonPhaseName::NewMoon => "New Moon",
+            MoonPhaseName::WaxingCrescent => "Waxing Crescent",
+            MoonPhaseName::FirstQuarter => "First Quarter",
+            MoonPhaseName::WaxingGibbous => "Waxing Gibbous",
+            MoonPhaseName::FullMoon => "Full Moon",
+            MoonPhaseName::WaningGibbous => "Waning Gibbous",
+            MoonPhaseName::LastQuarter => "Last Quarter",
+            MoonPhaseName::WaningCrescent => "Waning Crescent",
+        }
+    }
+}
+
+/// The phase of the Moon at a time.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MoonPhase {
+    /// The time of the phase.
+    pub time: DateTime<Utc>,
+    /// The angle between the Sun and the Earth as seen from the Moon, in degrees, from 0 at full moon to 180 at new
+    /// moon.
+    pub phase_angle: f64,
+    /// The fraction of the disk of the Moon that is lit, from 0 to 1.
+    pub illumination: f64,
+    /// The time since the last new moon, in days, from 0 to about 29.5.
+    pub age_days: f64,
+    /// The named phase.
+    pub name: MoonPhaseName,
+}
+
+/// Computes the phase of the Moon at noon UTC on the given date. See `moon_phase_at`.
+pub fn moon_phase(date: NaiveDate) -> MoonPhase {
+    moon_phase_at(date.and_hms_opt(12, 0, 0).unwrap_or_default().and_utc())
+}
+
+/// Computes the phase of the Moon at the given time, using the main periodic terms of the lunar theory, which gives
+/// the illumination to within about a percent.
+pub fn moon_phase_at(time: DateTime<Utc>) -> MoonPhase {
+    let centuries = (time.timestamp_millis() as f64 / 86_400_000.0 - 10_957.5) / 36_525.0;
+    let elongation = (297.850_192_1 + 445_267.111_403_4 * centuries).to_radians();
+    let sun_anomaly = (357.529_109_2 + 35_999.050_290_9 * centuries).to_radians();
+    let moon_anomaly = (134.963_396_4 + 477_198.867_505_5 * centuries).to_radians();
+
+    // the phase angle runs from 0 to 360 degrees over a lunation, starting at full moon
+    let phase_angle = (180.0 - elongation.to_degrees() - 6.289 * moon_anomaly.sin()
+        + 2.100 * sun_anomaly.sin()
+        - 1.274 * (2.0 * elongation - moon_anomaly).sin()
+        - 0.658 * (2.0 * elongation).sin()
+        - 0.214 * (2.0 * moon_anomaly).sin()
+        - 0.110 * elongation.sin())
+    .rem_euclid(360.0);
+    // the true elongation of the Moon from the Sun, from 0 at new moon to 180 at full moon and back to 360
+    let cycle = (180.0 - phase_angle).rem_euclid(360.0);
+
+    let name = match ((cycle + 22.5) / 45.0) as u32 % 8 {
+        0 => MoonPhaseName::NewMoon,
+        1 => MoonPhaseName::WaxingCrescent,
+        2 => MoonPhaseName::FirstQuarter,
+        3 => MoonPhaseName::WaxingGibbous,
+        4 => MoonPhaseName::FullMoon,
+        5 => MoonPhaseName::WaningGibbous,
+        6 => MoonPhaseName::LastQuarter,
+        _ => MoonPhaseName::WaningCrescent,
+    };
+
+    MoonPhase {
+        time,
+        phase_angle: 180.0 - (180.0 - phase_angle).abs(),
+        illumination: (1.0 + phase_angle.to_radians().cos()) / 2.0,
+        age_days: cycle / 360.0 * SYNODIC_MONTH_DAYS,
+        name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::TimeZone;
+
+    fn phase_at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> MoonPhase {
+        moon_phase_at(
+            Utc.with_ymd_and_hms(year, month, day, hour, minute, 0)
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn full_moon() {
+        // 2024-01-25 17:54 UTC
+        let phase = phase_at(2024, 1, 25, 17, 54);
+        assert_eq!(phase.name, MoonPhaseName::FullMoon);
+        assert!(phase.illumination > 0.99, "{:?}", phase);
+        assert!(phase.phase_angle < 5.0, "{:?}", phase);
+        assert!(
+            (phase.age_days - SYNODIC_MONTH_DAYS / 2.0).abs() < 1.0,
+            "{:?}",
+            phase
+        );
+    }
+
+    #[test]
+    fn new_moon() {
+        // 2024-01-11 11:57 UTC
+        let phase = phase_at(2024, 1, 11, 11, 57);
+        assert_eq!(phase.name, MoonPhaseName::NewMoon);
+        assert!(phase.illumination < 0.01, "{:?}", phase);
+        assert!(phase.phase_angle > 175.0, "{:?}", phase);
+    }
+
+    #[test]
+    fn quarter_moons() {
+        // 2024-01-18 03:53 UTC and 2024-02-02 23:18 UTC
+        let first = phase_at(2024, 1, 18, 3, 53);
+        assert_eq!(first.name, MoonPhaseName::FirstQuarter);
+        assert!((first.illumination - 0.5).abs() < 0.02, "{:?}", first);
+
+        let last = phase_at(2024, 2, 2, 23, 18);
+        assert_eq!(last.name, MoonPhaseName::LastQuarter);
+        assert!((last.illumination - 0.5).abs() < 0.02, "{:?}", last);
+    }
+
+    #[test]
+    fn phase_of_date_is_at_noon() {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 25).unwrap();
+        assert_eq!(moon_phase(date).time, phase_at(2024, 1, 25, 12, 0).time);
+    }
+}
@@ -0,0 +1,199 @@
+//! Resolution of asteroid and comet designations through the Minor Planet Center (MPC).
+
+use chrono::{Duration, Utc};
+
+use reqwest::header::CONTENT_TYPE;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use tracing::instrument;
+
+use std::error::Error;
+
+use crate::archive::{EarendelFits, ObservationArchive};
+use crate::coords::icrs_from_degrees;
+use crate::{EarendelServer, EphemerisPoint, Upstream};
+
+const MPC_ORBIT_URL: &str = "https://data.minorplanetcenter.net/api/get-orb";
+
+/// The heliocentric cometary orbital elements of a small body.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct OrbitalElements {
+    /// The epoch of the elements, as a modified Julian date.
+    pub epoch_mjd: Option<f64>,
+    /// The perihelion distance, in astronomical units.
+    pub perihelion_distance: Option<f64>,
+    /// The eccentricity.
+    pub eccentricity: Option<f64>,
+    /// The inclination, in degrees.
+    pub inclination: Option<f64>,
+    /// The longitude of the ascending node, in degrees.
+    pub ascending_node: Option<f64>,
+    /// The argument of perihelion, in degrees.
+    pub argument_of_perihelion: Option<f64>,
+    /// The time of perihelion passage, as a modified Julian date.
+    pub perihelion_time_mjd: Option<f64>,
+}
+
+/// An asteroid or comet resolved by the MPC.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SmallBody {
+    /// The designation of the body, such as `12P` or `433`.
+    pub designation: String,
+    /// The orbital elements of the body.
+    pub elements: OrbitalElements,
+    /// The current geocentric position of the body.
+    pub position: EphemerisPoint,
+}
+
+#[derive(Debug, Deserialize)]
+struct MpcOrbitEntry {
+    mpc_orb: MpcOrbit,
+}
+
+#[derive(Debug, Deserialize)]
+struct MpcOrbit {
+    #[serde(rename = "COM")]
+    com: MpcCometaryElements,
+    epoch_data: Option<MpcEpoch>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MpcCometaryElements {
+    coefficient_names: Vec<String>,
+    coefficient_values: Vec<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MpcEpoch {
+    epoch: f64,
+}
+
+impl From<&MpcOrbit> for OrbitalElements {
+    fn from(orbit: &MpcOrbit) -> Self {
+        let get = |name: &str| {
+            orbit
+                .com
+                .coefficient_names
+                .iter()
+                .position(|coefficient| coefficient == name)
+                .and_then(|index| orbit.com.coefficient_values.get(index))
+                .copied()
+        };
+        OrbitalElements {
+            epoch_mjd: orbit.epoch_data.as_ref().map(|epoch| epoch.epoch),
+            perihelion_distance: get("q"),
+            eccentricity: get("e"),
+            inclination: get("i"),
+            ascending_node: get("node"),
+            argument_of_perihelion: get("argperi"),
+            perihelion_time_mjd: get("peri_time"),
+        }
+    }
+}
+
+/// Extracts a small body designation from text such as an APOD title. Periodic comets (`12P/Pons-Brooks`), other
+/// comets (`C/2023 A3`), and numbered asteroids (`(433) Eros`) are recognized.
+pub fn extract_designation(text: &str) -> Option<String> {
+    let words = text.split_whitespace().collect::<Vec<&str>>();
+    for (index, word) in words.iter().enumerate() {
+        if let Some((prefix, _)) = word.split_once('/') {
+            let (number, kind) = prefix.split_at(prefix.len().saturating_sub(1));
+            if !number.is_empty()
+                && number.chars().all(|c| c.is_ascii_digit())
+                && (kind == "P" || kind == "I")
+            {
+                return Some(prefix.to_owned());
+            }
+            if matches!(prefix, "C" | "P" | "D" | "X" | "I" | "A") {
+                let year = word
+                    .split_once('/')
+                    .map(|(_, year)| year)
+                    .unwrap_or_default();
+                if year.len() == 4 && year.chars().all(|c| c.is_ascii_digit()) {
+                    if let Some(code) = words.get(index + 1) {
+                        let code = code.trim_end_matches(|c: char| !c.is_ascii_alphanumeric());
+                        return Some(format!("{} {}", word, code));
+                    }
+                }
+            }
+        }
+        let number = word.trim_start_matches('(');
+        if number.len() < word.len() {
+            if let Some(number) = number.strip_suffix(')') {
+                if !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()) {
+                    return Some(number.to_owned());
+                }
+            }
+        }
+    }
+
+    None
+}
+
+impl EarendelServer {
+    /// Resolves the small body designated in the given text, such as `Comet 12P/Pons-Brooks`, to its orbital elements
+    /// from the MPC and its current position from JPL Horizons. Text without a recognized designation is used as the
+    /// designation itself. Returns an error if the body is unknown or if a web request fails.
+    #[instrument(skip(self))]
+    pub async fn resolve_small_body(
+        &self,
+        text: &str,
+    ) -> Result<SmallBody, Box<dyn Error + Send + Sync>> {
+        let designation = extract_designation(text).unwrap_or_else(|| text.trim().to_owned());
+
+        let request = self
+            .client
+            .get(MPC_ORBIT_URL)
+            .header(CONTENT_TYPE, "application/json")
+            .body(json!({ "desig": designation }).to_string());
+        let resp = self
+            .send(Upstream::Mpc, request)
+            .await?
+            .error_for_status()?;
+        let body = resp.text().await?;
+        let entries = self.parse::<Vec<Value>>(Upstream::Mpc, &body)?;
+        let entry = entries
+            .into_iter()
+            .next()
+            .ok_or_else(|| format!("the MPC has no orbit for {}", designation))?;
+        let orbit = serde_json::from_value::<MpcOrbitEntry>(entry)?.mpc_orb;
+
+        // Horizons selects the current apparition of periodic comets with CAP
+        let is_comet =
+            designation.contains('/') || designation.ends_with('P') || designation.ends_with('I');
+        let body = if is_comet {
+            format!("DES={};CAP;", designation)
+        } else {
+            format!("{};", designation)
+        };
+        let now = Utc::now();
+        let position = self
+            .get_ephemeris(&body, now, now + Duration::minutes(1), Duration::minutes(1))
+            .await?
+            .into_iter()
+            .next()
+            .ok_or("Horizons returned no ephemeris")?;
+
+        Ok(SmallBody {
+            designation,
+            elements: OrbitalElements::from(&orbit),
+            position,
+        })
+    }
+
+    /// Gets a page of observations from the given archive at the current position of the small body designated in
+    /// the given text. Returns an error if the body cannot be resolved or if a web request fails.
+    pub async fn search_archive_for_small_body(
+        &self,
+        archive: &dyn ObservationArchive,
+        text: &str,
+        page: usize,
+    ) -> Result<EarendelFits, Box<dyn Error + Send + Sync>> {
+        let body = self.resolve_small_body(text).await?;
+        let coords = icrs_from_degrees(body.position.ra, body.position.dec);
+
+        self.search_archive(archive, &coords, page).await
+    }
+}
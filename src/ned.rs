@@ -0,0 +1,135 @@
+//! Queries against the NASA/IPAC Extragalactic Database (NED) for the details of a named object.
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use tracing::instrument;
+
+use std::error::Error;
+
+use crate::{EarendelServer, Upstream};
+
+const NED_LOOKUP_URL: &str = "https://ned.ipac.caltech.edu/srs/ObjectLookup";
+/// The speed of light, in kilometers per second.
+const SPEED_OF_LIGHT_KM_S: f64 = 299_792.458;
+/// The Hubble constant used for redshift distances, in kilometers per second per megaparsec.
+const HUBBLE_CONSTANT: f64 = 70.0;
+
+/// The NED details of an extragalactic object.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct NedObject {
+    /// The preferred NED name of the object.
+    pub name: String,
+    /// The NED object type, such as G for galaxy or QSO for quasar.
+    pub object_type: Option<String>,
+    /// The right ascension of the object, in degrees.
+    pub ra: Option<f64>,
+    /// The declination of the object, in degrees.
+    pub dec: Option<f64>,
+    /// The preferred redshift of the object.
+    pub redshift: Option<f64>,
+    /// The uncertainty of the redshift.
+    pub redshift_uncertainty: Option<f64>,
+}
+
+impl NedObject {
+    /// Gets the distance to the object implied by its redshift under a linear Hubble flow, in megaparsecs. Returns
+    /// None if the redshift is missing or not positive. The estimate is poor for nearby objects dominated by
+    /// peculiar velocities.
+    pub fn hubble_distance_mpc(&self) -> Option<f64> {
+        self.redshift
+            .filter(|redshift| *redshift > 0.0)
+            .map(|redshift| redshift * SPEED_OF_LIGHT_KM_S / HUBBLE_CONSTANT)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct NedLookupResponse {
+    #[serde(rename = "Preferred")]
+    preferred: Option<NedPreferred>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NedPreferred {
+    #[serde(rename = "Name")]
+    name: String,
+    #[serde(rename = "Position")]
+    position: Option<NedPosition>,
+    #[serde(rename = "ObjType")]
+    object_type: Option<NedValue<String>>,
+    #[serde(rename = "Redshift")]
+    redshift: Option<NedRedshift>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NedPosition {
+    #[serde(rename = "RA")]
+    ra: Option<f64>,
+    #[serde(rename = "Dec")]
+    dec: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NedValue<T> {
+    #[serde(rename = "Value")]
+    value: Option<T>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NedRedshift {
+    #[serde(rename = "Value")]
+    value: Option<f64>,
+    #[serde(rename = "Uncertainty")]
+    uncertainty: Option<f64>,
+}
+
+impl EarendelServer {
+    /// Gets the NED details of the object with the given name. Returns an error if the object is unknown or if the
+    /// web request fails.
+    #[instrument(skip(self))]
+    pub async fn get_ned_object(
+        &self,
+        name: &str,
+    ) -> Result<NedObject, Box<dyn Error + Send + Sync>> {
+        let lookup = json!({ "name": { "v": name } }).to_string();
+        let request = self
+            .client
+            .post(NED_LOOKUP_URL)
+            .form(&[("json", lookup.as_str())]);
+        let resp = self
+            .send(Upstream::Ned, request)
+            .await?
+            .error_for_status()?;
+        let body = resp.text().await?;
+        let preferred = self
+            .parse::<NedLookupResponse>(Upstream::Ned, &body)?
+            .preferred
+            .ok_or_else(|| format!("NED does not know the object {}", name))?;
+
+        Ok(NedObject {
+            name: preferred.name,
+            object_type: preferred
+                .object_type
+                .and_then(|object_type| object_type.value),
+            ra: preferred.position.as_ref().and_then(|position| position.ra),
+            dec: preferred
+                .position
+                .as_ref()
+                .and_then(|position| position.dec),
+            redshift: preferred
+                .redshift
+                .as_ref()
+                .and_then(|redshift| redshift.value),
+            redshift_uncertainty: preferred
+                .redshift
+                .as_ref()
+                .and_then(|redshift| redshift.uncertainty),
+        })
+    }
+
+    /// Gets the NED details of the target of the current APOD. Returns an error if the target is unknown to NED or if
+    /// the web request fails.
+    pub async fn get_ned_object_for_apod(&self) -> Result<NedObject, Box<dyn Error + Send + Sync>> {
+        self.get_ned_object(self.apod_target_name()).await
+    }
+}
@@ -0,0 +1,164 @@
+//! Close approaches of near-Earth objects from the NASA NeoWs feed.
+
+use chrono::NaiveDate;
+
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use tracing::instrument;
+
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use crate::{EarendelServer, Upstream};
+
+const NEO_FEED_URL: &str = "https://api.nasa.gov/neo/rest/v1/feed";
+/// The longest date range accepted by the feed, in days.
+const MAX_FEED_DAYS: i64 = 7;
+
+/// A close approach of a near-Earth object.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CloseApproach {
+    /// The NeoWs identifier of the object.
+    pub id: String,
+    /// The name of the object.
+    pub name: String,
+    /// The URL of the JPL Small-Body Database page of the object.
+    pub jpl_url: String,
+    /// The absolute magnitude (H) of the object.
+    pub absolute_magnitude: f64,
+    /// The lower estimate of the diameter of the object, in meters.
+    pub diameter_min_m: f64,
+    /// The upper estimate of the diameter of the object, in meters.
+    pub diameter_max_m: f64,
+    /// Whether the object is classified as potentially hazardous.
+    pub potentially_hazardous: bool,
+    /// The date of the close approach.
+    pub date: NaiveDate,
+    /// The velocity of the object relative to the orbited body, in kilometers per second.
+    pub relative_velocity_km_s: f64,
+    /// The distance of the object from the orbited body at closest approach, in kilometers.
+    pub miss_distance_km: f64,
+    /// The distance of the object from the orbited body at closest approach, in lunar distances.
+    pub miss_distance_lunar: f64,
+    /// The body approached, usually Earth.
+    pub orbiting_body: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct NeoFeed {
+    near_earth_objects: BTreeMap<String, Vec<NeoObject>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NeoObject {
+    id: String,
+    name: String,
+    nasa_jpl_url: String,
+    absolute_magnitude_h: f64,
+    estimated_diameter: NeoDiameters,
+    is_potentially_hazardous_asteroid: bool,
+    close_approach_data: Vec<NeoApproach>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NeoDiameters {
+    meters: NeoDiameter,
+}
+
+#[derive(Debug, Deserialize)]
+struct NeoDiameter {
+    estimated_diameter_min: f64,
+    estimated_diameter_max: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct NeoApproach {
+    close_approach_date: NaiveDate,
+    relative_velocity: NeoVelocity,
+    miss_distance: NeoDistance,
+    orbiting_body: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct NeoVelocity {
+    #[serde(deserialize_with = "string_f64")]
+    kilometers_per_second: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct NeoDistance {
+    #[serde(deserialize_with = "string_f64")]
+    kilometers: f64,
+    #[serde(deserialize_with = "string_f64")]
+    lunar: f64,
+}
+
+/// Deserializes a number that the feed encodes as a string.
+fn string_f64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    String::deserialize(deserializer)?
+        .parse()
+        .map_err(D::Error::custom)
+}
+
+impl EarendelServer {
+    /// Gets the close approaches of near-Earth objects between the given dates, inclusive, in chronological order. The
+    /// range may span at most 7 days. Returns an error if the range is invalid, if the web request fails, or if
+    /// deserialization fails.
+    #[instrument(skip(self))]
+    pub async fn get_neo_feed(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<Vec<CloseApproach>, Box<dyn Error + Send + Sync>> {
+        let days = (end - start).num_days();
+        if !(0..MAX_FEED_DAYS).contains(&days) {
+            return Err(format!(
+                "NEO feed range must span between 1 and {} days",
+                MAX_FEED_DAYS
+            )
+            .into());
+        }
+
+        let request = self.nasa_api_get(NEO_FEED_URL)?.query(&[
+            ("start_date", start.to_string()),
+            ("end_date", end.to_string()),
+        ]);
+        let resp = self
+            .send(Upstream::Neo, request)
+            .await?
+            .error_for_status()?;
+        self.record_rate_limit(resp.headers());
+        let body = resp.text().await?;
+        let feed = self.parse::<NeoFeed>(Upstream::Neo, &body)?;
+
+        let mut approaches = feed
+            .near_earth_objects
+            .into_values()
+            .flatten()
+            .flat_map(|object| {
+                object
+                    .close_approach_data
+                    .into_iter()
+                    .map(|approach| CloseApproach {
+                        id: object.id.to_owned(),
+                        name: object.name.to_owned(),
+                        jpl_url: object.nasa_jpl_url.to_owned(),
+                        absolute_magnitude: object.absolute_magnitude_h,
+                        diameter_min_m: object.estimated_diameter.meters.estimated_diameter_min,
+                        diameter_max_m: object.estimated_diameter.meters.estimated_diameter_max,
+                        potentially_hazardous: object.is_potentially_hazardous_asteroid,
+                        date: approach.close_approach_date,
+                        relative_velocity_km_s: approach.relative_velocity.kilometers_per_second,
+                        miss_distance_km: approach.miss_distance.kilometers,
+                        miss_distance_lunar: approach.miss_distance.lunar,
+                        orbiting_body: approach.orbiting_body,
+                    })
+                    .collect::<Vec<CloseApproach>>()
+            })
+            .collect::<Vec<CloseApproach>>();
+        approaches.sort_by_key(|approach| approach.date);
+
+        Ok(approaches)
+    }
+}
@@ -0,0 +1,153 @@
+//! The observability of a target from a location on the Earth over a night, computed locally.
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "mast")]
+use tracing::instrument;
+
+#[cfg(feature = "mast")]
+use std::error::Error;
+
+#[cfg(feature = "mast")]
+use crate::coords::icrs_to_degrees;
+use crate::sun::{hour_angle, sun_altitude, SUNRISE_ALTITUDE};
+#[cfg(feature = "mast")]
+use crate::EarendelServer;
+use crate::Observer;
+
+/// The altitude of the Sun below which the sky is dark enough for faint targets, in degrees.
+const DARK_SKY_SUN_ALTITUDE: f64 = -18.0;
+/// The interval between the positions of a target computed over a night, in minutes.
+const SAMPLE_STEP_MINUTES: i64 = 10;
+
+/// The position of a target in the sky of an observer at a time during the night.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SkyPosition {
+    /// The time of the position.
+    pub time: DateTime<Utc>,
+    /// The altitude of the target above the horizon, in degrees.
+    pub altitude: f64,
+    /// The azimuth of the target, in degrees east of north.
+    pub azimuth: f64,
+    /// Whether the sky is dark at the time, with the Sun at least 18 degrees below the horizon.
+    pub dark: bool,
+}
+
+/// The observability of a target from a location over a night.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ObservabilityReport {
+    /// The local date of the evening starting the night.
+    pub date: NaiveDate,
+    /// The positions of the target at regular intervals while the Sun is below the horizon, in chronological order.
+    pub positions: Vec<SkyPosition>,
+    /// The lowest altitude at which the target was considered observable, in degrees.
+    pub min_altitude: f64,
+    /// Whether the target is above the minimum altitude at any time while the sky is dark.
+    pub observable: bool,
+    /// The first time the target is observable, if it is observable.
+    pub observable_from: Option<DateTime<Utc>>,
+    /// The last time the target is observable, if it is observable.
+    pub observable_until: Option<DateTime<Utc>>,
+    /// The time the target is highest while the sky is dark, which is the best time to observe it, if it is
+    /// observable.
+    pub best_time: Option<DateTime<Utc>>,
+    /// The highest altitude of the target while the Sun is below the horizon, in degrees, or None if the Sun does not
+    /// set.
+    pub max_altitude: Option<f64>,
+}
+
+/// Gets the altitude and azimuth, in degrees, of the given equatorial coordinates, in radians, for the given observer
+/// at the given time.
+fn horizontal(observer: &Observer, ra: f64, dec: f64, time: DateTime<Utc>) -> (f64, f64) {
+    let latitude = observer.latitude.to_radians();
+    let hour_angle = hour_angle(observer, ra, time);
+
+    let altitude = (latitude.sin() * dec.sin() + latitude.cos() * dec.cos() * hour_angle.cos())
+        .clamp(-1.0, 1.0)
+        .asin();
+    let azimuth = (-hour_angle.sin() * dec.cos())
+        .atan2(dec.sin() * latitude.cos() - dec.cos() * latitude.sin() * hour_angle.cos());
+
+    (
+        altitude.to_degrees(),
+        azimuth.to_degrees().rem_euclid(360.0),
+    )
+}
+
+/// Computes the observability of the target at the given ICRS right ascension and declination, in degrees, from the
+/// given observer over the night starting on the given local date. The target is observable while the sky is dark
+/// and the target is at least the given altitude above the horizon, in degrees. Precession since J2000 is ignored,
+/// which shifts positions by well under a degree.
+pub fn observability(
+    ra: f64,
+    dec: f64,
+    observer: &Observer,
+    date: NaiveDate,
+    min_altitude: f64,
+) -> ObservabilityReport {
+    // the night runs from local mean noon to the next local mean noon
+    let noon = date.and_hms_opt(12, 0, 0).unwrap_or_default().and_utc()
+        - Duration::seconds((observer.longitude * 240.0).round() as i64);
+    let steps = 24 * 60 / SAMPLE_STEP_MINUTES;
+
+    let positions = (0..=steps)
+        .map(|step| noon + Duration::minutes(step * SAMPLE_STEP_MINUTES))
+        .filter_map(|time| {
+            let sun_altitude = sun_altitude(observer, time);
+            if sun_altitude >= SUNRISE_ALTITUDE {
+                return None;
+            }
+            let (altitude, azimuth) = horizontal(observer, ra.to_radians(), dec.to_radians(), time);
+            Some(SkyPosition {
+                time,
+                altitude,
+                azimuth,
+                dark: sun_altitude < DARK_SKY_SUN_ALTITUDE,
+            })
+        })
+        .collect::<Vec<SkyPosition>>();
+
+    let observable = positions
+        .iter()
+        .filter(|position| position.dark && position.altitude >= min_altitude)
+        .collect::<Vec<&SkyPosition>>();
+    let best_time = observable
+        .iter()
+        .max_by(|a, b| a.altitude.total_cmp(&b.altitude))
+        .map(|position| position.time);
+
+    ObservabilityReport {
+        date,
+        min_altitude,
+        observable: !observable.is_empty(),
+        observable_from: observable.first().map(|position| position.time),
+        observable_until: observable.last().map(|position| position.time),
+        best_time,
+        max_altitude: positions
+            .iter()
+            .map(|position| position.altitude)
+            .reduce(f64::max),
+        positions,
+    }
+}
+
+#[cfg(feature = "mast")]
+impl EarendelServer {
+    /// Computes the observability of the current APOD's target from the given observer over the night starting on
+    /// the given local date, counting the target as observable while the sky is dark and the target is at least the
+    /// given altitude above the horizon, in degrees. Returns an error if the target cannot be resolved.
+    #[instrument(skip(self))]
+    pub async fn get_apod_observability(
+        &self,
+        observer: &Observer,
+        date: NaiveDate,
+        min_altitude: f64,
+    ) -> Result<ObservabilityReport, Box<dyn Error + Send + Sync>> {
+        let coords = self.resolve_apod_target().await?;
+        let (ra, dec) = icrs_to_degrees(&coords);
+
+        Ok(observability(ra, dec, observer, date, min_altitude))
+    }
+}
@@ -0,0 +1,237 @@
+//! Compositing of attribution text onto images, for digital signage and wallpapers that must credit the image.
+
+use ab_glyph::{point, Font, FontVec, GlyphId, PxScale, ScaleFont};
+
+use image::{DynamicImage, ImageFormat, ImageOutputFormat, Rgba, RgbaImage};
+
+use serde::{Deserialize, Serialize};
+
+use std::error::Error;
+use std::io::Cursor;
+
+/// The quality of JPEG output.
+const JPEG_QUALITY: u8 = 90;
+/// The size of the copyright line, relative to the title.
+const COPYRIGHT_SCALE: f32 = 0.75;
+/// The space between lines, relative to the size of the title.
+const LINE_SPACING: f32 = 0.25;
+/// The space between the text and the edges of its background, relative to the size of the title.
+const BACKGROUND_PADDING: f32 = 0.5;
+
+/// The corner of the image the attribution is drawn in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum OverlayPosition {
+    /// The top left corner.
+    TopLeft,
+    /// The top right corner.
+    TopRight,
+    /// The bottom left corner.
+    #[default]
+    BottomLeft,
+    /// The bottom right corner.
+    BottomRight,
+}
+
+/// The options used to draw an attribution onto an image.
+#[derive(Clone, Debug)]
+pub struct OverlayOptions {
+    /// The TrueType or OpenType font the text is drawn with.
+    pub font: Vec<u8>,
+    /// The height of the title, as a fraction of the height of the image, so that the text keeps its proportions at
+    /// any resolution. The copyright line is drawn smaller.
+    pub size: f32,
+    /// The corner the text is drawn in, at a margin of the height of the title from the edges.
+    pub position: OverlayPosition,
+    /// The red, green, and blue channels of the text.
+    pub color: [u8; 3],
+    /// The opacity of the text, from 0 to 1.
+    pub opacity: f32,
+    /// The opacity of the black box drawn behind the text to keep it legible, from 0 for no box to 1.
+    pub background_opacity: f32,
+}
+
+impl OverlayOptions {
+    /// Creates options drawing white text with the given font in the bottom left corner, over a translucent box.
+    pub fn new(font: Vec<u8>) -> Self {
+        OverlayOptions {
+            font,
+            size: 0.03,
+            position: OverlayPosition::default(),
+            color: [255, 255, 255],
+            opacity: 0.9,
+            background_opacity: 0.5,
+        }
+    }
+}
+
+/// Draws the given title and copyright onto the given encoded image, as described by the given options, and encodes
+/// the result in the same format as the original. Lines too wide for the image are drawn smaller to fit. Returns an
+/// error if the font is invalid or if the image cannot be decoded or encoded.
+pub fn overlay_attribution(
+    img: &[u8],
+    title: &str,
+    copyright: Option<&str>,
+    options: &OverlayOptions,
+) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    let font = FontVec::try_from_vec(options.font.to_owned())?;
+    let format = image::guess_format(img)?;
+    let mut canvas = image::load_from_memory_with_format(img, format)?.to_rgba8();
+    let (width, height) = (canvas.width() as f32, canvas.height() as f32);
+    let title_size = (height * options.size).max(1.0);
+    let max_width = (width - 2.0 * title_size).max(1.0);
+
+    let mut lines = vec![(title.trim().to_owned(), title_size)];
+    // the API breaks long credits over several lines
+    let copyright = copyright
+        .unwrap_or_default()
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ");
+    if !copyright.is_empty() {
+        lines.push((
+            format!("\u{a9} {}", copyright),
+            title_size * COPYRIGHT_SCALE,
+        ));
+    }
+    let lines = lines
+        .into_iter()
+        .map(|(text, size)| {
+            let text_width = text_width(&font, &text, size);
+            if text_width > max_width {
+                (text, size * max_width / text_width, max_width)
+            } else {
+                (text, size, text_width)
+            }
+        })
+        .collect::<Vec<(String, f32, f32)>>();
+
+    let spacing = title_size * LINE_SPACING;
+    let block_width = lines.iter().map(|line| line.2).fold(0.0, f32::max);
+    let block_height = lines.iter().map(|line| line.1).sum::<f32>()
+        + spacing * lines.len().saturating_sub(1) as f32;
+    let left = match options.position {
+        OverlayPosition::TopLeft | OverlayPosition::BottomLeft => title_size,
+        OverlayPosition::TopRight | OverlayPosition::BottomRight => {
+            width - title_size - block_width
+        }
+    };
+    let top = match options.position {
+        OverlayPosition::TopLeft | OverlayPosition::TopRight => title_size,
+        OverlayPosition::BottomLeft | OverlayPosition::BottomRight => {
+            height - title_size - block_height
+        }
+    };
+
+    if options.background_opacity > 0.0 {
+        let padding = title_size * BACKGROUND_PADDING;
+        let x_range = (left - padding).max(0.0) as u32..((left + block_width + padding) as u32);
+        let y_range = (top - padding).max(0.0) as u32..((top + block_height + padding) as u32);
+        for y in y_range.filter(|&y| y < canvas.height()) {
+            for x in x_range.clone().filter(|&x| x < canvas.width()) {
+                blend(
+                    canvas.get_pixel_mut(x, y),
+                    [0, 0, 0],
+                    options.background_opacity.clamp(0.0, 1.0),
+                );
+            }
+        }
+    }
+
+    let mut y = top;
+    for (text, size, text_width) in lines {
+        let x = match options.position {
+            OverlayPosition::TopLeft | OverlayPosition::BottomLeft => left,
+            // right-aligned text lines up with the edge of the image
+            OverlayPosition::TopRight | OverlayPosition::BottomRight => {
+                left + block_width - text_width
+            }
+        };
+        draw_text(&mut canvas, &font, &text, size, (x, y), options);
+        y += size + spacing;
+    }
+
+    let (output_image, output_format) = match format {
+        // JPEG has no alpha channel
+        ImageFormat::Jpeg => (
+            DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(canvas).to_rgb8()),
+            ImageOutputFormat::Jpeg(JPEG_QUALITY),
+        ),
+        other => (
+            DynamicImage::ImageRgba8(canvas),
+            ImageOutputFormat::from(other),
+        ),
+    };
+    let mut output = Vec::new();
+    output_image.write_to(&mut Cursor::new(&mut output), output_format)?;
+
+    Ok(output)
+}
+
+/// Measures the width of the given text drawn with the given font at the given size, in pixels.
+fn text_width(font: &FontVec, text: &str, size: f32) -> f32 {
+    let scaled = font.as_scaled(PxScale::from(size));
+    let mut width = 0.0;
+    let mut previous: Option<GlyphId> = None;
+    for c in text.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(previous) = previous {
+            width += scaled.kern(previous, id);
+        }
+        width += scaled.h_advance(id);
+        previous = Some(id);
+    }
+
+    width
+}
+
+/// Draws the given text onto the given image with its top left corner at the given position, in pixels.
+fn draw_text(
+    canvas: &mut RgbaImage,
+    font: &FontVec,
+    text: &str,
+    size: f32,
+    (x, y): (f32, f32),
+    options: &OverlayOptions,
+) {
+    let scale = PxScale::from(size);
+    let scaled = font.as_scaled(scale);
+    let baseline = y + scaled.ascent();
+    let opacity = options.opacity.clamp(0.0, 1.0);
+    let mut caret = x;
+    let mut previous: Option<GlyphId> = None;
+    for c in text.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(previous) = previous {
+            caret += scaled.kern(previous, id);
+        }
+        let glyph = id.with_scale_and_position(scale, point(caret, baseline));
+        caret += scaled.h_advance(id);
+        previous = Some(id);
+
+        let Some(outlined) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|glyph_x, glyph_y, coverage| {
+            let pixel_x = bounds.min.x as i64 + i64::from(glyph_x);
+            let pixel_y = bounds.min.y as i64 + i64::from(glyph_y);
+            if (0..i64::from(canvas.width())).contains(&pixel_x)
+                && (0..i64::from(canvas.height())).contains(&pixel_y)
+            {
+                blend(
+                    canvas.get_pixel_mut(pixel_x as u32, pixel_y as u32),
+                    options.color,
+                    coverage * opacity,
+                );
+            }
+        });
+    }
+}
+
+/// Blends the given color over the given pixel with the given opacity, from 0 to 1.
+fn blend(pixel: &mut Rgba<u8>, color: [u8; 3], opacity: f32) {
+    for (channel, value) in pixel.0.iter_mut().zip(color) {
+        *channel =
+            (f32::from(*channel) * (1.0 - opacity) + f32::from(value) * opacity).round() as u8;
+    }
+}
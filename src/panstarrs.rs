@@ -0,0 +1,170 @@
+//! Image cutouts from the Pan-STARRS1 image server.
+
+use astro_rs::coordinates::Icrs;
+
+use serde::{Deserialize, Serialize};
+
+use tracing::instrument;
+
+use uom::si::angle::arcsecond;
+use uom::si::f64::Angle;
+
+use std::collections::HashMap;
+use std::error::Error;
+
+use crate::coords::icrs_to_degrees;
+use crate::{CutoutFormat, EarendelCutout, EarendelServer, Upstream};
+
+const PS1_FILENAMES_URL: &str = "https://ps1images.stsci.edu/cgi-bin/ps1filenames.py";
+const PS1_FITSCUT_URL: &str = "https://ps1images.stsci.edu/cgi-bin/fitscut.cgi";
+/// The pixel scale of Pan-STARRS1 stacked images, in arcseconds per pixel.
+const PS1_PIXEL_SCALE: f64 = 0.25;
+/// The width and height of returned cutouts, in pixels.
+const CUTOUT_SIZE: u32 = 512;
+
+/// A Pan-STARRS1 filter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum Ps1Filter {
+    /// The g filter.
+    G,
+    /// The r filter.
+    R,
+    /// The i filter.
+    I,
+    /// The z filter.
+    Z,
+    /// The y filter.
+    Y,
+}
+
+impl Ps1Filter {
+    fn as_param(&self) -> &'static str {
+        match self {
+            Ps1Filter::G => "g",
+            Ps1Filter::R => "r",
+            Ps1Filter::I => "i",
+            Ps1Filter::Z => "z",
+            Ps1Filter::Y => "y",
+        }
+    }
+}
+
+/// The bands combined into a Pan-STARRS1 cutout.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Ps1Bands {
+    /// A color composite of the i, r, and g filters.
+    Color,
+    /// A single filter.
+    Single(Ps1Filter),
+}
+
+impl EarendelServer {
+    /// Gets a square Pan-STARRS1 cutout centered on the given coordinates. Pan-STARRS1 covers the sky north of
+    /// declination -30 degrees, and color cutouts cannot be returned as FITS. Returns an error if the position is not
+    /// covered or if a web request fails.
+    #[instrument(skip(self, coords, fov))]
+    pub async fn get_panstarrs_cutout(
+        &self,
+        coords: &Icrs,
+        fov: Angle,
+        bands: Ps1Bands,
+        format: CutoutFormat,
+    ) -> Result<EarendelCutout, Box<dyn Error + Send + Sync>> {
+        let filters = match bands {
+            Ps1Bands::Color => vec![Ps1Filter::I, Ps1Filter::R, Ps1Filter::G],
+            Ps1Bands::Single(filter) => vec![filter],
+        };
+        if filters.len() > 1 && format == CutoutFormat::Fits {
+            return Err("Pan-STARRS1 color cutouts cannot be returned as FITS".into());
+        }
+
+        let (ra, dec) = icrs_to_degrees(coords);
+        let filter_param = filters.iter().map(Ps1Filter::as_param).collect::<String>();
+        let request = self.client.get(PS1_FILENAMES_URL).query(&[
+            ("ra", ra.to_string()),
+            ("dec", dec.to_string()),
+            ("filters", filter_param),
+        ]);
+        let resp = self
+            .send(Upstream::PanStarrs, request)
+            .await?
+            .error_for_status()?;
+        let files = parse_filenames(&resp.text().await?);
+
+        let size = (fov.get::<arcsecond>() / PS1_PIXEL_SCALE).round().max(1.0) as u32;
+        let mut query = vec![
+            ("ra", ra.to_string()),
+            ("dec", dec.to_string()),
+            ("size", size.to_string()),
+            ("output_size", CUTOUT_SIZE.to_string()),
+            ("format", String::from(format.as_param())),
+        ];
+        let channels = if filters.len() > 1 {
+            vec!["red", "green", "blue"]
+        } else {
+            vec!["red"]
+        };
+        for (channel, filter) in channels.into_iter().zip(&filters) {
+            let file = files.get(filter.as_param()).ok_or_else(|| {
+                format!(
+                    "Pan-STARRS1 has no {} image at this position",
+                    filter.as_param()
+                )
+            })?;
+            query.push((channel, file.to_owned()));
+        }
+
+        let request = self.client.get(PS1_FITSCUT_URL).query(&query);
+        let resp = self
+            .send(Upstream::PanStarrs, request)
+            .await?
+            .error_for_status()?;
+        let img = resp.bytes().await?;
+
+        Ok(EarendelCutout {
+            survey: match bands {
+                Ps1Bands::Color => String::from("PS1/color"),
+                Ps1Bands::Single(filter) => format!("PS1/{}", filter.as_param()),
+            },
+            format,
+            img: img.to_vec(),
+        })
+    }
+
+    /// Gets a square Pan-STARRS1 cutout centered on the target of the current APOD. Returns an error if the target
+    /// cannot be resolved, is not covered, or if a web request fails.
+    pub async fn get_panstarrs_cutout_for_apod(
+        &self,
+        fov: Angle,
+        bands: Ps1Bands,
+        format: CutoutFormat,
+    ) -> Result<EarendelCutout, Box<dyn Error + Send + Sync>> {
+        let coords = self.resolve_apod_target().await?;
+
+        self.get_panstarrs_cutout(&coords, fov, bands, format).await
+    }
+}
+
+/// Parses the whitespace-separated table listing the stacked images at a position into image paths keyed by filter.
+fn parse_filenames(body: &str) -> HashMap<String, String> {
+    let mut lines = body.lines();
+    let header = lines
+        .next()
+        .map(|line| line.split_whitespace().collect::<Vec<&str>>())
+        .unwrap_or_default();
+    let filter_index = header.iter().position(|column| *column == "filter");
+    let filename_index = header.iter().position(|column| *column == "filename");
+    let (Some(filter_index), Some(filename_index)) = (filter_index, filename_index) else {
+        return HashMap::new();
+    };
+
+    lines
+        .filter_map(|line| {
+            let fields = line.split_whitespace().collect::<Vec<&str>>();
+            Some((
+                fields.get(filter_index)?.to_string(),
+                fields.get(filename_index)?.to_string(),
+            ))
+        })
+        .collect()
+}
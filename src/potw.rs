@@ -0,0 +1,217 @@
+//! Retrieval of the pictures of the week and month published by ESO and ESA, alternative daily image sources.
+
+use async_trait::async_trait;
+
+use chrono::{DateTime, Utc};
+
+use std::error::Error;
+
+use crate::{DailyImage, DailyImageProvider, EarendelServer, Upstream};
+
+/// The license of the ESO and ESA pictures, which may be reused with credit.
+const PICTURE_LICENSE: &str = "CC BY 4.0";
+
+/// A djangoplicity picture feed, as published by ESO and ESA.
+struct PictureFeed {
+    name: &'static str,
+    upstream: Upstream,
+    feed_url: &'static str,
+    /// The URL of the directory of large images, named by picture identifier.
+    image_url: &'static str,
+    /// The credit used when a picture does not state its own.
+    credit: &'static str,
+}
+
+const ESO_POTW: PictureFeed = PictureFeed {
+    name: "eso-potw",
+    upstream: Upstream::EsoPotw,
+    feed_url: "https://www.eso.org/public/images/potw/feed/",
+    image_url: "https://cdn.eso.org/images/large/",
+    credit: "ESO",
+};
+const ESA_HUBBLE_POTW: PictureFeed = PictureFeed {
+    name: "esa-hubble-potw",
+    upstream: Upstream::EsaHubble,
+    feed_url: "https://esahubble.org/images/potw/feed/",
+    image_url: "https://cdn.esahubble.org/archives/images/large/",
+    credit: "ESA/Hubble & NASA",
+};
+const ESA_WEBB_POTM: PictureFeed = PictureFeed {
+    name: "esa-webb-potm",
+    upstream: Upstream::EsaWebb,
+    feed_url: "https://esawebb.org/images/potm/feed/",
+    image_url: "https://cdn.esawebb.org/archives/images/large/",
+    credit: "ESA/Webb, NASA & CSA",
+};
+
+/// The ESO Picture of the Week, published every Monday, served under the `eso-potw` source.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EsoPotwProvider;
+
+#[async_trait]
+impl DailyImageProvider for EsoPotwProvider {
+    fn name(&self) -> &str {
+        ESO_POTW.name
+    }
+
+    async fn fetch(
+        &self,
+        server: &EarendelServer,
+    ) -> Result<DailyImage, Box<dyn Error + Send + Sync>> {
+        ESO_POTW.fetch(server).await
+    }
+}
+
+/// The ESA/Hubble Picture of the Week, published every Monday, served under the `esa-hubble-potw` source.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EsaHubblePotwProvider;
+
+#[async_trait]
+impl DailyImageProvider for EsaHubblePotwProvider {
+    fn name(&self) -> &str {
+        ESA_HUBBLE_POTW.name
+    }
+
+    async fn fetch(
+        &self,
+        server: &EarendelServer,
+    ) -> Result<DailyImage, Box<dyn Error + Send + Sync>> {
+        ESA_HUBBLE_POTW.fetch(server).await
+    }
+}
+
+/// The ESA/Webb Picture of the Month, served under the `esa-webb-potm` source.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EsaWebbPotmProvider;
+
+#[async_trait]
+impl DailyImageProvider for EsaWebbPotmProvider {
+    fn name(&self) -> &str {
+        ESA_WEBB_POTM.name
+    }
+
+    async fn fetch(
+        &self,
+        server: &EarendelServer,
+    ) -> Result<DailyImage, Box<dyn Error + Send + Sync>> {
+        ESA_WEBB_POTM.fetch(server).await
+    }
+}
+
+impl PictureFeed {
+    /// Fetches the latest picture of the feed.
+    async fn fetch(
+        &self,
+        server: &EarendelServer,
+    ) -> Result<DailyImage, Box<dyn Error + Send + Sync>> {
+        let resp = server
+            .send(self.upstream, server.client.get(self.feed_url))
+            .await?
+            .error_for_status()?;
+        let feed = resp.text().await?;
+        let item =
+            element(&feed, "item").ok_or_else(|| format!("{} feed has no items", self.name))?;
+
+        let title = element(item, "title")
+            .map(text)
+            .ok_or_else(|| format!("{} picture has no title", self.name))?;
+        let date = element(item, "pubDate")
+            .and_then(|date| DateTime::parse_from_rfc2822(date.trim()).ok())
+            .map(|date| date.with_timezone(&Utc).date_naive())
+            .unwrap_or_else(|| Utc::now().date_naive());
+        let page_url = element(item, "link").map(text);
+        // the enclosure is a reduced image, so the large image is preferred when the identifier is known
+        let image_url = page_url
+            .as_deref()
+            .and_then(|link| {
+                let id = link.trim_end_matches('/').rsplit('/').next()?;
+                (!id.is_empty()).then(|| [self.image_url, id, ".jpg"].concat())
+            })
+            .or_else(|| attribute(item, "enclosure", "url"))
+            .ok_or_else(|| format!("{} picture has no image", self.name))?;
+        let description = element(item, "description").map(text).unwrap_or_default();
+        let (explanation, credit) = match description.split_once("Credit:") {
+            Some((explanation, credit)) => (explanation.trim(), credit.trim()),
+            None => (description.trim(), self.credit),
+        };
+
+        let resp = server
+            .send(self.upstream, server.client.get(&image_url))
+            .await?
+            .error_for_status()?;
+        let img = server.read_limited(resp).await?;
+
+        Ok(DailyImage {
+            source: String::from(self.name),
+            title,
+            date,
+            image_url,
+            page_url,
+            credit: Some(credit.to_owned()),
+            license: Some(String::from(PICTURE_LICENSE)),
+            explanation: (!explanation.is_empty()).then(|| explanation.to_owned()),
+            img,
+        })
+    }
+}
+
+/// Gets the contents of the first element with the given name in the given XML, without parsing it fully.
+pub(crate) fn element<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let open = xml
+        .find(&format!("<{}>", name))
+        .map(|start| start + name.len() + 2)?;
+    let close = xml[open..].find(&format!("</{}>", name))?;
+
+    Some(&xml[open..open + close])
+}
+
+/// Gets the value of the given attribute of the first element with the given name in the given XML.
+pub(crate) fn attribute(xml: &str, name: &str, attribute: &str) -> Option<String> {
+    let start = xml.find(&format!("<{} ", name))?;
+    let tag = &xml[start..start + xml[start..].find('>')?];
+    let value = tag.split_once(&format!("{}=\"", attribute))?.1;
+
+    Some(unescape(&value[..value.find('"')?]))
+}
+
+/// Converts the given element contents to plain text, removing any CDATA section and HTML markup.
+pub(crate) fn text(contents: &str) -> String {
+    let contents = contents.trim();
+    let contents = contents
+        .strip_prefix("<![CDATA[")
+        .and_then(|contents| contents.strip_suffix("]]>"))
+        .map(String::from)
+        .unwrap_or_else(|| unescape(contents));
+
+    let mut text = String::with_capacity(contents.len());
+    let mut in_tag = false;
+    for c in contents.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+
+    // markup is replaced by spaces, which must not separate words from their punctuation
+    let text = text
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .replace(" ,", ",")
+        .replace(" .", ".");
+
+    unescape(&text)
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
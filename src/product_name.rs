@@ -0,0 +1,193 @@
+//! Parsing of the naming conventions of HST and JWST observation identifiers and product file names.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{MastProduct, Observation};
+
+/// The structured parts of an HST or JWST observation identifier or product file name, such as `ib2j02010_drz.fits`
+/// or `jw02736001001_02105_00001_nrcb1_cal.fits`, used to group and label products.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ProductName {
+    /// The mission, either `HST` or `JWST`.
+    pub mission: String,
+    /// The instrument, such as `WFC3` or `NIRCAM`, if the name identifies it.
+    pub instrument: Option<String>,
+    /// The detector, such as `UVIS` or `NRCB1`, if the name identifies it.
+    pub detector: Option<String>,
+    /// The proposal or program identifier, such as `b2j` for HST or `02736` for JWST.
+    pub program: String,
+    /// The observation number within the program, if the name identifies it. JWST only.
+    pub observation: Option<String>,
+    /// The visit or observation set, if the name identifies it.
+    pub visit: Option<String>,
+    /// The exposure identifier, if the name identifies a single exposure.
+    pub exposure: Option<String>,
+    /// The filters or other optical elements, such as `f606w` or `clear-f090w`, if the name identifies them.
+    pub optical_elements: Option<String>,
+    /// The product type suffix, such as `drz` or `cal`, if the name is that of a product file.
+    pub suffix: Option<String>,
+}
+
+impl ProductName {
+    /// Parses the given observation identifier or product file name, which may be a path or URL. Returns None if it
+    /// does not follow a known HST or JWST convention.
+    pub fn parse(name: &str) -> Option<Self> {
+        let name = name.split(['?', '#']).next()?;
+        let name = name.rsplit(['/', ':']).next()?;
+        // the extension may be compound, such as `.fits.gz`
+        let stem = name.split('.').next()?.to_ascii_lowercase();
+
+        if stem.starts_with("jw") {
+            parse_jwst(&stem)
+        } else if let Some(rest) = stem.strip_prefix("hst_") {
+            parse_hap(rest)
+        } else {
+            parse_ipppssoot(&stem)
+        }
+    }
+}
+
+/// Parses a classic HST name, such as `ib2j02010_drz`, whose identifier is of the form `ipppssoot`: the instrument,
+/// the program, the observation set, the observation, and the data source.
+fn parse_ipppssoot(stem: &str) -> Option<ProductName> {
+    let (id, suffix) = match stem.split_once('_') {
+        Some((id, suffix)) => (id, Some(suffix)),
+        None => (stem, None),
+    };
+    if id.len() != 9 || !id.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+    let instrument = match id.as_bytes()[0] {
+        b'f' => "FGS",
+        b'i' => "WFC3",
+        b'j' => "ACS",
+        b'l' => "COS",
+        b'n' => "NICMOS",
+        b'o' => "STIS",
+        b'u' => "WFPC2",
+        b'v' => "HSP",
+        b'w' => "WFPC",
+        b'x' => "FOC",
+        b'y' => "FOS",
+        b'z' => "GHRS",
+        _ => return None,
+    };
+
+    Some(ProductName {
+        mission: String::from("HST"),
+        instrument: Some(String::from(instrument)),
+        detector: None,
+        program: id[1..4].to_owned(),
+        observation: None,
+        visit: Some(id[4..6].to_owned()),
+        exposure: Some(id[6..9].to_owned()),
+        optical_elements: None,
+        suffix: suffix.map(str::to_owned),
+    })
+}
+
+/// Parses an HST Advanced Product name following its `hst_` prefix, such as
+/// `12345_01_wfc3_uvis_f606w_ib2j01_drc`: the proposal, the visit, the instrument, the detector, the filter or
+/// `total`, the optional exposure, and the suffix.
+fn parse_hap(rest: &str) -> Option<ProductName> {
+    let parts = rest.split('_').collect::<Vec<&str>>();
+    // skycell mosaics are named by their position rather than a proposal
+    if parts.len() < 4 || !parts[0].chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let elements = &parts[4..];
+    // observation identifiers omit the suffix, so an exposure is told apart by its `ipppss` form
+    let is_exposure = |element: &str| {
+        matches!(element.len(), 6 | 8 | 9)
+            && element.chars().all(|c| c.is_ascii_alphanumeric())
+            && element.chars().any(|c| c.is_ascii_digit())
+    };
+
+    Some(ProductName {
+        mission: String::from("HST"),
+        instrument: Some(parts[2].to_ascii_uppercase()),
+        detector: Some(parts[3].to_ascii_uppercase()),
+        program: parts[0].to_owned(),
+        observation: None,
+        visit: Some(parts[1].to_owned()),
+        exposure: elements
+            .get(1)
+            .filter(|exposure| is_exposure(exposure))
+            .map(|exposure| (*exposure).to_owned()),
+        optical_elements: elements.first().map(|element| (*element).to_owned()),
+        suffix: elements
+            .last()
+            .filter(|suffix| elements.len() > 1 && !is_exposure(suffix))
+            .map(|suffix| (*suffix).to_owned()),
+    })
+}
+
+/// Parses a JWST name, either of an exposure, such as `jw02736001001_02105_00001_nrcb1_cal`, or of a combined
+/// product, such as `jw02736-o001_t001_nircam_clear-f090w_i2d`.
+fn parse_jwst(stem: &str) -> Option<ProductName> {
+    let parts = stem.split('_').collect::<Vec<&str>>();
+    let id = parts[0].strip_prefix("jw")?;
+
+    if let Some((program, observation)) = id.split_once('-') {
+        // combined products are numbered by observation (`o`), candidate (`c`), or association (`a`)
+        if parts.len() < 4 {
+            return None;
+        }
+        return Some(ProductName {
+            mission: String::from("JWST"),
+            instrument: Some(parts[2].to_ascii_uppercase()),
+            detector: None,
+            program: program.to_owned(),
+            observation: Some(observation.get(1..)?.to_owned()),
+            visit: None,
+            exposure: None,
+            optical_elements: Some(parts[3].to_owned()),
+            suffix: parts.get(4).map(|suffix| (*suffix).to_owned()),
+        });
+    }
+
+    if id.len() != 11 || !id.chars().all(|c| c.is_ascii_digit()) || parts.len() < 4 {
+        return None;
+    }
+    let detector = parts[3];
+    let instrument = if detector.starts_with("nrc") {
+        Some("NIRCAM")
+    } else if detector.starts_with("nrs") {
+        Some("NIRSPEC")
+    } else if detector.starts_with("nis") {
+        Some("NIRISS")
+    } else if detector.starts_with("mir") {
+        Some("MIRI")
+    } else if detector.starts_with("guider") {
+        Some("FGS")
+    } else {
+        None
+    };
+
+    Some(ProductName {
+        mission: String::from("JWST"),
+        instrument: instrument.map(String::from),
+        detector: Some(detector.to_ascii_uppercase()),
+        program: id[..5].to_owned(),
+        observation: Some(id[5..8].to_owned()),
+        visit: Some(id[8..11].to_owned()),
+        exposure: Some(parts[2].to_owned()),
+        optical_elements: None,
+        suffix: parts.get(4).map(|suffix| (*suffix).to_owned()),
+    })
+}
+
+impl Observation {
+    /// Parses the identifier of the observation by the HST and JWST naming conventions. Returns None for other
+    /// missions.
+    pub fn product_name(&self) -> Option<ProductName> {
+        ProductName::parse(&self.obs_id)
+    }
+}
+
+impl MastProduct {
+    /// Parses the file name of the product by the HST and JWST naming conventions. Returns None for other missions.
+    pub fn product_name(&self) -> Option<ProductName> {
+        ProductName::parse(&self.filename)
+    }
+}
@@ -0,0 +1,80 @@
+//! Python bindings, built as the `earendel` extension module.
+//!
+//! The bindings are blocking: each call runs to completion on a runtime owned by the Python `EarendelServer`, with the
+//! GIL released while it waits. Results are converted to Python dictionaries, with images as `bytes`.
+
+use chrono::NaiveDate;
+
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+
+use pythonize::pythonize;
+
+use tokio::runtime::Runtime;
+
+use std::error::Error;
+
+use crate::EarendelServer;
+
+fn to_py_err(error: Box<dyn Error + Send + Sync>) -> PyErr {
+    PyRuntimeError::new_err(error.to_string())
+}
+
+/// The manager of the Earendel functionality and state.
+#[pyclass(name = "EarendelServer")]
+struct PyEarendelServer {
+    server: EarendelServer,
+    runtime: Runtime,
+}
+
+#[pymethods]
+impl PyEarendelServer {
+    /// Creates a server, optionally preferring high-resolution APOD images.
+    #[new]
+    #[pyo3(signature = (prefer_hd = false))]
+    fn new(prefer_hd: bool) -> PyResult<Self> {
+        Ok(PyEarendelServer {
+            server: EarendelServer::builder().prefer_hd(prefer_hd).build(),
+            runtime: Runtime::new()?,
+        })
+    }
+
+    /// Gets the current APOD as a dictionary.
+    fn get_apod_image(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let (server, runtime) = (&self.server, &self.runtime);
+        let apod = py
+            .allow_threads(|| runtime.block_on(server.get_apod_image()))
+            .map_err(to_py_err)?;
+
+        Ok(pythonize(py, &apod)?)
+    }
+
+    /// Gets the APOD of the given date as a dictionary.
+    fn get_apod_image_for_date(&self, py: Python<'_>, date: NaiveDate) -> PyResult<PyObject> {
+        let (server, runtime) = (&self.server, &self.runtime);
+        let apod = py
+            .allow_threads(|| runtime.block_on(server.get_apod_image_for_date(date)))
+            .map_err(to_py_err)?;
+
+        Ok(pythonize(py, &apod)?)
+    }
+
+    /// Gets a page of FITS observations of the current APOD target as a dictionary.
+    #[pyo3(signature = (page = 0))]
+    fn get_fits_for_apod(&self, py: Python<'_>, page: usize) -> PyResult<PyObject> {
+        let (server, runtime) = (&self.server, &self.runtime);
+        let fits = py
+            .allow_threads(|| runtime.block_on(server.get_fits_for_apod(page)))
+            .map_err(to_py_err)?;
+
+        Ok(pythonize(py, &fits)?)
+    }
+}
+
+/// The `earendel` Python module.
+#[pymodule]
+fn earendel(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyEarendelServer>()?;
+
+    Ok(())
+}
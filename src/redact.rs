@@ -0,0 +1,89 @@
+//! Redaction of API keys and other credentials from text that is logged or reported, such as request URLs.
+
+use reqwest::header::{HeaderMap, AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION, SET_COOKIE};
+
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// The text substituted for a redacted credential.
+const REDACTED: &str = "REDACTED";
+/// The query parameters whose values are credentials.
+const SENSITIVE_PARAMS: [&str; 5] = ["api_key", "apikey", "key", "token", "access_token"];
+/// The characters that end the value of a query parameter within a longer text, such as an error message.
+const VALUE_TERMINATORS: [char; 11] = ['&', '#', ' ', '"', '\'', ')', '<', '>', '\n', '\r', '\t'];
+
+/// The credentials configured in code rather than in the environment, such as the keys given to
+/// `EarendelServerBuilder::nasa_api_keys`.
+static REGISTERED_SECRETS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Registers the given credentials, so that `redact` replaces them from then on.
+#[cfg_attr(not(feature = "apod"), allow(dead_code))]
+pub(crate) fn register_secrets<S: AsRef<str>>(secrets: &[S]) {
+    let mut registered = registered_secrets();
+    for secret in secrets.iter().map(AsRef::as_ref) {
+        if !secret.is_empty() && !registered.iter().any(|registered| registered == secret) {
+            registered.push(secret.to_owned());
+        }
+    }
+}
+
+fn registered_secrets() -> MutexGuard<'static, Vec<String>> {
+    REGISTERED_SECRETS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Replaces the NASA API keys, the registered credentials, and the values of credential query parameters, such as
+/// `api_key`, in the given text.
+pub(crate) fn redact(text: &str) -> String {
+    let mut redacted = text.to_owned();
+    #[cfg(feature = "apod")]
+    for key in crate::apod::nasa_api_keys() {
+        redacted = redacted.replace(&key, REDACTED);
+    }
+    for secret in registered_secrets().iter() {
+        redacted = redacted.replace(secret.as_str(), REDACTED);
+    }
+
+    for param in SENSITIVE_PARAMS {
+        let pattern = format!("{}=", param);
+        let mut start = 0;
+        while let Some(found) = redacted[start..].find(&pattern) {
+            let param_start = start + found;
+            let value_start = param_start + pattern.len();
+            // parameters that merely end with the name, such as `api_key` for `key`, are matched by their own name
+            let is_param =
+                !redacted[..param_start].ends_with(|c: char| c.is_ascii_alphanumeric() || c == '_');
+            let value_end = redacted[value_start..]
+                .find(VALUE_TERMINATORS)
+                .map_or(redacted.len(), |end| value_start + end);
+            if is_param && value_end > value_start && &redacted[value_start..value_end] != REDACTED
+            {
+                redacted.replace_range(value_start..value_end, REDACTED);
+                start = value_start + REDACTED.len();
+            } else {
+                start = value_start;
+            }
+        }
+    }
+
+    redacted
+}
+
+/// Formats the given headers for logging, one `name: value` pair per line, with the values of credential headers
+/// replaced.
+pub(crate) fn redact_headers(headers: &HeaderMap) -> String {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let sensitive = [AUTHORIZATION, PROXY_AUTHORIZATION, COOKIE, SET_COOKIE].contains(name)
+                || name.as_str() == "x-api-key";
+            let value = if sensitive {
+                String::from(REDACTED)
+            } else {
+                redact(&String::from_utf8_lossy(value.as_bytes()))
+            };
+            format!("{}: {}", name, value)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
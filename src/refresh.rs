@@ -0,0 +1,89 @@
+//! Background refreshing of the cached APOD.
+
+use chrono::Utc;
+
+use tokio::task::JoinHandle;
+
+use tracing::{info, warn};
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::apod::{next_rollover, PENDING_RECHECK};
+use crate::runtime::sleep;
+use crate::{EarendelError, EarendelServer, Upstream};
+
+/// The maximum random delay added after the daily rollover, so that many servers do not refresh at once.
+const MAX_JITTER: Duration = Duration::from_secs(5 * 60);
+/// The delay before the first retry of a failed refresh. Each subsequent retry waits twice as long.
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+/// The maximum delay between retries of a failed refresh.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30 * 60);
+
+impl EarendelServer {
+    /// Spawns a task that refreshes the APOD cache of the given server immediately and after each daily rollover at
+    /// midnight US Eastern time, so that requests never wait on the first fetch of the day. Until the new APOD is
+    /// published, the cache is refreshed periodically. Failed refreshes are retried with exponential backoff. The task
+    /// runs until it is aborted.
+    pub fn spawn_refresher(server: Arc<EarendelServer>) -> JoinHandle<()> {
+        tokio::spawn(Self::run_refresher(server))
+    }
+
+    /// Refreshes the APOD cache of the given server as `EarendelServer::spawn_refresher` does, for spawning onto an
+    /// executor other than tokio. The future never completes.
+    pub async fn run_refresher(server: Arc<EarendelServer>) {
+        loop {
+            refresh(&server).await;
+            let delay = if server.apod_pending() {
+                PENDING_RECHECK
+            } else {
+                until_rollover() + jitter()
+            };
+            sleep(delay).await;
+        }
+    }
+}
+
+/// Refreshes the APOD cache of the given server, retrying until it succeeds.
+async fn refresh(server: &EarendelServer) {
+    let mut delay = RETRY_DELAY;
+    loop {
+        match server.get_apod_image().await {
+            Ok(apod) => {
+                info!("refreshed APOD: {}", apod.title);
+                return;
+            }
+            // retrying cannot turn the APOD into an image
+            Err(e)
+                if matches!(
+                    e.downcast_ref::<EarendelError>(),
+                    Some(
+                        EarendelError::ApodNotImage { .. }
+                            | EarendelError::ApodImageUrlMissing { .. }
+                    )
+                ) =>
+            {
+                info!("not refreshing APOD: {}", e);
+                return;
+            }
+            Err(e) => warn!("failed to refresh APOD, retrying in {:?}: {}", delay, e),
+        }
+        server.metrics.record_retry(Upstream::Apod);
+        sleep(delay).await;
+        delay = (delay * 2).min(MAX_RETRY_DELAY);
+    }
+}
+
+/// Gets the time remaining until the next midnight US Eastern time, when the cached APOD expires.
+fn until_rollover() -> Duration {
+    (next_rollover() - Utc::now()).to_std().unwrap_or_default()
+}
+
+/// Gets a random delay of up to `MAX_JITTER`.
+fn jitter() -> Duration {
+    let random = RandomState::new().build_hasher().finish();
+
+    Duration::from_millis(random % MAX_JITTER.as_millis() as u64)
+}
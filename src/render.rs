@@ -0,0 +1,287 @@
+//! Rendering of FITS image data into viewable previews.
+
+use image::{DynamicImage, ImageBuffer, ImageOutputFormat, Rgb, RgbImage};
+
+use serde::{Deserialize, Serialize};
+
+use std::error::Error;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+use crate::fits::{self, FitsImage};
+
+/// The number of pixels sampled when computing zscale limits.
+const ZSCALE_SAMPLES: usize = 1000;
+/// The contrast used when computing zscale limits.
+const ZSCALE_CONTRAST: f64 = 0.25;
+/// The scaling applied before taking the logarithm in the log stretch.
+const LOG_EXPONENT: f64 = 1000.0;
+/// The quality of JPEG output.
+const JPEG_QUALITY: u8 = 90;
+
+/// The mapping from pixel values to display intensity.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Stretch {
+    /// A linear mapping between the minimum and maximum pixel values.
+    Linear,
+    /// A logarithmic mapping between the minimum and maximum pixel values, emphasizing faint structure.
+    Log,
+    /// A linear mapping between limits chosen by the IRAF zscale algorithm.
+    #[default]
+    ZScale,
+}
+
+/// The mapping from display intensity to color.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Colormap {
+    /// Black to white.
+    #[default]
+    Grayscale,
+    /// Black through red and yellow to white.
+    Heat,
+    /// Dark purple through blue and green to yellow.
+    Viridis,
+}
+
+impl Colormap {
+    fn stops(&self) -> &'static [[f64; 3]] {
+        match self {
+            Colormap::Grayscale => &[[0.0, 0.0, 0.0], [1.0, 1.0, 1.0]],
+            Colormap::Heat => &[
+                [0.0, 0.0, 0.0],
+                [0.9, 0.0, 0.0],
+                [1.0, 0.9, 0.0],
+                [1.0, 1.0, 1.0],
+            ],
+            Colormap::Viridis => &[
+                [0.267, 0.005, 0.329],
+                [0.229, 0.322, 0.546],
+                [0.128, 0.567, 0.551],
+                [0.369, 0.789, 0.383],
+                [0.993, 0.906, 0.144],
+            ],
+        }
+    }
+
+    /// Gets the color of the given intensity, with each channel between 0 and 1.
+    fn color(&self, intensity: f64) -> [f64; 3] {
+        let stops = self.stops();
+        let position = intensity.clamp(0.0, 1.0) * (stops.len() - 1) as f64;
+        let lower = (position.floor() as usize).min(stops.len() - 2);
+        let fraction = position - lower as f64;
+
+        let mut color = [0.0; 3];
+        for (channel, value) in color.iter_mut().enumerate() {
+            let start = stops[lower][channel];
+            let end = stops[lower + 1][channel];
+            *value = start + (end - start) * fraction;
+        }
+        color
+    }
+}
+
+/// The options used to render a FITS image.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub struct RenderOptions {
+    /// The mapping from pixel values to display intensity.
+    pub stretch: Stretch,
+    /// The mapping from display intensity to color.
+    pub colormap: Colormap,
+}
+
+/// The file format of a converted image.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum OutputFormat {
+    /// A PNG image.
+    #[default]
+    Png,
+    /// A JPEG image. Only 8-bit output is supported.
+    Jpeg,
+    /// A TIFF image.
+    Tiff,
+}
+
+/// The number of bits per color channel of a converted image.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum BitDepth {
+    /// 8 bits per channel.
+    #[default]
+    Eight,
+    /// 16 bits per channel, preserving more of the dynamic range of the science data.
+    Sixteen,
+}
+
+/// The options used to convert a FITS image to a standard image format.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub struct ConvertOptions {
+    /// The options used to render the image data.
+    pub render: RenderOptions,
+    /// The file format of the output.
+    pub format: OutputFormat,
+    /// The number of bits per color channel of the output.
+    pub bit_depth: BitDepth,
+}
+
+/// Computes the display limits of the given finite pixel values.
+fn limits(values: &[f64], stretch: Stretch) -> (f64, f64) {
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    match stretch {
+        Stretch::Linear | Stretch::Log => (min, max),
+        Stretch::ZScale => zscale(values).unwrap_or((min, max)),
+    }
+}
+
+/// Computes display limits with a simplified version of the IRAF zscale algorithm.
+fn zscale(values: &[f64]) -> Option<(f64, f64)> {
+    let step = (values.len() / ZSCALE_SAMPLES).max(1);
+    let mut samples = values.iter().step_by(step).copied().collect::<Vec<f64>>();
+    if samples.len() < 3 {
+        return None;
+    }
+    samples.sort_by(f64::total_cmp);
+
+    let n = samples.len();
+    let center = n / 2;
+    let median = samples[center];
+
+    // fit a line to the sorted samples, iteratively rejecting outliers
+    let mut mask = vec![true; n];
+    let mut slope = 0.0;
+    for _ in 0..5 {
+        let points = (0..n).filter(|i| mask[*i]).collect::<Vec<usize>>();
+        if points.len() < n / 2 {
+            break;
+        }
+        let count = points.len() as f64;
+        let mean_x = points.iter().map(|i| *i as f64).sum::<f64>() / count;
+        let mean_y = points.iter().map(|i| samples[*i]).sum::<f64>() / count;
+        let covariance = points
+            .iter()
+            .map(|i| (*i as f64 - mean_x) * (samples[*i] - mean_y))
+            .sum::<f64>();
+        let variance = points
+            .iter()
+            .map(|i| (*i as f64 - mean_x).powi(2))
+            .sum::<f64>();
+        if variance == 0.0 {
+            break;
+        }
+        slope = covariance / variance;
+        let intercept = mean_y - slope * mean_x;
+
+        let residuals = (0..n)
+            .map(|i| samples[i] - (intercept + slope * i as f64))
+            .collect::<Vec<f64>>();
+        let sigma = (points.iter().map(|i| residuals[*i].powi(2)).sum::<f64>() / count).sqrt();
+        let rejected = (0..n)
+            .filter(|i| mask[*i] && residuals[*i].abs() > 2.5 * sigma)
+            .count();
+        if rejected == 0 {
+            break;
+        }
+        for (i, keep) in mask.iter_mut().enumerate() {
+            *keep = residuals[i].abs() <= 2.5 * sigma;
+        }
+    }
+
+    let slope = slope / ZSCALE_CONTRAST;
+    let low = (median - center as f64 * slope).max(samples[0]);
+    let high = (median + (n - 1 - center) as f64 * slope).min(samples[n - 1]);
+
+    (low < high).then_some((low, high))
+}
+
+/// Renders the given FITS image into an RGB image with the given options and bit depth.
+pub(crate) fn render_image(
+    image: &FitsImage,
+    options: &RenderOptions,
+    bit_depth: BitDepth,
+) -> DynamicImage {
+    let finite = image
+        .data
+        .iter()
+        .copied()
+        .filter(|value| value.is_finite())
+        .collect::<Vec<f64>>();
+    let (low, high) = limits(&finite, options.stretch);
+    let range = if high > low { high - low } else { 1.0 };
+
+    let color = |x: u32, y: u32| {
+        // FITS images start at the bottom row
+        let row = image.height - 1 - y as usize;
+        let value = image.get(x as usize, row).unwrap_or(f64::NAN);
+        if !value.is_finite() {
+            return [0.0; 3];
+        }
+        let mut intensity = ((value - low) / range).clamp(0.0, 1.0);
+        if options.stretch == Stretch::Log {
+            intensity = (1.0 + LOG_EXPONENT * intensity).ln() / (1.0 + LOG_EXPONENT).ln();
+        }
+        options.colormap.color(intensity)
+    };
+
+    let width = image.width as u32;
+    let height = image.height as u32;
+    match bit_depth {
+        BitDepth::Eight => DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+            Rgb(color(x, y).map(|channel| (channel * f64::from(u8::MAX)).round() as u8))
+        })),
+        BitDepth::Sixteen => {
+            DynamicImage::ImageRgb16(ImageBuffer::from_fn(width, height, |x, y| {
+                Rgb(color(x, y).map(|channel| (channel * f64::from(u16::MAX)).round() as u16))
+            }))
+        }
+    }
+}
+
+/// Converts the first image HDU of the given FITS file contents to the format described by the given options.
+pub fn convert(
+    bytes: &[u8],
+    options: &ConvertOptions,
+) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    let format = match (options.format, options.bit_depth) {
+        (OutputFormat::Png, _) => ImageOutputFormat::Png,
+        (OutputFormat::Jpeg, BitDepth::Eight) => ImageOutputFormat::Jpeg(JPEG_QUALITY),
+        (OutputFormat::Jpeg, BitDepth::Sixteen) => {
+            return Err("JPEG output does not support 16-bit channels".into())
+        }
+        (OutputFormat::Tiff, _) => ImageOutputFormat::Tiff,
+    };
+
+    let image = fits::read_image(bytes)?;
+    let rendered = render_image(&image, &options.render, options.bit_depth);
+
+    let mut output = Vec::new();
+    rendered.write_to(&mut Cursor::new(&mut output), format)?;
+
+    Ok(output)
+}
+
+/// Converts the first image HDU of the FITS file at the given path, writing the result to the output path.
+pub fn convert_file<P: AsRef<Path>, Q: AsRef<Path>>(
+    input: P,
+    output: Q,
+    options: &ConvertOptions,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let bytes = fs::read(input)?;
+    fs::write(output, convert(&bytes, options)?)?;
+
+    Ok(())
+}
+
+/// Renders the first image HDU of the given FITS file contents to PNG bytes.
+pub fn render_png(
+    bytes: &[u8],
+    options: &RenderOptions,
+) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    convert(
+        bytes,
+        &ConvertOptions {
+            render: *options,
+            format: OutputFormat::Png,
+            bit_depth: BitDepth::Eight,
+        },
+    )
+}
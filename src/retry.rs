@@ -0,0 +1,80 @@
+//! Retrying of failed upstream requests.
+
+use serde::{Deserialize, Serialize};
+
+use std::time::Duration;
+
+use crate::ErrorCategory;
+
+/// Determines which failed upstream requests are retried, and how long to wait between attempts. The policy is
+/// applied uniformly to every upstream, including name resolution and downloads.
+///
+/// The delay before each retry doubles from `base_delay`, up to `max_delay`. Requests whose bodies cannot be replayed,
+/// such as streamed uploads, are never retried.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts of a request, including the first. A value of 1 disables retries.
+    pub max_attempts: u32,
+    /// The delay before the first retry.
+    pub base_delay: Duration,
+    /// The maximum delay before any retry.
+    pub max_delay: Duration,
+    /// The response status codes that are retried.
+    pub retryable_statuses: Vec<u16>,
+    /// The categories of failed requests that are retried.
+    pub retryable_errors: Vec<ErrorCategory>,
+    /// The maximum time spent on a request across all of its attempts, if limited. A retry is not attempted if its
+    /// delay would exceed the remaining budget.
+    pub budget: Option<Duration>,
+}
+
+impl Default for RetryPolicy {
+    /// Retries connection failures, timeouts, rate limiting, and gateway errors up to twice.
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+            retryable_statuses: vec![429, 502, 503, 504],
+            retryable_errors: vec![ErrorCategory::Connection, ErrorCategory::Timeout],
+            budget: Some(Duration::from_secs(2 * 60)),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Creates a policy that never retries.
+    pub fn none() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Determines whether a response with the given status code is retried.
+    pub fn retries_status(&self, status: u16) -> bool {
+        self.retryable_statuses.contains(&status)
+    }
+
+    /// Determines whether a failure of the given category is retried.
+    pub fn retries_error(&self, category: ErrorCategory) -> bool {
+        self.retryable_errors.contains(&category)
+    }
+
+    /// Gets the delay before retrying the given one-based attempt, or `None` if it should not be retried because the
+    /// attempts or the budget, given the time already spent, are exhausted.
+    pub fn delay(&self, attempt: u32, elapsed: Duration) -> Option<Duration> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+
+        match self.budget {
+            Some(budget) if elapsed + delay > budget => None,
+            _ => Some(delay),
+        }
+    }
+}
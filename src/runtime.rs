@@ -0,0 +1,51 @@
+//! The runtime primitives Earendel waits on, so that its retries, rate limiting, polling, file access, and blocking work
+//! do not require a tokio runtime when the `runtime-agnostic` feature is enabled.
+
+#[cfg(feature = "mast")]
+use std::error::Error;
+use std::time::Duration;
+
+/// The asynchronous file system operations. With the `runtime-agnostic` feature, they are run on the thread pool of
+/// `async-fs` rather than the tokio blocking pool, so that they complete under any executor.
+#[cfg(all(feature = "mast", feature = "runtime-agnostic"))]
+pub(crate) use async_fs as fs;
+#[cfg(all(feature = "mast", not(feature = "runtime-agnostic")))]
+pub(crate) use tokio::fs;
+
+/// The extension traits for reading and writing the files opened with `fs`.
+#[cfg(all(feature = "mast", feature = "runtime-agnostic"))]
+pub(crate) use futures::io::{AsyncReadExt, AsyncWriteExt};
+#[cfg(all(feature = "mast", not(feature = "runtime-agnostic")))]
+pub(crate) use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// Waits until the given duration has elapsed. With the `runtime-agnostic` feature, the wait is driven by a timer
+/// thread rather than the tokio timer, so that it completes under any executor.
+pub(crate) async fn sleep(duration: Duration) {
+    #[cfg(feature = "runtime-agnostic")]
+    futures_timer::Delay::new(duration).await;
+    #[cfg(not(feature = "runtime-agnostic"))]
+    tokio::time::sleep(duration).await;
+}
+
+/// Runs the given blocking function on a thread where blocking is allowed, returning its result. With the
+/// `runtime-agnostic` feature, the function is run on the thread pool of `blocking` rather than the tokio blocking
+/// pool, so that it completes under any executor.
+#[cfg(all(feature = "mast", feature = "runtime-agnostic"))]
+pub(crate) async fn spawn_blocking<T, F>(f: F) -> Result<T, Box<dyn Error + Send + Sync>>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    Ok(blocking::unblock(f).await)
+}
+
+/// Runs the given blocking function on a thread where blocking is allowed, returning its result. Returns an error if
+/// the function panicked.
+#[cfg(all(feature = "mast", not(feature = "runtime-agnostic")))]
+pub(crate) async fn spawn_blocking<T, F>(f: F) -> Result<T, Box<dyn Error + Send + Sync>>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    Ok(tokio::task::spawn_blocking(f).await?)
+}
@@ -0,0 +1,165 @@
+//! Image cutouts and spectra from the SDSS SkyServer.
+
+use astro_rs::coordinates::Icrs;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use tracing::instrument;
+
+use uom::si::angle::{arcminute, arcsecond};
+use uom::si::f64::Angle;
+
+use std::collections::HashMap;
+use std::error::Error;
+
+use crate::coords::icrs_to_degrees;
+use crate::{EarendelServer, Upstream};
+
+const SKYSERVER_URL: &str = "https://skyserver.sdss.org/dr18";
+/// The width and height of requested cutouts, in pixels.
+const CUTOUT_SIZE: u32 = 512;
+/// The maximum number of spectra listed around a position.
+const MAX_SPECTRA: usize = 25;
+
+/// A spectrum observed by SDSS near a position.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SdssSpectrum {
+    /// The SDSS identifier of the spectrum.
+    pub spec_obj_id: String,
+    /// The right ascension of the spectrum, in degrees.
+    pub ra: Option<f64>,
+    /// The declination of the spectrum, in degrees.
+    pub dec: Option<f64>,
+    /// The measured redshift.
+    pub redshift: Option<f64>,
+    /// The spectral class, such as GALAXY, QSO, or STAR.
+    pub class: Option<String>,
+    /// The spectral subclass.
+    pub subclass: Option<String>,
+    /// The angular distance from the searched position, in arcminutes.
+    pub distance_arcmin: Option<f64>,
+    /// The URL of a plot of the spectrum.
+    pub plot_url: String,
+}
+
+/// SDSS coverage of a position.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SdssField {
+    /// The JPEG color cutout centered on the position.
+    #[serde(with = "serde_bytes")]
+    pub img: Vec<u8>,
+    /// The spectra observed near the position, nearest first.
+    pub spectra: Vec<SdssSpectrum>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SqlSearchTable {
+    #[serde(rename = "Rows")]
+    rows: Vec<HashMap<String, Value>>,
+}
+
+impl SdssSpectrum {
+    fn from_row(row: &HashMap<String, Value>) -> Option<Self> {
+        let string = |key: &str| {
+            row.get(key)
+                .and_then(Value::as_str)
+                .map(|value| value.trim().to_owned())
+        };
+        let float = |key: &str| row.get(key).and_then(Value::as_f64);
+        let spec_obj_id = string("specObjID")?;
+
+        Some(SdssSpectrum {
+            plot_url: format!("{}/en/get/SpecById.ashx?id={}", SKYSERVER_URL, spec_obj_id),
+            spec_obj_id,
+            ra: float("ra"),
+            dec: float("dec"),
+            redshift: float("z"),
+            class: string("class"),
+            subclass: string("subClass"),
+            distance_arcmin: float("distance"),
+        })
+    }
+}
+
+impl EarendelServer {
+    /// Gets a square JPEG color cutout from SDSS centered on the given coordinates. Returns an error if the web
+    /// request fails.
+    #[instrument(skip(self, coords, fov))]
+    pub async fn get_sdss_cutout(
+        &self,
+        coords: &Icrs,
+        fov: Angle,
+    ) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        let (ra, dec) = icrs_to_degrees(coords);
+        let scale = fov.get::<arcsecond>() / f64::from(CUTOUT_SIZE);
+
+        let request = self
+            .client
+            .get([SKYSERVER_URL, "/SkyServerWS/ImgCutout/getjpeg"].concat())
+            .query(&[
+                ("ra", ra.to_string()),
+                ("dec", dec.to_string()),
+                ("scale", scale.to_string()),
+                ("width", CUTOUT_SIZE.to_string()),
+                ("height", CUTOUT_SIZE.to_string()),
+            ]);
+        let resp = self
+            .send(Upstream::Sdss, request)
+            .await?
+            .error_for_status()?;
+
+        Ok(resp.bytes().await?.to_vec())
+    }
+
+    /// Gets the SDSS spectra within the given radius of the given coordinates, nearest first. Returns an error if the
+    /// web request fails.
+    #[instrument(skip(self, coords, radius))]
+    pub async fn get_sdss_spectra(
+        &self,
+        coords: &Icrs,
+        radius: Angle,
+    ) -> Result<Vec<SdssSpectrum>, Box<dyn Error + Send + Sync>> {
+        let (ra, dec) = icrs_to_degrees(coords);
+        // the identifier exceeds the precision of a JSON number, so it is returned as a string
+        let sql = format!(
+            "SELECT TOP {} CAST(s.specObjID AS VARCHAR(20)) AS specObjID, s.ra, s.dec, s.z, s.class, s.subClass, \
+             n.distance FROM dbo.fGetNearbySpecObjEq({}, {}, {}) AS n JOIN SpecObj AS s ON s.specObjID = \
+             n.specObjID ORDER BY n.distance",
+            MAX_SPECTRA,
+            ra,
+            dec,
+            radius.get::<arcminute>()
+        );
+
+        let request = self
+            .client
+            .get([SKYSERVER_URL, "/SkyServerWS/SearchTools/SqlSearch"].concat())
+            .query(&[("cmd", sql.as_str()), ("format", "json")]);
+        let resp = self
+            .send(Upstream::Sdss, request)
+            .await?
+            .error_for_status()?;
+        let body = resp.text().await?;
+        let tables = self.parse::<Vec<SqlSearchTable>>(Upstream::Sdss, &body)?;
+
+        Ok(tables
+            .iter()
+            .flat_map(|table| table.rows.iter())
+            .filter_map(SdssSpectrum::from_row)
+            .collect())
+    }
+
+    /// Gets the SDSS cutout and nearby spectra for the target of the current APOD. Returns an error if the target
+    /// cannot be resolved or if a web request fails.
+    pub async fn get_sdss_for_apod(
+        &self,
+        fov: Angle,
+    ) -> Result<SdssField, Box<dyn Error + Send + Sync>> {
+        let coords = self.resolve_apod_target().await?;
+        let img = self.get_sdss_cutout(&coords, fov).await?;
+        let spectra = self.get_sdss_spectra(&coords, fov / 2.0).await?;
+
+        Ok(SdssField { img, spectra })
+    }
+}
@@ -0,0 +1,132 @@
+//! A local full-text index of APOD titles and explanations.
+
+use chrono::NaiveDate;
+
+use tantivy::collector::TopDocs;
+use tantivy::directory::MmapDirectory;
+use tantivy::query::QueryParser;
+use tantivy::schema::{Field, Schema, Value, STORED, STRING, TEXT};
+use tantivy::{doc, Index, IndexReader, IndexWriter, TantivyDocument, Term};
+
+use tracing::instrument;
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::sync::{Mutex, PoisonError};
+
+use crate::{ApodMetadata, EarendelServer};
+
+/// The memory used by the index writer, in bytes.
+const WRITER_MEMORY: usize = 50_000_000;
+
+/// A full-text index of APOD titles and explanations. Searches return the dates of the matching APODs, which can be
+/// fetched with `EarendelServer::get_apod_image_for_date`.
+pub struct ApodIndex {
+    index: Index,
+    reader: IndexReader,
+    writer: Mutex<IndexWriter>,
+    date: Field,
+    title: Field,
+    explanation: Field,
+}
+
+impl ApodIndex {
+    /// Opens the index in the given directory, creating it if it does not exist. Returns an error if the directory
+    /// cannot be created or does not hold an index.
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        fs::create_dir_all(dir.as_ref())?;
+        let index = Index::open_or_create(MmapDirectory::open(dir.as_ref())?, schema())?;
+
+        Self::new(index)
+    }
+
+    /// Creates an index held in memory, which is lost when dropped.
+    pub fn in_memory() -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Self::new(Index::create_in_ram(schema()))
+    }
+
+    fn new(index: Index) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let schema = index.schema();
+
+        Ok(ApodIndex {
+            date: schema.get_field("date")?,
+            title: schema.get_field("title")?,
+            explanation: schema.get_field("explanation")?,
+            reader: index.reader()?,
+            writer: Mutex::new(index.writer(WRITER_MEMORY)?),
+            index,
+        })
+    }
+
+    /// Adds the given APODs to the index, replacing any previously indexed APODs of the same dates.
+    pub fn add(&self, apods: &[ApodMetadata]) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        for apod in apods {
+            let date = apod.date.format("%Y-%m-%d").to_string();
+            writer.delete_term(Term::from_field_text(self.date, &date));
+            writer.add_document(doc!(
+                self.date => date,
+                self.title => apod.title.as_str(),
+                self.explanation => apod.explanation.as_deref().unwrap_or_default(),
+            ))?;
+        }
+        writer.commit()?;
+        self.reader.reload()?;
+
+        Ok(())
+    }
+
+    /// Searches the titles and explanations of the indexed APODs with the given query, such as `andromeda` or
+    /// `title:"solar eclipse"`, returning the dates of up to the given number of matching APODs, most relevant first.
+    /// Returns an error if the query is invalid.
+    pub fn search(
+        &self,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<NaiveDate>, Box<dyn Error + Send + Sync>> {
+        let searcher = self.reader.searcher();
+        let parser = QueryParser::for_index(&self.index, vec![self.title, self.explanation]);
+        let query = parser.parse_query(query)?;
+
+        searcher
+            .search(&query, &TopDocs::with_limit(limit))?
+            .into_iter()
+            .map(|(_, address)| {
+                let doc = searcher.doc::<TantivyDocument>(address)?;
+                let date = doc
+                    .get_first(self.date)
+                    .and_then(|value| value.as_str())
+                    .ok_or("indexed APOD has no date")?;
+                Ok(NaiveDate::parse_from_str(date, "%Y-%m-%d")?)
+            })
+            .collect()
+    }
+}
+
+fn schema() -> Schema {
+    let mut builder = Schema::builder();
+    builder.add_text_field("date", STRING | STORED);
+    builder.add_text_field("title", TEXT);
+    builder.add_text_field("explanation", TEXT);
+
+    builder.build()
+}
+
+impl EarendelServer {
+    /// Fetches the APODs published between the given dates, inclusive, and adds their titles and explanations to the
+    /// given index, returning the number of indexed APODs. Returns an Error if either date is outside the archive, if
+    /// a web request fails, or if the index cannot be written.
+    #[instrument(skip(self, index))]
+    pub async fn index_apods(
+        &self,
+        index: &ApodIndex,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<usize, Box<dyn Error + Send + Sync>> {
+        let apods = self.fetch_apod_range(start, end).await?;
+        index.add(&apods)?;
+
+        Ok(apods.len())
+    }
+}
@@ -0,0 +1,233 @@
+//! An HTTP server exposing the APOD and its FITS observations.
+//!
+//! The routes are:
+//! - `GET /apod`: the title, copyright, dimensions, and metadata of the current APOD, as JSON
+//! - `GET /apod/image`: the current APOD image
+//! - `GET /apod/events`: a stream of server-sent `apod` events, each carrying the JSON description of a newly cached
+//!   APOD
+//! - `GET /metrics`: the metrics recorded by the server, in the Prometheus text format
+//! - `GET /health`: the state of the circuit breaker of each contacted upstream, as JSON
+//! - `GET /ready`: the outcome of probing the NASA API and MAST, as JSON, with a 503 status if either is unhealthy
+//! - `GET /cache`: the entries of the in-memory caches, with their ages and sizes, as JSON
+//! - `DELETE /cache`: drops every entry of the in-memory caches
+//! - `DELETE /cache/YYYY-MM-DD`: drops the cached entries of the given date, with a 404 status if there were none
+//! - `GET /fits?page=N`: a page of FITS observations of the current APOD target, as JSON (requires `mast`)
+
+#[cfg(feature = "mast")]
+use axum::extract::Query;
+use axum::extract::{Path, State};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::StatusCode;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get};
+use axum::{Json, Router};
+
+use chrono::NaiveDate;
+
+use serde::{Deserialize, Serialize};
+
+use tokio::net::{TcpListener, ToSocketAddrs};
+
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
+
+use tracing::{info, warn};
+
+use std::convert::Infallible;
+use std::error::Error;
+use std::sync::Arc;
+
+use crate::metadata::content_type;
+#[cfg(feature = "mast")]
+use crate::EarendelFits;
+use crate::{
+    CacheStats, CircuitState, EarendelApod, EarendelClient, EarendelServer, HealthReport,
+    ImageMetadata,
+};
+
+/// An `EarendelServer` shared between request handlers.
+pub type SharedServer = Arc<EarendelServer>;
+
+/// The description of the APOD returned by `GET /apod`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ApodSummary {
+    /// The title of the APOD.
+    pub title: String,
+    /// The copyright string.
+    pub copyright: Option<String>,
+    /// The width of the image, in pixels, if known.
+    pub width: Option<u32>,
+    /// The height of the image, in pixels, if known.
+    pub height: Option<u32>,
+    /// The EXIF and XMP metadata embedded in the image, if any.
+    pub metadata: Option<ImageMetadata>,
+}
+
+impl From<&EarendelApod> for ApodSummary {
+    fn from(apod: &EarendelApod) -> Self {
+        ApodSummary {
+            title: apod.title.to_owned(),
+            copyright: apod.copyright.to_owned(),
+            width: apod.width,
+            height: apod.height,
+            metadata: apod.metadata.to_owned(),
+        }
+    }
+}
+
+#[cfg(feature = "mast")]
+#[derive(Debug, Deserialize)]
+struct FitsParams {
+    #[serde(default)]
+    page: usize,
+}
+
+/// An upstream failure, reported to the client as a `502 Bad Gateway`.
+struct ApiError(Box<dyn Error + Send + Sync>);
+
+impl From<Box<dyn Error + Send + Sync>> for ApiError {
+    fn from(error: Box<dyn Error + Send + Sync>) -> Self {
+        ApiError(error)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        warn!("request failed: {}", self.0);
+        let body = serde_json::json!({ "error": self.0.to_string() });
+        (StatusCode::BAD_GATEWAY, Json(body)).into_response()
+    }
+}
+
+/// Creates the router serving the APOD and FITS endpoints from the given server, which may be an `EarendelClient` or a
+/// `SharedServer`.
+pub fn router<S: Into<EarendelClient>>(server: S) -> Router {
+    let router = Router::new()
+        .route("/apod", get(get_apod))
+        .route("/apod/image", get(get_apod_image))
+        .route("/apod/events", get(get_apod_events))
+        .route("/metrics", get(get_metrics))
+        .route("/health", get(get_health))
+        .route("/ready", get(get_ready))
+        .route("/cache", get(get_cache).delete(clear_cache))
+        .route("/cache/:date", delete(invalidate_date));
+    #[cfg(feature = "mast")]
+    let router = router.route("/fits", get(get_fits));
+
+    router.with_state(server.into())
+}
+
+/// Serves the APOD and FITS endpoints from the given server on the given address until the listener fails. The APOD
+/// cache is refreshed in the background at each daily rollover.
+pub async fn serve<A: ToSocketAddrs>(
+    server: EarendelServer,
+    addr: A,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("listening on {}", listener.local_addr()?);
+    let client = EarendelClient::new(server);
+    let refresher = client.spawn_refresher();
+    let result = axum::serve(listener, router(client)).await;
+    refresher.abort();
+    result?;
+
+    Ok(())
+}
+
+async fn get_apod(State(client): State<EarendelClient>) -> Result<Json<ApodSummary>, ApiError> {
+    let apod = client.get_apod_image().await?;
+
+    Ok(Json(ApodSummary::from(&apod)))
+}
+
+async fn get_apod_image(State(client): State<EarendelClient>) -> Result<Response, ApiError> {
+    let apod = client.get_apod_image().await?;
+
+    let content_type = content_type(apod.img());
+
+    Ok((
+        [(CONTENT_TYPE, content_type)],
+        apod.image.into_bytes().unwrap_or_default(),
+    )
+        .into_response())
+}
+
+async fn get_apod_events(
+    State(client): State<EarendelClient>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let receiver = client.subscribe_apod();
+    // lagged subscribers skip the APODs they missed
+    let events = BroadcastStream::new(receiver).filter_map(|apod| {
+        let apod = apod.ok()?;
+        Event::default()
+            .event("apod")
+            .json_data(ApodSummary::from(apod.as_ref()))
+            .ok()
+            .map(Ok)
+    });
+
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+async fn get_metrics(State(client): State<EarendelClient>) -> impl IntoResponse {
+    let text = client.metrics().to_prometheus();
+
+    ([(CONTENT_TYPE, "text/plain; version=0.0.4")], text)
+}
+
+async fn get_health(State(client): State<EarendelClient>) -> Json<serde_json::Value> {
+    let upstreams = client.circuit_states();
+    let status = if upstreams
+        .values()
+        .all(|state| *state == CircuitState::Closed)
+    {
+        "ok"
+    } else {
+        "degraded"
+    };
+
+    Json(serde_json::json!({ "status": status, "upstreams": upstreams }))
+}
+
+async fn get_ready(State(client): State<EarendelClient>) -> (StatusCode, Json<HealthReport>) {
+    let report = client.health().await;
+    let status = if report.is_healthy() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (status, Json(report))
+}
+
+async fn get_cache(State(client): State<EarendelClient>) -> Json<CacheStats> {
+    Json(client.cache_stats())
+}
+
+async fn clear_cache(State(client): State<EarendelClient>) -> StatusCode {
+    client.clear_cache();
+
+    StatusCode::NO_CONTENT
+}
+
+async fn invalidate_date(
+    State(client): State<EarendelClient>,
+    Path(date): Path<NaiveDate>,
+) -> StatusCode {
+    if client.invalidate_date(date) {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+#[cfg(feature = "mast")]
+async fn get_fits(
+    State(client): State<EarendelClient>,
+    Query(params): Query<FitsParams>,
+) -> Result<Json<EarendelFits>, ApiError> {
+    let fits = client.get_fits_for_apod(params.page).await?;
+
+    Ok(Json(fits))
+}
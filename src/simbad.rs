@@ -0,0 +1,83 @@
+//! Queries against SIMBAD for the details of a named object.
+
+use serde::{Deserialize, Serialize};
+
+use tracing::instrument;
+
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use crate::tap::{adql_escape, TapFormat};
+use crate::{EarendelServer, Upstream};
+
+const SIMBAD_TAP_URL: &str = "https://simbad.cds.unistra.fr/simbad/sim-tap";
+
+/// The SIMBAD details of an astronomical object.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct TargetInfo {
+    /// The main SIMBAD identifier of the object.
+    pub main_id: String,
+    /// The SIMBAD object type, such as G for galaxy or PN for planetary nebula.
+    pub object_type: Option<String>,
+    /// All identifiers of the object across catalogs.
+    pub identifiers: Vec<String>,
+    /// The right ascension of the object, in degrees.
+    pub ra: Option<f64>,
+    /// The declination of the object, in degrees.
+    pub dec: Option<f64>,
+    /// The radial velocity of the object, in kilometers per second.
+    pub radial_velocity: Option<f64>,
+    /// The redshift of the object.
+    pub redshift: Option<f64>,
+    /// The magnitudes of the object, keyed by filter name.
+    pub fluxes: BTreeMap<String, f64>,
+}
+
+impl EarendelServer {
+    /// Gets the SIMBAD details of the object with the given name. Returns an error if the object is unknown or if
+    /// the web request fails.
+    #[instrument(skip(self))]
+    pub async fn get_target_info(
+        &self,
+        name: &str,
+    ) -> Result<TargetInfo, Box<dyn Error + Send + Sync>> {
+        let query = format!(
+            "SELECT basic.oid, main_id, otype, ra, dec, rvz_radvel, rvz_redshift, ids FROM basic \
+             JOIN ident ON ident.oidref = basic.oid JOIN ids ON ids.oidref = basic.oid WHERE ident.id = '{}'",
+            adql_escape(name)
+        );
+        let table = self
+            .tap_query(Upstream::Simbad, SIMBAD_TAP_URL, TapFormat::Csv, &query)
+            .await?;
+        let row = table
+            .rows()
+            .next()
+            .ok_or_else(|| format!("SIMBAD does not know the object {}", name))?;
+        let oid = row.get_string("oid").ok_or("SIMBAD object has no oid")?;
+
+        let mut info = TargetInfo {
+            main_id: row.get_string("main_id").unwrap_or_else(|| name.to_owned()),
+            object_type: row.get_string("otype"),
+            identifiers: row
+                .get_string("ids")
+                .map(|ids| ids.split('|').map(|id| id.trim().to_owned()).collect())
+                .unwrap_or_default(),
+            ra: row.get_f64("ra"),
+            dec: row.get_f64("dec"),
+            radial_velocity: row.get_f64("rvz_radvel"),
+            redshift: row.get_f64("rvz_redshift"),
+            ..Default::default()
+        };
+
+        let query = format!("SELECT filter, flux FROM flux WHERE oidref = {}", oid);
+        let table = self
+            .tap_query(Upstream::Simbad, SIMBAD_TAP_URL, TapFormat::Csv, &query)
+            .await?;
+        info.fluxes = table
+            .rows()
+            .filter_map(|row| Some((row.get_string("filter")?, row.get_f64("flux")?)))
+            .collect();
+
+        Ok(info)
+    }
+}
@@ -0,0 +1,101 @@
+//! Export and import of the cached state of a server.
+//!
+//! Only the in-memory APOD and EPIC caches are included. MAST query results are never cached in memory, and
+//! downloaded FITS products are kept in the on-disk caches configured by `FitsCacheConfig` and `HttpCacheConfig`,
+//! which already survive a restart and can be shared by copying or mounting their directories. Copying gigabytes of
+//! FITS data into every snapshot would defeat the point of a quick pre-warm.
+
+use serde::{Deserialize, Serialize};
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::apod::CachedApod;
+use crate::epic::CachedEpic;
+use crate::EarendelServer;
+
+/// The version of the snapshot format, incremented when a snapshot can no longer be read by older versions.
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// The encoding of a saved cache snapshot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum SnapshotFormat {
+    /// JSON, which can be inspected by hand but stores images inefficiently.
+    Json,
+    /// MessagePack, which stores images as raw bytes.
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+}
+
+/// A copy of the cached APOD and EPIC images of a server, which can be saved and restored on another server.
+///
+/// Cached entries are only served on the day they were published, so a restored snapshot pre-warms a server for the
+/// rest of that day.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CacheSnapshot {
+    version: u32,
+    apod: Option<CachedApod>,
+    epic: Option<CachedEpic>,
+}
+
+impl EarendelServer {
+    /// Copies the cached state of this server.
+    pub fn export_cache(&self) -> CacheSnapshot {
+        CacheSnapshot {
+            version: SNAPSHOT_VERSION,
+            apod: self.cached_apod().to_owned(),
+            epic: self.cached_epic().to_owned(),
+        }
+    }
+
+    /// Replaces the cached state of this server with the given snapshot. Returns an error if the snapshot was created
+    /// by an incompatible version.
+    pub fn import_cache(
+        &self,
+        snapshot: CacheSnapshot,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(format!("unsupported cache snapshot version {}", snapshot.version).into());
+        }
+        self.cache_apod(snapshot.apod);
+        *self.cached_epic() = snapshot.epic;
+
+        Ok(())
+    }
+
+    /// Saves the cached state of this server to the file at the given path, in the given format. Returns an error if
+    /// the file cannot be written.
+    pub fn save_cache<P: AsRef<Path>>(
+        &self,
+        path: P,
+        format: SnapshotFormat,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let snapshot = self.export_cache();
+        let bytes = match format {
+            SnapshotFormat::Json => serde_json::to_vec(&snapshot)?,
+            #[cfg(feature = "msgpack")]
+            SnapshotFormat::MessagePack => rmp_serde::to_vec_named(&snapshot)?,
+        };
+        fs::write(path, bytes)?;
+
+        Ok(())
+    }
+
+    /// Restores the cached state of this server from the file at the given path, as saved by `save_cache` in the
+    /// given format. Returns an error if the file cannot be read or was saved by an incompatible version.
+    pub fn load_cache<P: AsRef<Path>>(
+        &self,
+        path: P,
+        format: SnapshotFormat,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let bytes = fs::read(path)?;
+        let snapshot = match format {
+            SnapshotFormat::Json => serde_json::from_slice::<CacheSnapshot>(&bytes)?,
+            #[cfg(feature = "msgpack")]
+            SnapshotFormat::MessagePack => rmp_serde::from_slice::<CacheSnapshot>(&bytes)?,
+        };
+
+        self.import_cache(snapshot)
+    }
+}
@@ -0,0 +1,110 @@
+//! Streamed downloads, so that large images and FITS files can be passed on, such as by a web server proxying them to
+//! its clients, without holding them in memory.
+
+use bytes::Bytes;
+
+use futures::stream::{self, BoxStream, Stream, StreamExt};
+
+use reqwest::header::CONTENT_TYPE;
+
+use tracing::instrument;
+
+use std::error::Error;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use crate::{EarendelError, EarendelServer, Upstream};
+
+/// The body of a download, yielded in chunks as it is received. The stream ends with an error if the web request
+/// fails or if the body exceeds the configured maximum download size.
+pub struct ByteStream {
+    /// The media type of the body, if known.
+    pub content_type: Option<String>,
+    /// The length of the body, in bytes, if known.
+    pub content_length: Option<u64>,
+    chunks: BoxStream<'static, Result<Bytes, Box<dyn Error + Send + Sync>>>,
+}
+
+impl ByteStream {
+    /// Creates a stream of the body of the given response, ending with an error if it exceeds the given limit.
+    pub(crate) fn from_response(
+        resp: reqwest::Response,
+        limit: Option<u64>,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let content_length = resp.content_length();
+        if let Some(limit) =
+            limit.filter(|limit| content_length.is_some_and(|length| length > *limit))
+        {
+            return Err(EarendelError::DownloadTooLarge {
+                url: resp.url().to_string(),
+                limit,
+            }
+            .into());
+        }
+        let content_type = resp
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(String::from);
+
+        let chunks = stream::try_unfold(
+            (resp, 0),
+            move |(mut resp, read): (reqwest::Response, u64)| async move {
+                let Some(chunk) = resp.chunk().await? else {
+                    return Ok(None);
+                };
+                let read = read + chunk.len() as u64;
+                if let Some(limit) = limit.filter(|limit| read > *limit) {
+                    return Err(EarendelError::DownloadTooLarge {
+                        url: resp.url().to_string(),
+                        limit,
+                    }
+                    .into());
+                }
+
+                Ok::<_, Box<dyn Error + Send + Sync>>(Some((chunk, (resp, read))))
+            },
+        );
+
+        Ok(ByteStream {
+            content_type,
+            content_length,
+            chunks: chunks.boxed(),
+        })
+    }
+
+    /// Creates a stream of the given body, which is already held in memory.
+    pub(crate) fn from_bytes(body: Vec<u8>) -> Self {
+        ByteStream {
+            content_type: None,
+            content_length: Some(body.len() as u64),
+            chunks: stream::once(async move { Ok(Bytes::from(body)) }).boxed(),
+        }
+    }
+}
+
+impl Stream for ByteStream {
+    type Item = Result<Bytes, Box<dyn Error + Send + Sync>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.chunks.poll_next_unpin(cx)
+    }
+}
+
+impl EarendelServer {
+    /// Downloads the body of the given URL as a stream, such as the data URL of an observation. Unlike
+    /// `EarendelServer::download`, the body is not held in memory, and concurrent downloads of the same URL are not
+    /// shared. Returns an error if the web request fails.
+    #[instrument(skip(self))]
+    pub async fn download_stream(
+        &self,
+        url: &str,
+    ) -> Result<ByteStream, Box<dyn Error + Send + Sync>> {
+        let resp = self
+            .send(Upstream::Download, self.client.get(url))
+            .await?
+            .error_for_status()?;
+
+        ByteStream::from_response(resp, self.max_download_size)
+    }
+}
@@ -0,0 +1,217 @@
+//! Local computation of the times of sunrise, sunset, and twilight, without any web request.
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+
+use serde::{Deserialize, Serialize};
+
+use std::f64::consts::TAU;
+
+use crate::iss::gmst;
+use crate::Observer;
+
+/// The altitude of the center of the Sun at sunrise and sunset, in degrees, allowing for refraction and the radius of
+/// the disk.
+pub(crate) const SUNRISE_ALTITUDE: f64 = -0.833;
+/// The altitude of the center of the Sun at the start and end of astronomical twilight, in degrees.
+const ASTRONOMICAL_TWILIGHT_ALTITUDE: f64 = -18.0;
+/// The interval between altitudes checked for sunrise, sunset, and twilight, in minutes.
+const SEARCH_STEP_MINUTES: i64 = 10;
+
+/// The times of sunrise, sunset, and twilight of a day at a location.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SunTimes {
+    /// The local date of the times.
+    pub date: NaiveDate,
+    /// The start of astronomical twilight, when the sky starts to brighten, or None if the Sun does not reach 18
+    /// degrees below the horizon during the night.
+    pub astronomical_dawn: Option<DateTime<Utc>>,
+    /// The time of sunrise, or None during polar day or night.
+    pub sunrise: Option<DateTime<Utc>>,
+    /// The time the Sun crosses the meridian.
+    pub solar_noon: DateTime<Utc>,
+    /// The time of sunset, or None during polar day or night.
+    pub sunset: Option<DateTime<Utc>>,
+    /// The end of astronomical twilight, when the sky is fully dark, or None if the Sun does not reach 18 degrees
+    /// below the horizon during the night.
+    pub astronomical_dusk: Option<DateTime<Utc>>,
+}
+
+/// Gets the equatorial coordinates of the Sun at the given time, in radians, accurate to about a hundredth of a degree.
+pub(crate) fn sun_position(time: DateTime<Utc>) -> (f64, f64) {
+    let days = time.timestamp_millis() as f64 / 86_400_000.0 - 10_957.5;
+    let mean_longitude = 280.460 + 0.985_647_4 * days;
+    let mean_anomaly = (357.528 + 0.985_600_3 * days).to_radians();
+    let longitude =
+        (mean_longitude + 1.915 * mean_anomaly.sin() + 0.020 * (2.0 * mean_anomaly).sin())
+            .to_radians();
+    let obliquity = (23.439 - 0.000_000_4 * days).to_radians();
+
+    let ra = (obliquity.cos() * longitude.sin())
+        .atan2(longitude.cos())
+        .rem_euclid(TAU);
+    let dec = (obliquity.sin() * longitude.sin()).asin();
+
+    (ra, dec)
+}
+
+/// Gets the hour angle of the given right ascension for the given observer at the given time, in radians.
+pub(crate) fn hour_angle(observer: &Observer, ra: f64, time: DateTime<Utc>) -> f64 {
+    (gmst(time) + observer.longitude.to_radians() - ra).rem_euclid(TAU)
+}
+
+/// Gets the altitude of the center of the Sun above the horizon of the given observer at the given time, in degrees.
+pub(crate) fn sun_altitude(observer: &Observer, time: DateTime<Utc>) -> f64 {
+    let (ra, dec) = sun_position(time);
+    let latitude = observer.latitude.to_radians();
+    let hour_angle = hour_angle(observer, ra, time);
+
+    (latitude.sin() * dec.sin() + latitude.cos() * dec.cos() * hour_angle.cos())
+        .clamp(-1.0, 1.0)
+        .asin()
+        .to_degrees()
+}
+
+/// Finds the first times the Sun rises above and sets below the given altitude, in degrees, between the given times.
+fn find_crossings(
+    observer: &Observer,
+    altitude: f64,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> (Option<DateTime<Utc>>, Option<DateTime<Utc>>) {
+    let mut rising = None;
+    let mut setting = None;
+    let mut previous = start;
+    let mut was_above = sun_altitude(observer, start) >= altitude;
+
+    while previous < end {
+        let time = end.min(previous + Duration::minutes(SEARCH_STEP_MINUTES));
+        let above = sun_altitude(observer, time) >= altitude;
+        if above != was_above {
+            // bisect the step to the second
+            let (mut low, mut high) = (previous, time);
+            while high - low > Duration::seconds(1) {
+                let middle = low + (high - low) / 2;
+                if (sun_altitude(observer, middle) >= altitude) == was_above {
+                    low = middle;
+                } else {
+                    high = middle;
+                }
+            }
+            if above {
+                rising = rising.or(Some(high));
+            } else {
+                setting = setting.or(Some(high));
+            }
+        }
+        previous = time;
+        was_above = above;
+    }
+
+    (rising, setting)
+}
+
+/// Computes the times of sunrise, sunset, and astronomical twilight at the given observer on the given date, in the
+/// local mean time of the longitude of the observer. The altitude of the observer is ignored.
+pub fn sun_times(observer: &Observer, date: NaiveDate) -> SunTimes {
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc()
+        - Duration::seconds((observer.longitude * 240.0).round() as i64);
+    let next_midnight = midnight + Duration::days(1);
+
+    // the Sun transits when its hour angle is zero, which moves with the Sun itself
+    let mut solar_noon = midnight + Duration::hours(12);
+    for _ in 0..3 {
+        let (ra, _) = sun_position(solar_noon);
+        let offset = (hour_angle(observer, ra, solar_noon) + TAU / 2.0).rem_euclid(TAU) - TAU / 2.0;
+        solar_noon -= Duration::milliseconds((offset / TAU * 86_400_000.0) as i64);
+    }
+
+    let (sunrise, sunset) = find_crossings(observer, SUNRISE_ALTITUDE, midnight, next_midnight);
+    let (astronomical_dawn, astronomical_dusk) = find_crossings(
+        observer,
+        ASTRONOMICAL_TWILIGHT_ALTITUDE,
+        midnight,
+        next_midnight,
+    );
+
+    SunTimes {
+        date,
+        astronomical_dawn,
+        sunrise,
+        solar_noon,
+        sunset,
+        astronomical_dusk,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::TimeZone;
+
+    const GREENWICH: Observer = Observer {
+        latitude: 51.4769,
+        longitude: -0.0005,
+        altitude: 0.0,
+    };
+    const TROMSO: Observer = Observer {
+        latitude: 69.6492,
+        longitude: 18.9553,
+        altitude: 0.0,
+    };
+
+    /// Asserts that the given time is within two minutes of the given published time, which is given to the minute.
+    fn assert_near(actual: Option<DateTime<Utc>>, expected: DateTime<Utc>) {
+        let actual = actual.unwrap_or_else(|| panic!("expected {}, got None", expected));
+        assert!(
+            (actual - expected).num_seconds().abs() <= 120,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    fn utc(date: NaiveDate, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.from_utc_datetime(&date.and_hms_opt(hour, minute, 0).unwrap())
+    }
+
+    #[test]
+    fn equinox_at_greenwich() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 20).unwrap();
+        let times = sun_times(&GREENWICH, date);
+        assert_near(times.sunrise, utc(date, 6, 2));
+        assert_near(Some(times.solar_noon), utc(date, 12, 7));
+        assert_near(times.sunset, utc(date, 18, 14));
+        assert_near(times.astronomical_dawn, utc(date, 4, 8));
+        assert_near(times.astronomical_dusk, utc(date, 20, 8));
+    }
+
+    #[test]
+    fn summer_solstice_at_greenwich_stays_in_twilight() {
+        let date = NaiveDate::from_ymd_opt(2024, 6, 21).unwrap();
+        let times = sun_times(&GREENWICH, date);
+        assert_near(times.sunrise, utc(date, 3, 43));
+        assert_near(times.sunset, utc(date, 20, 21));
+        assert_eq!(times.astronomical_dawn, None);
+        assert_eq!(times.astronomical_dusk, None);
+    }
+
+    #[test]
+    fn polar_night_has_no_sunrise() {
+        let date = NaiveDate::from_ymd_opt(2024, 12, 21).unwrap();
+        let times = sun_times(&TROMSO, date);
+        assert_eq!(times.sunrise, None);
+        assert_eq!(times.sunset, None);
+        assert!(times.astronomical_dawn.is_some());
+        assert!(times.astronomical_dusk.is_some());
+    }
+
+    #[test]
+    fn midnight_sun_has_no_sunset() {
+        let date = NaiveDate::from_ymd_opt(2024, 6, 21).unwrap();
+        let times = sun_times(&TROMSO, date);
+        assert_eq!(times.sunrise, None);
+        assert_eq!(times.sunset, None);
+        assert!(sun_altitude(&TROMSO, times.solar_noon) > 40.0);
+    }
+}
@@ -0,0 +1,294 @@
+//! Queries against IVOA Table Access Protocol (TAP) services.
+
+use astro_rs::coordinates::Icrs;
+
+use async_trait::async_trait;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use uom::si::f64::Length;
+use uom::si::length::meter;
+
+use std::error::Error;
+
+use crate::archive::{EarendelFits, Observation, ObservationArchive, PAGE_SIZE, SEARCH_RADIUS_DEG};
+use crate::coords::icrs_to_degrees;
+use crate::{EarendelServer, Upstream};
+
+/// The output format requested from a TAP service.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum TapFormat {
+    /// The JSON format produced by ESAC services, with separate metadata and data arrays.
+    EsacJson,
+    /// Comma-separated values with a header row, supported by most services.
+    Csv,
+}
+
+#[derive(Debug, Deserialize)]
+struct TapColumn {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct EsacJsonTable {
+    metadata: Vec<TapColumn>,
+    data: Vec<Vec<Value>>,
+}
+
+/// The result of a synchronous TAP query.
+#[derive(Debug, Default)]
+pub(crate) struct TapTable {
+    columns: Vec<String>,
+    data: Vec<Vec<Value>>,
+}
+
+impl From<EsacJsonTable> for TapTable {
+    fn from(table: EsacJsonTable) -> Self {
+        TapTable {
+            columns: table
+                .metadata
+                .into_iter()
+                .map(|column| column.name)
+                .collect(),
+            data: table.data,
+        }
+    }
+}
+
+impl TapTable {
+    /// Parses a table from comma-separated values with a header row. Empty fields are treated as null.
+    pub(crate) fn from_csv(body: &str) -> Self {
+        let mut records = Vec::new();
+        let mut record = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut chars = body.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if quoted && chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => quoted = !quoted,
+                ',' if !quoted => record.push(std::mem::take(&mut field)),
+                '\n' if !quoted => {
+                    record.push(std::mem::take(&mut field));
+                    records.push(std::mem::take(&mut record));
+                }
+                '\r' if !quoted => {}
+                _ => field.push(c),
+            }
+        }
+        if !field.is_empty() || !record.is_empty() {
+            record.push(field);
+            records.push(record);
+        }
+
+        let mut records = records.into_iter();
+        let columns = records.next().unwrap_or_default();
+        let data = records
+            .filter(|record| record.iter().any(|field| !field.is_empty()))
+            .map(|record| {
+                record
+                    .into_iter()
+                    .map(|field| {
+                        if field.is_empty() {
+                            Value::Null
+                        } else {
+                            Value::String(field)
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
+        TapTable { columns, data }
+    }
+
+    /// Gets the rows of the table.
+    pub(crate) fn rows(&self) -> impl Iterator<Item = TapRow<'_>> {
+        self.data.iter().map(|values| TapRow {
+            table: self,
+            values,
+        })
+    }
+
+    fn column(&self, name: &str) -> Option<usize> {
+        self.columns
+            .iter()
+            .position(|column| column.eq_ignore_ascii_case(name))
+    }
+}
+
+/// A single row of a TAP result.
+pub(crate) struct TapRow<'a> {
+    table: &'a TapTable,
+    values: &'a [Value],
+}
+
+impl TapRow<'_> {
+    /// Gets the value of the given column, if present and not null.
+    pub(crate) fn get(&self, name: &str) -> Option<&Value> {
+        self.table
+            .column(name)
+            .and_then(|index| self.values.get(index))
+            .filter(|value| !value.is_null())
+    }
+
+    /// Gets the value of the given column as a string.
+    pub(crate) fn get_string(&self, name: &str) -> Option<String> {
+        self.get(name).map(|value| match value {
+            Value::String(value) => value.trim().to_owned(),
+            other => other.to_string(),
+        })
+    }
+
+    /// Gets the value of the given column as a float.
+    pub(crate) fn get_f64(&self, name: &str) -> Option<f64> {
+        self.get(name).and_then(|value| match value {
+            Value::String(value) => value.trim().parse::<f64>().ok(),
+            other => other.as_f64(),
+        })
+    }
+}
+
+/// Escapes the given value for use within a single-quoted ADQL string literal.
+pub(crate) fn adql_escape(value: &str) -> String {
+    value.replace('\'', "''")
+}
+
+impl EarendelServer {
+    /// Runs a synchronous ADQL query against the TAP service at the given base URL.
+    pub(crate) async fn tap_query(
+        &self,
+        upstream: Upstream,
+        base_url: &str,
+        format: TapFormat,
+        query: &str,
+    ) -> Result<TapTable, Box<dyn Error + Send + Sync>> {
+        let format_param = match format {
+            TapFormat::EsacJson => "json",
+            TapFormat::Csv => "csv",
+        };
+        let request = self.client.get([base_url, "/sync"].concat()).query(&[
+            ("REQUEST", "doQuery"),
+            ("LANG", "ADQL"),
+            ("FORMAT", format_param),
+            ("QUERY", query),
+        ]);
+        let resp = self.send(upstream, request).await?.error_for_status()?;
+        let body = resp.text().await?;
+
+        match format {
+            TapFormat::EsacJson => Ok(self.parse::<EsacJsonTable>(upstream, &body)?.into()),
+            TapFormat::Csv => Ok(TapTable::from_csv(&body)),
+        }
+    }
+
+    /// Counts the rows matched by the given ADQL `FROM ... WHERE ...` clause.
+    pub(crate) async fn tap_count(
+        &self,
+        upstream: Upstream,
+        base_url: &str,
+        format: TapFormat,
+        from_where: &str,
+    ) -> Result<usize, Box<dyn Error + Send + Sync>> {
+        let query = format!("SELECT COUNT(*) AS n {}", from_where);
+        let table = self.tap_query(upstream, base_url, format, &query).await?;
+
+        Ok(table
+            .rows()
+            .next()
+            .and_then(|row| row.get_f64("n"))
+            .unwrap_or(0.0) as usize)
+    }
+}
+
+/// An archive searched through the standard `ivoa.ObsCore` table of its TAP service. The archives differ only in
+/// their service and in the column identifying their observations.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ObsCoreArchive {
+    /// The name of the archive, as reported in each observation.
+    pub(crate) name: &'static str,
+    /// The base URL of the TAP service.
+    pub(crate) base_url: &'static str,
+    /// The upstream the requests are attributed to.
+    pub(crate) upstream: Upstream,
+    /// The column identifying each observation, such as `obs_id`.
+    pub(crate) id_column: &'static str,
+}
+
+impl ObsCoreArchive {
+    fn observation(&self, row: &TapRow<'_>) -> Observation {
+        Observation {
+            archive: String::from(self.name),
+            obs_id: row.get_string(self.id_column).unwrap_or_default(),
+            collection: row.get_string("obs_collection"),
+            instrument: row.get_string("instrument_name"),
+            target_name: row.get_string("target_name"),
+            dataproduct_type: row.get_string("dataproduct_type"),
+            ra: row.get_f64("s_ra"),
+            dec: row.get_f64("s_dec"),
+            exposure_time: row.get_f64("t_exptime"),
+            data_url: row.get_string("access_url"),
+            // ObsCore reports wavelengths in meters
+            em_min: row.get_f64("em_min").map(Length::new::<meter>),
+            em_max: row.get_f64("em_max").map(Length::new::<meter>),
+            ..Default::default()
+        }
+    }
+}
+
+#[async_trait]
+impl ObservationArchive for ObsCoreArchive {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    async fn search(
+        &self,
+        server: &EarendelServer,
+        coords: &Icrs,
+        page: usize,
+    ) -> Result<EarendelFits, Box<dyn Error + Send + Sync>> {
+        let (ra, dec) = icrs_to_degrees(coords);
+        let from_where = format!(
+            "FROM ivoa.ObsCore WHERE CONTAINS(POINT('ICRS', s_ra, s_dec), CIRCLE('ICRS', {}, {}, {}))=1",
+            ra, dec, SEARCH_RADIUS_DEG
+        );
+        // ADQL 2.0 has no OFFSET, so earlier pages are fetched and skipped
+        let skip = page.saturating_sub(1) * PAGE_SIZE;
+        let query = format!(
+            "SELECT TOP {} {id}, obs_collection, instrument_name, target_name, dataproduct_type, s_ra, s_dec, \
+             t_exptime, access_url, em_min, em_max {} ORDER BY {id}",
+            skip + PAGE_SIZE,
+            from_where,
+            id = self.id_column,
+        );
+
+        let table = server
+            .tap_query(self.upstream, self.base_url, TapFormat::Csv, &query)
+            .await?;
+        let total_hits = server
+            .tap_count(self.upstream, self.base_url, TapFormat::Csv, &from_where)
+            .await?;
+
+        let observations = table
+            .rows()
+            .skip(skip)
+            .map(|row| self.observation(&row))
+            .collect::<Vec<Observation>>();
+
+        Ok(EarendelFits::new(
+            observations
+                .iter()
+                .filter_map(|observation| observation.data_url.to_owned())
+                .collect(),
+            observations,
+            page,
+            PAGE_SIZE,
+            total_hits,
+        ))
+    }
+}
@@ -0,0 +1,159 @@
+//! Discovery and parsing of TESS light curves, for plotting the brightness of exoplanet hosts and variable stars.
+
+use serde::{Deserialize, Serialize};
+
+use tracing::instrument;
+
+use std::error::Error;
+
+use crate::fits::{self, FitsTable};
+use crate::mast::download_url;
+use crate::redact::redact;
+use crate::{DataProductType, EarendelServer, FitsFileFilter, MastArchive, Observation};
+
+/// The file name suffix of TESS light curve files.
+const LIGHT_CURVE_SUFFIX: &str = "_lc.fits";
+
+/// The TESS light curves of a target.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct TessLightCurves {
+    /// The TESS sectors in which the target was observed, in ascending order.
+    pub sectors: Vec<u32>,
+    /// The URLs of the light curve FITS files of the target. See `EarendelServer::get_light_curve`.
+    pub light_curve_urls: Vec<String>,
+    /// The TESS time series observations of the target.
+    pub observations: Vec<Observation>,
+}
+
+/// The brightness of a target over time, as measured in one TESS light curve file.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct LightCurve {
+    /// The name of the observed target, such as `TIC 25155310`.
+    pub object: Option<String>,
+    /// The TESS sector of the light curve.
+    pub sector: Option<u32>,
+    /// The time of each measurement, as a Barycentric TESS Julian Date (BJD - 2457000), in days.
+    pub time: Vec<f64>,
+    /// The flux of each measurement, in electrons per second. The systematics-corrected PDCSAP flux is used where
+    /// available, otherwise the simple aperture photometry flux.
+    pub flux: Vec<f64>,
+    /// The uncertainty of each flux, in electrons per second, or NaN if unknown.
+    pub flux_err: Vec<f64>,
+}
+
+impl LightCurve {
+    /// Parses the light curve in the given TESS light curve FITS file contents. Measurements without a time or flux,
+    /// such as those taken during momentum dumps, are left out. Returns an error if the file has no binary table or
+    /// if the table has no time or flux column.
+    pub fn from_fits(bytes: &[u8]) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let primary = fits::read_primary_header(bytes)?;
+        let table = fits::read_table(bytes)?;
+        let time = table
+            .column("TIME")
+            .ok_or("light curve has no TIME column")?;
+        let (flux, flux_err) = Self::flux_columns(&table)?;
+
+        let mut light_curve = LightCurve {
+            object: primary
+                .get_str("OBJECT")
+                .map(|object| object.trim().to_owned()),
+            sector: primary
+                .get_i64("SECTOR")
+                .and_then(|sector| u32::try_from(sector).ok()),
+            ..Default::default()
+        };
+        for ((time, flux), flux_err) in time.into_iter().zip(flux).zip(flux_err) {
+            if time.is_finite() && flux.is_finite() {
+                light_curve.time.push(time);
+                light_curve.flux.push(flux);
+                light_curve.flux_err.push(flux_err);
+            }
+        }
+
+        Ok(light_curve)
+    }
+
+    /// Gets the flux and flux uncertainty columns of the given table, preferring the corrected PDCSAP flux.
+    fn flux_columns(
+        table: &FitsTable,
+    ) -> Result<(Vec<f64>, Vec<f64>), Box<dyn Error + Send + Sync>> {
+        for (flux_name, err_name) in [
+            ("PDCSAP_FLUX", "PDCSAP_FLUX_ERR"),
+            ("SAP_FLUX", "SAP_FLUX_ERR"),
+        ] {
+            if let Some(flux) = table.column(flux_name) {
+                let flux_err = table
+                    .column(err_name)
+                    .unwrap_or_else(|| vec![f64::NAN; flux.len()]);
+                return Ok((flux, flux_err));
+            }
+        }
+
+        Err("light curve has no flux column".into())
+    }
+}
+
+/// Gets the TESS sector of the observation with the given identifier, such as
+/// `tess2018206045859-s0001-0000000025155310-0120-s`.
+fn sector(obs_id: &str) -> Option<u32> {
+    obs_id
+        .split(['-', '_'])
+        .filter_map(|part| part.strip_prefix('s'))
+        .find(|digits| digits.len() == 4 && digits.bytes().all(|b| b.is_ascii_digit()))
+        .and_then(|digits| digits.parse().ok())
+}
+
+impl EarendelServer {
+    /// Finds the TESS light curves of the current APOD target, listing the sectors in which it was observed and the
+    /// URLs of its light curve files. Returns an error if the target cannot be resolved or if a web request fails.
+    #[instrument(skip(self))]
+    pub async fn get_tess_light_curves_for_apod(
+        &self,
+    ) -> Result<TessLightCurves, Box<dyn Error + Send + Sync>> {
+        let archive = MastArchive::with_filter(FitsFileFilter {
+            extensions: vec![String::from(LIGHT_CURVE_SUFFIX)],
+            product_types: Vec::new(),
+        })
+        .collection("TESS")
+        .dataproduct_types(&[DataProductType::Timeseries]);
+        let fits = self.get_all_archive_fits_for_apod(&archive).await?;
+
+        let mut sectors = fits
+            .observations
+            .iter()
+            .filter_map(|observation| sector(&observation.obs_id))
+            .collect::<Vec<u32>>();
+        sectors.sort_unstable();
+        sectors.dedup();
+
+        Ok(TessLightCurves {
+            sectors,
+            // MAST lists data URIs, such as `mast:TESS/product/<filename>`, rather than URLs
+            light_curve_urls: fits
+                .files
+                .iter()
+                .map(|file| {
+                    if file.starts_with("mast:") {
+                        download_url(file)
+                    } else {
+                        file.to_owned()
+                    }
+                })
+                .collect(),
+            observations: fits.observations,
+        })
+    }
+
+    /// Downloads and parses the TESS light curve at the given URL, as listed by
+    /// `EarendelServer::get_tess_light_curves_for_apod`. Returns an error if the download fails or if the file is not a
+    /// light curve.
+    #[instrument(skip(self, url), fields(url = %redact(url)))]
+    pub async fn get_light_curve(
+        &self,
+        url: &str,
+    ) -> Result<LightCurve, Box<dyn Error + Send + Sync>> {
+        let bytes = self.download(url).await?;
+
+        LightCurve::from_fits(&bytes)
+    }
+}
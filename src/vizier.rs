@@ -0,0 +1,133 @@
+//! Cross-matches against selected VizieR catalogs, answering which known objects lie in a field.
+
+use astro_rs::coordinates::Icrs;
+
+use serde::{Deserialize, Serialize};
+
+use tracing::instrument;
+
+use uom::si::angle::degree;
+use uom::si::f64::Angle;
+
+use std::error::Error;
+
+use crate::coords::icrs_to_degrees;
+use crate::tap::{TapFormat, TapRow};
+use crate::{EarendelServer, Upstream};
+
+const VIZIER_TAP_URL: &str = "https://tapvizier.cds.unistra.fr/TAPVizieR/tap";
+/// The maximum number of rows listed per catalog.
+const MAX_ROWS: usize = 200;
+
+/// A VizieR catalog supported by the cross-match.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum VizierCatalog {
+    /// The Hipparcos main catalog (I/239).
+    Hipparcos,
+    /// The 2MASS all-sky point source catalog (II/246).
+    TwoMass,
+    /// The NGC 2000.0 catalog of nebulae, clusters, and galaxies (VII/118).
+    Ngc2000,
+}
+
+impl VizierCatalog {
+    /// Gets the VizieR identifier of the catalog table.
+    pub fn table(&self) -> &'static str {
+        match self {
+            VizierCatalog::Hipparcos => "I/239/hip_main",
+            VizierCatalog::TwoMass => "II/246/out",
+            VizierCatalog::Ngc2000 => "VII/118/ngc2000",
+        }
+    }
+
+    /// Gets the identifier, right ascension, declination, and magnitude columns of the catalog table.
+    fn columns(&self) -> [&'static str; 4] {
+        match self {
+            VizierCatalog::Hipparcos => ["HIP", "RAICRS", "DEICRS", "Vmag"],
+            VizierCatalog::TwoMass => ["2MASS", "RAJ2000", "DEJ2000", "Jmag"],
+            VizierCatalog::Ngc2000 => ["Name", "RAB2000", "DEB2000", "mag"],
+        }
+    }
+}
+
+/// An object listed by a VizieR catalog.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct VizierRow {
+    /// The catalog that listed the object.
+    pub catalog: VizierCatalog,
+    /// The identifier of the object within the catalog.
+    pub id: String,
+    /// The right ascension of the object, in degrees.
+    pub ra: f64,
+    /// The declination of the object, in degrees.
+    pub dec: f64,
+    /// The magnitude of the object in the catalog's primary band.
+    pub magnitude: Option<f64>,
+}
+
+impl VizierRow {
+    fn from_row(catalog: VizierCatalog, row: &TapRow<'_>) -> Option<Self> {
+        let [id, ra, dec, magnitude] = catalog.columns();
+        Some(VizierRow {
+            catalog,
+            id: row.get_string(id)?,
+            ra: row.get_f64(ra)?,
+            dec: row.get_f64(dec)?,
+            magnitude: row.get_f64(magnitude),
+        })
+    }
+}
+
+impl EarendelServer {
+    /// Gets the objects from the given VizieR catalog within the given radius of the given coordinates. Returns an
+    /// error if the web request fails.
+    #[instrument(skip(self, coords, radius))]
+    pub async fn query_vizier(
+        &self,
+        catalog: VizierCatalog,
+        coords: &Icrs,
+        radius: Angle,
+    ) -> Result<Vec<VizierRow>, Box<dyn Error + Send + Sync>> {
+        let (ra, dec) = icrs_to_degrees(coords);
+        let [id_column, ra_column, dec_column, mag_column] = catalog.columns();
+        let query = format!(
+            "SELECT TOP {limit} \"{id}\", \"{ra}\", \"{dec}\", \"{mag}\" FROM \"{table}\" WHERE \
+             1=CONTAINS(POINT('ICRS', \"{ra}\", \"{dec}\"), CIRCLE('ICRS', {}, {}, {}))",
+            ra,
+            dec,
+            radius.get::<degree>(),
+            limit = MAX_ROWS,
+            id = id_column,
+            ra = ra_column,
+            dec = dec_column,
+            mag = mag_column,
+            table = catalog.table(),
+        );
+
+        let table = self
+            .tap_query(Upstream::Vizier, VIZIER_TAP_URL, TapFormat::Csv, &query)
+            .await?;
+
+        Ok(table
+            .rows()
+            .filter_map(|row| VizierRow::from_row(catalog, &row))
+            .collect())
+    }
+
+    /// Gets the objects from the given VizieR catalogs within the given radius of the target of the current APOD.
+    /// Returns an error if the target cannot be resolved or if a web request fails.
+    pub async fn get_vizier_objects_for_apod(
+        &self,
+        catalogs: &[VizierCatalog],
+        radius: Angle,
+    ) -> Result<Vec<VizierRow>, Box<dyn Error + Send + Sync>> {
+        let coords = self.resolve_apod_target().await?;
+
+        let mut rows = Vec::new();
+        for catalog in catalogs {
+            rows.extend(self.query_vizier(*catalog, &coords, radius).await?);
+        }
+
+        Ok(rows)
+    }
+}
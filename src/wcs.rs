@@ -0,0 +1,174 @@
+//! World coordinate system (WCS) support for FITS images.
+
+#[cfg(feature = "mast")]
+use astro_rs::coordinates::Icrs;
+
+use serde::{Deserialize, Serialize};
+
+use std::error::Error;
+
+use crate::fits::FitsHeader;
+
+/// The sky projection of a celestial WCS.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Projection {
+    /// The gnomonic (TAN) projection.
+    Tan,
+    /// The orthographic (SIN) projection.
+    Sin,
+}
+
+impl Projection {
+    fn from_ctype(ctype: &str) -> Option<Self> {
+        match ctype.trim().get(5..8)? {
+            "TAN" => Some(Projection::Tan),
+            "SIN" => Some(Projection::Sin),
+            _ => None,
+        }
+    }
+}
+
+/// A linear celestial WCS, mapping pixel coordinates to right ascension and declination.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Wcs {
+    /// The one-based pixel coordinates of the reference point (CRPIXn).
+    pub crpix: [f64; 2],
+    /// The right ascension and declination of the reference point, in degrees (CRVALn).
+    pub crval: [f64; 2],
+    /// The linear transformation from pixel offsets to intermediate world coordinates, in degrees per pixel (CDi_j).
+    pub cd: [[f64; 2]; 2],
+    /// The sky projection.
+    pub projection: Projection,
+}
+
+impl Wcs {
+    /// Extracts the celestial WCS from the given header. Returns an error if the keywords are missing or use an unsupported projection.
+    pub fn from_header(header: &FitsHeader) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let ctype = header
+            .get_str("CTYPE1")
+            .ok_or("FITS header is missing CTYPE1")?;
+        if !ctype.starts_with("RA--") {
+            return Err(format!("unsupported celestial axis type {}", ctype).into());
+        }
+        let projection = Projection::from_ctype(ctype)
+            .ok_or_else(|| format!("unsupported WCS projection {}", ctype))?;
+
+        let get = |keyword: &str| {
+            header
+                .get_f64(keyword)
+                .ok_or_else(|| format!("FITS header is missing {}", keyword))
+        };
+        let crpix = [get("CRPIX1")?, get("CRPIX2")?];
+        let crval = [get("CRVAL1")?, get("CRVAL2")?];
+
+        let cd = if header.get("CD1_1").is_some() {
+            let cd = |keyword: &str| header.get_f64(keyword).unwrap_or(0.0);
+            [[cd("CD1_1"), cd("CD1_2")], [cd("CD2_1"), cd("CD2_2")]]
+        } else {
+            let cdelt = [get("CDELT1")?, get("CDELT2")?];
+            let pc = if header.get("PC1_1").is_some() {
+                let pc = |keyword: &str, default: f64| header.get_f64(keyword).unwrap_or(default);
+                [
+                    [pc("PC1_1", 1.0), pc("PC1_2", 0.0)],
+                    [pc("PC2_1", 0.0), pc("PC2_2", 1.0)],
+                ]
+            } else {
+                let rotation = header.get_f64("CROTA2").unwrap_or(0.0).to_radians();
+                [
+                    [rotation.cos(), -rotation.sin() * cdelt[1] / cdelt[0]],
+                    [rotation.sin() * cdelt[0] / cdelt[1], rotation.cos()],
+                ]
+            };
+            [
+                [cdelt[0] * pc[0][0], cdelt[0] * pc[0][1]],
+                [cdelt[1] * pc[1][0], cdelt[1] * pc[1][1]],
+            ]
+        };
+
+        Ok(Wcs {
+            crpix,
+            crval,
+            cd,
+            projection,
+        })
+    }
+
+    /// Converts one-based pixel coordinates to right ascension and declination, in degrees.
+    pub fn pixel_to_world(&self, x: f64, y: f64) -> (f64, f64) {
+        let dx = x - self.crpix[0];
+        let dy = y - self.crpix[1];
+        let xi = (self.cd[0][0] * dx + self.cd[0][1] * dy).to_radians();
+        let eta = (self.cd[1][0] * dx + self.cd[1][1] * dy).to_radians();
+
+        let ra0 = self.crval[0].to_radians();
+        let dec0 = self.crval[1].to_radians();
+        let (ra, dec) = match self.projection {
+            Projection::Tan => {
+                let denominator = dec0.cos() - eta * dec0.sin();
+                let ra = ra0 + xi.atan2(denominator);
+                let dec = (dec0.sin() + eta * dec0.cos()).atan2(xi.hypot(denominator));
+                (ra, dec)
+            }
+            Projection::Sin => {
+                let rho = xi.hypot(eta);
+                if rho == 0.0 {
+                    (ra0, dec0)
+                } else {
+                    let c = rho.min(1.0).asin();
+                    let dec = (c.cos() * dec0.sin() + eta * c.sin() * dec0.cos() / rho).asin();
+                    let ra = ra0
+                        + (xi * c.sin())
+                            .atan2(rho * dec0.cos() * c.cos() - eta * dec0.sin() * c.sin());
+                    (ra, dec)
+                }
+            }
+        };
+
+        (ra.to_degrees().rem_euclid(360.0), dec.to_degrees())
+    }
+
+    /// Converts right ascension and declination, in degrees, to one-based pixel coordinates. Returns None if the position is on the far side of the projection.
+    pub fn world_to_pixel(&self, ra: f64, dec: f64) -> Option<(f64, f64)> {
+        let ra = ra.to_radians();
+        let dec = dec.to_radians();
+        let ra0 = self.crval[0].to_radians();
+        let dec0 = self.crval[1].to_radians();
+        let delta_ra = ra - ra0;
+
+        let cos_c = dec0.sin() * dec.sin() + dec0.cos() * dec.cos() * delta_ra.cos();
+        if cos_c <= 0.0 {
+            return None;
+        }
+        let xi = dec.cos() * delta_ra.sin();
+        let eta = dec0.cos() * dec.sin() - dec0.sin() * dec.cos() * delta_ra.cos();
+        let (xi, eta) = match self.projection {
+            Projection::Tan => (xi / cos_c, eta / cos_c),
+            Projection::Sin => (xi, eta),
+        };
+        let xi = xi.to_degrees();
+        let eta = eta.to_degrees();
+
+        let determinant = self.cd[0][0] * self.cd[1][1] - self.cd[0][1] * self.cd[1][0];
+        if determinant == 0.0 {
+            return None;
+        }
+        let dx = (self.cd[1][1] * xi - self.cd[0][1] * eta) / determinant;
+        let dy = (self.cd[0][0] * eta - self.cd[1][0] * xi) / determinant;
+
+        Some((dx + self.crpix[0], dy + self.crpix[1]))
+    }
+
+    /// Converts one-based pixel coordinates to ICRS coordinates.
+    #[cfg(feature = "mast")]
+    pub fn pixel_to_icrs(&self, x: f64, y: f64) -> Icrs {
+        let (ra, dec) = self.pixel_to_world(x, y);
+        crate::coords::icrs_from_degrees(ra, dec)
+    }
+
+    /// Converts ICRS coordinates to one-based pixel coordinates. Returns None if the position is on the far side of the projection.
+    #[cfg(feature = "mast")]
+    pub fn icrs_to_pixel(&self, coords: &Icrs) -> Option<(f64, f64)> {
+        let (ra, dec) = crate::coords::icrs_to_degrees(coords);
+        self.world_to_pixel(ra, dec)
+    }
+}
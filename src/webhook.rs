@@ -0,0 +1,149 @@
+//! Webhook notifications of newly published APODs.
+
+use chrono::NaiveDate;
+
+use hmac::{Hmac, Mac};
+
+use reqwest::header::CONTENT_TYPE;
+
+use serde::{Deserialize, Serialize};
+
+use sha2::Sha256;
+
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+
+use tracing::warn;
+
+use std::error::Error;
+use std::sync::Arc;
+
+use crate::redact::redact;
+use crate::EarendelApod;
+
+/// The header carrying the HMAC-SHA256 signature of a signed payload.
+pub const SIGNATURE_HEADER: &str = "X-Earendel-Signature";
+
+/// The JSON payload posted to webhooks when a new APOD is published.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct WebhookPayload {
+    /// The title of the APOD.
+    pub title: String,
+    /// The date the APOD was published.
+    pub date: NaiveDate,
+    /// The URL of the image.
+    pub image_url: String,
+    /// The copyright string.
+    pub copyright: Option<String>,
+}
+
+impl From<&EarendelApod> for WebhookPayload {
+    fn from(apod: &EarendelApod) -> Self {
+        WebhookPayload {
+            title: apod.title.to_owned(),
+            date: apod.date,
+            image_url: apod.image_url.to_owned(),
+            copyright: apod.copyright.to_owned(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Webhook {
+    url: String,
+    secret: Option<String>,
+}
+
+/// Posts a `WebhookPayload` to each registered webhook when a new APOD is published.
+///
+/// Payloads sent to webhooks registered with a secret are signed with HMAC-SHA256, and the hex-encoded signature is
+/// sent in the `X-Earendel-Signature` header as `sha256=<signature>`.
+#[derive(Clone, Debug, Default)]
+pub struct WebhookNotifier {
+    webhooks: Vec<Webhook>,
+    client: reqwest::Client,
+}
+
+impl WebhookNotifier {
+    /// Creates a notifier with no registered webhooks.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the given webhook URL, signing its payloads with the given secret if one is given.
+    pub fn register(&mut self, url: &str, secret: Option<&str>) {
+        self.webhooks.push(Webhook {
+            url: url.to_owned(),
+            secret: secret.map(String::from),
+        });
+    }
+
+    /// Posts the payload for the given APOD to every registered webhook. Failed deliveries are logged rather than
+    /// returned, so that one failing webhook does not prevent the others from being notified.
+    pub async fn notify(&self, apod: &EarendelApod) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let body = serde_json::to_vec(&WebhookPayload::from(apod))?;
+        for webhook in self.webhooks.iter() {
+            let mut request = self
+                .client
+                .post(&webhook.url)
+                .header(CONTENT_TYPE, "application/json")
+                .body(body.to_owned());
+            if let Some(secret) = webhook.secret.as_ref() {
+                request = request.header(SIGNATURE_HEADER, sign(secret, &body)?);
+            }
+            if let Err(e) = request
+                .send()
+                .await
+                .and_then(|resp| resp.error_for_status())
+            {
+                warn!(
+                    "failed to notify webhook {}: {}",
+                    redact(&webhook.url),
+                    redact(&e.to_string())
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Spawns a task that notifies the registered webhooks of each APOD received from the given subscription, such
+    /// as one created by `EarendelServer::subscribe_apod`. The task runs until the server is dropped.
+    pub fn spawn(self, receiver: broadcast::Receiver<Arc<EarendelApod>>) -> JoinHandle<()> {
+        tokio::spawn(self.run(receiver))
+    }
+
+    /// Notifies the registered webhooks of each APOD received from the given subscription as `spawn` does, for
+    /// spawning onto an executor other than tokio. The future completes when the server is dropped.
+    pub async fn run(self, mut receiver: broadcast::Receiver<Arc<EarendelApod>>) {
+        loop {
+            match receiver.recv().await {
+                Ok(apod) => {
+                    if let Err(e) = self.notify(&apod).await {
+                        warn!("failed to notify webhooks: {}", e);
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("skipped notifying webhooks of {} APODs", skipped)
+                }
+                Err(RecvError::Closed) => return,
+            }
+        }
+    }
+}
+
+/// Computes the signature header value of the given body.
+fn sign(secret: &str, body: &[u8]) -> Result<String, Box<dyn Error + Send + Sync>> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).map_err(|_| "invalid webhook secret")?;
+    mac.update(body);
+    let signature = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+
+    Ok(["sha256=", &signature].concat())
+}